@@ -0,0 +1 @@
+ALTER TABLE challenges DROP COLUMN started_at, DROP COLUMN ended_at
@@ -0,0 +1 @@
+ALTER TABLE challenges ADD COLUMN started_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL, ADD COLUMN ended_at TIMESTAMP NULL DEFAULT NULL
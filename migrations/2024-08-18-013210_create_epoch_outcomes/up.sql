@@ -0,0 +1,10 @@
+CREATE TABLE epoch_outcomes (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  pool_id INT NOT NULL,
+  challenge_id INT NOT NULL,
+  outcome VARCHAR(30) NOT NULL,
+  attempts_used TINYINT UNSIGNED NOT NULL,
+  final_priority_fee BIGINT UNSIGNED DEFAULT 0 NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  INDEX idx_epoch_outcomes_pool_created (pool_id, created_at)
+)
@@ -0,0 +1,11 @@
+CREATE TABLE miner_settings (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  miner_id INT NOT NULL UNIQUE,
+  notify_url VARCHAR(255),
+  min_notify_amount BIGINT UNSIGNED DEFAULT 0 NOT NULL,
+  notifications_enabled BOOL DEFAULT true NOT NULL,
+  consecutive_failures INT UNSIGNED DEFAULT 0 NOT NULL,
+  disabled_reason VARCHAR(255),
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL
+)
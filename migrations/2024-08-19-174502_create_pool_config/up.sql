@@ -0,0 +1,8 @@
+CREATE TABLE pool_config (
+  pool_id INT NOT NULL,
+  config_key VARCHAR(64) NOT NULL,
+  config_value VARCHAR(255) NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+  PRIMARY KEY (pool_id, config_key)
+)
@@ -0,0 +1,9 @@
+CREATE TABLE config_history (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  pool_id INT NOT NULL,
+  config_key VARCHAR(64) NOT NULL,
+  old_value VARCHAR(255),
+  new_value VARCHAR(255) NOT NULL,
+  changed_by VARCHAR(100) NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+)
@@ -0,0 +1,10 @@
+CREATE TABLE miner_delegates (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  miner_id INT NOT NULL,
+  delegate_pubkey VARCHAR(44) NOT NULL UNIQUE,
+  authorization_signature VARCHAR(200) NOT NULL,
+  revoked_at TIMESTAMP NULL DEFAULT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+  INDEX idx_miner_delegates_miner_id (miner_id)
+)
@@ -0,0 +1 @@
+ALTER TABLE claims DROP INDEX idx_claims_miner_id
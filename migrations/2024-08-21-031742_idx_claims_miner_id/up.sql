@@ -0,0 +1 @@
+CREATE INDEX idx_claims_miner_id ON claims (miner_id, created_at)
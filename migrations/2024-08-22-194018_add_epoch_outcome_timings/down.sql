@@ -0,0 +1 @@
+ALTER TABLE epoch_outcomes DROP COLUMN first_send_ms, DROP COLUMN confirmed_ms, DROP COLUMN mine_event_ms
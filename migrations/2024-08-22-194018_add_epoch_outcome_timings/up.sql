@@ -0,0 +1 @@
+ALTER TABLE epoch_outcomes ADD COLUMN first_send_ms INT UNSIGNED NULL, ADD COLUMN confirmed_ms INT UNSIGNED NULL, ADD COLUMN mine_event_ms INT UNSIGNED NULL
@@ -0,0 +1 @@
+ALTER TABLE challenges DROP COLUMN txn_id, DROP COLUMN commission, DROP COLUMN total_hashpower
@@ -0,0 +1 @@
+ALTER TABLE challenges ADD COLUMN txn_id INT NULL, ADD COLUMN commission BIGINT UNSIGNED NULL, ADD COLUMN total_hashpower BIGINT UNSIGNED NULL
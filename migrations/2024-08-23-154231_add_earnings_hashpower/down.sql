@@ -0,0 +1 @@
+ALTER TABLE earnings DROP COLUMN hashpower
@@ -0,0 +1 @@
+ALTER TABLE earnings ADD COLUMN hashpower BIGINT UNSIGNED NULL
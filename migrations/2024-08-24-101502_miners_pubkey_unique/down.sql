@@ -0,0 +1 @@
+ALTER TABLE miners DROP INDEX uc_miners_pubkey
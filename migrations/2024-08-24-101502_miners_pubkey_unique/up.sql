@@ -0,0 +1,13 @@
+CREATE TABLE miner_duplicates AS SELECT m.id, k.keep_id FROM miners m JOIN (SELECT pubkey, MIN(id) AS keep_id FROM miners GROUP BY pubkey HAVING COUNT(*) > 1) k ON m.pubkey = k.pubkey AND m.id > k.keep_id;
+UPDATE miners k JOIN miner_duplicates d ON k.id = d.keep_id JOIN miners m ON m.id = d.id SET k.enabled = k.enabled OR m.enabled;
+UPDATE submissions s JOIN miner_duplicates d ON s.miner_id = d.id SET s.miner_id = d.keep_id;
+UPDATE claims c JOIN miner_duplicates d ON c.miner_id = d.id SET c.miner_id = d.keep_id;
+UPDATE rewards r JOIN miner_duplicates d ON r.miner_id = d.id SET r.miner_id = d.keep_id;
+UPDATE earnings e JOIN miner_duplicates d ON e.miner_id = d.id SET e.miner_id = d.keep_id;
+UPDATE miner_delegates md JOIN miner_duplicates d ON md.miner_id = d.id SET md.miner_id = d.keep_id;
+DELETE s FROM miner_settings s JOIN miner_duplicates d ON s.miner_id = d.id JOIN miner_settings k ON k.miner_id = d.keep_id;
+DELETE s FROM miner_settings s JOIN miner_duplicates d ON s.miner_id = d.id JOIN miner_duplicates d2 ON d2.keep_id = d.keep_id JOIN miner_settings s2 ON s2.miner_id = d2.id AND s2.id < s.id;
+UPDATE miner_settings s JOIN miner_duplicates d ON s.miner_id = d.id SET s.miner_id = d.keep_id;
+DELETE m FROM miners m JOIN miner_duplicates d ON m.id = d.id;
+DROP TABLE miner_duplicates;
+ALTER TABLE miners ADD CONSTRAINT uc_miners_pubkey UNIQUE (pubkey)
@@ -0,0 +1 @@
+ALTER TABLE rewards DROP INDEX uc_rewards_miner_pool
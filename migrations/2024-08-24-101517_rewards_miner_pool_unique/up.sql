@@ -0,0 +1,3 @@
+UPDATE rewards r JOIN (SELECT MIN(id) AS id, SUM(balance) AS balance FROM rewards GROUP BY miner_id, pool_id HAVING COUNT(*) > 1) d ON r.id = d.id SET r.balance = d.balance;
+DELETE r FROM rewards r JOIN rewards k ON r.miner_id = k.miner_id AND r.pool_id = k.pool_id AND r.id > k.id;
+ALTER TABLE rewards ADD CONSTRAINT uc_rewards_miner_pool UNIQUE (miner_id, pool_id)
@@ -0,0 +1 @@
+ALTER TABLE txns DROP INDEX uc_txns_signature
@@ -0,0 +1,6 @@
+CREATE TABLE txn_duplicates AS SELECT t.id, k.keep_id FROM txns t JOIN (SELECT signature, MIN(id) AS keep_id FROM txns GROUP BY signature HAVING COUNT(*) > 1) k ON t.signature = k.signature AND t.id > k.keep_id;
+UPDATE claims c JOIN txn_duplicates d ON c.txn_id = d.id SET c.txn_id = d.keep_id;
+UPDATE challenges c JOIN txn_duplicates d ON c.txn_id = d.id SET c.txn_id = d.keep_id;
+DELETE t FROM txns t JOIN txn_duplicates d ON t.id = d.id;
+DROP TABLE txn_duplicates;
+ALTER TABLE txns ADD CONSTRAINT uc_txns_signature UNIQUE (signature)
@@ -0,0 +1,8 @@
+CREATE TABLE pending_rewards (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  miner_id INT NOT NULL,
+  pool_id INT NOT NULL,
+  balance BIGINT UNSIGNED NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  INDEX idx_pending_rewards_pool_id (pool_id)
+)
@@ -0,0 +1 @@
+ALTER TABLE txns DROP INDEX idx_txns_pool_id, DROP COLUMN fee_paid_lamports, DROP COLUMN pool_id
@@ -0,0 +1 @@
+ALTER TABLE txns ADD COLUMN pool_id INT NULL, ADD COLUMN fee_paid_lamports BIGINT UNSIGNED NULL, ADD INDEX idx_txns_pool_id (pool_id)
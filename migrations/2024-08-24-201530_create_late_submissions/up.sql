@@ -0,0 +1,9 @@
+CREATE TABLE late_submissions (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  miner_id INT NOT NULL,
+  challenge_id INT NOT NULL,
+  nonce BIGINT UNSIGNED NOT NULL,
+  difficulty TINYINT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  INDEX idx_late_submissions_challenge_id (challenge_id)
+)
@@ -0,0 +1,13 @@
+CREATE TABLE miner_sessions (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  miner_id INT NOT NULL,
+  pool_id INT NOT NULL,
+  device_id VARCHAR(64) NULL,
+  started_at TIMESTAMP NOT NULL,
+  ended_at TIMESTAMP NOT NULL,
+  epochs INT UNSIGNED NOT NULL,
+  submissions INT UNSIGNED NOT NULL,
+  best_difficulty INT UNSIGNED NOT NULL,
+  earned BIGINT UNSIGNED NOT NULL,
+  INDEX idx_miner_sessions_miner_id_ended_at (miner_id, ended_at)
+)
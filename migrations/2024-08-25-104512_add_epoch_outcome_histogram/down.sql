@@ -0,0 +1 @@
+ALTER TABLE epoch_outcomes DROP COLUMN difficulty_histogram
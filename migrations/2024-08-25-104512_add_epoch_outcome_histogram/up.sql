@@ -0,0 +1 @@
+ALTER TABLE epoch_outcomes ADD COLUMN difficulty_histogram TEXT NULL
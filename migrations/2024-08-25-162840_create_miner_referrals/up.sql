@@ -0,0 +1,8 @@
+CREATE TABLE miner_referrals (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  referrer_miner_id INT NOT NULL,
+  referred_miner_id INT NOT NULL UNIQUE,
+  bonus_earned BIGINT UNSIGNED DEFAULT 0 NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  INDEX idx_miner_referrals_referrer_miner_id (referrer_miner_id)
+)
@@ -0,0 +1 @@
+ALTER TABLE earnings DROP INDEX uc_earnings_miner_challenge
@@ -0,0 +1,3 @@
+UPDATE earnings e JOIN (SELECT MIN(id) AS id, SUM(amount) AS amount, SUM(hashpower) AS hashpower FROM earnings GROUP BY miner_id, challenge_id HAVING COUNT(*) > 1) d ON e.id = d.id SET e.amount = d.amount, e.hashpower = d.hashpower;
+DELETE e FROM earnings e JOIN earnings k ON e.miner_id = k.miner_id AND e.challenge_id = k.challenge_id AND e.id > k.id;
+ALTER TABLE earnings ADD CONSTRAINT uc_earnings_miner_challenge UNIQUE (miner_id, challenge_id)
@@ -0,0 +1,14 @@
+CREATE TABLE audit_log (
+  id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  pool_id INT NOT NULL,
+  action VARCHAR(16) NOT NULL,
+  pubkey VARCHAR(64) NOT NULL,
+  ip VARCHAR(45) NOT NULL,
+  amount BIGINT UNSIGNED NULL,
+  outcome VARCHAR(16) NOT NULL,
+  error_code VARCHAR(32) NULL,
+  created_at TIMESTAMP NOT NULL,
+  INDEX idx_audit_log_pool_created (pool_id, created_at),
+  INDEX idx_audit_log_pubkey_created (pubkey, created_at),
+  INDEX idx_audit_log_ip_created (ip, created_at)
+)
@@ -0,0 +1 @@
+ALTER TABLE miner_settings DROP COLUMN auto_claim_threshold
@@ -0,0 +1 @@
+ALTER TABLE miner_settings ADD COLUMN auto_claim_threshold BIGINT UNSIGNED NULL
@@ -0,0 +1,8 @@
+CREATE TABLE miner_reputations (
+  miner_id INT NOT NULL PRIMARY KEY,
+  reputation_score INT DEFAULT 0 NOT NULL,
+  total_valid_submissions BIGINT UNSIGNED DEFAULT 0 NOT NULL,
+  total_invalid_submissions BIGINT UNSIGNED DEFAULT 0 NOT NULL,
+  consecutive_invalid TINYINT UNSIGNED DEFAULT 0 NOT NULL,
+  last_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL
+)
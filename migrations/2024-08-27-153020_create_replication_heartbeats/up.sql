@@ -0,0 +1,4 @@
+CREATE TABLE replication_heartbeats (
+  pool_id INT NOT NULL PRIMARY KEY,
+  beat_at TIMESTAMP(3) NOT NULL
+)
@@ -0,0 +1,2 @@
+ALTER TABLE submissions MODIFY COLUMN difficulty SMALLINT NOT NULL;
+ALTER TABLE late_submissions MODIFY COLUMN difficulty SMALLINT NOT NULL
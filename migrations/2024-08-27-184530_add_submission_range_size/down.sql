@@ -0,0 +1,2 @@
+ALTER TABLE challenges DROP COLUMN nonces_assigned;
+ALTER TABLE submissions DROP COLUMN range_size
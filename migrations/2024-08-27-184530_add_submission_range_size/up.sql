@@ -0,0 +1,2 @@
+ALTER TABLE submissions ADD COLUMN range_size BIGINT UNSIGNED DEFAULT 0 NOT NULL;
+ALTER TABLE challenges ADD COLUMN nonces_assigned BIGINT UNSIGNED NULL
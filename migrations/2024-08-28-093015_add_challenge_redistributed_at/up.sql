@@ -0,0 +1 @@
+ALTER TABLE challenges ADD COLUMN redistributed_at TIMESTAMP NULL
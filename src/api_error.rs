@@ -0,0 +1,271 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderName, HeaderValue, Response, StatusCode,
+    },
+    middleware::Next,
+    response::IntoResponse,
+};
+use rand::Rng;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Error bodies are short messages, anything larger is passed through untouched.
+const MAX_ERROR_BODY_BYTES: usize = 4096;
+// Request ids supplied by clients longer than this are replaced with our own.
+const MAX_REQUEST_ID_LEN: usize = 64;
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Stable, machine readable error codes. Each code always comes with the same
+/// http status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiErrorCode {
+    /// 400, a pubkey parameter or header is not a valid pubkey
+    InvalidPubkey,
+    /// 400, the signup transaction is malformed or not the expected transfer
+    InvalidTransaction,
+    /// 400, a parameter or payload failed validation
+    InvalidRequest,
+    /// 400, the claim is below the pool's minimum claim amount
+    ClaimBelowMinimum,
+    /// 400, the claim is larger than the miner's rewards balance
+    InsufficientBalance,
+    /// 400, the wallet has no token account balance to report
+    TokenAccountNotFound,
+    /// 401, the request signature is invalid or its timestamp expired
+    InvalidSignature,
+    /// 401, the pubkey has no miner account with the pool
+    NotSignedUp,
+    /// 401, the miner account is disabled
+    MinerDisabled,
+    /// 401, the miner's reputation score is below the pool's minimum
+    LowReputation,
+    /// 401, missing or wrong admin password
+    Unauthorized,
+    /// 404, the requested resource doesn't exist
+    NotFound,
+    /// 409, the delegate pubkey is a miner or delegated by another wallet
+    DelegateConflict,
+    /// 409, the challenge's earnings changed while it was being redistributed
+    EarningsConflict,
+    /// 409, the pool wallet can't be swapped now, or its key belongs to another pool
+    WalletConflict,
+    /// 429, the miner claimed too recently
+    ClaimCooldown,
+    /// 429, another client is already mining with the wallet
+    AlreadyConnected,
+    /// 429, too many requests
+    RateLimited,
+    /// 500, a database query failed
+    DatabaseError,
+    /// 500, a transaction failed to build, send or confirm
+    TransactionFailed,
+    /// 500, an rpc request failed
+    RpcError,
+    /// 500, any other server side failure
+    InternalError,
+    /// 503, the pool is at capacity, see the Retry-After header
+    PoolFull,
+}
+
+impl ApiErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiErrorCode::InvalidPubkey
+            | ApiErrorCode::InvalidTransaction
+            | ApiErrorCode::InvalidRequest
+            | ApiErrorCode::ClaimBelowMinimum
+            | ApiErrorCode::InsufficientBalance
+            | ApiErrorCode::TokenAccountNotFound => StatusCode::BAD_REQUEST,
+            ApiErrorCode::InvalidSignature
+            | ApiErrorCode::NotSignedUp
+            | ApiErrorCode::MinerDisabled
+            | ApiErrorCode::LowReputation
+            | ApiErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ApiErrorCode::DelegateConflict
+            | ApiErrorCode::EarningsConflict
+            | ApiErrorCode::WalletConflict => StatusCode::CONFLICT,
+            ApiErrorCode::ClaimCooldown
+            | ApiErrorCode::AlreadyConnected
+            | ApiErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ApiErrorCode::DatabaseError
+            | ApiErrorCode::TransactionFailed
+            | ApiErrorCode::RpcError
+            | ApiErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiErrorCode::PoolFull => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Code used for error responses that weren't built from an ApiError,
+    /// such as extractor rejections.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ApiErrorCode::Unauthorized,
+            StatusCode::NOT_FOUND => ApiErrorCode::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => ApiErrorCode::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE => ApiErrorCode::PoolFull,
+            s if s.is_client_error() => ApiErrorCode::InvalidRequest,
+            _ => ApiErrorCode::InternalError,
+        }
+    }
+}
+
+/// Error returned by every http handler. It is sent as json unless the client
+/// only accepts `text/plain`, those get the plain text message.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiError {
+    #[serde(rename = "error_code")]
+    pub code: ApiErrorCode,
+    pub message: String,
+    #[schema(value_type = Option<Object>)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    // set by json_error_envelope, also sent as the X-Request-Id header
+    pub request_id: String,
+    // unix seconds
+    pub timestamp: u64,
+    // plain text body kept from before the json errors, defaults to the message
+    #[serde(skip)]
+    text: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+            request_id: String::new(),
+            timestamp: 0,
+            text: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Overrides the plain text body sent to clients that don't accept json.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let text = self.text.clone().unwrap_or_else(|| self.message.clone());
+        let mut response = (self.code.status(), text).into_response();
+        // picked up by json_error_envelope
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// True when the request's Accept header asks for json.
+pub fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("application/json"))
+        .unwrap_or(false)
+}
+
+/// True when the client accepts plain text but not json, the error bodies
+/// from before the json envelope are kept for those.
+fn prefers_text(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("text/plain") && !v.contains("application/json"))
+        .unwrap_or(false)
+}
+
+/// The client's X-Request-Id when it sent a usable one, a random id otherwise.
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN)
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| format!("{:016x}", rand::thread_rng().gen::<u64>()))
+}
+
+/// Tags every response with an X-Request-Id and renders error responses as
+/// ApiError json. Responses from handlers carry their ApiError, anything else
+/// (extractor rejections, timeouts) has its plain text body wrapped with a
+/// code derived from the status.
+pub async fn json_error_envelope(req: Request, next: Next) -> Response<Body> {
+    let wants_text = prefers_text(req.headers());
+    let request_id = request_id(req.headers());
+
+    let mut res = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let status = res.status();
+    if wants_text || !(status.is_client_error() || status.is_server_error()) {
+        return res;
+    }
+
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/json"))
+        .unwrap_or(false);
+    if is_json {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let mut error = match parts.extensions.remove::<ApiError>() {
+        Some(error) => error,
+        None => {
+            let message = match axum::body::to_bytes(body, MAX_ERROR_BODY_BYTES).await {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(_) => status.canonical_reason().unwrap_or_default().to_string(),
+            };
+            ApiError::new(ApiErrorCode::from_status(status), message)
+        }
+    };
+    error.request_id = request_id;
+    error.timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let body = serde_json::to_vec(&error).unwrap_or_default();
+
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn accepts_json_only_when_asked_for() {
+        assert!(accepts_json(&accept("application/json")));
+        assert!(accepts_json(&accept("text/plain, application/json;q=0.9")));
+        assert!(!accepts_json(&accept("text/plain")));
+        assert!(!accepts_json(&accept("*/*")));
+        assert!(!accepts_json(&HeaderMap::new()));
+    }
+}
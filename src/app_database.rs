@@ -1,16 +1,43 @@
+use chrono::NaiveDateTime;
 use deadpool_diesel::{
     mysql::{Manager, Pool},
 };
 use diesel::{
     connection::SimpleConnection,
     insert_into,
-    sql_types::{BigInt, Binary, Bool, Integer, Nullable, Text, TinyInt, Unsigned},
-    MysqlConnection, RunQueryDsl,
+    result::DatabaseErrorKind,
+    sql_types::{BigInt, Binary, Bool, Integer, Nullable, SmallInt, Text, TinyInt, Timestamp, Unsigned},
+    Connection, ExpressionMethods, MysqlConnection, QueryDsl, RunQueryDsl,
 };
-use tracing::{error, info};
+use std::{collections::HashSet, time::Duration};
+
+use tracing::{error, info, warn};
 
 use crate::{models, InsertReward, Miner, Submission, SubmissionWithId};
 
+// Attempts at a rewards batch before it is parked in pending_rewards.
+const REWARDS_UPDATE_ATTEMPTS: u32 = 3;
+// Backoff after a deadlock, multiplied by the attempt number.
+const REWARDS_DEADLOCK_BACKOFF: Duration = Duration::from_millis(200);
+// Highest reputation score, each valid solution adds 1.
+const MAX_REPUTATION_SCORE: i32 = 100;
+// Reputation lost for each invalid solution.
+const INVALID_SOLUTION_PENALTY: i32 = 5;
+// Miners whose notify settings are loaded per query.
+const NOTIFY_SETTINGS_BATCH_SIZE: usize = 1000;
+// Lowest reputation score, however many invalid solutions a miner sends.
+const MIN_REPUTATION_SCORE: i32 = -50;
+// A negative reputation score recovers this much each hour, up to 0.
+const REPUTATION_RECOVERY_PER_HOUR: i32 = 5;
+
+// reputation_score with the recovery since last_updated applied
+fn recovered_reputation_score() -> String {
+    format!(
+        "IF(reputation_score < 0, LEAST(reputation_score + {} * TIMESTAMPDIFF(HOUR, last_updated, CURRENT_TIMESTAMP), 0), reputation_score)",
+        REPUTATION_RECOVERY_PER_HOUR
+    )
+}
+
 #[derive(Debug)]
 pub enum AppDatabaseError {
     FailedToGetConnectionFromPool,
@@ -18,6 +45,23 @@ pub enum AppDatabaseError {
     FailedToInsertRow,
     InteractionFailed,
     QueryFailed,
+    DuplicateEntry,
+}
+
+impl AppDatabaseError {
+    /// Returns false for errors that will fail the same way if retried.
+    pub fn is_retriable(&self) -> bool {
+        !matches!(self, AppDatabaseError::DuplicateEntry)
+    }
+}
+
+fn query_error(e: diesel::result::Error) -> AppDatabaseError {
+    match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppDatabaseError::DuplicateEntry
+        }
+        _ => AppDatabaseError::QueryFailed,
+    }
 }
 
 pub struct AppDatabase {
@@ -41,7 +85,7 @@ impl AppDatabase {
     ) -> Result<models::Challenge, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("SELECT id, pool_id, submission_id, challenge, rewards_earned FROM challenges WHERE challenges.challenge = ?")
+                diesel::sql_query("SELECT id, pool_id, submission_id, challenge, rewards_earned, started_at, ended_at FROM challenges WHERE challenges.challenge = ?")
                 .bind::<Binary, _>(challenge)
                 .get_result::<models::Challenge>(conn)
             }).await;
@@ -53,7 +97,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -69,11 +113,13 @@ impl AppDatabase {
     pub async fn get_miner_rewards(
         &self,
         miner_pubkey: String,
+        pool_id: i32,
     ) -> Result<models::Reward, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("SELECT r.balance, r.miner_id FROM miners m JOIN rewards r ON m.id = r.miner_id WHERE m.pubkey = ?")
+                diesel::sql_query("SELECT r.balance, r.miner_id FROM miners m JOIN rewards r ON m.id = r.miner_id WHERE m.pubkey = ? AND r.pool_id = ?")
                 .bind::<Text, _>(miner_pubkey)
+                .bind::<Integer, _>(pool_id)
                 .get_result::<models::Reward>(conn)
             }).await;
 
@@ -84,7 +130,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -97,11 +143,12 @@ impl AppDatabase {
         };
     }
 
+    /// Adds the miner's rewards tracker for a pool, does nothing if it already exists.
     pub async fn add_new_reward(&self, reward: InsertReward) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("INSERT INTO rewards (miner_id, pool_id) VALUES (?, ?)")
+                    diesel::sql_query("INSERT INTO rewards (miner_id, pool_id) VALUES (?, ?) ON DUPLICATE KEY UPDATE id = id")
                         .bind::<Integer, _>(reward.miner_id)
                         .bind::<Integer, _>(reward.pool_id)
                         .execute(conn)
@@ -115,7 +162,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -128,33 +175,170 @@ impl AppDatabase {
         };
     }
 
+    /// Adds the earned balances to the miners' rewards in one transaction.
+    /// Rows are updated in miner_id order so concurrent batches lock them in
+    /// the same order, deadlocks with claims are retried a few times. Fails
+    /// with FailedToUpdateRow, applying nothing, when a miner has no rewards
+    /// row in the pool.
     pub async fn update_rewards(
         &self,
-        rewards: Vec<models::UpdateReward>,
+        mut rewards: Vec<models::UpdateReward>,
+        pool_id: i32,
     ) -> Result<(), AppDatabaseError> {
-        let mut query = String::new();
-        for reward in rewards {
-            query.push_str(&format!(
-                "UPDATE rewards SET balance = balance + {} WHERE miner_id = {};",
-                reward.balance, reward.miner_id
-            ));
+        rewards.sort_by_key(|r| r.miner_id);
+
+        let mut attempt = 1;
+        loop {
+            let db_conn = match self.connection_pool.get().await {
+                Ok(db_conn) => db_conn,
+                Err(_) => return Err(AppDatabaseError::FailedToGetConnectionFromPool),
+            };
+            let batch = rewards.clone();
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        for reward in batch {
+                            let updated = diesel::sql_query("UPDATE rewards SET balance = balance + ? WHERE miner_id = ? AND pool_id = ?")
+                                .bind::<Unsigned<BigInt>, _>(reward.balance)
+                                .bind::<Integer, _>(reward.miner_id)
+                                .bind::<Integer, _>(pool_id)
+                                .execute(conn)?;
+                            if updated != 1 {
+                                error!("Miner {} has no rewards row in pool {}", reward.miner_id, pool_id);
+                                return Err(diesel::result::Error::NotFound);
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(()) => {
+                        return Ok(());
+                    }
+                    Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _))
+                        if attempt < REWARDS_UPDATE_ATTEMPTS =>
+                    {
+                        warn!("Deadlock updating rewards, retrying ({}/{})", attempt, REWARDS_UPDATE_ATTEMPTS);
+                        tokio::time::sleep(REWARDS_DEADLOCK_BACKOFF * attempt).await;
+                        attempt += 1;
+                    }
+                    Err(diesel::result::Error::NotFound) => {
+                        return Err(AppDatabaseError::FailedToUpdateRow);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
         }
+    }
 
+    /// Parks a rewards batch that couldn't be applied, apply_pending_rewards
+    /// adds it to the balances later.
+    pub async fn add_pending_rewards(
+        &self,
+        rewards: Vec<models::UpdateReward>,
+        pool_id: i32,
+    ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
-            let conn_query = query.clone();
             let res = db_conn
-                .interact(move |conn: &mut MysqlConnection| conn.batch_execute(&conn_query))
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        for reward in rewards {
+                            diesel::sql_query("INSERT INTO pending_rewards (miner_id, pool_id, balance) VALUES (?, ?, ?)")
+                                .bind::<Integer, _>(reward.miner_id)
+                                .bind::<Integer, _>(pool_id)
+                                .bind::<Unsigned<BigInt>, _>(reward.balance)
+                                .execute(conn)?;
+                        }
+                        Ok(())
+                    })
+                })
                 .await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(_query) => {
+                    Ok(()) => {
                         return Ok(());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        error!("QUERY: {}", query);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Adds the parked rewards of the pool to the balances and removes them,
+    /// in one transaction. Returns whether anything was applied. Fails with
+    /// FailedToUpdateRow, leaving them parked, when a miner has no rewards
+    /// row in the pool.
+    pub async fn apply_pending_rewards(&self, pool_id: i32) -> Result<bool, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        // rows parked while this runs are left for the next call
+                        let max_id = diesel::sql_query("SELECT MAX(id) AS max_id FROM pending_rewards WHERE pool_id = ?")
+                            .bind::<Integer, _>(pool_id)
+                            .get_result::<models::MaxId>(conn)?
+                            .max_id;
+                        let max_id = match max_id {
+                            Some(max_id) => max_id,
+                            None => return Ok(false),
+                        };
+                        let miners = diesel::sql_query("SELECT CAST(COUNT(DISTINCT miner_id) AS UNSIGNED) AS miner_count FROM pending_rewards WHERE pool_id = ? AND id <= ?")
+                            .bind::<Integer, _>(pool_id)
+                            .bind::<Integer, _>(max_id)
+                            .get_result::<models::MinerCount>(conn)?
+                            .miner_count;
+                        let updated = diesel::sql_query("UPDATE rewards r JOIN (SELECT miner_id, SUM(balance) AS balance FROM pending_rewards WHERE pool_id = ? AND id <= ? GROUP BY miner_id) p ON r.miner_id = p.miner_id SET r.balance = r.balance + p.balance WHERE r.pool_id = ?")
+                            .bind::<Integer, _>(pool_id)
+                            .bind::<Integer, _>(max_id)
+                            .bind::<Integer, _>(pool_id)
+                            .execute(conn)?;
+                        if updated as u64 != miners {
+                            error!(
+                                "{} of {} miners with pending rewards have a rewards row in pool {}",
+                                updated, miners, pool_id
+                            );
+                            return Err(diesel::result::Error::NotFound);
+                        }
+                        diesel::sql_query("DELETE FROM pending_rewards WHERE pool_id = ? AND id <= ?")
+                            .bind::<Integer, _>(pool_id)
+                            .bind::<Integer, _>(max_id)
+                            .execute(conn)?;
+                        Ok(true)
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(applied) => {
+                        return Ok(applied);
+                    }
+                    Err(diesel::result::Error::NotFound) => {
+                        return Err(AppDatabaseError::FailedToUpdateRow);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -167,29 +351,51 @@ impl AppDatabase {
         };
     }
 
-    pub async fn decrease_miner_reward(
+    /// Credits each bonus to the referrer of the referred miner, in one
+    /// transaction. Bonuses are paid out of the epoch's commission, once it's
+    /// used up the remaining ones are cut or skipped. Miners without a
+    /// referrer, and referrers without rewards in the pool, are skipped.
+    /// Returns the total credited.
+    pub async fn credit_referral_bonuses(
         &self,
-        miner_id: i32,
-        rewards_to_decrease: u64,
-    ) -> Result<(), AppDatabaseError> {
+        pool_id: i32,
+        mut bonuses: Vec<models::ReferralBonus>,
+        commission: u64,
+    ) -> Result<u64, AppDatabaseError> {
+        bonuses.sort_by_key(|bonus| bonus.referred_miner_id);
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("UPDATE rewards SET balance = balance - ? WHERE miner_id = ?")
-                        .bind::<Unsigned<BigInt>, _>(rewards_to_decrease)
-                        .bind::<Integer, _>(miner_id)
-                        .execute(conn)
+                    conn.transaction(|conn| {
+                        let mut credited = 0u64;
+                        for bonus in bonuses {
+                            let amount = bonus.amount.min(commission - credited);
+                            if amount == 0 {
+                                break;
+                            }
+                            let updated = diesel::sql_query("UPDATE rewards r JOIN miner_referrals mr ON r.miner_id = mr.referrer_miner_id SET r.balance = r.balance + ?, mr.bonus_earned = mr.bonus_earned + ? WHERE mr.referred_miner_id = ? AND r.pool_id = ?")
+                                .bind::<Unsigned<BigInt>, _>(amount)
+                                .bind::<Unsigned<BigInt>, _>(amount)
+                                .bind::<Integer, _>(bonus.referred_miner_id)
+                                .bind::<Integer, _>(pool_id)
+                                .execute(conn)?;
+                            if updated > 0 {
+                                credited += amount;
+                            }
+                        }
+                        Ok(credited)
+                    })
                 })
                 .await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(_query) => {
-                        return Ok(());
+                    Ok(credited) => {
+                        return Ok(credited);
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -208,11 +414,50 @@ impl AppDatabase {
     ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("INSERT INTO submissions (miner_id, challenge_id, nonce, difficulty) VALUES (?, ?, ?, ?)")
+                diesel::sql_query("INSERT INTO submissions (miner_id, challenge_id, nonce, difficulty, range_size) VALUES (?, ?, ?, ?, ?)")
+                .bind::<Integer, _>(submission.miner_id)
+                .bind::<Integer, _>(submission.challenge_id)
+                .bind::<Unsigned<BigInt>, _>(submission.nonce)
+                .bind::<SmallInt, _>(submission.difficulty)
+                .bind::<Unsigned<BigInt>, _>(submission.range_size)
+                .execute(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        if query != 1 {
+                            return Err(AppDatabaseError::FailedToInsertRow);
+                        }
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Records a valid submission that arrived after its challenge was replaced.
+    pub async fn add_late_submission(
+        &self,
+        submission: models::InsertSubmission,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("INSERT INTO late_submissions (miner_id, challenge_id, nonce, difficulty) VALUES (?, ?, ?, ?)")
                 .bind::<Integer, _>(submission.miner_id)
                 .bind::<Integer, _>(submission.challenge_id)
                 .bind::<Unsigned<BigInt>, _>(submission.nonce)
-                .bind::<TinyInt, _>(submission.difficulty)
+                .bind::<SmallInt, _>(submission.difficulty)
                 .execute(conn)
             }).await;
 
@@ -226,7 +471,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -256,7 +501,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -295,7 +540,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -314,10 +559,11 @@ impl AppDatabase {
     ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("INSERT INTO challenges (pool_id, challenge, rewards_earned) VALUES (?, ?, ?)")
+                diesel::sql_query("INSERT INTO challenges (pool_id, challenge, rewards_earned, started_at) VALUES (?, ?, ?, ?)")
                 .bind::<Integer, _>(challenge.pool_id)
                 .bind::<Binary, _>(challenge.challenge)
                 .bind::<Nullable<Unsigned<BigInt>>, _>(challenge.rewards_earned)
+                .bind::<Timestamp, _>(challenge.started_at)
                 .execute(conn)
             }).await;
 
@@ -331,7 +577,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -344,25 +590,27 @@ impl AppDatabase {
         };
     }
 
-    pub async fn get_pool_by_authority_pubkey(
+    pub async fn close_challenge(
         &self,
-        pool_pubkey: String,
-    ) -> Result<models::Pool, AppDatabaseError> {
+        challenge: Vec<u8>,
+        ended_at: NaiveDateTime,
+    ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("SELECT id, proof_pubkey, authority_pubkey, total_rewards, claimed_rewards FROM pools WHERE pools.authority_pubkey = ?")
-                .bind::<Text, _>(pool_pubkey)
-                .get_result::<models::Pool>(conn)
+                diesel::sql_query("UPDATE challenges SET ended_at = ? WHERE challenge = ? AND ended_at IS NULL")
+                .bind::<Timestamp, _>(ended_at)
+                .bind::<Binary, _>(challenge)
+                .execute(conn)
             }).await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(query) => {
-                        return Ok(query);
+                    Ok(_query) => {
+                        return Ok(());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -375,34 +623,29 @@ impl AppDatabase {
         };
     }
 
-    pub async fn add_new_pool(
+    /// Adds to the nonces assigned for a challenge, every instance of a
+    /// federation adds its own.
+    pub async fn add_challenge_nonces_assigned(
         &self,
-        authority_pubkey: String,
-        proof_pubkey: String,
+        challenge: Vec<u8>,
+        nonces_assigned: u64,
     ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
-            let res = db_conn
-                .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query(
-                        "INSERT INTO pools (authority_pubkey, proof_pubkey) VALUES (?, ?)",
-                    )
-                    .bind::<Text, _>(authority_pubkey)
-                    .bind::<Text, _>(proof_pubkey)
-                    .execute(conn)
-                })
-                .await;
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("UPDATE challenges SET nonces_assigned = COALESCE(nonces_assigned, 0) + ? WHERE challenge = ?")
+                .bind::<Unsigned<BigInt>, _>(nonces_assigned)
+                .bind::<Binary, _>(challenge)
+                .execute(conn)
+            }).await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(query) => {
-                        if query != 1 {
-                            return Err(AppDatabaseError::FailedToInsertRow);
-                        }
+                    Ok(_query) => {
                         return Ok(());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -415,31 +658,25 @@ impl AppDatabase {
         };
     }
 
-    pub async fn update_pool_rewards(
+    pub async fn get_pool_by_authority_pubkey(
         &self,
-        pool_authority_pubkey: String,
-        earned_rewards: u64,
-    ) -> Result<(), AppDatabaseError> {
+        pool_pubkey: String,
+    ) -> Result<models::Pool, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("UPDATE pools SET total_rewards = total_rewards + ? WHERE authority_pubkey = ?")
-                .bind::<Unsigned<BigInt>, _>(earned_rewards)
-                .bind::<Text, _>(pool_authority_pubkey)
-                .execute(conn)
+                diesel::sql_query("SELECT id, proof_pubkey, authority_pubkey, total_rewards, claimed_rewards FROM pools WHERE pools.authority_pubkey = ?")
+                .bind::<Text, _>(pool_pubkey)
+                .get_result::<models::Pool>(conn)
             }).await;
 
             match res {
                 Ok(interaction) => match interaction {
                     Ok(query) => {
-                        if query != 1 {
-                            return Err(AppDatabaseError::FailedToUpdateRow);
-                        }
-                        info!("Successfully updated pool rewards");
-                        return Ok(());
+                        return Ok(query);
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -452,30 +689,26 @@ impl AppDatabase {
         };
     }
 
-    pub async fn update_pool_claimed(
+    /// The pool whose authority is the pubkey, None when there's none.
+    pub async fn find_pool_by_authority_pubkey(
         &self,
-        pool_authority_pubkey: String,
-        claimed_rewards: u64,
-    ) -> Result<(), AppDatabaseError> {
+        pool_pubkey: String,
+    ) -> Result<Option<models::Pool>, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("UPDATE pools SET claimed_rewards = claimed_rewards + ? WHERE authority_pubkey = ?")
-                .bind::<Unsigned<BigInt>, _>(claimed_rewards)
-                .bind::<Text, _>(pool_authority_pubkey)
-                .execute(conn)
+                diesel::sql_query("SELECT id, proof_pubkey, authority_pubkey, total_rewards, claimed_rewards FROM pools WHERE pools.authority_pubkey = ?")
+                .bind::<Text, _>(pool_pubkey)
+                .load::<models::Pool>(conn)
             }).await;
 
             match res {
                 Ok(interaction) => match interaction {
                     Ok(query) => {
-                        if query != 1 {
-                            return Err(AppDatabaseError::FailedToUpdateRow);
-                        }
-                        return Ok(());
+                        return Ok(query.into_iter().next());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -488,29 +721,34 @@ impl AppDatabase {
         };
     }
 
-    pub async fn add_new_miner(
+    pub async fn add_new_pool(
         &self,
-        miner_pubkey: String,
-        is_enabled: bool,
+        authority_pubkey: String,
+        proof_pubkey: String,
     ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("INSERT INTO miners (pubkey, enabled) VALUES (?, ?)")
-                        .bind::<Text, _>(miner_pubkey)
-                        .bind::<Bool, _>(is_enabled)
-                        .execute(conn)
+                    diesel::sql_query(
+                        "INSERT INTO pools (authority_pubkey, proof_pubkey) VALUES (?, ?)",
+                    )
+                    .bind::<Text, _>(authority_pubkey)
+                    .bind::<Text, _>(proof_pubkey)
+                    .execute(conn)
                 })
                 .await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(_query) => {
+                    Ok(query) => {
+                        if query != 1 {
+                            return Err(AppDatabaseError::FailedToInsertRow);
+                        }
                         return Ok(());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -523,28 +761,38 @@ impl AppDatabase {
         };
     }
 
-    pub async fn get_miner_by_pubkey_str(
+    /// Points the pool's row at a new authority and proof, the pool keeps its
+    /// id so miner balances and history stay with it.
+    pub async fn update_pool_authority(
         &self,
-        miner_pubkey: String,
-    ) -> Result<Miner, AppDatabaseError> {
+        pool_id: i32,
+        authority_pubkey: String,
+        proof_pubkey: String,
+    ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
                     diesel::sql_query(
-                        "SELECT id, pubkey, enabled FROM miners WHERE miners.pubkey = ?",
+                        "UPDATE pools SET authority_pubkey = ?, proof_pubkey = ? WHERE id = ?",
                     )
-                    .bind::<Text, _>(miner_pubkey)
-                    .get_result::<Miner>(conn)
+                    .bind::<Text, _>(authority_pubkey)
+                    .bind::<Text, _>(proof_pubkey)
+                    .bind::<Integer, _>(pool_id)
+                    .execute(conn)
                 })
                 .await;
+
             match res {
                 Ok(interaction) => match interaction {
                     Ok(query) => {
-                        return Ok(query);
+                        if query != 1 {
+                            return Err(AppDatabaseError::FailedToUpdateRow);
+                        }
+                        return Ok(());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -557,25 +805,31 @@ impl AppDatabase {
         };
     }
 
-    pub async fn add_new_claim(&self, claim: models::InsertClaim) -> Result<(), AppDatabaseError> {
+    pub async fn update_pool_rewards(
+        &self,
+        pool_authority_pubkey: String,
+        earned_rewards: u64,
+    ) -> Result<(), AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("INSERT INTO claims (miner_id, pool_id, txn_id, amount) VALUES (?, ?, ?, ?)")
-                .bind::<Integer, _>(claim.miner_id)
-                .bind::<Integer, _>(claim.pool_id)
-                .bind::<Integer, _>(claim.txn_id)
-                .bind::<Unsigned<BigInt>, _>(claim.amount)
+                diesel::sql_query("UPDATE pools SET total_rewards = total_rewards + ? WHERE authority_pubkey = ?")
+                .bind::<Unsigned<BigInt>, _>(earned_rewards)
+                .bind::<Text, _>(pool_authority_pubkey)
                 .execute(conn)
             }).await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(_query) => {
+                    Ok(query) => {
+                        if query != 1 {
+                            return Err(AppDatabaseError::FailedToUpdateRow);
+                        }
+                        info!("Successfully updated pool rewards");
                         return Ok(());
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -588,13 +842,43 @@ impl AppDatabase {
         };
     }
 
-    pub async fn get_last_claim(&self, miner_id: i32) -> Result<models::LastClaim, AppDatabaseError> {
+    /// Creates the miner if it doesn't exist and its rewards tracker for the
+    /// pool, in one transaction. Concurrent signups for the same pubkey resolve
+    /// to the same rows through the unique keys, the existing miner is returned
+    /// unchanged. The referrer is only recorded for a miner created here.
+    pub async fn signup_miner(
+        &self,
+        miner_pubkey: String,
+        pool_id: i32,
+        referrer_miner_id: Option<i32>,
+    ) -> Result<Miner, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT created_at FROM claims WHERE miner_id = ? ORDER BY id DESC")
-                        .bind::<Integer, _>(miner_id)
-                        .get_result::<models::LastClaim>(conn)
+                    conn.transaction(|conn| {
+                        // counted, and locked, before the upsert, which reports a duplicate as affected
+                        let existing_miners = diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS miner_count FROM miners WHERE pubkey = ? FOR UPDATE")
+                            .bind::<Text, _>(&miner_pubkey)
+                            .get_result::<models::MinerCount>(conn)?;
+                        diesel::sql_query("INSERT INTO miners (pubkey, enabled) VALUES (?, true) ON DUPLICATE KEY UPDATE id = id")
+                            .bind::<Text, _>(&miner_pubkey)
+                            .execute(conn)?;
+                        diesel::sql_query("INSERT INTO rewards (miner_id, pool_id) SELECT id, ? FROM miners WHERE pubkey = ? ON DUPLICATE KEY UPDATE rewards.id = rewards.id")
+                            .bind::<Integer, _>(pool_id)
+                            .bind::<Text, _>(&miner_pubkey)
+                            .execute(conn)?;
+                        // an existing miner can't pick a referrer to share its earnings with
+                        if let Some(referrer_miner_id) = referrer_miner_id.filter(|_| existing_miners.miner_count == 0) {
+                            diesel::sql_query("INSERT INTO miner_referrals (referrer_miner_id, referred_miner_id) SELECT ?, id FROM miners WHERE pubkey = ? AND id <> ? ON DUPLICATE KEY UPDATE miner_referrals.id = miner_referrals.id")
+                                .bind::<Integer, _>(referrer_miner_id)
+                                .bind::<Text, _>(&miner_pubkey)
+                                .bind::<Integer, _>(referrer_miner_id)
+                                .execute(conn)?;
+                        }
+                        diesel::sql_query("SELECT id, pubkey, enabled FROM miners WHERE miners.pubkey = ?")
+                            .bind::<Text, _>(&miner_pubkey)
+                            .get_result::<Miner>(conn)
+                    })
                 })
                 .await;
 
@@ -605,7 +889,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -618,28 +902,52 @@ impl AppDatabase {
         };
     }
 
-    pub async fn add_new_txn(&self, txn: models::InsertTxn) -> Result<(), AppDatabaseError> {
+    /// Signs up each pubkey with the pool like signup_miner, in a single
+    /// transaction. Returns per pubkey whether its miner row was created and
+    /// whether its rewards row for the pool was created, both false for a
+    /// pubkey already signed up with the pool.
+    pub async fn import_miners(
+        &self,
+        miner_pubkeys: Vec<String>,
+        pool_id: i32,
+    ) -> Result<Vec<(bool, bool)>, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query(
-                        "INSERT INTO txns (txn_type, signature, priority_fee) VALUES (?, ?, ?)",
-                    )
-                    .bind::<Text, _>(txn.txn_type)
-                    .bind::<Text, _>(txn.signature)
-                    .bind::<Unsigned<Integer>, _>(txn.priority_fee)
-                    .execute(conn)
+                    conn.transaction(|conn| {
+                        let mut results = Vec::with_capacity(miner_pubkeys.len());
+                        for miner_pubkey in &miner_pubkeys {
+                            // a duplicate is an affected row with CLIENT_FOUND_ROWS, so the
+                            // existing rows are counted, and locked, before the upserts
+                            let existing_miners = diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS miner_count FROM miners WHERE pubkey = ? FOR UPDATE")
+                                .bind::<Text, _>(miner_pubkey)
+                                .get_result::<models::MinerCount>(conn)?;
+                            let existing_rewards = diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS miner_count FROM rewards r JOIN miners m ON r.miner_id = m.id WHERE m.pubkey = ? AND r.pool_id = ? FOR UPDATE")
+                                .bind::<Text, _>(miner_pubkey)
+                                .bind::<Integer, _>(pool_id)
+                                .get_result::<models::MinerCount>(conn)?;
+                            diesel::sql_query("INSERT INTO miners (pubkey, enabled) VALUES (?, true) ON DUPLICATE KEY UPDATE id = id")
+                                .bind::<Text, _>(miner_pubkey)
+                                .execute(conn)?;
+                            diesel::sql_query("INSERT INTO rewards (miner_id, pool_id) SELECT id, ? FROM miners WHERE pubkey = ? ON DUPLICATE KEY UPDATE rewards.id = rewards.id")
+                                .bind::<Integer, _>(pool_id)
+                                .bind::<Text, _>(miner_pubkey)
+                                .execute(conn)?;
+                            results.push((existing_miners.miner_count == 0, existing_rewards.miner_count == 0));
+                        }
+                        Ok(results)
+                    })
                 })
                 .await;
 
             match res {
                 Ok(interaction) => match interaction {
-                    Ok(_query) => {
-                        return Ok(());
+                    Ok(query) => {
+                        return Ok(query);
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -652,16 +960,20 @@ impl AppDatabase {
         };
     }
 
-    pub async fn get_txn_by_sig(&self, sig: String) -> Result<models::TxnId, AppDatabaseError> {
+    pub async fn get_miner_by_pubkey_str(
+        &self,
+        miner_pubkey: String,
+    ) -> Result<Miner, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT id FROM txns WHERE signature = ?")
-                        .bind::<Text, _>(sig)
-                        .get_result::<models::TxnId>(conn)
+                    diesel::sql_query(
+                        "SELECT id, pubkey, enabled FROM miners WHERE miners.pubkey = ?",
+                    )
+                    .bind::<Text, _>(miner_pubkey)
+                    .get_result::<Miner>(conn)
                 })
                 .await;
-
             match res {
                 Ok(interaction) => match interaction {
                     Ok(query) => {
@@ -669,7 +981,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -682,51 +994,929 @@ impl AppDatabase {
         };
     }
 
-    // pub async fn add_new_earning(
-    //     &self,
-    //     earning: models::InsertEarning,
-    // ) -> Result<(), AppDatabaseError> {
-    //     if let Ok(db_conn) = self.connection_pool.get().await {
-    //         let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-    //             diesel::sql_query("INSERT INTO earnings (miner_id, pool_id, challenge_id, amount) VALUES (?, ?, ?, ?)")
-    //             .bind::<Integer, _>(earning.miner_id)
-    //             .bind::<Integer, _>(earning.pool_id)
-    //             .bind::<Integer, _>(earning.challenge_id)
-    //             .bind::<Unsigned<BigInt>, _>(earning.amount)
-    //             .execute(conn)
-    //         }).await;
-
-    //         match res {
-    //             Ok(interaction) => match interaction {
-    //                 Ok(_query) => {
-    //                     return Ok(());
-    //                 }
-    //                 Err(e) => {
-    //                     error!("{:?}", e);
-    //                     return Err(AppDatabaseError::QueryFailed);
-    //                 }
-    //             },
-    //             Err(e) => {
-    //                 error!("{:?}", e);
-    //                 return Err(AppDatabaseError::InteractionFailed);
-    //             }
-    //         }
-    //     } else {
-    //         return Err(AppDatabaseError::FailedToGetConnectionFromPool);
-    //     };
-    // }
-
-    pub async fn add_new_earnings_batch(
+    /// Records a confirmed claim. The reward decrease, the pool's claimed
+    /// counter and the claim row are written in one transaction so a failure
+    /// can't leave the balance decreased without a claim.
+    pub async fn claim_atomic(
         &self,
-        earnings: Vec<models::InsertEarning>,
-    ) -> Result<(), AppDatabaseError> {
+        miner_id: i32,
+        pool_id: i32,
+        txn_id: i32,
+        amount: u64,
+    ) -> Result<models::InsertClaimResult, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    insert_into(crate::schema::earnings::dsl::earnings)
-                        .values(&earnings)
-                        .execute(conn)
-                })
+                    conn.transaction(|conn| {
+                        let updated = diesel::sql_query("UPDATE rewards SET balance = balance - ? WHERE miner_id = ? AND pool_id = ?")
+                            .bind::<Unsigned<BigInt>, _>(amount)
+                            .bind::<Integer, _>(miner_id)
+                            .bind::<Integer, _>(pool_id)
+                            .execute(conn)?;
+                        if updated != 1 {
+                            return Err(diesel::result::Error::NotFound);
+                        }
+                        let updated = diesel::sql_query("UPDATE pools SET claimed_rewards = claimed_rewards + ? WHERE id = ?")
+                            .bind::<Unsigned<BigInt>, _>(amount)
+                            .bind::<Integer, _>(pool_id)
+                            .execute(conn)?;
+                        if updated != 1 {
+                            return Err(diesel::result::Error::NotFound);
+                        }
+                        diesel::sql_query("INSERT INTO claims (miner_id, pool_id, txn_id, amount) VALUES (?, ?, ?, ?)")
+                            .bind::<Integer, _>(miner_id)
+                            .bind::<Integer, _>(pool_id)
+                            .bind::<Integer, _>(txn_id)
+                            .bind::<Unsigned<BigInt>, _>(amount)
+                            .execute(conn)?;
+                        diesel::sql_query("SELECT CAST(LAST_INSERT_ID() AS SIGNED) AS claim_id")
+                            .get_result::<models::InsertClaimResult>(conn)
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(diesel::result::Error::NotFound) => {
+                        error!("Claim for miner {} matched no rewards or pool row", miner_id);
+                        return Err(AppDatabaseError::FailedToUpdateRow);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_last_claim(&self, miner_id: i32, pool_id: i32) -> Result<models::LastClaim, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT created_at FROM claims WHERE miner_id = ? AND pool_id = ? ORDER BY id DESC")
+                        .bind::<Integer, _>(miner_id)
+                        .bind::<Integer, _>(pool_id)
+                        .get_result::<models::LastClaim>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Records a transaction and returns its row id. Adding a signature that
+    /// is already recorded keeps the existing row and returns its id, so
+    /// retries after an insert that did commit are harmless.
+    pub async fn add_new_txn(&self, txn: models::InsertTxn) -> Result<models::TxnId, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        diesel::sql_query(
+                            "INSERT INTO txns (txn_type, signature, priority_fee, pool_id, fee_paid_lamports) VALUES (?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE id = id",
+                        )
+                        .bind::<Text, _>(txn.txn_type)
+                        .bind::<Text, _>(&txn.signature)
+                        .bind::<Unsigned<Integer>, _>(txn.priority_fee)
+                        .bind::<Nullable<Integer>, _>(txn.pool_id)
+                        .bind::<Nullable<Unsigned<BigInt>>, _>(txn.fee_paid_lamports)
+                        .execute(conn)?;
+                        diesel::sql_query("SELECT id FROM txns WHERE signature = ?")
+                            .bind::<Text, _>(&txn.signature)
+                            .get_result::<models::TxnId>(conn)
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Fees paid by the pool's transactions, of one type when txn_type is set.
+    pub async fn get_total_fees_paid(
+        &self,
+        pool_id: i32,
+        txn_type: Option<String>,
+    ) -> Result<models::FeesPaid, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COALESCE(SUM(fee_paid_lamports), 0) AS UNSIGNED) AS fees_paid, CAST(COUNT(*) AS UNSIGNED) AS txn_count, CAST(COUNT(fee_paid_lamports) AS UNSIGNED) AS txns_with_fee FROM txns WHERE pool_id = ? AND (? IS NULL OR txn_type = ?)")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Nullable<Text>, _>(txn_type.clone())
+                        .bind::<Nullable<Text>, _>(txn_type)
+                        .get_result::<models::FeesPaid>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn add_miner_session(
+        &self,
+        miner_session: models::InsertMinerSession,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query(
+                        "INSERT INTO miner_sessions (miner_id, pool_id, device_id, started_at, ended_at, epochs, submissions, best_difficulty, earned) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind::<Integer, _>(miner_session.miner_id)
+                    .bind::<Integer, _>(miner_session.pool_id)
+                    .bind::<Nullable<Text>, _>(miner_session.device_id)
+                    .bind::<Timestamp, _>(miner_session.started_at)
+                    .bind::<Timestamp, _>(miner_session.ended_at)
+                    .bind::<Unsigned<Integer>, _>(miner_session.epochs)
+                    .bind::<Unsigned<Integer>, _>(miner_session.submissions)
+                    .bind::<Unsigned<Integer>, _>(miner_session.best_difficulty)
+                    .bind::<Unsigned<BigInt>, _>(miner_session.earned)
+                    .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn add_audit_entry(
+        &self,
+        entry: models::InsertAuditEntry,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query(
+                        "INSERT INTO audit_log (pool_id, action, pubkey, ip, amount, outcome, error_code, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind::<Integer, _>(entry.pool_id)
+                    .bind::<Text, _>(entry.action)
+                    .bind::<Text, _>(entry.pubkey)
+                    .bind::<Text, _>(entry.ip)
+                    .bind::<Nullable<Unsigned<BigInt>>, _>(entry.amount)
+                    .bind::<Text, _>(entry.outcome)
+                    .bind::<Nullable<Text>, _>(entry.error_code)
+                    .bind::<Timestamp, _>(entry.created_at)
+                    .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Deletes up to `limit` audit entries of the pool created before
+    /// `before`, returns how many were deleted.
+    pub async fn prune_audit_log(
+        &self,
+        pool_id: i32,
+        before: NaiveDateTime,
+        limit: u32,
+    ) -> Result<usize, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("DELETE FROM audit_log WHERE pool_id = ? AND created_at < ? LIMIT ?")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Timestamp, _>(before)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(deleted) => {
+                        return Ok(deleted);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn record_epoch_outcome(
+        &self,
+        epoch_outcome: models::InsertEpochOutcome,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query(
+                        "INSERT INTO epoch_outcomes (pool_id, challenge_id, outcome, attempts_used, final_priority_fee, first_send_ms, confirmed_ms, mine_event_ms, difficulty_histogram) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind::<Integer, _>(epoch_outcome.pool_id)
+                    .bind::<Integer, _>(epoch_outcome.challenge_id)
+                    .bind::<Text, _>(epoch_outcome.outcome.as_str())
+                    .bind::<Unsigned<TinyInt>, _>(epoch_outcome.attempts_used)
+                    .bind::<Unsigned<BigInt>, _>(epoch_outcome.final_priority_fee)
+                    .bind::<Nullable<Unsigned<Integer>>, _>(epoch_outcome.first_send_ms)
+                    .bind::<Nullable<Unsigned<Integer>>, _>(epoch_outcome.confirmed_ms)
+                    .bind::<Nullable<Unsigned<Integer>>, _>(epoch_outcome.mine_event_ms)
+                    .bind::<Nullable<Text>, _>(epoch_outcome.difficulty_histogram)
+                    .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn update_challenge_distribution(
+        &self,
+        challenge_id: i32,
+        txn_id: Option<i32>,
+        commission: u64,
+        total_hashpower: u64,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("UPDATE challenges SET txn_id = ?, commission = ?, total_hashpower = ? WHERE id = ?")
+                .bind::<Nullable<Integer>, _>(txn_id)
+                .bind::<Unsigned<BigInt>, _>(commission)
+                .bind::<Unsigned<BigInt>, _>(total_hashpower)
+                .bind::<Integer, _>(challenge_id)
+                .execute(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        if query != 1 {
+                            return Err(AppDatabaseError::FailedToUpdateRow);
+                        }
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Inserts the earnings, skipping those of a miner and challenge that
+    /// already has one. Safe to retry after an error, a batch that was
+    /// written already is skipped. update_rewards isn't idempotent, callers
+    /// must only credit the miners that aren't in skipped_miner_ids.
+    pub async fn add_new_earnings_batch(
+        &self,
+        earnings: Vec<models::InsertEarning>,
+    ) -> Result<models::BatchInsertResult, AppDatabaseError> {
+        use crate::schema::earnings::dsl;
+
+        let total = earnings.len();
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        // diesel connects with CLIENT_FOUND_ROWS, a duplicate counts as an
+                        // affected row, so the existing ones are locked and left out instead
+                        let mut challenge_ids: Vec<i32> = earnings.iter().map(|e| e.challenge_id).collect();
+                        challenge_ids.sort_unstable();
+                        challenge_ids.dedup();
+                        let existing: HashSet<(i32, i32)> = dsl::earnings
+                            .filter(dsl::challenge_id.eq_any(challenge_ids))
+                            .select((dsl::miner_id, dsl::challenge_id))
+                            .for_update()
+                            .load::<(i32, i32)>(conn)?
+                            .into_iter()
+                            .collect();
+                        let (skipped, new): (Vec<models::InsertEarning>, Vec<models::InsertEarning>) = earnings
+                            .into_iter()
+                            .partition(|e| existing.contains(&(e.miner_id, e.challenge_id)));
+                        if !new.is_empty() {
+                            insert_into(dsl::earnings)
+                                .values(&new)
+                                .on_conflict(diesel::dsl::DuplicatedKeys)
+                                .do_update()
+                                .set(dsl::id.eq(dsl::id))
+                                .execute(conn)?;
+                        }
+                        Ok(skipped.into_iter().map(|e| e.miner_id).collect::<Vec<i32>>())
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(skipped_miner_ids) => {
+                        let skipped = skipped_miner_ids.len();
+                        if skipped > 0 {
+                            warn!("Skipped {} of {} earnings already in db, the batch was retried", skipped, total);
+                        }
+                        return Ok(models::BatchInsertResult {
+                            inserted: total - skipped,
+                            skipped,
+                            skipped_miner_ids,
+                        });
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_challenge_distribution(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+    ) -> Result<Option<models::ChallengeDistribution>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT c.id, c.rewards_earned, c.commission, c.total_hashpower, t.signature AS txn_signature, c.redistributed_at FROM challenges c LEFT JOIN txns t ON c.txn_id = t.id WHERE c.id = ? AND c.pool_id = ?")
+                .bind::<Integer, _>(challenge_id)
+                .bind::<Integer, _>(pool_id)
+                .load::<models::ChallengeDistribution>(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Every miner that submitted for or earned in the challenge.
+    pub async fn get_challenge_miner_shares(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+    ) -> Result<Vec<models::ChallengeMinerShare>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT m.id AS miner_id, m.pubkey, s.best_difficulty, e.recorded, e.recorded_hashpower FROM miners m LEFT JOIN (SELECT miner_id, MAX(difficulty) AS best_difficulty FROM submissions WHERE challenge_id = ? GROUP BY miner_id) s ON s.miner_id = m.id LEFT JOIN (SELECT miner_id, CAST(SUM(amount) AS UNSIGNED) AS recorded, CAST(SUM(hashpower) AS UNSIGNED) AS recorded_hashpower FROM earnings WHERE challenge_id = ? AND pool_id = ? GROUP BY miner_id) e ON e.miner_id = m.id WHERE s.miner_id IS NOT NULL OR e.miner_id IS NOT NULL ORDER BY m.id")
+                        .bind::<Integer, _>(challenge_id)
+                        .bind::<Integer, _>(challenge_id)
+                        .bind::<Integer, _>(pool_id)
+                        .load::<models::ChallengeMinerShare>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Inserts the missing earnings of a challenge and credits the balances
+    /// in one transaction, and sets the challenge's redistributed_at. Fails
+    /// with DuplicateEntry, applying nothing, when one of the miners got an
+    /// earnings row for the challenge in the meantime, or with
+    /// first_redistribution when the challenge was already redistributed.
+    pub async fn apply_challenge_redistribution(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+        earnings: Vec<models::InsertEarning>,
+        credits: Vec<models::UpdateReward>,
+        first_redistribution: bool,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        // marks the challenge in the same transaction as the
+                        // credits, so a repeat can tell they were applied
+                        let marked = diesel::sql_query("UPDATE challenges SET redistributed_at = COALESCE(redistributed_at, NOW()) WHERE id = ? AND pool_id = ? AND (redistributed_at IS NULL OR ?)")
+                            .bind::<Integer, _>(challenge_id)
+                            .bind::<Integer, _>(pool_id)
+                            .bind::<Bool, _>(!first_redistribution)
+                            .execute(conn)?;
+                        if first_redistribution && marked != 1 {
+                            return Err(diesel::result::Error::RollbackTransaction);
+                        }
+                        for earning in earnings {
+                            let inserted = diesel::sql_query("INSERT INTO earnings (miner_id, pool_id, challenge_id, amount, hashpower) SELECT ?, ?, ?, ?, ? FROM DUAL WHERE NOT EXISTS (SELECT 1 FROM earnings WHERE miner_id = ? AND challenge_id = ? AND pool_id = ?)")
+                                .bind::<Integer, _>(earning.miner_id)
+                                .bind::<Integer, _>(earning.pool_id)
+                                .bind::<Integer, _>(earning.challenge_id)
+                                .bind::<Unsigned<BigInt>, _>(earning.amount)
+                                .bind::<Unsigned<BigInt>, _>(earning.hashpower)
+                                .bind::<Integer, _>(earning.miner_id)
+                                .bind::<Integer, _>(earning.challenge_id)
+                                .bind::<Integer, _>(earning.pool_id)
+                                .execute(conn)?;
+                            if inserted != 1 {
+                                return Err(diesel::result::Error::RollbackTransaction);
+                            }
+                        }
+                        for credit in credits {
+                            let updated = diesel::sql_query("UPDATE rewards SET balance = balance + ? WHERE miner_id = ? AND pool_id = ?")
+                                .bind::<Unsigned<BigInt>, _>(credit.balance)
+                                .bind::<Integer, _>(credit.miner_id)
+                                .bind::<Integer, _>(pool_id)
+                                .execute(conn)?;
+                            if updated != 1 {
+                                return Err(diesel::result::Error::NotFound);
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(()) => {
+                        return Ok(());
+                    }
+                    Err(diesel::result::Error::RollbackTransaction) => {
+                        return Err(AppDatabaseError::DuplicateEntry);
+                    }
+                    Err(diesel::result::Error::NotFound) => {
+                        error!("Redistribution credited a miner without a rewards row in pool {}", pool_id);
+                        return Err(AppDatabaseError::FailedToUpdateRow);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_submissions(&self, pubkey: String) -> Result<Vec<Submission>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.* FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE m.pubkey = ? ORDER BY s.created_at DESC LIMIT 100")
+                        .bind::<Text, _>(pubkey)
+                        .load::<Submission>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn upsert_miner_settings(
+        &self,
+        miner_id: i32,
+        notify_url: Option<String>,
+        min_notify_amount: u64,
+        auto_claim_threshold: Option<u64>,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    // Saving settings re-enables notifications that were auto-disabled.
+                    diesel::sql_query("INSERT INTO miner_settings (miner_id, notify_url, min_notify_amount, auto_claim_threshold) VALUES (?, ?, ?, ?) ON DUPLICATE KEY UPDATE notify_url = VALUES(notify_url), min_notify_amount = VALUES(min_notify_amount), auto_claim_threshold = VALUES(auto_claim_threshold), notifications_enabled = true, consecutive_failures = 0, disabled_reason = NULL")
+                        .bind::<Integer, _>(miner_id)
+                        .bind::<Nullable<Text>, _>(notify_url)
+                        .bind::<Unsigned<BigInt>, _>(min_notify_amount)
+                        .bind::<Nullable<Unsigned<BigInt>>, _>(auto_claim_threshold)
+                        .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn write_replication_heartbeat(
+        &self,
+        pool_id: i32,
+        beat_at: NaiveDateTime,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("INSERT INTO replication_heartbeats (pool_id, beat_at) VALUES (?, ?) ON DUPLICATE KEY UPDATE beat_at = VALUES(beat_at)")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Timestamp, _>(beat_at)
+                        .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Updates the miner's reputation with a valid or invalid solution.
+    pub async fn record_miner_solution(&self, miner_id: i32, valid: bool) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    if valid {
+                        diesel::sql_query(format!("INSERT INTO miner_reputations (miner_id, reputation_score, total_valid_submissions) VALUES (?, 1, 1) ON DUPLICATE KEY UPDATE reputation_score = LEAST({} + 1, ?), total_valid_submissions = total_valid_submissions + 1, consecutive_invalid = 0", recovered_reputation_score()))
+                            .bind::<Integer, _>(miner_id)
+                            .bind::<Integer, _>(MAX_REPUTATION_SCORE)
+                            .execute(conn)
+                    } else {
+                        diesel::sql_query(format!("INSERT INTO miner_reputations (miner_id, reputation_score, total_invalid_submissions, consecutive_invalid) VALUES (?, ?, 1, 1) ON DUPLICATE KEY UPDATE reputation_score = GREATEST({} - ?, ?), total_invalid_submissions = total_invalid_submissions + 1, consecutive_invalid = LEAST(consecutive_invalid + 1, 255)", recovered_reputation_score()))
+                            .bind::<Integer, _>(miner_id)
+                            .bind::<Integer, _>(-INVALID_SOLUTION_PENALTY)
+                            .bind::<Integer, _>(INVALID_SOLUTION_PENALTY)
+                            .bind::<Integer, _>(MIN_REPUTATION_SCORE)
+                            .execute(conn)
+                    }
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// The miner's reputation, None before its first solution. A negative
+    /// score includes its recovery since the last update.
+    pub async fn get_miner_reputation(
+        &self,
+        miner_id: i32,
+    ) -> Result<Option<models::MinerReputation>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query(format!("SELECT miner_id, {} AS reputation_score, total_valid_submissions, total_invalid_submissions, consecutive_invalid, last_updated FROM miner_reputations WHERE miner_id = ?", recovered_reputation_score()))
+                        .bind::<Integer, _>(miner_id)
+                        .load::<models::MinerReputation>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Enabled notify settings of the given miners whose balance is at or
+    /// above their min_notify_amount.
+    pub async fn get_notify_settings(
+        &self,
+        pool_id: i32,
+        miner_ids: Vec<i32>,
+    ) -> Result<Vec<models::NotifySetting>, AppDatabaseError> {
+        if miner_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    let mut settings = Vec::new();
+                    for miner_ids in miner_ids.chunks(NOTIFY_SETTINGS_BATCH_SIZE) {
+                        let placeholders = vec!["?"; miner_ids.len()].join(", ");
+                        let mut query = diesel::sql_query(format!("SELECT ms.miner_id, m.pubkey, ms.notify_url, ms.min_notify_amount, r.balance FROM miner_settings ms JOIN miners m ON ms.miner_id = m.id JOIN rewards r ON ms.miner_id = r.miner_id WHERE r.pool_id = ? AND ms.notifications_enabled = true AND ms.notify_url IS NOT NULL AND r.balance >= ms.min_notify_amount AND ms.miner_id IN ({})", placeholders))
+                            .into_boxed()
+                            .bind::<Integer, _>(pool_id);
+                        for miner_id in miner_ids {
+                            query = query.bind::<Integer, _>(*miner_id);
+                        }
+                        settings.extend(query.load::<models::NotifySetting>(conn)?);
+                    }
+                    Ok(settings)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// The miner's notify setting, None when it has no enabled notify_url.
+    pub async fn get_miner_notify_setting(
+        &self,
+        miner_id: i32,
+        pool_id: i32,
+    ) -> Result<Option<models::NotifySetting>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT ms.miner_id, m.pubkey, ms.notify_url, ms.min_notify_amount, r.balance FROM miner_settings ms JOIN miners m ON ms.miner_id = m.id JOIN rewards r ON ms.miner_id = r.miner_id WHERE ms.miner_id = ? AND r.pool_id = ? AND ms.notifications_enabled = true AND ms.notify_url IS NOT NULL")
+                        .bind::<Integer, _>(miner_id)
+                        .bind::<Integer, _>(pool_id)
+                        .load::<models::NotifySetting>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Counts a failed webhook delivery for the miner, returns true if this
+    /// failure disabled their notifications.
+    pub async fn record_notify_failure(
+        &self,
+        miner_id: i32,
+        max_failures: u32,
+    ) -> Result<bool, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("UPDATE miner_settings SET consecutive_failures = consecutive_failures + 1 WHERE miner_id = ?")
+                        .bind::<Integer, _>(miner_id)
+                        .execute(conn)?;
+                    diesel::sql_query("UPDATE miner_settings SET notifications_enabled = false, disabled_reason = ? WHERE miner_id = ? AND notifications_enabled = true AND consecutive_failures >= ?")
+                        .bind::<Text, _>(format!("Disabled after {} consecutive failed deliveries", max_failures))
+                        .bind::<Integer, _>(miner_id)
+                        .bind::<Unsigned<Integer>, _>(max_failures)
+                        .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query > 0);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn reset_notify_failures(&self, miner_id: i32) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("UPDATE miner_settings SET consecutive_failures = 0 WHERE miner_id = ? AND consecutive_failures > 0")
+                        .bind::<Integer, _>(miner_id)
+                        .execute(conn)
+                })
                 .await;
 
             match res {
@@ -736,7 +1926,7 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -749,13 +1939,142 @@ impl AppDatabase {
         };
     }
 
-    pub async fn get_miner_submissions(&self, pubkey: String) -> Result<Vec<Submission>, AppDatabaseError> {
+    pub async fn get_pool_config(
+        &self,
+        pool_id: i32,
+    ) -> Result<Vec<models::PoolConfigEntry>, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT s.* FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE m.pubkey = ? ORDER BY s.created_at DESC LIMIT 100")
-                        .bind::<Text, _>(pubkey)
-                        .load::<Submission>(conn)
+                    diesel::sql_query("SELECT config_key, config_value FROM pool_config WHERE pool_id = ?")
+                        .bind::<Integer, _>(pool_id)
+                        .load::<models::PoolConfigEntry>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Inserts any missing config keys, existing values are left untouched.
+    pub async fn add_pool_config_defaults(
+        &self,
+        pool_id: i32,
+        entries: Vec<(String, String)>,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        for (key, value) in entries {
+                            diesel::sql_query("INSERT IGNORE INTO pool_config (pool_id, config_key, config_value) VALUES (?, ?, ?)")
+                                .bind::<Integer, _>(pool_id)
+                                .bind::<Text, _>(key)
+                                .bind::<Text, _>(value)
+                                .execute(conn)?;
+                        }
+                        diesel::QueryResult::Ok(())
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Applies all config changes and their audit rows in a single transaction.
+    pub async fn update_pool_config(
+        &self,
+        pool_id: i32,
+        changes: Vec<models::ConfigChange>,
+        changed_by: String,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    conn.transaction(|conn| {
+                        for change in changes {
+                            diesel::sql_query("INSERT INTO pool_config (pool_id, config_key, config_value) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE config_value = VALUES(config_value)")
+                                .bind::<Integer, _>(pool_id)
+                                .bind::<Text, _>(&change.config_key)
+                                .bind::<Text, _>(&change.new_value)
+                                .execute(conn)?;
+                            diesel::sql_query("INSERT INTO config_history (pool_id, config_key, old_value, new_value, changed_by) VALUES (?, ?, ?, ?, ?)")
+                                .bind::<Integer, _>(pool_id)
+                                .bind::<Text, _>(&change.config_key)
+                                .bind::<Nullable<Text>, _>(&change.old_value)
+                                .bind::<Text, _>(&change.new_value)
+                                .bind::<Text, _>(&changed_by)
+                                .execute(conn)?;
+                        }
+                        diesel::QueryResult::Ok(())
+                    })
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Returns the miner a hot key is actively delegated to.
+    pub async fn get_delegated_miner(
+        &self,
+        delegate_pubkey: String,
+    ) -> Result<Miner, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT m.id, m.pubkey, m.enabled FROM miner_delegates d JOIN miners m ON d.miner_id = m.id WHERE d.delegate_pubkey = ? AND d.revoked_at IS NULL")
+                        .bind::<Text, _>(delegate_pubkey)
+                        .get_result::<Miner>(conn)
                 })
                 .await;
 
@@ -766,7 +2085,80 @@ impl AppDatabase {
                     }
                     Err(e) => {
                         error!("{:?}", e);
-                        return Err(AppDatabaseError::QueryFailed);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn add_miner_delegate(
+        &self,
+        miner_id: i32,
+        delegate_pubkey: String,
+        authorization_signature: String,
+    ) -> Result<(), AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("INSERT INTO miner_delegates (miner_id, delegate_pubkey, authorization_signature) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE miner_id = VALUES(miner_id), authorization_signature = VALUES(authorization_signature), revoked_at = NULL")
+                        .bind::<Integer, _>(miner_id)
+                        .bind::<Text, _>(delegate_pubkey)
+                        .bind::<Text, _>(authorization_signature)
+                        .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(_query) => {
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Revokes an active delegation, returns false if there was none.
+    pub async fn revoke_miner_delegate(
+        &self,
+        miner_id: i32,
+        delegate_pubkey: String,
+    ) -> Result<bool, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("UPDATE miner_delegates SET revoked_at = CURRENT_TIMESTAMP WHERE miner_id = ? AND delegate_pubkey = ? AND revoked_at IS NULL")
+                        .bind::<Integer, _>(miner_id)
+                        .bind::<Text, _>(delegate_pubkey)
+                        .execute(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query > 0);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(query_error(e));
                     }
                 },
                 Err(e) => {
@@ -779,3 +2171,493 @@ impl AppDatabase {
         };
     }
 }
+
+// These run against the migrated database in DATABASE_URL and are skipped
+// without one. Each test creates its own pool and miners.
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::future::join_all;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use super::*;
+
+    fn test_database() -> Option<Arc<AppDatabase>> {
+        match std::env::var("DATABASE_URL") {
+            Ok(url) => Some(Arc::new(AppDatabase::new(url))),
+            Err(_) => {
+                eprintln!("DATABASE_URL is not set, skipping");
+                None
+            }
+        }
+    }
+
+    fn random_pubkey() -> String {
+        Keypair::new().pubkey().to_string()
+    }
+
+    async fn add_test_pool(app_database: &AppDatabase) -> i32 {
+        let authority = random_pubkey();
+        app_database
+            .add_new_pool(authority.clone(), random_pubkey())
+            .await
+            .unwrap();
+        app_database
+            .get_pool_by_authority_pubkey(authority)
+            .await
+            .unwrap()
+            .id
+    }
+
+    async fn add_test_txn(app_database: &AppDatabase, pool_id: i32) -> i32 {
+        app_database
+            .add_new_txn(models::InsertTxn {
+                txn_type: "claim".to_string(),
+                signature: Keypair::new().sign_message(b"test").to_string(),
+                priority_fee: 0,
+                pool_id: Some(pool_id),
+                fee_paid_lamports: None,
+            })
+            .await
+            .unwrap()
+            .id
+    }
+
+    async fn balance(app_database: &AppDatabase, pubkey: &str, pool_id: i32) -> u64 {
+        app_database
+            .get_miner_rewards(pubkey.to_string(), pool_id)
+            .await
+            .unwrap()
+            .balance
+    }
+
+    #[tokio::test]
+    async fn concurrent_claims_and_reward_batches_lose_no_updates() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let mut miners = Vec::new();
+        for _ in 0..3 {
+            let pubkey = random_pubkey();
+            let miner = app_database.signup_miner(pubkey.clone(), pool_id, None).await.unwrap();
+            miners.push((pubkey, miner.id));
+        }
+        let batch = |amount: u64| -> Vec<models::UpdateReward> {
+            miners
+                .iter()
+                .map(|(_, miner_id)| models::UpdateReward {
+                    miner_id: *miner_id,
+                    balance: amount,
+                })
+                .collect()
+        };
+        app_database.update_rewards(batch(1_000_000), pool_id).await.unwrap();
+        // parked before the race, applied by one of the batches
+        app_database.add_pending_rewards(batch(5), pool_id).await.unwrap();
+
+        const ROUNDS: u64 = 10;
+        let mut tasks = Vec::new();
+        for _ in 0..ROUNDS {
+            for (_, miner_id) in miners.clone() {
+                let app_database = app_database.clone();
+                tasks.push(tokio::spawn(async move {
+                    let txn_id = add_test_txn(&app_database, pool_id).await;
+                    app_database.claim_atomic(miner_id, pool_id, txn_id, 100).await.unwrap();
+                }));
+            }
+            let app_database = app_database.clone();
+            let batch = batch(7);
+            tasks.push(tokio::spawn(async move {
+                // what the mine success handler does with a batch
+                if app_database.update_rewards(batch.clone(), pool_id).await.is_err() {
+                    app_database.add_pending_rewards(batch, pool_id).await.unwrap();
+                }
+                app_database.apply_pending_rewards(pool_id).await.unwrap();
+            }));
+        }
+        for task in join_all(tasks).await {
+            task.unwrap();
+        }
+        app_database.apply_pending_rewards(pool_id).await.unwrap();
+
+        for (pubkey, _) in &miners {
+            assert_eq!(
+                balance(&app_database, pubkey, pool_id).await,
+                1_000_000 + 5 + ROUNDS * 7 - ROUNDS * 100
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_miner_is_credited_on_a_second_pool() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let first_pool = add_test_pool(&app_database).await;
+        let second_pool = add_test_pool(&app_database).await;
+        let pubkey = random_pubkey();
+        let miner = app_database.signup_miner(pubkey.clone(), first_pool, None).await.unwrap();
+        let earned = vec![models::UpdateReward {
+            miner_id: miner.id,
+            balance: 42,
+        }];
+
+        // without a rewards row in the second pool nothing is credited or dropped
+        assert!(matches!(
+            app_database.update_rewards(earned.clone(), second_pool).await,
+            Err(AppDatabaseError::FailedToUpdateRow)
+        ));
+        app_database.add_pending_rewards(earned.clone(), second_pool).await.unwrap();
+        assert!(matches!(
+            app_database.apply_pending_rewards(second_pool).await,
+            Err(AppDatabaseError::FailedToUpdateRow)
+        ));
+
+        // what ws_handler does when the miner connects to the second pool
+        let new_reward = InsertReward {
+            miner_id: miner.id,
+            pool_id: second_pool,
+        };
+        app_database.add_new_reward(new_reward.clone()).await.unwrap();
+        app_database.add_new_reward(new_reward).await.unwrap();
+
+        app_database.update_rewards(earned, second_pool).await.unwrap();
+        assert!(app_database.apply_pending_rewards(second_pool).await.unwrap());
+        assert_eq!(balance(&app_database, &pubkey, second_pool).await, 84);
+        assert_eq!(balance(&app_database, &pubkey, first_pool).await, 0);
+    }
+
+    #[tokio::test]
+    async fn referral_bonuses_are_paid_out_of_the_commission() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let referrer = random_pubkey();
+        let referrer_id = app_database.signup_miner(referrer.clone(), pool_id, None).await.unwrap().id;
+        let mut bonuses = Vec::new();
+        for referrer_miner_id in [Some(referrer_id), Some(referrer_id), None] {
+            let miner = app_database.signup_miner(random_pubkey(), pool_id, referrer_miner_id).await.unwrap();
+            bonuses.push(models::ReferralBonus {
+                referred_miner_id: miner.id,
+                amount: 30,
+            });
+        }
+
+        // the second bonus is cut to what's left of the commission
+        let credited = app_database
+            .credit_referral_bonuses(pool_id, bonuses.clone(), 50)
+            .await
+            .unwrap();
+        assert_eq!(credited, 50);
+        assert_eq!(balance(&app_database, &referrer, pool_id).await, 50);
+
+        // the miner without a referrer doesn't use up the commission
+        let credited = app_database
+            .credit_referral_bonuses(pool_id, bonuses, 1_000)
+            .await
+            .unwrap();
+        assert_eq!(credited, 60);
+        assert_eq!(balance(&app_database, &referrer, pool_id).await, 110);
+    }
+
+    #[tokio::test]
+    async fn an_existing_miner_cant_add_a_referrer() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let referrer = random_pubkey();
+        let referrer_id = app_database.signup_miner(referrer.clone(), pool_id, None).await.unwrap().id;
+        let pubkey = random_pubkey();
+        let miner = app_database.signup_miner(pubkey.clone(), pool_id, None).await.unwrap();
+
+        let signed_up_again = app_database
+            .signup_miner(pubkey, pool_id, Some(referrer_id))
+            .await
+            .unwrap();
+        assert_eq!(signed_up_again.id, miner.id);
+
+        let bonus = models::ReferralBonus {
+            referred_miner_id: miner.id,
+            amount: 30,
+        };
+        let credited = app_database
+            .credit_referral_bonuses(pool_id, vec![bonus], 1_000)
+            .await
+            .unwrap();
+        assert_eq!(credited, 0);
+        assert_eq!(balance(&app_database, &referrer, pool_id).await, 0);
+    }
+
+    // the miner_count column of a COUNT query with one text parameter
+    async fn count(app_database: &AppDatabase, query: String, pubkey: String) -> u64 {
+        let db_conn = app_database.connection_pool.get().await.unwrap();
+        db_conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query(query)
+                    .bind::<Text, _>(pubkey)
+                    .get_result::<models::MinerCount>(conn)
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .miner_count
+    }
+
+    #[tokio::test]
+    async fn concurrent_signups_create_one_miner() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let pubkey = random_pubkey();
+
+        let signups = (0..8).map(|_| {
+            let app_database = app_database.clone();
+            let pubkey = pubkey.clone();
+            tokio::spawn(async move { app_database.signup_miner(pubkey, pool_id, None).await })
+        });
+        let miners: Vec<Miner> = join_all(signups)
+            .await
+            .into_iter()
+            .map(|signup| signup.unwrap().unwrap())
+            .collect();
+
+        assert!(miners.iter().all(|miner| miner.id == miners[0].id && miner.pubkey == pubkey));
+        let miner_rows = count(
+            &app_database,
+            "SELECT CAST(COUNT(*) AS UNSIGNED) AS miner_count FROM miners WHERE pubkey = ?".to_string(),
+            pubkey.clone(),
+        )
+        .await;
+        let reward_rows = count(
+            &app_database,
+            format!("SELECT CAST(COUNT(*) AS UNSIGNED) AS miner_count FROM rewards r JOIN miners m ON r.miner_id = m.id WHERE m.pubkey = ? AND r.pool_id = {}", pool_id),
+            pubkey.clone(),
+        )
+        .await;
+        assert_eq!((miner_rows, reward_rows), (1, 1));
+        assert_eq!(balance(&app_database, &pubkey, pool_id).await, 0);
+    }
+
+    #[tokio::test]
+    async fn adding_a_txn_twice_keeps_one_row() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let signature = Keypair::new().sign_message(b"test").to_string();
+        let txn = models::InsertTxn {
+            txn_type: "mine".to_string(),
+            signature: signature.clone(),
+            priority_fee: 0,
+            pool_id: Some(pool_id),
+            fee_paid_lamports: Some(5000),
+        };
+
+        let first = app_database.add_new_txn(txn.clone()).await.unwrap().id;
+        let retried = app_database.add_new_txn(txn.clone()).await.unwrap().id;
+        let concurrent = join_all((0..4).map(|_| {
+            let app_database = app_database.clone();
+            let txn = txn.clone();
+            tokio::spawn(async move { app_database.add_new_txn(txn).await })
+        }))
+        .await;
+
+        assert_eq!(retried, first);
+        for txn_id in concurrent {
+            assert_eq!(txn_id.unwrap().unwrap().id, first);
+        }
+        let rows = count(
+            &app_database,
+            "SELECT CAST(COUNT(*) AS UNSIGNED) AS miner_count FROM txns WHERE signature = ?".to_string(),
+            signature,
+        )
+        .await;
+        assert_eq!(rows, 1);
+    }
+
+    async fn reputation_score(app_database: &AppDatabase, miner_id: i32) -> i32 {
+        app_database
+            .get_miner_reputation(miner_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .reputation_score
+    }
+
+    #[tokio::test]
+    async fn invalid_solutions_bottom_out_at_the_floor() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let miner = app_database.signup_miner(random_pubkey(), pool_id, None).await.unwrap();
+
+        for _ in 0..(2 * -MIN_REPUTATION_SCORE / INVALID_SOLUTION_PENALTY) {
+            app_database.record_miner_solution(miner.id, false).await.unwrap();
+        }
+        assert_eq!(reputation_score(&app_database, miner.id).await, MIN_REPUTATION_SCORE);
+    }
+
+    #[tokio::test]
+    async fn a_negative_reputation_recovers_over_time() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let miner = app_database.signup_miner(random_pubkey(), pool_id, None).await.unwrap();
+        for _ in 0..4 {
+            app_database.record_miner_solution(miner.id, false).await.unwrap();
+        }
+        assert_eq!(reputation_score(&app_database, miner.id).await, -20);
+
+        // three hours without a solution
+        let miner_id = miner.id;
+        let db_conn = app_database.connection_pool.get().await.unwrap();
+        db_conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("UPDATE miner_reputations SET last_updated = last_updated - INTERVAL 3 HOUR WHERE miner_id = ?")
+                    .bind::<Integer, _>(miner_id)
+                    .execute(conn)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reputation_score(&app_database, miner.id).await, -20 + 3 * REPUTATION_RECOVERY_PER_HOUR);
+
+        // the recovery is kept when the score is next updated
+        app_database.record_miner_solution(miner.id, true).await.unwrap();
+        assert_eq!(reputation_score(&app_database, miner.id).await, -20 + 3 * REPUTATION_RECOVERY_PER_HOUR + 1);
+
+        // and never lifts a score past 0
+        app_database.record_miner_solution(miner.id, false).await.unwrap();
+        let db_conn = app_database.connection_pool.get().await.unwrap();
+        db_conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("UPDATE miner_reputations SET last_updated = last_updated - INTERVAL 100 HOUR WHERE miner_id = ?")
+                    .bind::<Integer, _>(miner_id)
+                    .execute(conn)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reputation_score(&app_database, miner.id).await, 0);
+    }
+
+    #[tokio::test]
+    async fn a_missing_pool_authority_is_none() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let authority = random_pubkey();
+        app_database
+            .add_new_pool(authority.clone(), random_pubkey())
+            .await
+            .unwrap();
+
+        let pool = app_database.find_pool_by_authority_pubkey(authority.clone()).await.unwrap();
+        let pool_id = app_database.get_pool_by_authority_pubkey(authority).await.unwrap().id;
+        assert_eq!(pool.map(|pool| pool.id), Some(pool_id));
+        assert!(app_database
+            .find_pool_by_authority_pubkey(random_pubkey())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn notify_settings_are_loaded_for_the_given_miners_over_their_threshold() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let mut miner_ids = Vec::new();
+        for _ in 0..3 {
+            let miner = app_database.signup_miner(random_pubkey(), pool_id, None).await.unwrap();
+            app_database
+                .upsert_miner_settings(miner.id, Some("https://example.com".to_string()), 100, None)
+                .await
+                .unwrap();
+            miner_ids.push(miner.id);
+        }
+        let earned = miner_ids
+            .iter()
+            .zip([100, 99, 500])
+            .map(|(miner_id, balance)| models::UpdateReward {
+                miner_id: *miner_id,
+                balance,
+            })
+            .collect();
+        app_database.update_rewards(earned, pool_id).await.unwrap();
+
+        let settings = app_database
+            .get_notify_settings(pool_id, miner_ids[..2].to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            settings.iter().map(|setting| setting.miner_id).collect::<Vec<_>>(),
+            vec![miner_ids[0]]
+        );
+        assert!(app_database.get_notify_settings(pool_id, Vec::new()).await.unwrap().is_empty());
+    }
+
+    #[derive(diesel::QueryableByName)]
+    struct ChallengeTxnId {
+        #[diesel(sql_type = Nullable<Integer>)]
+        txn_id: Option<i32>,
+    }
+
+    async fn challenge_txn_id(app_database: &AppDatabase, challenge_id: i32) -> Option<i32> {
+        let db_conn = app_database.connection_pool.get().await.unwrap();
+        db_conn
+            .interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT txn_id FROM challenges WHERE id = ?")
+                    .bind::<Integer, _>(challenge_id)
+                    .get_result::<ChallengeTxnId>(conn)
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .txn_id
+    }
+
+    #[tokio::test]
+    async fn a_distribution_records_the_txn_it_was_given() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let pool_id = add_test_pool(&app_database).await;
+        let mut challenge_ids = Vec::new();
+        for _ in 0..2 {
+            let challenge = Keypair::new().pubkey().to_bytes().to_vec();
+            app_database
+                .add_new_challenge(models::InsertChallenge {
+                    pool_id,
+                    challenge: challenge.clone(),
+                    rewards_earned: None,
+                    started_at: chrono::Utc::now().naive_utc(),
+                })
+                .await
+                .unwrap();
+            challenge_ids.push(app_database.get_challenge_by_challenge(challenge).await.unwrap().id);
+        }
+        let txn_id = add_test_txn(&app_database, pool_id).await;
+
+        app_database
+            .update_challenge_distribution(challenge_ids[0], Some(txn_id), 5, 100)
+            .await
+            .unwrap();
+        assert_eq!(challenge_txn_id(&app_database, challenge_ids[0]).await, Some(txn_id));
+
+        // a txn that was never recorded leaves the challenge without one
+        app_database
+            .update_challenge_distribution(challenge_ids[1], None, 5, 100)
+            .await
+            .unwrap();
+        assert_eq!(challenge_txn_id(&app_database, challenge_ids[1]).await, None);
+    }
+}
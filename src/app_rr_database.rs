@@ -2,14 +2,13 @@ use deadpool_diesel::{
     mysql::{Manager, Pool},
 };
 use diesel::{
-    connection::SimpleConnection,
-    insert_into,
-    sql_types::{BigInt, Binary, Bool, Integer, Nullable, Text, TinyInt, Unsigned},
+    sql_types::{BigInt, Binary, Integer, Nullable, SmallInt, Text, Timestamp, Unsigned},
     MysqlConnection, RunQueryDsl,
 };
-use tracing::{error, info};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use tracing::error;
 
-use crate::{app_database::AppDatabaseError, models, InsertReward, Miner, Submission, SubmissionWithId, SubmissionWithPubkey};
+use crate::{app_database::AppDatabaseError, coal_utils::amount_to_coal, hashpower_for_difficulty, models, Submission, SubmissionWithPubkey};
 
 pub struct AppRRDatabase {
     connection_pool: Pool,
@@ -32,7 +31,7 @@ impl AppRRDatabase {
     ) -> Result<models::Challenge, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("SELECT id, pool_id, submission_id, challenge, rewards_earned FROM challenges WHERE challenges.challenge = ?")
+                diesel::sql_query("SELECT id, pool_id, submission_id, challenge, rewards_earned, started_at, ended_at FROM challenges WHERE challenges.challenge = ?")
                 .bind::<Binary, _>(challenge)
                 .get_result::<models::Challenge>(conn)
             }).await;
@@ -57,14 +56,326 @@ impl AppRRDatabase {
         };
     }
 
+    pub async fn get_challenge_by_id(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+    ) -> Result<Option<models::Challenge>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT id, pool_id, submission_id, challenge, rewards_earned, started_at, ended_at FROM challenges WHERE id = ? AND pool_id = ?")
+                .bind::<Integer, _>(challenge_id)
+                .bind::<Integer, _>(pool_id)
+                .load::<models::Challenge>(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Most recent challenges of the pool first, with their submission stats.
+    pub async fn get_challenges_paginated(
+        &self,
+        pool_id: i32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<models::ChallengeDetail>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT c.id AS challenge_id, LOWER(HEX(LEFT(c.challenge, 8))) AS challenge_hex_prefix, c.started_at, c.ended_at, TIMESTAMPDIFF(SECOND, c.started_at, c.ended_at) AS duration_secs, c.rewards_earned, CAST(COUNT(s.id) AS UNSIGNED) AS submission_count, MAX(s.difficulty) AS best_difficulty, CAST(COUNT(DISTINCT s.miner_id) AS UNSIGNED) AS participating_miners FROM (SELECT id, challenge, started_at, ended_at, rewards_earned FROM challenges WHERE pool_id = ? ORDER BY id DESC LIMIT ? OFFSET ?) c LEFT JOIN submissions s ON s.challenge_id = c.id GROUP BY c.id, c.challenge, c.started_at, c.ended_at, c.rewards_earned ORDER BY c.id DESC")
+                .bind::<Integer, _>(pool_id)
+                .bind::<Unsigned<Integer>, _>(limit)
+                .bind::<Unsigned<Integer>, _>(offset)
+                .load::<models::ChallengeDetail>(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Pubkeys of the miners that submitted for a challenge.
+    pub async fn get_challenge_miner_pubkeys(
+        &self,
+        challenge_id: i32,
+    ) -> Result<Vec<String>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT DISTINCT m.pubkey FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE s.challenge_id = ? ORDER BY m.pubkey")
+                .bind::<Integer, _>(challenge_id)
+                .load::<models::MinerPubkey>(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().map(|m| m.pubkey).collect());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_challenge_distribution(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+    ) -> Result<Option<models::ChallengeDistribution>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT c.id, c.rewards_earned, c.commission, c.total_hashpower, t.signature AS txn_signature, c.redistributed_at FROM challenges c LEFT JOIN txns t ON c.txn_id = t.id WHERE c.id = ? AND c.pool_id = ?")
+                .bind::<Integer, _>(challenge_id)
+                .bind::<Integer, _>(pool_id)
+                .load::<models::ChallengeDistribution>(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_epoch_histogram(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+    ) -> Result<Option<models::EpochHistogram>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn.interact(move |conn: &mut MysqlConnection| {
+                diesel::sql_query("SELECT challenge_id, difficulty_histogram FROM epoch_outcomes WHERE pool_id = ? AND challenge_id = ? AND difficulty_histogram IS NOT NULL ORDER BY id DESC LIMIT 1")
+                .bind::<Integer, _>(pool_id)
+                .bind::<Integer, _>(challenge_id)
+                .load::<models::EpochHistogram>(conn)
+            }).await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_challenge_earnings(
+        &self,
+        challenge_id: i32,
+    ) -> Result<Vec<models::ChallengeEarning>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT m.pubkey, e.amount, e.hashpower FROM earnings e JOIN miners m ON e.miner_id = m.id WHERE e.challenge_id = ? ORDER BY e.amount DESC")
+                        .bind::<Integer, _>(challenge_id)
+                        .load::<models::ChallengeEarning>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Payouts of an epoch, largest first.
+    pub async fn get_epoch_reward_distribution(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+        limit: u32,
+    ) -> Result<Vec<models::EpochDistributionEntry>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT m.pubkey, MAX(s.difficulty) AS difficulty_submitted, e.hashpower, e.amount AS earned FROM earnings e JOIN miners m ON e.miner_id = m.id LEFT JOIN submissions s ON s.miner_id = e.miner_id AND s.challenge_id = e.challenge_id WHERE e.challenge_id = ? AND e.pool_id = ? GROUP BY e.id, m.pubkey, e.hashpower, e.amount ORDER BY e.amount DESC LIMIT ?")
+                        .bind::<Integer, _>(challenge_id)
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .load::<models::EpochDistributionEntry>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_epoch_distribution_totals(
+        &self,
+        pool_id: i32,
+        challenge_id: i32,
+    ) -> Result<models::EpochDistributionTotals, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COALESCE(SUM(e.amount), 0) AS UNSIGNED) AS total_distributed, CAST(COUNT(*) AS UNSIGNED) AS participant_count FROM earnings e WHERE e.challenge_id = ? AND e.pool_id = ?")
+                        .bind::<Integer, _>(challenge_id)
+                        .bind::<Integer, _>(pool_id)
+                        .get_result::<models::EpochDistributionTotals>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_challenge_submissions(
+        &self,
+        challenge_id: i32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<SubmissionWithPubkey>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.*, m.pubkey FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE s.challenge_id = ? ORDER BY s.id LIMIT ? OFFSET ?")
+                        .bind::<Integer, _>(challenge_id)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .bind::<Unsigned<Integer>, _>(offset)
+                        .load::<SubmissionWithPubkey>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
     pub async fn get_miner_rewards(
         &self,
         miner_pubkey: String,
+        pool_id: i32,
     ) -> Result<models::Reward, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn.interact(move |conn: &mut MysqlConnection| {
-                diesel::sql_query("SELECT r.balance, r.miner_id FROM miners m JOIN rewards r ON m.id = r.miner_id WHERE m.pubkey = ?")
+                diesel::sql_query("SELECT r.balance, r.miner_id FROM miners m JOIN rewards r ON m.id = r.miner_id WHERE m.pubkey = ? AND r.pool_id = ?")
                 .bind::<Text, _>(miner_pubkey)
+                .bind::<Integer, _>(pool_id)
                 .get_result::<models::Reward>(conn)
             }).await;
 
@@ -88,12 +399,21 @@ impl AppRRDatabase {
         };
     }
 
-    pub async fn get_last_challenge_submissions(&self) -> Result<Vec<SubmissionWithPubkey>, AppDatabaseError> {
+    pub async fn get_last_challenge_submissions(
+        &self,
+        pool_id: i32,
+        min_difficulty: i16,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<SubmissionWithPubkey>, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-
-                    diesel::sql_query("SELECT s.*, m.pubkey FROM submissions s JOIN miners m ON s.miner_id = m.id JOIN challenges c ON s.challenge_id = c.id WHERE c.id = (SELECT id from challenges ORDER BY created_at DESC LIMIT 1 OFFSET 1)")
+                    diesel::sql_query("SELECT s.*, m.pubkey FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE s.challenge_id = (SELECT id from challenges WHERE pool_id = ? ORDER BY created_at DESC LIMIT 1 OFFSET 1) AND s.difficulty >= ? ORDER BY s.id LIMIT ? OFFSET ?")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<SmallInt, _>(min_difficulty)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .bind::<Unsigned<Integer>, _>(offset)
                         .load::<SubmissionWithPubkey>(conn)
                 })
                 .await;
@@ -118,21 +438,25 @@ impl AppRRDatabase {
         };
     }
 
-    pub async fn get_miner_earnings(&self, pubkey: String) -> Result<Vec<Submission>, AppDatabaseError> {
+    pub async fn get_last_challenge_submission_count(
+        &self,
+        pool_id: i32,
+        min_difficulty: i16,
+    ) -> Result<u64, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-
-                    diesel::sql_query("SELECT s.* FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE m.pubkey = ? ORDER BY s.created_at DESC LIMIT 100")
-                        .bind::<Text, _>(pubkey)
-                        .load::<Submission>(conn)
+                    diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS submission_count FROM submissions s WHERE s.challenge_id = (SELECT id from challenges WHERE pool_id = ? ORDER BY created_at DESC LIMIT 1 OFFSET 1) AND s.difficulty >= ?")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<SmallInt, _>(min_difficulty)
+                        .get_result::<models::SubmissionCount>(conn)
                 })
                 .await;
 
             match res {
                 Ok(interaction) => match interaction {
                     Ok(query) => {
-                        return Ok(query);
+                        return Ok(query.submission_count);
                     }
                     Err(e) => {
                         error!("{:?}", e);
@@ -149,20 +473,24 @@ impl AppRRDatabase {
         };
     }
 
-    pub async fn get_miner_submissions(&self, pubkey: String) -> Result<Vec<Submission>, AppDatabaseError> {
+    /// The last replication heartbeat the replica has, None before the first one.
+    pub async fn get_replication_heartbeat(
+        &self,
+        pool_id: i32,
+    ) -> Result<Option<NaiveDateTime>, AppDatabaseError> {
         if let Ok(db_conn) = self.connection_pool.get().await {
             let res = db_conn
                 .interact(move |conn: &mut MysqlConnection| {
-                    diesel::sql_query("SELECT s.* FROM submissions s JOIN miners m ON s.miner_id = m.id WHERE m.pubkey = ? ORDER BY s.created_at DESC LIMIT 100")
-                        .bind::<Text, _>(pubkey)
-                        .load::<Submission>(conn)
+                    diesel::sql_query("SELECT beat_at FROM replication_heartbeats WHERE pool_id = ?")
+                        .bind::<Integer, _>(pool_id)
+                        .load::<models::ReplicationHeartbeat>(conn)
                 })
                 .await;
 
             match res {
                 Ok(interaction) => match interaction {
                     Ok(query) => {
-                        return Ok(query);
+                        return Ok(query.into_iter().next().map(|heartbeat| heartbeat.beat_at));
                     }
                     Err(e) => {
                         error!("{:?}", e);
@@ -178,4 +506,893 @@ impl AppRRDatabase {
             return Err(AppDatabaseError::FailedToGetConnectionFromPool);
         };
     }
+
+    /// Number of the pool's miner sessions that ended in the last `hours`.
+    pub async fn get_sessions_ended_count(
+        &self,
+        pool_id: i32,
+        hours: u32,
+    ) -> Result<u64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS session_count FROM miner_sessions WHERE pool_id = ? AND ended_at >= NOW() - INTERVAL ? HOUR")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(hours)
+                        .get_result::<models::SessionCount>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.session_count);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Distributed rewards per unit of hashpower of the pool's epochs with a
+    /// recorded reward over the last days.
+    pub async fn get_reward_yield(
+        &self,
+        pool_id: i32,
+        days: u32,
+    ) -> Result<models::RewardYield, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    // dividing by a DOUBLE keeps the sum a DOUBLE rather than a DECIMAL
+                    diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS epochs, COALESCE(SUM((rewards_earned - COALESCE(commission, 0)) / (total_hashpower * 1e0)), 0) AS rewards_per_hashpower, CAST(COALESCE(TIMESTAMPDIFF(SECOND, MIN(started_at), NOW()), 0) AS SIGNED) AS span_secs FROM challenges WHERE pool_id = ? AND rewards_earned IS NOT NULL AND total_hashpower > 0 AND started_at >= NOW() - INTERVAL ? DAY")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(days)
+                        .get_result::<models::RewardYield>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Epochs won, their average reward and the hashes the best submissions
+    /// are expected to have taken, over the last hours.
+    pub async fn get_pool_listing_stats(
+        &self,
+        pool_id: i32,
+        hours: u32,
+    ) -> Result<models::PoolListingStats, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST((SELECT COUNT(*) FROM epoch_outcomes WHERE pool_id = ? AND outcome = 'success' AND created_at >= NOW() - INTERVAL ? HOUR) AS UNSIGNED) AS epochs_won, CAST((SELECT AVG(rewards_earned) FROM challenges WHERE pool_id = ? AND rewards_earned IS NOT NULL AND started_at >= NOW() - INTERVAL ? HOUR) AS UNSIGNED) AS avg_reward, (SELECT COALESCE(SUM(POW(2, b.best_difficulty)), 0) FROM (SELECT MAX(s.difficulty) AS best_difficulty FROM submissions s JOIN challenges c ON s.challenge_id = c.id WHERE c.pool_id = ? AND s.created_at >= NOW() - INTERVAL ? HOUR GROUP BY s.challenge_id, s.miner_id) b) AS expected_hashes")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(hours)
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(hours)
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(hours)
+                        .get_result::<models::PoolListingStats>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Fraction of the nonces assigned in the pool's challenges ended since
+    /// since_ts that were in a range producing a submission of at least
+    /// MIN_DIFF. Ranges are aligned to their size, so nonce DIV
+    /// range_size tells them apart. 0 when no nonces were assigned.
+    pub async fn get_mining_efficiency(
+        &self,
+        pool_id: i32,
+        since_ts: i64,
+    ) -> Result<f64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COALESCE((SELECT SUM(r.range_size) FROM (SELECT DISTINCT s.challenge_id, s.nonce DIV s.range_size AS range_index, s.range_size FROM submissions s JOIN challenges c ON s.challenge_id = c.id WHERE c.pool_id = ? AND c.ended_at >= FROM_UNIXTIME(?) AND c.nonces_assigned IS NOT NULL AND s.range_size > 0 AND s.difficulty >= ?) r), 0) AS UNSIGNED) AS useful_nonces, CAST(COALESCE((SELECT SUM(nonces_assigned) FROM challenges WHERE pool_id = ? AND ended_at >= FROM_UNIXTIME(?)), 0) AS UNSIGNED) AS assigned_nonces")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<BigInt, _>(since_ts)
+                        .bind::<SmallInt, _>(crate::MIN_DIFF as i16)
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<BigInt, _>(since_ts)
+                        .get_result::<models::MiningEfficiency>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        if query.assigned_nonces == 0 {
+                            return Ok(0.0);
+                        }
+                        return Ok(query.useful_nonces as f64 / query.assigned_nonces as f64);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Number of submissions per difficulty for the last challenge, highest difficulty first.
+    pub async fn get_last_challenge_difficulty_counts(
+        &self,
+        pool_id: i32,
+    ) -> Result<Vec<models::DifficultyCount>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.difficulty, COUNT(*) AS count FROM submissions s WHERE s.challenge_id = (SELECT id from challenges WHERE pool_id = ? ORDER BY created_at DESC LIMIT 1 OFFSET 1) GROUP BY s.difficulty ORDER BY s.difficulty DESC")
+                        .bind::<Integer, _>(pool_id)
+                        .load::<models::DifficultyCount>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_earnings(&self, pubkey: String, pool_id: i32) -> Result<Vec<Submission>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+
+                    diesel::sql_query("SELECT s.* FROM submissions s JOIN miners m ON s.miner_id = m.id JOIN challenges c ON s.challenge_id = c.id WHERE m.pubkey = ? AND c.pool_id = ? ORDER BY s.created_at DESC LIMIT 100")
+                        .bind::<Text, _>(pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .load::<Submission>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_submissions(&self, pubkey: String, pool_id: i32) -> Result<Vec<Submission>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.* FROM submissions s JOIN miners m ON s.miner_id = m.id JOIN challenges c ON s.challenge_id = c.id WHERE m.pubkey = ? AND c.pool_id = ? ORDER BY s.created_at DESC LIMIT 100")
+                        .bind::<Text, _>(pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .load::<Submission>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_epoch_history(
+        &self,
+        pool_id: i32,
+        limit: u32,
+    ) -> Result<Vec<models::EpochHistoryEntry>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT id, rewards_earned, started_at, ended_at, TIMESTAMPDIFF(SECOND, started_at, ended_at) AS duration_secs FROM challenges WHERE pool_id = ? ORDER BY id DESC LIMIT ?")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .load::<models::EpochHistoryEntry>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_total_hashpower(
+        &self,
+        pubkey: String,
+        pool_id: i32,
+        since_ts: Option<i64>,
+    ) -> Result<u64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.difficulty, COUNT(*) AS count FROM submissions s JOIN miners m ON s.miner_id = m.id JOIN challenges c ON s.challenge_id = c.id WHERE m.pubkey = ? AND c.pool_id = ? AND s.created_at >= FROM_UNIXTIME(?) GROUP BY s.difficulty")
+                        .bind::<Text, _>(pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<BigInt, _>(since_ts.unwrap_or(0))
+                        .load::<models::DifficultyCount>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(sum_hashpower(&query));
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_referrals(
+        &self,
+        pubkey: String,
+    ) -> Result<Vec<models::ReferredMiner>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT m.pubkey, mr.bonus_earned FROM miner_referrals mr JOIN miners rm ON mr.referrer_miner_id = rm.id JOIN miners m ON mr.referred_miner_id = m.id WHERE rm.pubkey = ? ORDER BY mr.created_at ASC")
+                        .bind::<Text, _>(pubkey)
+                        .load::<models::ReferredMiner>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_payout_progress(
+        &self,
+        miner_pubkey: String,
+        pool_id: i32,
+    ) -> Result<Option<models::PayoutProgress>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT r.balance, ms.auto_claim_threshold FROM miners m JOIN rewards r ON m.id = r.miner_id LEFT JOIN miner_settings ms ON ms.miner_id = m.id WHERE m.pubkey = ? AND r.pool_id = ?")
+                        .bind::<Text, _>(miner_pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .load::<models::PayoutProgress>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Average COAL the miner earned over the pool's last `window` ended
+    /// epochs, counting the epochs it earned nothing in.
+    pub async fn get_miner_avg_earnings_per_epoch(
+        &self,
+        miner_pubkey: String,
+        pool_id: i32,
+        window: u32,
+    ) -> Result<f64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COALESCE(SUM(e.amount), 0) AS UNSIGNED) AS total_earned, CAST(COUNT(DISTINCT c.id) AS UNSIGNED) AS epochs FROM (SELECT id FROM challenges WHERE pool_id = ? AND ended_at IS NOT NULL ORDER BY id DESC LIMIT ?) c LEFT JOIN earnings e ON e.challenge_id = c.id AND e.miner_id = (SELECT id FROM miners WHERE pubkey = ?)")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(window)
+                        .bind::<Text, _>(miner_pubkey)
+                        .get_result::<models::EpochEarningsTotal>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        if query.epochs == 0 {
+                            return Ok(0.0);
+                        }
+                        return Ok(amount_to_coal(query.total_earned) / query.epochs as f64);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Audit entries of the pool in [since, until), newest first, optionally
+    /// only those of a pubkey or an ip.
+    pub async fn get_audit_log(
+        &self,
+        pool_id: i32,
+        pubkey: Option<String>,
+        ip: Option<String>,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        limit: u32,
+    ) -> Result<Vec<models::AuditEntry>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT id, action, pubkey, ip, amount, outcome, error_code, created_at FROM audit_log WHERE pool_id = ? AND (? IS NULL OR pubkey = ?) AND (? IS NULL OR ip = ?) AND created_at >= ? AND created_at < ? ORDER BY id DESC LIMIT ?")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Nullable<Text>, _>(pubkey.clone())
+                        .bind::<Nullable<Text>, _>(pubkey)
+                        .bind::<Nullable<Text>, _>(ip.clone())
+                        .bind::<Nullable<Text>, _>(ip)
+                        .bind::<Timestamp, _>(since)
+                        .bind::<Timestamp, _>(until)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .load::<models::AuditEntry>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_pool_total_hashpower(
+        &self,
+        pool_id: i32,
+        since_ts: Option<i64>,
+    ) -> Result<u64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.difficulty, COUNT(*) AS count FROM submissions s JOIN challenges c ON s.challenge_id = c.id WHERE c.pool_id = ? AND s.created_at >= FROM_UNIXTIME(?) GROUP BY s.difficulty")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<BigInt, _>(since_ts.unwrap_or(0))
+                        .load::<models::DifficultyCount>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(sum_hashpower(&query));
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_epoch_outcomes_by_day(
+        &self,
+        pool_id: i32,
+        days: u32,
+    ) -> Result<Vec<models::EpochOutcomeDay>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT DATE(created_at) AS day, CAST(SUM(outcome = 'success') AS UNSIGNED) AS success_count, CAST(SUM(outcome = 'all_attempts_failed') AS UNSIGNED) AS failure_count, CAST(SUM(IF(outcome = 'success', attempts_used, 0)) AS UNSIGNED) AS success_attempts, CAST(SUM(final_priority_fee) AS UNSIGNED) AS total_final_priority_fee FROM epoch_outcomes WHERE pool_id = ? AND created_at >= NOW() - INTERVAL ? DAY GROUP BY DATE(created_at) ORDER BY day ASC")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(days)
+                        .load::<models::EpochOutcomeDay>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+    pub async fn get_miner_claims(
+        &self,
+        pubkey: String,
+        pool_id: i32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<models::ClaimRecord>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT c.id AS claim_id, c.amount AS amount_coal, t.signature AS txn_signature, CONCAT('https://solscan.io/tx/', t.signature) AS explorer_url, c.created_at FROM claims c JOIN miners m ON c.miner_id = m.id JOIN txns t ON c.txn_id = t.id WHERE m.pubkey = ? AND c.pool_id = ? ORDER BY c.id DESC LIMIT ? OFFSET ?")
+                        .bind::<Text, _>(pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(limit)
+                        .bind::<Unsigned<Integer>, _>(offset)
+                        .load::<models::ClaimRecord>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_miner_total_claimed(
+        &self,
+        pubkey: String,
+        pool_id: i32,
+    ) -> Result<u64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COALESCE(SUM(c.amount), 0) AS UNSIGNED) AS total_claimed FROM claims c JOIN miners m ON c.miner_id = m.id WHERE m.pubkey = ? AND c.pool_id = ?")
+                        .bind::<Text, _>(pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .get_result::<models::ClaimTotal>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.total_claimed);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_pool_claim_stats(
+        &self,
+        pool_id: i32,
+        since_ts: i64,
+        until_ts: i64,
+    ) -> Result<models::PoolClaimStats, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COUNT(*) AS UNSIGNED) AS claim_count, CAST(COUNT(DISTINCT miner_id) AS UNSIGNED) AS unique_miners, CAST(COALESCE(SUM(amount), 0) AS UNSIGNED) AS total_amount, CAST(COALESCE(MAX(amount), 0) AS UNSIGNED) AS largest_amount, MIN(created_at) AS first_claim_at, MAX(created_at) AS last_claim_at FROM claims WHERE pool_id = ? AND created_at >= FROM_UNIXTIME(?) AND created_at < FROM_UNIXTIME(?)")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<BigInt, _>(since_ts)
+                        .bind::<BigInt, _>(until_ts)
+                        .get_result::<models::PoolClaimStats>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Distinct miners with a submission in the last `window_secs` seconds.
+    pub async fn get_submitting_miner_count(
+        &self,
+        pool_id: i32,
+        window_secs: u64,
+    ) -> Result<u64, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT CAST(COUNT(DISTINCT s.miner_id) AS UNSIGNED) AS miner_count FROM submissions s JOIN challenges c ON s.challenge_id = c.id WHERE c.pool_id = ? AND s.created_at >= NOW() - INTERVAL ? SECOND")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<BigInt>, _>(window_secs)
+                        .get_result::<models::MinerCount>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.miner_count);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// The miner's most recently ended session with the pool, if any.
+    pub async fn get_last_miner_session(
+        &self,
+        pubkey: String,
+        pool_id: i32,
+    ) -> Result<Option<models::MinerSession>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT s.device_id, s.started_at, s.ended_at, s.epochs, s.submissions, s.best_difficulty, s.earned FROM miner_sessions s JOIN miners m ON s.miner_id = m.id WHERE m.pubkey = ? AND s.pool_id = ? ORDER BY s.ended_at DESC LIMIT 1")
+                        .bind::<Text, _>(pubkey)
+                        .bind::<Integer, _>(pool_id)
+                        .get_results::<models::MinerSession>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query.into_iter().next());
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_pool_totals(&self, pool_id: i32) -> Result<models::PoolTotals, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT p.total_rewards, p.claimed_rewards, CAST(COALESCE((SELECT SUM(r.balance) FROM rewards r WHERE r.pool_id = p.id), 0) AS UNSIGNED) AS outstanding_rewards FROM pools p WHERE p.id = ?")
+                        .bind::<Integer, _>(pool_id)
+                        .get_result::<models::PoolTotals>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    /// Activity over the last `days` days as a flat array of 168 buckets,
+    /// ordered by day of week then hour. Averages are taken over every
+    /// occurrence of the hour in the window, including ones without activity.
+    pub async fn get_miner_activity_heatmap(
+        &self,
+        pool_id: i32,
+        days: u32,
+    ) -> Result<Vec<models::ActivityBucket>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT WEEKDAY(t.hour_start) AS day_of_week, HOUR(t.hour_start) AS hour_of_day, CAST(SUM(t.active_miners) AS UNSIGNED) AS total_active_miners, CAST(SUM(t.submissions) AS UNSIGNED) AS total_submissions FROM (SELECT TIMESTAMP(DATE_FORMAT(s.created_at, '%Y-%m-%d %H:00:00')) AS hour_start, COUNT(DISTINCT s.miner_id) AS active_miners, COUNT(*) AS submissions FROM submissions s JOIN challenges c ON s.challenge_id = c.id WHERE c.pool_id = ? AND s.created_at >= NOW() - INTERVAL ? DAY GROUP BY hour_start) t GROUP BY day_of_week, hour_of_day")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(days)
+                        .load::<models::ActivityTotals>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(activity_buckets(&query, days));
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+    pub async fn get_landing_samples(
+        &self,
+        pool_id: i32,
+        hours: u32,
+    ) -> Result<Vec<models::LandingSample>, AppDatabaseError> {
+        if let Ok(db_conn) = self.connection_pool.get().await {
+            let res = db_conn
+                .interact(move |conn: &mut MysqlConnection| {
+                    diesel::sql_query("SELECT outcome, first_send_ms, confirmed_ms, final_priority_fee FROM epoch_outcomes WHERE pool_id = ? AND outcome != 'missed' AND created_at >= NOW() - INTERVAL ? HOUR")
+                        .bind::<Integer, _>(pool_id)
+                        .bind::<Unsigned<Integer>, _>(hours)
+                        .load::<models::LandingSample>(conn)
+                })
+                .await;
+
+            match res {
+                Ok(interaction) => match interaction {
+                    Ok(query) => {
+                        return Ok(query);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                        return Err(AppDatabaseError::QueryFailed);
+                    }
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return Err(AppDatabaseError::InteractionFailed);
+                }
+            }
+        } else {
+            return Err(AppDatabaseError::FailedToGetConnectionFromPool);
+        };
+    }
+
+}
+
+// Hashpower is derived per submission from its difficulty, see hashpower_for_difficulty.
+fn sum_hashpower(counts: &[models::DifficultyCount]) -> u64 {
+    counts.iter().fold(0u64, |total, c| {
+        let hashpower = hashpower_for_difficulty(c.difficulty.max(0) as u32);
+        total.saturating_add(hashpower.saturating_mul(c.count.max(0) as u64))
+    })
+}
+
+// Database timestamps are expected to be in UTC.
+fn activity_buckets(totals: &[models::ActivityTotals], days: u32) -> Vec<models::ActivityBucket> {
+    let now = chrono::Utc::now().naive_utc();
+    let start = now - chrono::Duration::days(days as i64);
+
+    // number of times each hour of the week occurs in the window
+    let mut occurrences = [0u32; 168];
+    let mut hour = start
+        .date()
+        .and_hms_opt(start.hour(), 0, 0)
+        .unwrap_or(start);
+    while hour <= now {
+        let index = hour.weekday().num_days_from_monday() as usize * 24 + hour.hour() as usize;
+        occurrences[index] += 1;
+        hour += chrono::Duration::hours(1);
+    }
+
+    let mut buckets: Vec<models::ActivityBucket> = (0..168)
+        .map(|i| models::ActivityBucket {
+            hour_of_day: (i % 24) as u8,
+            day_of_week: (i / 24) as u8,
+            avg_active_miners: 0.0,
+            avg_submissions: 0.0,
+        })
+        .collect();
+
+    for total in totals {
+        if !(0..7).contains(&total.day_of_week) || !(0..24).contains(&total.hour_of_day) {
+            continue;
+        }
+        let index = total.day_of_week as usize * 24 + total.hour_of_day as usize;
+        let count = occurrences[index].max(1) as f64;
+        buckets[index].avg_active_miners = total.total_active_miners as f64 / count;
+        buckets[index].avg_submissions = total.total_submissions as f64 / count;
+    }
+
+    buckets
 }
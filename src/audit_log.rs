@@ -0,0 +1,228 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Query, Request},
+    http::Response,
+    middleware::Next,
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    api_error::{ApiError, ApiErrorCode},
+    app_database::AppDatabase,
+    models::InsertAuditEntry,
+    socket_utils::{extract_real_ip, TrustedProxies},
+};
+
+// Entries waiting to be written, more are dropped and counted as failed.
+const QUEUE_SIZE: usize = 10_000;
+// Longest pubkey stored, longer values from the query are cut.
+const MAX_PUBKEY_LEN: usize = 64;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+// Rows deleted per statement while pruning, keeps each delete short.
+const PRUNE_BATCH_SIZE: u32 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Signup,
+    Claim,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Signup => "signup",
+            AuditAction::Claim => "claim",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AuditLogStats {
+    written: AtomicU64,
+    // writes that failed, or were dropped because the queue was full
+    failed: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct AuditLogStatus {
+    pub written: u64,
+    pub failed: u64,
+}
+
+impl AuditLogStats {
+    pub fn status(&self) -> AuditLogStatus {
+        AuditLogStatus {
+            written: self.written.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Queues audit entries for the audit log writer, never waiting on the db.
+#[derive(Clone)]
+pub struct AuditLog {
+    pool_id: i32,
+    trusted_proxies: Arc<TrustedProxies>,
+    sender: Sender<InsertAuditEntry>,
+    stats: Arc<AuditLogStats>,
+}
+
+impl AuditLog {
+    /// The handle and the receiver to run audit_log_system with.
+    pub fn new(pool_id: i32, trusted_proxies: TrustedProxies) -> (Self, Receiver<InsertAuditEntry>) {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        let audit_log = AuditLog {
+            pool_id,
+            trusted_proxies: Arc::new(trusted_proxies),
+            sender,
+            stats: Arc::new(AuditLogStats::default()),
+        };
+        (audit_log, receiver)
+    }
+
+    pub fn stats(&self) -> Arc<AuditLogStats> {
+        self.stats.clone()
+    }
+
+    fn record(&self, action: AuditAction, params: AuditParams, ip: String, response: &Response<Body>) {
+        let status = response.status();
+        let error_code = match response.extensions().get::<ApiError>() {
+            Some(api_error) => Some(api_error.code),
+            None if status.is_client_error() || status.is_server_error() => {
+                Some(ApiErrorCode::from_status(status))
+            }
+            None => None,
+        };
+        let mut pubkey = params.pubkey.unwrap_or_default();
+        pubkey.truncate(MAX_PUBKEY_LEN);
+        let entry = InsertAuditEntry {
+            pool_id: self.pool_id,
+            action: action.as_str().to_string(),
+            pubkey,
+            ip,
+            amount: params.amount.and_then(|amount| amount.parse().ok()),
+            outcome: if error_code.is_some() { "failure" } else { "success" }.to_string(),
+            error_code: error_code.and_then(|code| {
+                serde_json::to_value(code)
+                    .ok()
+                    .and_then(|value| value.as_str().map(|s| s.to_string()))
+            }),
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+        match self.sender.try_send(entry) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                self.stats.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// Raw query values, recorded even when the handler rejects them.
+#[derive(Deserialize, Default)]
+struct AuditParams {
+    pubkey: Option<String>,
+    amount: Option<String>,
+}
+
+async fn audit(action: AuditAction, audit_log: AuditLog, req: Request, next: Next) -> Response<Body> {
+    let params = Query::<AuditParams>::try_from_uri(req.uri())
+        .map(|Query(params)| params)
+        .unwrap_or_default();
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| {
+            extract_real_ip(req.headers(), *addr, &audit_log.trusted_proxies).to_string()
+        })
+        .unwrap_or_default();
+
+    let response = next.run(req).await;
+    audit_log.record(action, params, ip, &response);
+    response
+}
+
+/// Records every signup attempt in the audit log.
+pub async fn audit_signup(
+    Extension(audit_log): Extension<AuditLog>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    audit(AuditAction::Signup, audit_log, req, next).await
+}
+
+/// Records every claim attempt in the audit log.
+pub async fn audit_claim(
+    Extension(audit_log): Extension<AuditLog>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    audit(AuditAction::Claim, audit_log, req, next).await
+}
+
+/// Writes the queued audit entries. A failed write is logged and counted,
+/// it is not retried.
+pub async fn audit_log_system(
+    mut receiver: Receiver<InsertAuditEntry>,
+    app_database: Arc<AppDatabase>,
+    stats: Arc<AuditLogStats>,
+) {
+    while let Some(entry) = receiver.recv().await {
+        match app_database.add_audit_entry(entry).await {
+            Ok(()) => {
+                stats.written.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                error!("Failed to write audit entry: {:?}", e);
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Deletes the pool's audit entries older than retention_days, every hour.
+pub async fn audit_log_pruning_system(
+    app_database: Arc<AppDatabase>,
+    pool_id: i32,
+    retention_days: u32,
+) {
+    loop {
+        let before = chrono::Utc::now().naive_utc() - chrono::Duration::days(retention_days as i64);
+        let mut pruned = 0;
+        loop {
+            match app_database
+                .prune_audit_log(pool_id, before, PRUNE_BATCH_SIZE)
+                .await
+            {
+                Ok(deleted) => {
+                    pruned += deleted;
+                    if deleted < PRUNE_BATCH_SIZE as usize {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to prune audit log: {:?}", e);
+                    break;
+                }
+            }
+        }
+        if pruned > 0 {
+            info!("Pruned {} audit entries older than {} days", pruned, retention_days);
+        }
+
+        tokio::time::sleep(PRUNE_INTERVAL).await;
+    }
+}
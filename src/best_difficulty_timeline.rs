@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use tokio::{sync::Mutex, time::Instant};
+use utoipa::ToSchema;
+
+/// Each improvement of the epoch's best difficulty, with when it happened and
+/// who found it. Recorded and reset while holding the epoch hashes write
+/// lock, so it always matches best_hash.
+#[derive(Clone)]
+pub struct BestDifficultyTimeline(Arc<Mutex<EpochTimeline>>);
+
+struct EpochTimeline {
+    epoch_started_at: Instant,
+    improvements: Vec<(Instant, u32, Pubkey)>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BestDifficultyImprovement {
+    pub elapsed_secs_into_epoch: u64,
+    pub difficulty: u32,
+    pub finder_pubkey: String,
+}
+
+impl Default for BestDifficultyTimeline {
+    fn default() -> Self {
+        BestDifficultyTimeline(Arc::new(Mutex::new(EpochTimeline {
+            epoch_started_at: Instant::now(),
+            improvements: Vec::new(),
+        })))
+    }
+}
+
+impl BestDifficultyTimeline {
+    pub async fn record(&self, difficulty: u32, finder: Pubkey) {
+        self.0
+            .lock()
+            .await
+            .improvements
+            .push((Instant::now(), difficulty, finder));
+    }
+
+    /// Called along with the reset of the epoch hashes.
+    pub async fn reset(&self) {
+        let mut timeline = self.0.lock().await;
+        timeline.epoch_started_at = Instant::now();
+        timeline.improvements.clear();
+    }
+
+    /// The improvements so far, and the seconds since the last one.
+    pub async fn snapshot(&self) -> (Vec<BestDifficultyImprovement>, Option<u64>) {
+        let timeline = self.0.lock().await;
+        let improvements = timeline
+            .improvements
+            .iter()
+            .map(|(at, difficulty, finder)| BestDifficultyImprovement {
+                elapsed_secs_into_epoch: at.duration_since(timeline.epoch_started_at).as_secs(),
+                difficulty: *difficulty,
+                finder_pubkey: crate::pubkey_display::display_pubkey(finder),
+            })
+            .collect();
+        let since_last = timeline
+            .improvements
+            .last()
+            .map(|(at, _, _)| at.elapsed().as_secs());
+        (improvements, since_last)
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use coal_api::{consts::BUS_COUNT, state::Bus};
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+use crate::coal_utils::GetBusError;
+
+// Weight given to the newest sample in the bus rewards moving average.
+const EWMA_ALPHA: f64 = 0.3;
+// Number of best estimated busses to randomly choose from with the weighted strategy.
+const WEIGHTED_TOP_K: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BusSelectionStrategy {
+    /// Always pick the bus with the most rewards at submission time
+    Highest,
+    /// Pick randomly among the top busses by moving average of observed rewards
+    Weighted,
+    /// Pick any bus at random
+    Random,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BusObservation {
+    pub bus: usize,
+    pub rewards: Option<u64>,
+    pub estimated_rewards: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BusStatsResponse {
+    pub samples: u64,
+    pub last_sampled_at: Option<u64>,
+    pub busses: Vec<BusObservation>,
+}
+
+#[derive(Default)]
+pub struct BusStats {
+    latest: [Option<u64>; BUS_COUNT],
+    estimated: [Option<f64>; BUS_COUNT],
+    samples: u64,
+    last_sampled_at: Option<u64>,
+    // when the last recorded busses were fetched
+    last_fetched_at: Option<Instant>,
+}
+
+impl BusStats {
+    /// Records a sample of bus reward levels, updating the moving average of each bus.
+    /// Busses fetched at fetched_at are only recorded once, a cached sample
+    /// seen again doesn't weigh more in the average.
+    pub fn record(&mut self, busses: &[Result<Bus, GetBusError>], fetched_at: Instant) {
+        if self.last_fetched_at == Some(fetched_at) {
+            return;
+        }
+        self.last_fetched_at = Some(fetched_at);
+        for (i, bus) in busses.iter().enumerate().take(BUS_COUNT) {
+            if let Ok(bus) = bus {
+                self.latest[i] = Some(bus.rewards);
+                let rewards = bus.rewards as f64;
+                self.estimated[i] = Some(match self.estimated[i] {
+                    Some(estimate) => EWMA_ALPHA * rewards + (1.0 - EWMA_ALPHA) * estimate,
+                    None => rewards,
+                });
+            }
+        }
+        self.samples += 1;
+        self.last_sampled_at = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs(),
+        );
+    }
+
+    pub fn select_bus(&self, strategy: BusSelectionStrategy) -> usize {
+        let mut rng = rand::thread_rng();
+        match strategy {
+            BusSelectionStrategy::Highest => {
+                let mut best_bus = None;
+                for (i, rewards) in self.latest.iter().enumerate() {
+                    if let Some(rewards) = rewards {
+                        match best_bus {
+                            Some((_, best_rewards)) if best_rewards >= *rewards => {}
+                            _ => best_bus = Some((i, *rewards)),
+                        }
+                    }
+                }
+                best_bus
+                    .map(|(i, _)| i)
+                    .unwrap_or_else(|| rng.gen_range(0..BUS_COUNT))
+            }
+            BusSelectionStrategy::Weighted => {
+                let mut candidates: Vec<(usize, f64)> = self
+                    .estimated
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, estimate)| estimate.map(|e| (i, e)))
+                    .collect();
+                candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+                candidates.truncate(WEIGHTED_TOP_K);
+                candidates
+                    .choose(&mut rng)
+                    .map(|(i, _)| *i)
+                    .unwrap_or_else(|| rng.gen_range(0..BUS_COUNT))
+            }
+            BusSelectionStrategy::Random => rng.gen_range(0..BUS_COUNT),
+        }
+    }
+
+    pub fn to_response(&self) -> BusStatsResponse {
+        BusStatsResponse {
+            samples: self.samples,
+            last_sampled_at: self.last_sampled_at,
+            busses: (0..BUS_COUNT)
+                .map(|i| BusObservation {
+                    bus: i,
+                    rewards: self.latest[i],
+                    estimated_rewards: self.estimated[i],
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn busses(rewards: &[u64]) -> Vec<Result<Bus, GetBusError>> {
+        rewards
+            .iter()
+            .enumerate()
+            .map(|(i, rewards)| {
+                Ok(Bus {
+                    id: i as u64,
+                    rewards: *rewards,
+                    theoretical_rewards: 0,
+                    top_balance: 0,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn highest_follows_the_newest_busses() {
+        let mut bus_stats = BusStats::default();
+        let fetched_at = Instant::now();
+        bus_stats.record(&busses(&[1, 9, 3, 4, 5, 6, 7, 8]), fetched_at);
+        assert_eq!(bus_stats.select_bus(BusSelectionStrategy::Highest), 1);
+
+        // a retry after the busses were fetched again picks from the new ones
+        bus_stats.record(
+            &busses(&[1, 0, 3, 4, 5, 6, 7, 8]),
+            fetched_at + Duration::from_secs(15),
+        );
+        assert_eq!(bus_stats.select_bus(BusSelectionStrategy::Highest), 7);
+        assert_eq!(bus_stats.samples, 2);
+    }
+
+    #[test]
+    fn cached_busses_are_recorded_once() {
+        let mut bus_stats = BusStats::default();
+        let fetched_at = Instant::now();
+        bus_stats.record(&busses(&[100, 0, 0, 0, 0, 0, 0, 0]), fetched_at);
+        bus_stats.record(&busses(&[100, 0, 0, 0, 0, 0, 0, 0]), fetched_at);
+        assert_eq!(bus_stats.samples, 1);
+
+        bus_stats.record(
+            &busses(&[0, 0, 0, 0, 0, 0, 0, 0]),
+            fetched_at + Duration::from_secs(1),
+        );
+        assert_eq!(bus_stats.estimated[0], Some(70.0));
+    }
+
+    #[test]
+    fn failed_busses_are_skipped() {
+        let mut bus_stats = BusStats::default();
+        let mut sample = busses(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        sample[7] = Err(GetBusError::FailedToParseAccount);
+        bus_stats.record(&sample, Instant::now());
+
+        assert_eq!(bus_stats.latest[7], None);
+        assert_eq!(bus_stats.select_bus(BusSelectionStrategy::Highest), 6);
+    }
+
+    #[test]
+    fn weighted_picks_among_the_top_estimates() {
+        let mut bus_stats = BusStats::default();
+        bus_stats.record(&busses(&[1, 2, 3, 4, 5, 6, 7, 8]), Instant::now());
+        for _ in 0..50 {
+            assert!(bus_stats.select_bus(BusSelectionStrategy::Weighted) >= 5);
+        }
+    }
+}
@@ -1,21 +1,33 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    mem::size_of,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use drillx_2::Solution;
 use coal_api::{
     consts::{
-        BUS_ADDRESSES, CONFIG_ADDRESS, EPOCH_DURATION, MINT_ADDRESS, PROOF, TOKEN_DECIMALS,
-        TREASURY_ADDRESS,
+        BUS_ADDRESSES, CONFIG_ADDRESS, EPOCH_DURATION, MINT_ADDRESS, MINT_NOISE, PROOF,
+        TOKEN_DECIMALS, TREASURY_ADDRESS,
     },
+    event::MineEvent,
     instruction,
-    state::{Config, Proof, Treasury},
+    state::{Bus, Config, Proof, Treasury},
     ID as COAL_ID,
 };
+use coal_guilds_api::state::{member_pda, GuildsAccount, Guild, Member};
 pub use coal_utils::AccountDeserialize;
-use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
 use solana_sdk::{
-    account::ReadableAccount, clock::Clock, instruction::Instruction, pubkey::Pubkey, sysvar,
+    account::ReadableAccount,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Signature,
+    system_program, sysvar,
 };
+use solana_transaction_status::UiTransactionEncoding;
 use spl_associated_token_account::get_associated_token_address;
+use tracing::warn;
 
 pub const COAL_TOKEN_DECIMALS: u8 = TOKEN_DECIMALS;
 
@@ -25,8 +37,199 @@ pub fn get_auth_ix(signer: Pubkey) -> Instruction {
     instruction::auth(proof)
 }
 
-pub fn get_mine_ix(signer: Pubkey, solution: Solution, bus: usize) -> Instruction {
-    instruction::mine(signer, signer, BUS_ADDRESSES[bus], solution)
+/// Optional accounts appended to the mine instruction, in the order the coal
+/// program expects them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MineIxAccounts {
+    /// Tool equipped by the pool authority
+    pub tool: Option<Pubkey>,
+    /// Guild member and guild accounts of the pool authority
+    pub guild: Option<(Pubkey, Pubkey)>,
+}
+
+pub fn get_mine_ix(
+    signer: Pubkey,
+    solution: Solution,
+    bus: usize,
+    accounts: MineIxAccounts,
+) -> Instruction {
+    let mut ix = instruction::mine(signer, signer, BUS_ADDRESSES[bus], solution);
+
+    if let Some(tool) = accounts.tool {
+        // durability is decremented on every mine
+        ix.accounts.push(AccountMeta::new(tool, false));
+    }
+    if let Some((member, guild)) = accounts.guild {
+        ix.accounts.push(AccountMeta::new_readonly(
+            coal_guilds_api::consts::CONFIG_ADDRESS,
+            false,
+        ));
+        ix.accounts.push(AccountMeta::new_readonly(member, false));
+        ix.accounts.push(AccountMeta::new_readonly(guild, false));
+    }
+
+    ix
+}
+
+/// Mine event data. Newer coal programs append the tool and stake (guild)
+/// rewards to the base event.
+#[derive(Debug, Clone, Copy)]
+pub struct ParsedMineEvent {
+    pub difficulty: u64,
+    pub reward: u64,
+    pub timing: i64,
+    pub tool_reward: u64,
+    pub stake_reward: u64,
+}
+
+impl ParsedMineEvent {
+    /// Total amount added to the proof balance by the mine.
+    pub fn total_reward(&self) -> u64 {
+        self.reward
+            .saturating_add(self.tool_reward)
+            .saturating_add(self.stake_reward)
+    }
+}
+
+pub fn parse_mine_event(bytes: &[u8]) -> Option<ParsedMineEvent> {
+    let base_len = size_of::<MineEvent>();
+    if bytes.len() < base_len {
+        return None;
+    }
+    let event = bytemuck::try_from_bytes::<MineEvent>(&bytes[..base_len]).ok()?;
+
+    let read_u64 = |offset: usize| {
+        bytes
+            .get(offset..offset + 8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .unwrap_or(0)
+    };
+
+    Some(ParsedMineEvent {
+        difficulty: event.difficulty,
+        reward: event.reward,
+        timing: event.timing,
+        tool_reward: read_u64(base_len),
+        stake_reward: read_u64(base_len + 8),
+    })
+}
+
+// Tool account discriminator, the tool state is not part of the coal api version in use.
+const TOOL_DISCRIMINATOR: u8 = 107;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ToolAccount {
+    authority: Pubkey,
+    miner: Pubkey,
+    asset: Pubkey,
+    durability: u64,
+    multiplier: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ToolStatus {
+    pub tool: Pubkey,
+    pub miner: Pubkey,
+    pub durability: u64,
+    pub multiplier: u64,
+}
+
+impl ToolStatus {
+    /// A tool can only be used by its miner and while it has durability left.
+    pub fn is_usable(&self, authority: &Pubkey) -> bool {
+        self.durability > 0 && self.miner.eq(authority)
+    }
+}
+
+pub async fn get_tool_status(client: &RpcClient, tool: Pubkey) -> Result<ToolStatus, String> {
+    let data = client
+        .get_account_data(&tool)
+        .await
+        .map_err(|_| "Failed to get tool account".to_string())?;
+
+    if data.first() != Some(&TOOL_DISCRIMINATOR) {
+        return Err("Account is not a coal tool".to_string());
+    }
+    let account = data
+        .get(8..8 + size_of::<ToolAccount>())
+        .and_then(|d| bytemuck::try_from_bytes::<ToolAccount>(d).ok())
+        .ok_or("Failed to parse tool account")?;
+
+    Ok(ToolStatus {
+        tool,
+        miner: account.miner,
+        durability: account.durability,
+        multiplier: account.multiplier,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GuildStatus {
+    pub member: Pubkey,
+    pub guild: Pubkey,
+    pub member_stake: u64,
+    pub guild_stake: u64,
+    pub total_stake: u64,
+    pub total_multiplier: u64,
+    pub is_active: bool,
+}
+
+fn guilds_account<T: bytemuck::Pod>(data: &[u8], kind: GuildsAccount) -> Option<T> {
+    if data.first() != Some(&(kind as u8)) {
+        return None;
+    }
+    data.get(8..8 + size_of::<T>())
+        .and_then(|d| bytemuck::try_from_bytes::<T>(d).ok())
+        .copied()
+}
+
+/// Loads the guild config, the authority's member account and the guild, and
+/// checks that the authority is a member of the guild.
+pub async fn get_guild_status(
+    client: &RpcClient,
+    authority: Pubkey,
+    guild: Pubkey,
+) -> Result<GuildStatus, String> {
+    let member_address = member_pda(authority).0;
+    let account_pubkeys = vec![
+        coal_guilds_api::consts::CONFIG_ADDRESS,
+        member_address,
+        guild,
+    ];
+    let datas = client
+        .get_multiple_accounts(&account_pubkeys)
+        .await
+        .map_err(|_| "Failed to get guild accounts".to_string())?;
+
+    let config = datas[0]
+        .as_ref()
+        .and_then(|a| {
+            guilds_account::<coal_guilds_api::state::Config>(a.data(), GuildsAccount::Config)
+        })
+        .ok_or("Failed to load guild config account")?;
+    let member = datas[1]
+        .as_ref()
+        .and_then(|a| guilds_account::<Member>(a.data(), GuildsAccount::Member))
+        .ok_or("Failed to load guild member account")?;
+    let guild_account = datas[2]
+        .as_ref()
+        .and_then(|a| guilds_account::<Guild>(a.data(), GuildsAccount::Guild))
+        .ok_or("Failed to load guild account")?;
+
+    if member.guild != guild {
+        return Err(format!("Pool authority is not a member of guild {}", guild));
+    }
+
+    Ok(GuildStatus {
+        member: member_address,
+        guild,
+        member_stake: member.total_stake,
+        guild_stake: guild_account.total_stake,
+        total_stake: config.total_stake,
+        total_multiplier: config.total_multiplier,
+        is_active: member.is_active != 0,
+    })
 }
 
 pub fn get_register_ix(signer: Pubkey) -> Instruction {
@@ -54,137 +257,201 @@ pub fn get_coal_epoch_duration() -> i64 {
 }
 
 pub fn get_coal_decimals() -> u8 {
-    TOKEN_DECIMALS
-}
-
-pub async fn get_config(client: &RpcClient) -> Result<coal_api::state::Config, String> {
-    let data = client.get_account_data(&CONFIG_ADDRESS).await;
-    match data {
-        Ok(data) => {
-            let config = Config::try_from_bytes(&data);
-            if let Ok(config) = config {
-                return Ok(*config);
-            } else {
-                return Err("Failed to parse config account".to_string());
-            }
-        }
-        Err(_) => return Err("Failed to get config account".to_string()),
+    COAL_TOKEN_DECIMALS
+}
+
+/// Exact decimal string of a raw COAL amount, trailing zeros trimmed.
+pub fn amount_to_ui_string(amount: u64) -> String {
+    spl_token::amount_to_ui_amount_string_trimmed(amount, COAL_TOKEN_DECIMALS)
+}
+
+/// Raw COAL amount as a float for display, 0 if the conversion isn't finite.
+pub fn amount_to_coal(amount: u64) -> f64 {
+    let coal = amount as f64 / 10f64.powi(COAL_TOKEN_DECIMALS as i32);
+    if coal.is_nan() || coal.is_infinite() {
+        warn!("Amount {} did not convert to a finite COAL value", amount);
+        return 0.0;
     }
+    coal
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GetConfigError {
+    FailedToGetAccount,
+    FailedToParseAccount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GetProofError {
+    FailedToGetAccount,
+    FailedToParseAccount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GetBusError {
+    FailedToGetAccounts,
+    AccountMissing,
+    FailedToParseAccount,
+}
+
+/// The account from its data. try_from_bytes indexes the 8 byte header
+/// without checking the length, so shorter data is refused first.
+fn deserialize_account<T: AccountDeserialize + Copy>(data: &[u8]) -> Option<T> {
+    if data.len() < 8 {
+        return None;
+    }
+    T::try_from_bytes(data).ok().copied()
+}
+
+fn parse_config(data: Option<&[u8]>) -> Result<Config, GetConfigError> {
+    let data = data.ok_or(GetConfigError::FailedToGetAccount)?;
+    deserialize_account(data).ok_or(GetConfigError::FailedToParseAccount)
+}
+
+fn parse_proof(data: Option<&[u8]>) -> Result<Proof, GetProofError> {
+    let data = data.ok_or(GetProofError::FailedToGetAccount)?;
+    deserialize_account(data).ok_or(GetProofError::FailedToParseAccount)
+}
+
+fn parse_bus(data: Option<&[u8]>) -> Result<Bus, GetBusError> {
+    let data = data.ok_or(GetBusError::AccountMissing)?;
+    deserialize_account(data).ok_or(GetBusError::FailedToParseAccount)
+}
+
+pub async fn get_config(client: &RpcClient) -> Result<Config, GetConfigError> {
+    let data = client
+        .get_account_data(&CONFIG_ADDRESS)
+        .await
+        .map_err(|_| GetConfigError::FailedToGetAccount)?;
+    parse_config(Some(&data))
 }
 
 pub async fn get_proof_and_config_with_busses(
     client: &RpcClient,
     authority: Pubkey,
 ) -> (
-    Result<Proof, ()>,
-    Result<coal_api::state::Config, ()>,
-    Result<Vec<Result<coal_api::state::Bus, ()>>, ()>,
+    Result<Proof, GetProofError>,
+    Result<Config, GetConfigError>,
+    Result<Vec<Result<Bus, GetBusError>>, GetBusError>,
 ) {
-    let account_pubkeys = vec![
-        proof_pubkey(authority),
-        CONFIG_ADDRESS,
-        BUS_ADDRESSES[0],
-        BUS_ADDRESSES[1],
-        BUS_ADDRESSES[2],
-        BUS_ADDRESSES[3],
-        BUS_ADDRESSES[4],
-        BUS_ADDRESSES[5],
-        BUS_ADDRESSES[6],
-        BUS_ADDRESSES[7],
-    ];
-    let datas = client.get_multiple_accounts(&account_pubkeys).await;
-    if let Ok(datas) = datas {
-        let proof = if let Some(data) = &datas[0] {
-            Ok(*Proof::try_from_bytes(data.data()).expect("Failed to parse treasury account"))
-        } else {
-            Err(())
-        };
+    let mut account_pubkeys = vec![proof_pubkey(authority), CONFIG_ADDRESS];
+    account_pubkeys.extend_from_slice(&BUS_ADDRESSES);
 
-        let treasury_config = if let Some(data) = &datas[1] {
-            Ok(*coal_api::state::Config::try_from_bytes(data.data())
-                .expect("Failed to parse config account"))
-        } else {
-            Err(())
-        };
-        let bus_1 = if let Some(data) = &datas[2] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus1 account"))
-        } else {
-            Err(())
-        };
-        let bus_2 = if let Some(data) = &datas[3] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus2 account"))
-        } else {
-            Err(())
-        };
-        let bus_3 = if let Some(data) = &datas[4] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus3 account"))
-        } else {
-            Err(())
-        };
-        let bus_4 = if let Some(data) = &datas[5] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus4 account"))
-        } else {
-            Err(())
-        };
-        let bus_5 = if let Some(data) = &datas[6] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus5 account"))
-        } else {
-            Err(())
-        };
-        let bus_6 = if let Some(data) = &datas[7] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus6 account"))
-        } else {
-            Err(())
-        };
-        let bus_7 = if let Some(data) = &datas[8] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus7 account"))
-        } else {
-            Err(())
-        };
-        let bus_8 = if let Some(data) = &datas[9] {
-            Ok(*coal_api::state::Bus::try_from_bytes(data.data())
-                .expect("Failed to parse bus1 account"))
-        } else {
-            Err(())
-        };
+    let datas = match client.get_multiple_accounts(&account_pubkeys).await {
+        Ok(datas) => datas,
+        Err(_) => {
+            return (
+                Err(GetProofError::FailedToGetAccount),
+                Err(GetConfigError::FailedToGetAccount),
+                Err(GetBusError::FailedToGetAccounts),
+            )
+        }
+    };
 
-        (
-            proof,
-            treasury_config,
-            Ok(vec![bus_1, bus_2, bus_3, bus_4, bus_5, bus_6, bus_7, bus_8]),
-        )
-    } else {
-        (Err(()), Err(()), Err(()))
-    }
+    let proof = parse_proof(datas[0].as_ref().map(|account| account.data()));
+    let config = parse_config(datas[1].as_ref().map(|account| account.data()));
+    let busses = datas[2..]
+        .iter()
+        .map(|account| parse_bus(account.as_ref().map(|account| account.data())))
+        .collect();
+
+    (proof, config, Ok(busses))
 }
 
-pub async fn get_proof(client: &RpcClient, authority: Pubkey) -> Result<Proof, String> {
-    let proof_address = proof_pubkey(authority);
-    let data = client.get_account_data(&proof_address).await;
-    match data {
-        Ok(data) => {
-            let proof = Proof::try_from_bytes(&data);
-            if let Ok(proof) = proof {
-                return Ok(*proof);
-            } else {
-                return Err("Failed to parse proof account".to_string());
-            }
-        }
-        Err(_) => return Err("Failed to get proof account".to_string()),
-    }
+pub async fn get_proof(client: &RpcClient, authority: Pubkey) -> Result<Proof, GetProofError> {
+    let data = client
+        .get_account_data(&proof_pubkey(authority))
+        .await
+        .map_err(|_| GetProofError::FailedToGetAccount)?;
+    parse_proof(Some(&data))
 }
 
 pub fn proof_pubkey(authority: Pubkey) -> Pubkey {
     Pubkey::find_program_address(&[PROOF, authority.as_ref()], &COAL_ID).0
 }
 
+// Reprocess support is newer than the coal api version in use, these mirror
+// the program's seeds, instruction discriminators and account layout.
+const REPROCESSOR: &[u8] = b"reprocessor";
+const CHROMIUM_MINT: &[u8] = b"chromium_mint";
+const INIT_REPROCESS_DISCRIMINATOR: u8 = 10;
+const FINALIZE_REPROCESS_DISCRIMINATOR: u8 = 11;
+const REPROCESSOR_DISCRIMINATOR: u8 = 108;
+/// Slots to wait between starting and finalizing a reprocess.
+pub const REPROCESS_TARGET_SLOT: u64 = 20;
+
+pub fn reprocessor_pubkey(authority: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REPROCESSOR, authority.as_ref()], &COAL_ID)
+}
+
+pub fn get_chromium_mint() -> Pubkey {
+    Pubkey::find_program_address(&[CHROMIUM_MINT, &MINT_NOISE], &COAL_ID).0
+}
+
+pub fn get_init_reprocess_ix(signer: Pubkey) -> Instruction {
+    let (reprocessor, reprocessor_bump) = reprocessor_pubkey(signer);
+
+    Instruction {
+        program_id: COAL_ID,
+        accounts: vec![
+            AccountMeta::new(signer, true),
+            AccountMeta::new(TREASURY_ADDRESS, false),
+            AccountMeta::new(reprocessor, false),
+            AccountMeta::new_readonly(sysvar::slot_hashes::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: vec![INIT_REPROCESS_DISCRIMINATOR, reprocessor_bump],
+    }
+}
+
+pub fn get_reprocess_ix(signer: Pubkey) -> Instruction {
+    let (reprocessor, reprocessor_bump) = reprocessor_pubkey(signer);
+    let chromium_mint = get_chromium_mint();
+    let tokens = get_associated_token_address(&signer, &chromium_mint);
+
+    Instruction {
+        program_id: COAL_ID,
+        accounts: vec![
+            AccountMeta::new(signer, true),
+            AccountMeta::new(reprocessor, false),
+            AccountMeta::new(proof_pubkey(signer), false),
+            AccountMeta::new(BUS_ADDRESSES[0], false),
+            AccountMeta::new(chromium_mint, false),
+            AccountMeta::new(tokens, false),
+            AccountMeta::new_readonly(TREASURY_ADDRESS, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::slot_hashes::id(), false),
+        ],
+        data: vec![FINALIZE_REPROCESS_DISCRIMINATOR, reprocessor_bump],
+    }
+}
+
+/// Returns the slot an in-progress reprocess was started at, None when the
+/// authority has no reprocessor account.
+pub async fn get_reprocessor_slot(
+    client: &RpcClient,
+    authority: Pubkey,
+) -> Result<Option<u64>, String> {
+    let accounts = client
+        .get_multiple_accounts(&[reprocessor_pubkey(authority).0])
+        .await
+        .map_err(|_| "Failed to get reprocessor account".to_string())?;
+
+    match &accounts[0] {
+        Some(account) => {
+            let data = account.data();
+            if data.first() != Some(&REPROCESSOR_DISCRIMINATOR) {
+                return Err("Failed to parse reprocessor account".to_string());
+            }
+            // authority (32 bytes) followed by the slot
+            data.get(40..48)
+                .map(|b| Some(u64::from_le_bytes(b.try_into().unwrap())))
+                .ok_or("Failed to parse reprocessor account".to_string())
+        }
+        None => Ok(None),
+    }
+}
+
 pub fn treasury_tokens_pubkey() -> Pubkey {
     get_associated_token_address(&TREASURY_ADDRESS, &MINT_ADDRESS)
 }
@@ -201,6 +468,22 @@ pub async fn get_clock_account(client: &RpcClient) -> Result<Clock, ()> {
     }
 }
 
+/// Fee in lamports, base and priority fee, paid by a confirmed transaction.
+pub async fn get_fee_paid(client: &RpcClient, signature: &Signature) -> Option<u64> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(client.commitment()),
+        max_supported_transaction_version: Some(0),
+    };
+    match client.get_transaction_with_config(signature, config).await {
+        Ok(txn) => txn.transaction.meta.map(|meta| meta.fee),
+        Err(e) => {
+            warn!("Failed to get the fee paid by {}: {:?}", signature, e);
+            None
+        }
+    }
+}
+
 pub fn get_cutoff(proof: Proof, buffer_time: u64) -> i64 {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -212,3 +495,100 @@ pub fn get_cutoff(proof: Proof, buffer_time: u64) -> i64 {
         .saturating_sub(buffer_time as i64)
         .saturating_sub(now)
 }
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+    use coal_utils::Discriminator;
+
+    use super::*;
+
+    // account data as the program stores it, the discriminator in an 8 byte header
+    fn account_data<T: Discriminator + Pod>(account: &T) -> Vec<u8> {
+        let mut data = vec![T::discriminator(), 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(bytemuck::bytes_of(account));
+        data
+    }
+
+    fn mine_event(difficulty: u64, reward: u64, timing: i64) -> Vec<u8> {
+        bytemuck::bytes_of(&MineEvent {
+            difficulty,
+            reward,
+            timing,
+        })
+        .to_vec()
+    }
+
+    #[test]
+    fn base_mine_event_has_no_tool_or_stake_rewards() {
+        let event = parse_mine_event(&mine_event(21, 500, -3)).unwrap();
+        assert_eq!((event.difficulty, event.reward, event.timing), (21, 500, -3));
+        assert_eq!((event.tool_reward, event.stake_reward), (0, 0));
+        assert_eq!(event.total_reward(), 500);
+    }
+
+    #[test]
+    fn newer_mine_events_append_tool_and_stake_rewards() {
+        let mut bytes = mine_event(21, 500, 0);
+        bytes.extend_from_slice(&7u64.to_le_bytes());
+        let event = parse_mine_event(&bytes).unwrap();
+        assert_eq!((event.tool_reward, event.stake_reward), (7, 0));
+
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        let event = parse_mine_event(&bytes).unwrap();
+        assert_eq!((event.tool_reward, event.stake_reward), (7, u64::MAX));
+        assert_eq!(event.total_reward(), u64::MAX);
+    }
+
+    #[test]
+    fn short_mine_events_are_refused() {
+        assert!(parse_mine_event(&[]).is_none());
+        assert!(parse_mine_event(&mine_event(21, 500, 0)[..23]).is_none());
+    }
+
+    #[test]
+    fn missing_accounts_are_get_errors() {
+        assert_eq!(parse_config(None), Err(GetConfigError::FailedToGetAccount));
+        assert_eq!(parse_proof(None), Err(GetProofError::FailedToGetAccount));
+        assert_eq!(parse_bus(None), Err(GetBusError::AccountMissing));
+    }
+
+    #[test]
+    fn undecodable_accounts_are_parse_errors() {
+        let proof = account_data(&Proof::zeroed());
+        let mut wrong_discriminator = proof.clone();
+        wrong_discriminator[0] = wrong_discriminator[0].wrapping_add(1);
+        for data in [&[][..], &[0u8; 7], &proof[..proof.len() - 1], &wrong_discriminator] {
+            assert_eq!(parse_proof(Some(data)), Err(GetProofError::FailedToParseAccount));
+        }
+        // another account's data
+        assert_eq!(parse_config(Some(&proof)), Err(GetConfigError::FailedToParseAccount));
+        assert_eq!(parse_bus(Some(&proof)), Err(GetBusError::FailedToParseAccount));
+    }
+
+    #[test]
+    fn accounts_decode_from_their_data() {
+        let mut proof = Proof::zeroed();
+        proof.balance = 42;
+        assert_eq!(parse_proof(Some(&account_data(&proof))), Ok(proof));
+
+        let mut config = Config::zeroed();
+        config.min_difficulty = 8;
+        assert_eq!(parse_config(Some(&account_data(&config))), Ok(config));
+
+        let mut bus = Bus::zeroed();
+        bus.id = 3;
+        assert_eq!(parse_bus(Some(&account_data(&bus))), Ok(bus));
+    }
+
+    #[test]
+    fn ui_strings_are_exact_and_trimmed() {
+        assert_eq!(COAL_TOKEN_DECIMALS, 11);
+        assert_eq!(amount_to_ui_string(0), "0");
+        assert_eq!(amount_to_ui_string(1), "0.00000000001");
+        assert_eq!(amount_to_ui_string(100_000_000_000 - 1), "0.99999999999");
+        assert_eq!(amount_to_ui_string(100_000_000_000), "1");
+        assert_eq!(amount_to_ui_string(150_000_000_000), "1.5");
+        assert_eq!(amount_to_ui_string(u64::MAX), "184467440.73709551615");
+    }
+}
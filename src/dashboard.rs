@@ -0,0 +1,192 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        WebSocketUpgrade,
+    },
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use coal_api::state::Proof;
+use serde::Serialize;
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::{
+    api_error::{ApiError, ApiErrorCode},
+    coal_utils::get_cutoff,
+    is_admin,
+    pool_stats::get_pool_stats_snapshot,
+    AppState, Config, EpochHashes,
+};
+
+// Events a dashboard client may fall behind by before it is disconnected.
+// Sends never wait on receivers, so a slow dashboard can't hold up mining.
+pub const DASHBOARD_LAG_LIMIT: usize = 1024;
+const EPOCH_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DashboardEvent {
+    ConnectedMiners {
+        count: usize,
+    },
+    EpochProgress {
+        last_hash_at: i64,
+        seconds_until_cutoff: i64,
+        submissions: usize,
+        best_difficulty: u32,
+        connected_miners: usize,
+        total_hashpower: u64,
+    },
+    MineSuccess {
+        challenge_id: i32,
+        difficulty: u32,
+        rewards: u64,
+        distributable_rewards: u64,
+        total_balance: f64,
+        total_hashpower: u64,
+        submitting_miners: usize,
+        connected_miners: usize,
+    },
+    MinerJoined {
+        pubkey: String,
+    },
+    MinerLeft {
+        pubkey: String,
+    },
+    MinerSignedUp {
+        pubkey: String,
+    },
+    SubmissionAccepted {
+        pubkey: String,
+        device_id: Option<String>,
+        difficulty: u32,
+        hashpower: u64,
+    },
+    ClaimProcessed {
+        pubkey: String,
+        amount: u64,
+        signature: String,
+    },
+}
+
+/// Fans pool events out to the connected dashboards and the event publisher.
+#[derive(Clone)]
+pub struct DashboardEventBus {
+    sender: broadcast::Sender<DashboardEvent>,
+}
+
+impl DashboardEventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(DASHBOARD_LAG_LIMIT);
+        DashboardEventBus { sender }
+    }
+
+    pub fn send(&self, event: DashboardEvent) {
+        // only fails when no dashboard is connected
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DashboardEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/ws/dashboard",
+    tag = "admin",
+    security(("admin_password" = [])),
+    responses(
+        (status = 101, description = "Upgraded to the dashboard websocket, events are sent as json text messages"),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    )
+)]
+pub async fn dashboard_ws_handler(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(dashboard_bus): Extension<DashboardEventBus>,
+) -> Response {
+    if !is_admin(&headers, &app_config) {
+        return ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized").into_response();
+    }
+
+    let receiver = dashboard_bus.subscribe();
+    ws.on_upgrade(move |socket| handle_dashboard_socket(socket, receiver))
+}
+
+async fn handle_dashboard_socket(
+    mut socket: WebSocket,
+    mut receiver: broadcast::Receiver<DashboardEvent>,
+) {
+    info!("Dashboard connected");
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Dashboard fell {} events behind, disconnecting", skipped);
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let text = match serde_json::to_string(&event) {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                // dashboards only listen, anything but a close is ignored
+                match msg {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+    info!("Dashboard disconnected");
+}
+
+/// Posts an EpochProgress event every 30 seconds while a dashboard is connected.
+pub async fn epoch_progress_system(
+    proof: Arc<Mutex<Proof>>,
+    epoch_hashes: Arc<RwLock<EpochHashes>>,
+    app_state: Arc<RwLock<AppState>>,
+    dashboard_bus: DashboardEventBus,
+) {
+    loop {
+        tokio::time::sleep(EPOCH_PROGRESS_INTERVAL).await;
+        if !dashboard_bus.has_subscribers() {
+            continue;
+        }
+
+        let proof = *proof.lock().await;
+        let stats = get_pool_stats_snapshot(
+            &*app_state.read().await,
+            &*epoch_hashes.read().await,
+            &proof,
+        );
+
+        dashboard_bus.send(DashboardEvent::EpochProgress {
+            last_hash_at: stats.last_hash_at,
+            seconds_until_cutoff: get_cutoff(proof, 0),
+            submissions: stats.submissions,
+            best_difficulty: stats.best_difficulty,
+            connected_miners: stats.active_miners,
+            total_hashpower: stats.total_hashpower,
+        });
+    }
+}
@@ -0,0 +1,228 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// Websocket protocol version that adds the epoch id to work assignments and
+/// solution submissions. Clients opt in with the protocol_version query param.
+pub const EPOCH_PROTOCOL_VERSION: u8 = 2;
+
+/// Websocket protocol version that adds the proof's last_hash_at and the slot
+/// the server observed the proof at to work assignments, so clients can check
+/// the challenge against their own rpc. Work assignments are laid out as:
+/// - 0..1 message type, 0
+/// - 1..33 challenge
+/// - 33..41 seconds until the cutoff, i64 le
+/// - 41..57 nonce range start and end, u64 le
+/// - 57..65 epoch id, u64 le, from EPOCH_PROTOCOL_VERSION
+/// - 65..73 proof last_hash_at in unix seconds, i64 le
+/// - 73..81 slot of the proof notification, u64 le, 0 until one was received
+pub const PROOF_METADATA_PROTOCOL_VERSION: u8 = 3;
+
+/// Sent to a ready miner as a json text message, with
+/// `"type": "wait_for_next_epoch"`, instead of work it couldn't finish before
+/// the cutoff. The miner stays ready and gets work for the next epoch as
+/// soon as it starts, without sending Ready again.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "wait_for_next_epoch")]
+pub struct WaitForNextEpochNotice {
+    pub epoch_id: u64,
+    // seconds until the cutoff, 0 once it passed
+    pub seconds_to_cutoff: i64,
+}
+
+/// Whether ready miners still get work with cutoff seconds left. Once the
+/// epoch has a solution, work stops at admission_cutoff seconds before the
+/// cutoff, without one it's handed out until a solution comes in.
+pub fn admits_new_work(cutoff: i64, admission_cutoff: u64, has_solution: bool) -> bool {
+    !has_solution || cutoff > admission_cutoff.min(i64::MAX as u64) as i64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochChallenge {
+    pub epoch_id: u64,
+    pub challenge: [u8; 32],
+}
+
+/// Challenge work is handed out for. The generation is only bumped once the
+/// new challenge's db row exists, so work for a challenge is never assigned
+/// before submissions for it can be recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochGeneration {
+    pub generation: u64,
+    pub challenge: [u8; 32],
+}
+
+impl EpochGeneration {
+    pub fn new(challenge: [u8; 32]) -> Self {
+        EpochGeneration {
+            generation: 1,
+            challenge,
+        }
+    }
+
+    pub fn next(&self, challenge: [u8; 32]) -> Self {
+        EpochGeneration {
+            generation: self.generation + 1,
+            challenge,
+        }
+    }
+}
+
+/// Challenges work was handed out for. The previous one is kept for a single
+/// rotation so submissions that arrive just after the challenge changed can
+/// still be checked against the challenge they were computed for.
+#[derive(Debug)]
+pub struct EpochChallenges {
+    current: EpochChallenge,
+    previous: Option<EpochChallenge>,
+}
+
+impl EpochChallenges {
+    pub fn new(challenge: [u8; 32]) -> Self {
+        EpochChallenges {
+            current: EpochChallenge {
+                epoch_id: 1,
+                challenge,
+            },
+            previous: None,
+        }
+    }
+
+    /// Epoch of the challenge work is being assigned for, starting a new epoch
+    /// when the challenge changed.
+    pub fn assign(&mut self, challenge: [u8; 32]) -> EpochChallenge {
+        if self.current.challenge != challenge {
+            self.previous = Some(self.current);
+            self.current = EpochChallenge {
+                epoch_id: self.current.epoch_id + 1,
+                challenge,
+            };
+        }
+        self.current
+    }
+
+    pub fn current(&self) -> EpochChallenge {
+        self.current
+    }
+
+    pub fn previous(&self) -> Option<EpochChallenge> {
+        self.previous
+    }
+
+    /// Challenge of the current or previous epoch, None for any older epoch.
+    pub fn challenge(&self, epoch_id: u64) -> Option<[u8; 32]> {
+        if self.current.epoch_id == epoch_id {
+            return Some(self.current.challenge);
+        }
+        self.previous
+            .filter(|previous| previous.epoch_id == epoch_id)
+            .map(|previous| previous.challenge)
+    }
+}
+
+/// Nonce ranges assigned to a miner device in the current and previous epoch.
+#[derive(Debug, Clone)]
+pub struct AssignedNonceRanges {
+    current: (u64, Range<u64>),
+    previous: Option<(u64, Range<u64>)>,
+    // when the current range was assigned
+    assigned_at: Instant,
+}
+
+impl AssignedNonceRanges {
+    pub fn new(epoch_id: u64, range: Range<u64>) -> Self {
+        AssignedNonceRanges {
+            current: (epoch_id, range),
+            previous: None,
+            assigned_at: Instant::now(),
+        }
+    }
+
+    pub fn assign(&mut self, epoch_id: u64, range: Range<u64>) {
+        if self.current.0 != epoch_id {
+            self.previous = Some(self.current.clone());
+        }
+        self.current = (epoch_id, range);
+        self.assigned_at = Instant::now();
+    }
+
+    /// Epoch id and range of the latest assignment.
+    pub fn current(&self) -> (u64, Range<u64>) {
+        self.current.clone()
+    }
+
+    pub fn assigned_at(&self) -> Instant {
+        self.assigned_at
+    }
+
+    /// Range assigned for the epoch, the latest range for submissions that
+    /// don't name one.
+    pub fn get(&self, epoch_id: Option<u64>) -> Option<Range<u64>> {
+        match epoch_id {
+            None => Some(self.current.1.clone()),
+            Some(epoch_id) if self.current.0 == epoch_id => Some(self.current.1.clone()),
+            Some(epoch_id) => self
+                .previous
+                .as_ref()
+                .filter(|(previous_id, _)| *previous_id == epoch_id)
+                .map(|(_, range)| range.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn work_stops_at_the_admission_cutoff_once_solved() {
+        assert!(admits_new_work(11, 10, true));
+        assert!(!admits_new_work(10, 10, true));
+        assert!(!admits_new_work(9, 10, true));
+        assert!(!admits_new_work(-1, 0, true));
+    }
+
+    #[test]
+    fn work_is_handed_out_until_a_solution_comes_in() {
+        assert!(admits_new_work(11, 10, false));
+        assert!(admits_new_work(10, 10, false));
+        assert!(admits_new_work(9, 10, false));
+        assert!(admits_new_work(-1, 0, false));
+    }
+
+    #[test]
+    fn generations_count_up_from_one() {
+        let first = EpochGeneration::new([1; 32]);
+        assert_eq!(first.generation, 1);
+
+        let second = first.next([2; 32]);
+        assert_eq!(second, EpochGeneration { generation: 2, challenge: [2; 32] });
+        // a challenge seen again is still a new generation
+        assert_eq!(second.next([1; 32]).generation, 3);
+    }
+
+    #[test]
+    fn submissions_name_the_current_or_previous_epoch() {
+        let mut epoch_challenges = EpochChallenges::new([1; 32]);
+        assert_eq!(epoch_challenges.assign([1; 32]).epoch_id, 1);
+        assert_eq!(epoch_challenges.previous(), None);
+
+        assert_eq!(epoch_challenges.assign([2; 32]).epoch_id, 2);
+        assert_eq!(epoch_challenges.challenge(2), Some([2; 32]));
+        assert_eq!(epoch_challenges.challenge(1), Some([1; 32]));
+
+        epoch_challenges.assign([3; 32]);
+        assert_eq!(epoch_challenges.previous(), Some(EpochChallenge { epoch_id: 2, challenge: [2; 32] }));
+        assert_eq!(epoch_challenges.challenge(1), None);
+        assert_eq!(epoch_challenges.challenge(2), Some([2; 32]));
+        assert_eq!(epoch_challenges.challenge(4), None);
+    }
+
+    #[test]
+    fn admission_cutoff_above_i64_max_never_admits() {
+        assert!(!admits_new_work(i64::MAX, u64::MAX, true));
+        assert!(!admits_new_work(i64::MAX - 1, i64::MAX as u64, true));
+        assert!(admits_new_work(i64::MAX, u64::MAX, false));
+    }
+}
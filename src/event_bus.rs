@@ -0,0 +1,176 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use tokio::{sync::broadcast, time::Instant};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::dashboard::{DashboardEvent, DashboardEventBus};
+
+// Redis pub/sub channel events are published to when EVENT_BUS_CHANNEL isn't set.
+const DEFAULT_CHANNEL: &str = "coal-pool-events";
+// Longest a connect or publish may take before the event is dropped.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(2);
+// Events are dropped without connecting again until this long after a failure.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Broker settings read from EVENT_BUS_URL (redis://...) and EVENT_BUS_CHANNEL.
+#[derive(Debug, Clone)]
+pub struct EventBusConfig {
+    pub url: String,
+    pub channel: String,
+}
+
+impl EventBusConfig {
+    /// None when EVENT_BUS_URL isn't set, publishing is off.
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("EVENT_BUS_URL").ok()?;
+        let channel =
+            std::env::var("EVENT_BUS_CHANNEL").unwrap_or_else(|_| DEFAULT_CHANNEL.to_string());
+        Some(EventBusConfig { url, channel })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EventBusStats {
+    published: AtomicU64,
+    // lost to a broker that was down or slow, or to the publisher falling behind
+    dropped: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct EventBusStatus {
+    pub published: u64,
+    pub dropped: u64,
+}
+
+impl EventBusStats {
+    fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> EventBusStatus {
+        EventBusStatus {
+            published: self.published.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PublishedEvent<'a> {
+    pool_id: i32,
+    timestamp: i64,
+    #[serde(flatten)]
+    event: &'a DashboardEvent,
+}
+
+fn is_exported(event: &DashboardEvent) -> bool {
+    matches!(
+        event,
+        DashboardEvent::SubmissionAccepted { .. }
+            | DashboardEvent::MineSuccess { .. }
+            | DashboardEvent::ClaimProcessed { .. }
+            | DashboardEvent::MinerSignedUp { .. }
+    )
+}
+
+/// Publishes the analytics events of the pool's dashboard bus to redis.
+/// Delivery is best effort: the bus never waits on this task, and events the
+/// broker can't take in time are dropped and counted.
+pub async fn event_publisher_system(
+    config: EventBusConfig,
+    pool_id: i32,
+    dashboard_bus: DashboardEventBus,
+    stats: Arc<EventBusStats>,
+) {
+    let client = match redis::Client::open(config.url.as_str()) {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Invalid EVENT_BUS_URL, not publishing events: {:?}", e);
+            return;
+        }
+    };
+    info!("Publishing pool events to redis channel {}", config.channel);
+
+    let mut receiver = dashboard_bus.subscribe();
+    let mut connection: Option<MultiplexedConnection> = None;
+    let mut last_failure: Option<Instant> = None;
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Event publisher fell {} events behind", skipped);
+                stats.record_dropped(skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if !is_exported(&event) {
+            continue;
+        }
+
+        let payload = match serde_json::to_string(&PublishedEvent {
+            pool_id,
+            timestamp: chrono::Utc::now().timestamp(),
+            event: &event,
+        }) {
+            Ok(payload) => payload,
+            Err(_) => {
+                stats.record_dropped(1);
+                continue;
+            }
+        };
+
+        if connection.is_none() {
+            if last_failure.is_some_and(|at| at.elapsed() < RECONNECT_INTERVAL) {
+                stats.record_dropped(1);
+                continue;
+            }
+            match tokio::time::timeout(PUBLISH_TIMEOUT, client.get_multiplexed_tokio_connection())
+                .await
+            {
+                Ok(Ok(conn)) => connection = Some(conn),
+                Ok(Err(e)) => {
+                    warn!("Failed to connect to the event bus: {:?}", e);
+                }
+                Err(_) => {
+                    warn!("Timed out connecting to the event bus");
+                }
+            }
+        }
+        let conn = match connection.as_mut() {
+            Some(conn) => conn,
+            None => {
+                last_failure = Some(Instant::now());
+                stats.record_dropped(1);
+                continue;
+            }
+        };
+
+        let mut publish = redis::cmd("PUBLISH");
+        publish.arg(&config.channel).arg(payload);
+        match tokio::time::timeout(PUBLISH_TIMEOUT, publish.query_async::<_, i64>(conn)).await {
+            Ok(Ok(_)) => {
+                stats.published.fetch_add(1, Ordering::Relaxed);
+            }
+            result => {
+                if let Ok(Err(e)) = result {
+                    warn!("Failed to publish event: {:?}", e);
+                } else {
+                    warn!("Timed out publishing event");
+                }
+                connection = None;
+                last_failure = Some(Instant::now());
+                stats.record_dropped(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Highest priority fee the ratchet raises mine transactions to, in microlamports per CU.
+pub const MAX_PRIORITY_FEE: u64 = 1_000_000;
+// Share of the daily budget spent after which the fee cap starts coming down.
+const CAP_START_PCT: u64 = 50;
+
+/// Priority fees paid by landed mine transactions in the current UTC day,
+/// and the fee cap that keeps the spend within the daily budget.
+#[derive(Debug)]
+pub struct FeeBudget {
+    // lamports per UTC day, None for no limit
+    daily_budget: Option<u64>,
+    // fee used once the budget is spent, the configured --priority-fee
+    min_fee: u64,
+    spent: u64,
+    day: NaiveDate,
+    // an alert was sent for the current day
+    exhausted_alerted: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct FeeBudgetStatus {
+    // priority fees paid today (UTC), in lamports
+    pub spent_today_lamports: u64,
+    pub daily_budget_lamports: Option<u64>,
+    // priority fee the ratchet is currently limited to, in microlamports per CU
+    pub max_priority_fee: u64,
+}
+
+impl FeeBudget {
+    pub fn new(daily_budget: Option<u64>, min_fee: u64) -> Self {
+        FeeBudget {
+            daily_budget,
+            min_fee,
+            spent: 0,
+            day: chrono::Utc::now().date_naive(),
+            exhausted_alerted: false,
+        }
+    }
+
+    fn roll_day(&mut self) {
+        let today = chrono::Utc::now().date_naive();
+        if today != self.day {
+            self.day = today;
+            self.spent = 0;
+            self.exhausted_alerted = false;
+        }
+    }
+
+    /// Highest fee allowed right now. Uncapped below CAP_START_PCT of the
+    /// budget, then lowered linearly to the minimum fee as the budget runs out.
+    pub fn max_fee(&mut self) -> u64 {
+        self.roll_day();
+        let budget = match self.daily_budget {
+            Some(budget) => budget,
+            None => return MAX_PRIORITY_FEE,
+        };
+        if self.spent >= budget {
+            return self.min_fee;
+        }
+        let cap_start = budget / 100 * CAP_START_PCT;
+        if self.spent <= cap_start {
+            return MAX_PRIORITY_FEE;
+        }
+        let remaining = (budget - self.spent) as u128;
+        let window = (budget - cap_start).max(1) as u128;
+        let cap = (MAX_PRIORITY_FEE as u128 * remaining / window) as u64;
+        cap.max(self.min_fee)
+    }
+
+    /// The fee to send with, the ratchet's fee limited by the budget.
+    pub fn cap_fee(&mut self, fee: u64) -> u64 {
+        fee.min(self.max_fee())
+    }
+
+    /// Adds the priority fee of a landed transaction. Returns true the first
+    /// time in a day the budget is exhausted, the caller sends the alert.
+    pub fn record_landed(&mut self, cu_limit: u32, fee: u64) -> bool {
+        self.roll_day();
+        let lamports = (cu_limit as u128 * fee as u128 / 1_000_000) as u64;
+        self.spent = self.spent.saturating_add(lamports);
+        match self.daily_budget {
+            Some(budget) if self.spent >= budget && !self.exhausted_alerted => {
+                self.exhausted_alerted = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn status(&mut self) -> FeeBudgetStatus {
+        let max_priority_fee = self.max_fee();
+        FeeBudgetStatus {
+            spent_today_lamports: self.spent,
+            daily_budget_lamports: self.daily_budget,
+            max_priority_fee,
+        }
+    }
+}
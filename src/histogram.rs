@@ -0,0 +1,51 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct HistogramBucket {
+    pub difficulty: u32,
+    pub count: u32,
+}
+
+/// Number of submissions at each difficulty in an epoch, one submission per
+/// miner device, ordered by difficulty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(transparent)]
+pub struct DifficultyHistogram(pub Vec<HistogramBucket>);
+
+impl DifficultyHistogram {
+    pub fn from_difficulties(difficulties: impl IntoIterator<Item = u32>) -> Self {
+        let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+        for difficulty in difficulties {
+            let count = counts.entry(difficulty).or_insert(0);
+            *count = count.saturating_add(1);
+        }
+        DifficultyHistogram(
+            counts
+                .into_iter()
+                .map(|(difficulty, count)| HistogramBucket { difficulty, count })
+                .collect(),
+        )
+    }
+
+    pub fn submissions(&self) -> u32 {
+        self.0
+            .iter()
+            .fold(0u32, |total, bucket| total.saturating_add(bucket.count))
+    }
+}
+
+/// Compact form sent to the miners, `difficulty:count` pairs separated by spaces.
+impl fmt::Display for DifficultyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, bucket) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}:{}", bucket.difficulty, bucket.count)?;
+        }
+        Ok(())
+    }
+}
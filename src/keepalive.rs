@@ -0,0 +1,220 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+// Longest a single websocket send may take before it counts as failed.
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+// Unanswered pings remembered per connection, the oldest are forgotten first.
+const MAX_OUTSTANDING_PINGS: usize = 8;
+// Sequence number then send time in unix milliseconds, both u64 le.
+const PING_PAYLOAD_LEN: usize = 16;
+
+/// How often connections are pinged and how long a ping may go unanswered
+/// before the connection is dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct KeepaliveConfig {
+    pub ping_interval: Duration,
+    pub pong_timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PongOutcome {
+    // answers an outstanding ping
+    Valid { rtt: Duration },
+    // echoes a ping older than the last one answered, or a ping answered already
+    Stale,
+    // echoes no ping sent to the connection
+    Unsolicited,
+    // empty payload, the answer to the ping sent when the connection opened
+    Ignored,
+}
+
+impl PongOutcome {
+    pub fn is_suspicious(&self) -> bool {
+        matches!(self, PongOutcome::Stale | PongOutcome::Unsolicited)
+    }
+}
+
+#[derive(Debug)]
+struct OutstandingPing {
+    sequence: u64,
+    sent_at_ms: u64,
+    sent_at: Instant,
+}
+
+#[derive(Debug)]
+struct ConnectionKeepalive {
+    connection_id: u64,
+    next_sequence: u64,
+    // 0 until a ping is answered, sequences start at 1
+    answered_sequence: u64,
+    outstanding: VecDeque<OutstandingPing>,
+    // oldest ping sent since the last valid pong
+    waiting_since: Option<Instant>,
+    last_valid_pong: Option<Instant>,
+    suspicious_pongs: u32,
+}
+
+impl ConnectionKeepalive {
+    fn new(connection_id: u64) -> Self {
+        ConnectionKeepalive {
+            connection_id,
+            next_sequence: 1,
+            answered_sequence: 0,
+            outstanding: VecDeque::new(),
+            waiting_since: None,
+            last_valid_pong: None,
+            suspicious_pongs: 0,
+        }
+    }
+}
+
+/// Ping sequence bookkeeping of the miner connections. Every ping carries a
+/// sequence number and its send time, and only a pong echoing an
+/// outstanding ping counts as the connection being alive.
+#[derive(Debug, Default)]
+pub struct Keepalive {
+    connections: HashMap<SocketAddr, ConnectionKeepalive>,
+}
+
+impl Keepalive {
+    /// Records a ping to the connection and returns the payload to send.
+    pub fn next_ping(
+        &mut self,
+        addr: SocketAddr,
+        connection_id: u64,
+        now: Instant,
+        now_ms: u64,
+    ) -> Vec<u8> {
+        let connection = self
+            .connections
+            .entry(addr)
+            .or_insert_with(|| ConnectionKeepalive::new(connection_id));
+        if connection.connection_id != connection_id {
+            // the addr was reused by a new connection
+            *connection = ConnectionKeepalive::new(connection_id);
+        }
+
+        let sequence = connection.next_sequence;
+        connection.next_sequence += 1;
+        if connection.outstanding.len() >= MAX_OUTSTANDING_PINGS {
+            connection.outstanding.pop_front();
+        }
+        connection.outstanding.push_back(OutstandingPing {
+            sequence,
+            sent_at_ms: now_ms,
+            sent_at: now,
+        });
+        connection.waiting_since.get_or_insert(now);
+
+        let mut payload = Vec::with_capacity(PING_PAYLOAD_LEN);
+        payload.extend_from_slice(&sequence.to_le_bytes());
+        payload.extend_from_slice(&now_ms.to_le_bytes());
+        payload
+    }
+
+    /// Matches a pong against the connection's outstanding pings. A valid
+    /// pong also answers every older ping still outstanding.
+    pub fn record_pong(
+        &mut self,
+        addr: SocketAddr,
+        connection_id: u64,
+        payload: &[u8],
+        now: Instant,
+    ) -> PongOutcome {
+        if payload.is_empty() {
+            return PongOutcome::Ignored;
+        }
+        let connection = match self.connections.get_mut(&addr) {
+            Some(connection) if connection.connection_id == connection_id => connection,
+            _ => return PongOutcome::Unsolicited,
+        };
+
+        let outcome = match parse_payload(payload) {
+            None => PongOutcome::Unsolicited,
+            Some((sequence, _)) if sequence <= connection.answered_sequence => PongOutcome::Stale,
+            Some((sequence, sent_at_ms)) => {
+                match connection
+                    .outstanding
+                    .iter()
+                    .position(|ping| ping.sequence == sequence && ping.sent_at_ms == sent_at_ms)
+                {
+                    Some(index) => {
+                        let ping = connection.outstanding.drain(..=index).next_back();
+                        connection.answered_sequence = sequence;
+                        connection.waiting_since =
+                            connection.outstanding.front().map(|ping| ping.sent_at);
+                        connection.last_valid_pong = Some(now);
+                        PongOutcome::Valid {
+                            rtt: ping.map_or(Duration::ZERO, |ping| now - ping.sent_at),
+                        }
+                    }
+                    None => PongOutcome::Unsolicited,
+                }
+            }
+        };
+        if outcome.is_suspicious() {
+            connection.suspicious_pongs = connection.suspicious_pongs.saturating_add(1);
+        }
+        outcome
+    }
+
+    /// Suspicious pongs received on the connection so far.
+    pub fn suspicious_pongs(&self, addr: SocketAddr, connection_id: u64) -> u32 {
+        match self.connections.get(&addr) {
+            Some(connection) if connection.connection_id == connection_id => {
+                connection.suspicious_pongs
+            }
+            _ => 0,
+        }
+    }
+
+    /// When the connection last answered a ping, None if it never has.
+    pub fn last_valid_pong(&self, addr: SocketAddr, connection_id: u64) -> Option<Instant> {
+        match self.connections.get(&addr) {
+            Some(connection) if connection.connection_id == connection_id => {
+                connection.last_valid_pong
+            }
+            _ => None,
+        }
+    }
+
+    /// Connections with a ping unanswered for longer than pong_timeout.
+    pub fn timed_out(&self, now: Instant, pong_timeout: Duration) -> Vec<(SocketAddr, u64)> {
+        self.connections
+            .iter()
+            .filter(|(_, connection)| {
+                connection
+                    .waiting_since
+                    .is_some_and(|since| now.saturating_duration_since(since) > pong_timeout)
+            })
+            .map(|(addr, connection)| (*addr, connection.connection_id))
+            .collect()
+    }
+
+    /// Forgets the connection, unless the addr is already used by another one.
+    pub fn remove(&mut self, addr: SocketAddr, connection_id: u64) {
+        if self
+            .connections
+            .get(&addr)
+            .is_some_and(|connection| connection.connection_id == connection_id)
+        {
+            self.connections.remove(&addr);
+        }
+    }
+}
+
+fn parse_payload(payload: &[u8]) -> Option<(u64, u64)> {
+    if payload.len() != PING_PAYLOAD_LEN {
+        return None;
+    }
+    let mut sequence = [0u8; 8];
+    sequence.copy_from_slice(&payload[..8]);
+    let mut sent_at_ms = [0u8; 8];
+    sent_at_ms.copy_from_slice(&payload[8..]);
+    Some((u64::from_le_bytes(sequence), u64::from_le_bytes(sent_at_ms)))
+}
@@ -1,36 +1,81 @@
 use std::{
     collections::{HashMap, HashSet},
-    net::SocketAddr,
-    ops::{ControlFlow, Div, Range},
-    path::Path,
+    net::{IpAddr, SocketAddr},
+    future::Future,
+    ops::{ControlFlow, Range},
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use self::models::*;
 use app_rr_database::AppRRDatabase;
 use ::coal_utils::AccountDeserialize;
+use api_error::{accepts_json, ApiError, ApiErrorCode};
 use app_database::{AppDatabase, AppDatabaseError};
+use audit_log::{AuditLog, AuditLogStatus};
+use best_difficulty_timeline::{BestDifficultyImprovement, BestDifficultyTimeline};
+use bus_stats::{BusSelectionStrategy, BusStats};
+use dashboard::{DashboardEvent, DashboardEventBus};
+use epochs::{
+    admits_new_work, AssignedNonceRanges, EpochChallenge, EpochChallenges, EpochGeneration, WaitForNextEpochNotice,
+    EPOCH_PROTOCOL_VERSION, PROOF_METADATA_PROTOCOL_VERSION,
+};
+#[cfg(feature = "event-bus")]
+use event_bus::{EventBusConfig, EventBusStats, EventBusStatus};
+use fee_budget::{FeeBudget, FeeBudgetStatus, MAX_PRIORITY_FEE};
+use histogram::DifficultyHistogram;
+use keepalive::{Keepalive, KeepaliveConfig, PongOutcome, SEND_TIMEOUT};
+use miner_import::{MinerImportOutcome, MinerImportReport, MinerImportRow};
+use missed_epochs::{EpochMissedNotice, MissedEpochStatus, MissedEpochs};
+use pool_info::{Network, PoolInfo, PublicUrls};
+use pubkey_display::{display_pubkey, PubkeyFormat};
+use sessions::SessionStats;
+use socket_utils::{extract_real_ip, extract_user_agent, TrustedProxies};
+use submission_ack::{RejectReason, SubmissionAck, SubmissionRejected};
+use nonce_coverage::{NonceRangeEntry, SubmissionHeatmapResponse};
+use pool_stats::{get_pool_stats_snapshot, PoolStatsSnapshot};
+use pool_config_file::PoolConfigFile;
+use pool_wallet::PoolWallet;
+use proof_updates::{ProofUpdateStatus, ProofUpdates};
+use redistribution::{RedistributeCredit, RedistributionReport};
+use replica_lag::{ReplicaLag, ReplicaLagStatus};
+use validated_pubkey::{PubkeyParam, ValidatedPubkey};
+use reprocess::{ReprocessStatus, ReprocessSystem};
+use reward_cache::RewardCache;
+use reward_formula::{RewardFormula, EXAMPLE_DIFFICULTY};
+use runtime_config::RuntimeConfig;
+use tx_builder::{get_or_refresh_blockhash, SolanaTransactionBuilder, BLOCKHASH_MAX_AGE};
+use webhooks::{resolve_public_url, WebhookEvent, WebhookJob};
+use ws_auth::{AuthWindow, SignedRequestReplays};
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
+        ws::{close_code, CloseFrame, Message, WebSocket},
         ConnectInfo, Query, State, WebSocketUpgrade,
-    }, http::{Method, Response, StatusCode}, response::IntoResponse, routing::{get, post}, Extension, Json, Router
+    }, http::{header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER}, HeaderMap, HeaderValue, Method, Response, StatusCode}, response::IntoResponse, routing::{delete, get, post}, Extension, Json, Router
 };
 use axum_extra::{headers::authorization::Basic, TypedHeader};
 use base64::{prelude::BASE64_STANDARD, Engine};
 use clap::Parser;
 use drillx_2::Solution;
 use futures::{stream::SplitSink, SinkExt, StreamExt};
-use coal_api::{consts::BUS_COUNT, event::MineEvent, state::Proof};
+use coal_api::{
+    consts::BUS_COUNT,
+    state::{Bus, Proof},
+};
 use coal_utils::{
-    get_auth_ix, get_cutoff, get_mine_ix, get_coal_mint, get_proof,
-    get_proof_and_config_with_busses, get_register_ix, get_reset_ix, proof_pubkey,
-    COAL_TOKEN_DECIMALS,
+    get_auth_ix, get_cutoff, get_guild_status, get_mine_ix, get_coal_mint, get_proof,
+    get_tool_status, parse_mine_event, GuildStatus, MineIxAccounts, ToolStatus,
+    get_proof_and_config_with_busses, GetBusError, GetProofError, get_register_ix, get_reset_ix, proof_pubkey,
+    amount_to_coal, amount_to_ui_string, COAL_TOKEN_DECIMALS, get_coal_epoch_duration, get_fee_paid,
 };
+use rewards::{calculate_earned_rewards, calculate_referral_bonus, is_better_solution, RewardError};
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::{
     nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
@@ -38,45 +83,170 @@ use solana_client::{
 };
 use solana_sdk::{
     commitment_config::CommitmentConfig,
-    compute_budget::ComputeBudgetInstruction,
-    native_token::LAMPORTS_PER_SOL,
+    native_token::{lamports_to_sol, LAMPORTS_PER_SOL},
+    program_pack::Pack,
     pubkey::Pubkey,
-    signature::{read_keypair_file, Keypair, Signature},
+    signature::{read_keypair_file, Signature},
     signer::Signer,
     system_instruction,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use solana_transaction_status::UiTransactionEncoding;
 use spl_associated_token_account::get_associated_token_address;
+use subtle::ConstantTimeEq;
 use tokio::{
     io::AsyncReadExt,
     sync::{
-        mpsc::{UnboundedReceiver, UnboundedSender},
-        Mutex, RwLock,
+        mpsc::{error::TrySendError, Receiver, Sender, UnboundedSender},
+        watch, Mutex, RwLock,
     }, time::Instant,
 };
 use tower_http::{cors::CorsLayer, trace::{DefaultMakeSpan, TraceLayer}};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+use utoipa::{IntoParams, ToSchema};
 
 mod app_rr_database;
+mod api_error;
 mod app_database;
+mod audit_log;
+mod best_difficulty_timeline;
+mod bus_stats;
+mod dashboard;
+mod epochs;
+#[cfg(feature = "event-bus")]
+mod event_bus;
+mod fee_budget;
+mod histogram;
+mod keepalive;
+mod miner_import;
+mod missed_epochs;
 mod models;
+mod nonce_coverage;
+mod openapi;
+mod pool_config_file;
+mod pool_info;
+mod pool_stats;
+mod pool_wallet;
+mod proof_updates;
+mod pubkey_display;
+mod redistribution;
+mod replica_lag;
+mod reward_cache;
+mod reward_formula;
+mod reprocess;
+mod rewards;
+mod runtime_config;
 mod schema;
+mod sessions;
+mod socket_utils;
+mod state_snapshot;
+mod submission_ack;
+mod tx_builder;
+mod validated_pubkey;
+mod webhooks;
+mod ws_auth;
 
 const MIN_DIFF: u32 = 8;
 const MIN_HASHPOWER: u64 = 5;
+const MAX_HASHPOWER: u64 = 81_920;
+// Documents how hashpower totals are derived from submission difficulties.
+const HASHPOWER_FORMULA: &str = "min(5 * 2^(difficulty - 8), 81920) for difficulty >= 8";
+// Highest difficulty stored for a submission, far above anything found within an epoch.
+const MAX_SUBMISSION_DIFFICULTY: u32 = 64;
+// Difficulties this far above log2 of a miner's nonce range have a 2^-20 chance per epoch.
+const PLAUSIBLE_DIFFICULTY_MARGIN: u32 = 20;
+// Nonces handed to a client per challenge, max hashes possible in 60s for a single client.
+const NONCE_RANGE_SIZE: u64 = 4_000_000;
+// How often the cached coal config and busses are refreshed in the background.
+const CONFIG_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+// Cached coal config older than this is refetched directly before a submission.
+const CONFIG_CACHE_MAX_AGE: Duration = Duration::from_secs(15);
+// Seconds a client is told to wait before reconnecting to a full pool.
+const POOL_FULL_RETRY_AFTER_SECS: u64 = 30;
+// How often the equipped tool's durability is refreshed.
+const TOOL_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+// How long a miner's claim history is served from cache.
+const CLAIMS_CACHE_TTL: Duration = Duration::from_secs(30);
+// How long the miner activity heatmap is served from cache.
+const ACTIVITY_CACHE_TTL: Duration = Duration::from_secs(3600);
+// How long the db figures of /pool/listing are reused, listing sites poll it often.
+const LISTING_CACHE_TTL: Duration = Duration::from_secs(60);
+// Window of the /pool/listing figures.
+const LISTING_WINDOW_HOURS: u32 = 24;
+// Days of epochs /pool/estimated-apy averages over, recomputed every hour.
+const APY_WINDOW_DAYS: u32 = 30;
+const APY_CACHE_TTL: Duration = Duration::from_secs(3600);
+// Least SOL the pool authority needs, in lamports, to start or be swapped in.
+const MIN_AUTHORITY_BALANCE_LAMPORTS: u64 = 1_000_000;
+// Seconds between coal resets, as assumed by the mine transaction builder.
+const COAL_RESET_INTERVAL_SECS: i64 = 300;
+// A reset instruction is added to mine transactions this close to the next reset.
+const RESET_IX_WINDOW_SECS: i64 = 5;
+// Cached epoch cutoff older than this is recomputed from the proof.
+const CUTOFF_CACHE_MAX_AGE: Duration = Duration::from_secs(2);
+// After a landed mine transaction, how long to wait for the challenge rotation
+// notification before fetching the proof directly.
+const CHALLENGE_CHANGE_TIMEOUT: Duration = Duration::from_secs(20);
+// How long the proof may be on a challenge no epoch was started for before
+// work distribution starts it, when the rotation wasn't seen after a mine.
+const EPOCH_START_TIMEOUT: Duration = Duration::from_secs(45);
+// Attempts to find an epoch's challenge row, and to store its outcome, before it's dropped.
+const EPOCH_OUTCOME_MAX_ATTEMPTS: u32 = 30;
+// Attempts to store a confirmed transaction's txn row before it's dropped.
+const TXN_RECORD_MAX_ATTEMPTS: u32 = 30;
+// Attempts to record a confirmed claim before it's dropped.
+const CLAIM_RECORD_MAX_ATTEMPTS: u32 = 30;
+// Consecutive failed sends after which a connection is dropped without waiting for the ping check.
+const MAX_CONSECUTIVE_SEND_FAILURES: u32 = 3;
+// Seconds an evicted client is asked to wait before reconnecting, sent in the close reason.
+const RECONNECT_DELAY_SECS: u64 = 5;
+
+// Source of AppClientConnection::connection_id, never reused while the server runs.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Connection ids of clients waiting for work, with the addr they were
+/// connected from when they sent Ready.
+type ReadyClients = HashMap<u64, SocketAddr>;
 
 #[derive(Clone)]
 struct AppClientConnection {
+    // unique per websocket, unlike the addr which the OS may reuse
+    connection_id: u64,
+    // miner the connection's work is attributed to
     pubkey: Pubkey,
+    // key the client authenticated and signs solutions with, a delegate or the miner itself
+    signer: Pubkey,
     miner_id: i32,
+    // set when the miner tagged the connection to run several machines on one wallet
+    device_id: DeviceId,
     socket: Arc<Mutex<SplitSink<WebSocket, Message>>>,
+    // websocket protocol the client speaks, see EPOCH_PROTOCOL_VERSION
+    protocol_version: u8,
+    // consecutive failed sends, reset by a successful send or a pong
+    send_failures: Arc<AtomicU32>,
+    // summarized to the miner and stored when the connection ends
+    session: Arc<SessionStats>,
+    // the client's ip behind a reverse proxy, the addr's ip otherwise
+    real_ip: IpAddr,
+    user_agent: Option<String>,
 }
 
 struct AppState {
     sockets: HashMap<SocketAddr, AppClientConnection>,
 }
 
+impl AppState {
+    /// device_id of each connection of the miner.
+    fn device_ids(&self, miner_pubkey: &Pubkey) -> Vec<&DeviceId> {
+        self.sockets
+            .values()
+            .filter(|c| c.pubkey == *miner_pubkey)
+            .map(|c| &c.device_id)
+            .collect()
+    }
+}
+
 pub struct MessageInternalAllClients {
     text: String,
 }
@@ -87,24 +257,107 @@ pub struct MessageInternalMineSuccess {
     rewards: u64,
     challenge_id: i32,
     total_hashpower: u64,
-    submissions: HashMap<Pubkey, (i32, u32, u64)>,
+    submissions: HashMap<(Pubkey, DeviceId), (i32, u32, u64)>,
+    histogram: DifficultyHistogram,
+    // None when the mine transaction's txn row couldn't be stored
+    txn_id: Option<i32>,
+}
+
+#[derive(Default)]
+pub struct MinerClaimsCache {
+    entries: HashMap<(String, u32, u32), (Instant, MinerClaimsResponse)>,
 }
 
-pub struct LastPong {
-    pongs: HashMap<SocketAddr, Instant>
+#[derive(Default)]
+pub struct MinerActivityCache {
+    entries: HashMap<u32, (Instant, Vec<ActivityBucket>)>,
+}
+
+#[derive(Default)]
+pub struct PoolListingCache {
+    stats: Option<(Instant, models::PoolListingStats)>,
+}
+
+#[derive(Default)]
+pub struct RewardYieldCache {
+    reward_yield: Option<(Instant, models::RewardYield)>,
 }
 
 #[derive(Debug)]
 pub enum ClientMessage {
-    Ready(SocketAddr),
+    Ready(SocketAddr, u64),
     Mining(SocketAddr),
-    Pong(SocketAddr),
-    BestSolution(SocketAddr, Solution, Pubkey),
+    // connection id and the echoed ping payload
+    Pong(SocketAddr, u64, Vec<u8>),
+    // epoch id the solution was computed for, None for clients before EPOCH_PROTOCOL_VERSION
+    BestSolution(SocketAddr, Solution, Pubkey, Option<u64>),
+    // sent by handle_socket once the connection's receiver has ended
+    Disconnect(SocketAddr, u64),
 }
 
+/// Device tag of a miner connection, None for untagged connections.
+pub type DeviceId = Option<String>;
+
+// Nonce ranges last assigned to each connected miner device.
+type ClientNonceRanges = HashMap<(Pubkey, DeviceId), AssignedNonceRanges>;
+
+// Longest device_id accepted on the websocket connection.
+const MAX_DEVICE_ID_LEN: usize = 64;
+
 pub struct EpochHashes {
+    // EpochGeneration the submissions were made for
+    generation: u64,
     best_hash: BestHash,
-    submissions: HashMap<Pubkey, (i32, u32, u64)>,
+    submissions: HashMap<(Pubkey, DeviceId), (i32, u32, u64)>,
+}
+
+impl EpochHashes {
+    /// Credits a device's submission to the epoch of the generation it was
+    /// checked for. None when the epoch moved on since, otherwise whether it
+    /// improved the device's best and whether it's the epoch's new best.
+    fn credit(
+        &mut self,
+        generation: u64,
+        key: &(Pubkey, DeviceId),
+        miner_id: i32,
+        solution: Solution,
+        diff: u32,
+    ) -> Option<(bool, bool)> {
+        if self.generation != generation {
+            return None;
+        }
+        // each device is credited with its best submission of the epoch
+        let improved = match self.submissions.get(key) {
+            Some((_, best, _)) => diff > *best,
+            None => true,
+        };
+        if improved {
+            self.submissions
+                .insert(key.clone(), (miner_id, diff, hashpower_for_difficulty(diff)));
+        }
+        let better = match &self.best_hash.solution {
+            Some(best) => is_better_solution(&solution, best),
+            None => true,
+        };
+        if better {
+            self.best_hash.difficulty = diff;
+            self.best_hash.solution = Some(solution);
+        }
+        Some((improved, better))
+    }
+
+    /// Clears the hashes for the next generation if they're still on the
+    /// previous one, returns whether they were reset.
+    fn advance(&mut self, previous: u64, next: u64) -> bool {
+        if self.generation != previous {
+            return false;
+        }
+        self.generation = next;
+        self.best_hash.solution = None;
+        self.best_hash.difficulty = 0;
+        self.submissions = HashMap::new();
+        true
+    }
 }
 
 pub struct BestHash {
@@ -112,14 +365,107 @@ pub struct BestHash {
     difficulty: u32,
 }
 
+#[derive(Clone)]
+pub struct CoalConfigSnapshot {
+    config: coal_api::state::Config,
+    busses: Vec<Result<Bus, GetBusError>>,
+    fetched_at: Instant,
+}
+
+/// Last cutoff computed by the ready clients loop. Reused while the proof
+/// challenge is unchanged so the proof isn't locked every iteration.
+struct CutoffCache {
+    proof_challenge: [u8; 32],
+    computed_at: Instant,
+    cutoff_value: i64,
+    // of the proof the cutoff was computed from, sent in work assignments
+    last_hash_at: i64,
+}
+
+impl CutoffCache {
+    fn cutoff(&self, challenge: &[u8; 32]) -> Option<i64> {
+        let elapsed = self.computed_at.elapsed();
+        if self.proof_challenge != *challenge || elapsed > CUTOFF_CACHE_MAX_AGE {
+            return None;
+        }
+        Some(self.cutoff_value - elapsed.as_secs() as i64)
+    }
+}
+
 pub struct Config {
     password: String,
     whitelist: Option<HashSet<Pubkey>>,
     pool_id: i32,
+    max_miners: Option<usize>,
+    max_devices_per_miner: usize,
+    guild: Option<GuildStatus>,
+    tool: Option<Pubkey>,
+    tool_durability_warning: u64,
+    operator_webhook_url: Option<String>,
+    // None without --public-url
+    public_urls: Option<PublicUrls>,
+    network: Network,
+    // percentage of a referred miner's earnings credited to its referrer
+    referral_bonus_pct: f64,
+    // miners with a lower reputation score can't connect
+    min_reputation_score: i32,
+    // validity of the timestamps signed by miners
+    auth_window: AuthWindow,
+    // peers whose forwarding headers give the client's ip
+    trusted_proxies: TrustedProxies,
+}
+
+impl Config {
+    /// Whitelisted miners are never turned away when the pool is at capacity.
+    fn bypasses_capacity(&self, pubkey: &Pubkey) -> bool {
+        match &self.whitelist {
+            Some(whitelist) => whitelist.contains(pubkey),
+            None => false,
+        }
+    }
 }
 
 mod coal_utils;
 
+/// Hashpower credited for a submission: MIN_HASHPOWER * 2^(diff - MIN_DIFF),
+/// capped at MAX_HASHPOWER. Submissions below MIN_DIFF earn nothing.
+pub fn hashpower_for_difficulty(diff: u32) -> u64 {
+    if diff < MIN_DIFF {
+        return 0;
+    }
+    MIN_HASHPOWER
+        .checked_shl(diff - MIN_DIFF)
+        .unwrap_or(MAX_HASHPOWER)
+        .min(MAX_HASHPOWER)
+}
+
+/// The difficulty to store for a submission. It is computed server-side from
+/// the solution's hash, the miner doesn't send one, and range_size is the
+/// nonce range the miner searched for it.
+fn stored_difficulty(solution: &Solution, range_size: u64) -> i16 {
+    clamp_stored_difficulty(solution.to_hash().difficulty(), range_size)
+}
+
+/// Highest difficulty a miner plausibly finds in an epoch. Its nonce range is
+/// sized for the hashes one client does in an epoch, and finding difficulty d
+/// takes about 2^d hashes.
+fn max_plausible_difficulty(range_size: u64) -> u32 {
+    (u64::BITS - range_size.leading_zeros()) + PLAUSIBLE_DIFFICULTY_MARGIN
+}
+
+/// Warns about a difficulty that is implausible for the range it was found
+/// in and caps it at MAX_SUBMISSION_DIFFICULTY.
+fn clamp_stored_difficulty(difficulty: u32, range_size: u64) -> i16 {
+    let max_plausible = max_plausible_difficulty(range_size);
+    if difficulty > max_plausible {
+        warn!(
+            "Submission difficulty {} is implausible for a range of {} nonces, above {}",
+            difficulty, range_size, max_plausible
+        );
+    }
+    difficulty.min(MAX_SUBMISSION_DIFFICULTY) as i16
+}
+
 #[derive(Parser, Debug)]
 #[command(version, author, about, long_about = None)]
 struct Args {
@@ -147,6 +493,334 @@ struct Args {
         global = true
     )]
     signup_cost: u64,
+    #[arg(
+        long,
+        value_name = "max miners",
+        help = "Maximum number of concurrently connected miners, whitelisted miners bypass this limit",
+        default_value = None,
+        global = true
+    )]
+    max_miners: Option<usize>,
+    #[arg(
+        long,
+        value_name = "max devices per miner",
+        help = "Maximum concurrent connections per miner, connections beyond the first need distinct device_id values",
+        default_value = "1",
+        global = true
+    )]
+    max_devices_per_miner: usize,
+    #[arg(
+        long,
+        value_name = "commission pct",
+        help = "Initial percentage of mine rewards kept by the pool, editable at runtime",
+        default_value = "0",
+        global = true
+    )]
+    commission_pct: u8,
+    #[arg(
+        long,
+        value_name = "min claim amount",
+        help = "Initial minimum claim amount in grains, editable at runtime",
+        default_value = "0",
+        global = true
+    )]
+    min_claim_amount: u64,
+    #[arg(
+        long,
+        value_name = "claim cooldown",
+        help = "Initial number of seconds between claims, editable at runtime",
+        default_value = "1800",
+        global = true
+    )]
+    claim_cooldown: u64,
+    #[arg(
+        long,
+        value_name = "min difficulty",
+        help = "Initial minimum accepted difficulty, editable at runtime",
+        default_value = "8",
+        global = true
+    )]
+    min_difficulty: u32,
+    #[arg(
+        long,
+        value_name = "guild",
+        help = "Guild address the pool authority is a member of, enables guild boosted rewards",
+        default_value = None,
+        global = true
+    )]
+    guild: Option<String>,
+    #[arg(
+        long,
+        value_name = "tool",
+        help = "Tool account equipped by the pool authority, included in mine transactions",
+        default_value = None,
+        global = true
+    )]
+    tool: Option<String>,
+    #[arg(
+        long,
+        value_name = "tool durability warning",
+        help = "Send an operator webhook when the tool durability drops below this value",
+        default_value = "1000",
+        global = true
+    )]
+    tool_durability_warning: u64,
+    #[arg(
+        long,
+        value_name = "operator webhook url",
+        help = "Url that receives operator alerts such as low tool durability",
+        default_value = None,
+        global = true
+    )]
+    operator_webhook_url: Option<String>,
+    #[arg(
+        long,
+        value_name = "lamports",
+        help = "Priority fees each pool may spend per UTC day, the fee is capped as the budget runs out and drops to --priority-fee once it is spent",
+        default_value = None,
+        global = true
+    )]
+    daily_fee_budget_lamports: Option<u64>,
+    #[arg(
+        long,
+        help = "Periodically reprocess the pool wallet",
+        default_value = "false",
+        global = true
+    )]
+    auto_reprocess: bool,
+    #[arg(
+        long,
+        value_name = "reprocess interval",
+        help = "Seconds between automatic reprocess runs",
+        default_value = "3600",
+        global = true
+    )]
+    reprocess_interval: u64,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "bus selection",
+        help = "Strategy used to pick the bus for mine transactions",
+        default_value = "highest",
+        global = true
+    )]
+    bus_selection: BusSelectionStrategy,
+    #[arg(
+        long,
+        value_name = "url",
+        help = "Public url of the server such as wss://pool.example.com, reported to miners by /pool/info",
+        default_value = None,
+        global = true
+    )]
+    public_url: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "network",
+        help = "Cluster the pool mines on, reported by /pool/info. Derived from RPC_URL when not set",
+        default_value = None,
+        global = true
+    )]
+    network: Option<Network>,
+    #[arg(
+        long,
+        value_name = "name=wallet path",
+        help = "Additional pool wallet served under /pools/<name>, may be repeated",
+        global = true
+    )]
+    pool: Vec<String>,
+    #[arg(
+        long,
+        value_name = "path",
+        help = "TOML file of additional pools with their own wallet, min difficulty and commission",
+        default_value = None,
+        global = true
+    )]
+    config_file: Option<String>,
+    #[arg(
+        long,
+        value_name = "client message channel size",
+        help = "Client messages that may be queued for handling before a sending client is disconnected",
+        default_value = "10000",
+        global = true
+    )]
+    client_message_channel_size: usize,
+    #[arg(
+        long,
+        help = "Serve a Swagger UI for the OpenAPI spec at /swagger-ui",
+        default_value = "false",
+        global = true
+    )]
+    swagger_ui: bool,
+    #[arg(
+        long,
+        value_name = "port",
+        help = "Port the http server listens on, 0 lets the OS pick one",
+        default_value = "3000",
+        global = true
+    )]
+    port: u16,
+    #[arg(
+        long,
+        value_name = "ip address",
+        help = "Interface address the http server binds to",
+        default_value = "0.0.0.0",
+        global = true
+    )]
+    bind_interface: IpAddr,
+    #[arg(
+        long,
+        value_name = "index",
+        help = "Index of the first nonce range handed out by this instance, must be below --nonce-stride",
+        default_value = "0",
+        global = true
+    )]
+    nonce_start_offset: u64,
+    #[arg(
+        long,
+        value_name = "count",
+        help = "Number of pool instances sharing the nonce space, each hands out every stride-th nonce range",
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    nonce_stride: u64,
+    #[arg(
+        long,
+        value_name = "level",
+        help = "Log level for every module (error, warn, info, debug or trace), a shorthand for --log-filter",
+        global = true
+    )]
+    log_level: Option<String>,
+    #[arg(
+        long,
+        value_name = "directives",
+        help = "Tracing filter directives, e.g. \"info,coal_hq_server=debug,tower_http=warn\". Defaults to info, takes precedence over --log-level",
+        global = true
+    )]
+    log_filter: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "format",
+        help = "How miner pubkeys appear in logs and in public api responses, a miner's own data and admin endpoints always show them in full",
+        default_value = "full",
+        global = true
+    )]
+    log_pubkeys: PubkeyFormat,
+    #[arg(
+        long,
+        value_name = "bonus pct",
+        help = "Percentage of a referred miner's earnings credited to its referrer each epoch, paid out of the pool's commission and at most all of it, from 0 to 10",
+        default_value = "0",
+        value_parser = parse_referral_bonus_pct,
+        global = true
+    )]
+    referral_bonus_pct: f64,
+    #[arg(
+        long,
+        value_name = "score",
+        help = "Lowest reputation score a miner may connect with, valid solutions add 1 up to 100 and invalid ones take 5",
+        default_value = "-10",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    min_reputation_score: i32,
+    #[arg(
+        long,
+        value_name = "seconds",
+        help = "Seconds a timestamp signed by a miner stays valid, for websocket connections and signed requests",
+        default_value = "30",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    auth_max_age: u64,
+    #[arg(
+        long,
+        value_name = "seconds",
+        help = "Seconds a signed timestamp may be ahead of the server clock, for miners whose clock runs fast",
+        default_value = "5",
+        global = true
+    )]
+    auth_max_clock_skew: u64,
+    #[arg(
+        long,
+        value_name = "seconds",
+        help = "Seconds between the keepalive pings sent to each miner connection",
+        default_value = "3",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    ping_interval: u64,
+    #[arg(
+        long,
+        value_name = "seconds",
+        help = "Seconds a ping may go unanswered before the miner connection is dropped",
+        default_value = "6",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    pong_timeout: u64,
+    #[arg(
+        long,
+        value_name = "days",
+        help = "Days the signup and claim audit log is kept",
+        default_value = "90",
+        value_parser = clap::value_parser!(u32).range(1..),
+        global = true
+    )]
+    audit_log_retention_days: u32,
+    #[arg(
+        long,
+        value_name = "seconds",
+        help = "Seconds past the cutoff without a solution before the epoch is marked missed and miners get fresh work",
+        default_value = "30",
+        global = true
+    )]
+    missed_epoch_grace: u64,
+    #[arg(
+        long,
+        value_name = "seconds",
+        help = "Seconds before the cutoff at which ready miners are told to wait for the next epoch instead of getting work, once the epoch has a solution",
+        default_value = "2",
+        global = true
+    )]
+    work_admission_cutoff: u64,
+    #[arg(
+        long,
+        value_name = "path",
+        help = "File the epoch in progress is saved to every 30 seconds and restored from on restart, named pools append their name",
+        default_value = "./pool_state.bin",
+        global = true
+    )]
+    state_snapshot_path: String,
+    #[arg(
+        long,
+        value_name = "ips",
+        help = "Comma separated ips and networks of the reverse proxies whose X-Forwarded-For and X-Real-IP headers are trusted",
+        default_value = "127.0.0.1,::1",
+        global = true
+    )]
+    trusted_proxies: TrustedProxies,
+}
+
+fn parse_referral_bonus_pct(value: &str) -> Result<f64, String> {
+    let pct: f64 = value.parse().map_err(|_| format!("{} is not a number", value))?;
+    if !(0.0..=10.0).contains(&pct) {
+        return Err("must be between 0 and 10".to_string());
+    }
+    Ok(pct)
+}
+
+/// Filter for the server logs. `filter` accepts the full tracing directive
+/// syntax, `target[span{field=value}]=level` separated by commas, and wins
+/// over `level` when both are set. RUST_LOG isn't read, invalid directives
+/// are reported on stderr and ignored.
+fn build_log_filter(level: Option<String>, filter: Option<String>) -> EnvFilter {
+    let directives = filter
+        .or(level)
+        .unwrap_or_else(|| "info".to_string());
+    EnvFilter::new(directives)
 }
 
 #[tokio::main]
@@ -156,20 +830,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let file_appender = tracing_appender::rolling::daily("./logs", "coal-hq-server.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-    tracing_subscriber::fmt().with_writer(non_blocking).init();
+    tracing_subscriber::fmt()
+        .with_env_filter(build_log_filter(args.log_level.clone(), args.log_filter.clone()))
+        .with_writer(non_blocking)
+        .init();
+    pubkey_display::init(args.log_pubkeys);
+
+    if args.nonce_start_offset >= args.nonce_stride {
+        return Err("--nonce-start-offset must be lower than --nonce-stride".into());
+    }
 
     // load envs
     let wallet_path_str = std::env::var("WALLET_PATH").expect("WALLET_PATH must be set.");
     let rpc_url = std::env::var("RPC_URL").expect("RPC_URL must be set.");
     let rpc_ws_url = std::env::var("RPC_WS_URL").expect("RPC_WS_URL must be set.");
     let password = std::env::var("PASSWORD").expect("PASSWORD must be set.");
+    if password.is_empty() {
+        return Err("PASSWORD must not be empty, it is the admin api bearer token".into());
+    }
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
     let database_rr_url = std::env::var("DATABASE_RR_URL").expect("DATABASE_RR_URL must be set.");
 
     let app_database = Arc::new(AppDatabase::new(database_url));
     let app_rr_database = Arc::new(AppRRDatabase::new(database_rr_url));
 
-    let whitelist = if let Some(whitelist) = args.whitelist {
+    let whitelist = if let Some(whitelist) = &args.whitelist {
         let file = Path::new(&whitelist);
         if file.exists() {
             // load file
@@ -204,52 +889,290 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
-    let priority_fee = Arc::new(Mutex::new(args.priority_fee));
-
-    // load wallet
-    let wallet_path = Path::new(&wallet_path_str);
 
-    if !wallet_path.exists() {
-        tracing::error!("Failed to load wallet at: {}", wallet_path_str);
-        return Err("Failed to find wallet path.".into());
-    }
+    let rpc_client = Arc::new(RpcClient::new_with_commitment(
+        rpc_url,
+        CommitmentConfig::confirmed(),
+    ));
 
-    let wallet = read_keypair_file(wallet_path)
-        .expect("Failed to load keypair from file: {wallet_path_str}");
-    info!("loaded wallet {}", wallet.pubkey().to_string());
+    let (webhook_sender, webhook_receiver) =
+        tokio::sync::mpsc::unbounded_channel::<WebhookJob>();
 
-    info!("establishing rpc connection...");
-    let rpc_client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+    let app_app_database = app_database.clone();
+    tokio::spawn(async move {
+        webhooks::webhook_delivery_system(webhook_receiver, app_app_database).await;
+    });
 
-    info!("loading sol balance...");
-    let balance = if let Ok(balance) = rpc_client.get_balance(&wallet.pubkey()).await {
-        balance
-    } else {
-        return Err("Failed to load balance".into());
+    let shared = SharedResources {
+        app_database,
+        app_rr_database,
+        rpc_client,
+        webhook_sender,
+        whitelist,
+        password,
+        rpc_ws_url,
+        signed_request_replays: Arc::new(Mutex::new(SignedRequestReplays::default())),
     };
 
-    info!("Balance: {:.2}", balance as f64 / LAMPORTS_PER_SOL as f64);
+    let mut pools = vec![PoolStartup {
+        name: None,
+        wallet_path: wallet_path_str,
+        min_difficulty: None,
+        commission_pct: None,
+    }];
+    for pool in &args.pool {
+        pools.push(PoolStartup::parse(pool)?);
+    }
+    if let Some(config_file) = &args.config_file {
+        for entry in PoolConfigFile::load(Path::new(config_file))?.pools {
+            pools.push(PoolStartup::from_config(entry)?);
+        }
+    }
+    let mut names = HashSet::new();
+    let mut wallet_paths = HashSet::new();
+    for pool in &pools {
+        if let Some(name) = &pool.name {
+            if !names.insert(name.clone()) {
+                return Err(format!("Pool {} is configured more than once", name).into());
+            }
+        }
+        if !wallet_paths.insert(pool.wallet_path.clone()) {
+            return Err(format!("Wallet {} is used by more than one pool", pool.wallet_path).into());
+        }
+    }
 
-    if balance < 1_000_000 {
-        return Err("Sol balance is too low!".into());
+    let mut app = Router::new();
+    for pool in pools {
+        let name = pool.name.clone();
+        let (pool_id, pool_router) = start_pool(&args, pool, shared.clone()).await?;
+        info!("serving pool {} under /pool/{}", pool_id, pool_id);
+        app = app.nest(&format!("/pool/{}", pool_id), pool_router.clone());
+        app = match name {
+            Some(name) => {
+                info!("serving pool {} under /pools/{}", name, name);
+                app.nest(&format!("/pools/{}", name), pool_router)
+            }
+            None => app.merge(pool_router),
+        };
     }
 
-    let proof = if let Ok(loaded_proof) = get_proof(&rpc_client, wallet.pubkey()).await {
-        loaded_proof
-    } else {
-        error!("Failed to load proof.");
+    app = app.route("/openapi.json", get(openapi::get_openapi_json));
+    if args.swagger_ui {
+        app = app.route("/swagger-ui", get(openapi::get_swagger_ui));
+    }
+
+    let app = app
+        .layer(axum::middleware::from_fn(api_error::json_error_envelope))
+        // Logging
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
+        )
+        .layer(cors_layer());
+
+    let listen_addr = SocketAddr::new(args.bind_interface, args.port);
+    let listener = tokio::net::TcpListener::bind(listen_addr).await.unwrap();
+
+    tracing::info!("listening on {}", listener.local_addr().unwrap());
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+
+    Ok(())
+}
+
+/// Process wide resources shared by every pool.
+#[derive(Clone)]
+struct SharedResources {
+    app_database: Arc<AppDatabase>,
+    app_rr_database: Arc<AppRRDatabase>,
+    rpc_client: Arc<RpcClient>,
+    webhook_sender: UnboundedSender<WebhookJob>,
+    whitelist: Option<HashSet<Pubkey>>,
+    password: String,
+    rpc_ws_url: String,
+    // signed delegate requests already accepted, shared so a request can't
+    // be replayed on another pool's path
+    signed_request_replays: Arc<Mutex<SignedRequestReplays>>,
+}
+
+struct PoolStartup {
+    // None for the primary pool served at the root path
+    name: Option<String>,
+    wallet_path: String,
+    // seed the pool's config instead of --min-difficulty and --commission-pct
+    min_difficulty: Option<u32>,
+    commission_pct: Option<u8>,
+}
+
+impl PoolStartup {
+    /// Parses a `name=wallet path` pool argument.
+    fn parse(value: &str) -> Result<Self, String> {
+        let (name, wallet_path) = value
+            .split_once('=')
+            .ok_or(format!("Invalid pool {}, expected name=wallet path", value))?;
+        check_pool_name(name)?;
+
+        Ok(PoolStartup {
+            name: Some(name.to_string()),
+            wallet_path: wallet_path.to_string(),
+            min_difficulty: None,
+            commission_pct: None,
+        })
+    }
+
+    fn from_config(entry: pool_config_file::PoolConfigEntry) -> Result<Self, String> {
+        check_pool_name(&entry.name)?;
+
+        Ok(PoolStartup {
+            name: Some(entry.name),
+            wallet_path: entry.wallet_path,
+            min_difficulty: entry.min_difficulty,
+            commission_pct: entry.commission_pct,
+        })
+    }
+}
+
+fn check_pool_name(name: &str) -> Result<(), String> {
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid_name {
+        return Err(format!(
+            "Invalid pool name {}, use lowercase letters, digits, - and _",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Loads a pool wallet, starts its proof tracking, client handling and
+/// submission tasks, and returns the router serving its endpoints.
+/// Guild, tool and reprocess options only apply to the primary pool.
+async fn start_pool(
+    args: &Args,
+    pool: PoolStartup,
+    shared: SharedResources,
+) -> Result<(i32, Router), Box<dyn std::error::Error>> {
+    let SharedResources {
+        app_database,
+        app_rr_database,
+        rpc_client,
+        webhook_sender,
+        whitelist,
+        password,
+        rpc_ws_url,
+        signed_request_replays,
+    } = shared;
+    let is_primary = pool.name.is_none();
+    let wallet_path_str = pool.wallet_path;
+    let public_urls = match &args.public_url {
+        Some(public_url) => {
+            let path = match &pool.name {
+                Some(name) => format!("/pools/{}", name),
+                None => String::new(),
+            };
+            Some(PublicUrls::new(public_url, &path)?)
+        }
+        None => None,
+    };
+
+
+    let priority_fee = Arc::new(Mutex::new(args.priority_fee));
+    let fee_budget = Arc::new(Mutex::new(FeeBudget::new(
+        args.daily_fee_budget_lamports,
+        args.priority_fee,
+    )));
+    let bus_selection = args.bus_selection;
+    // first nonce of this instance, and the distance between its ranges
+    let nonce_start = args.nonce_start_offset.saturating_mul(NONCE_RANGE_SIZE);
+    let nonce_step = args.nonce_stride.saturating_mul(NONCE_RANGE_SIZE);
+    let bus_stats = Arc::new(RwLock::new(BusStats::default()));
+    let miner_claims_cache = Arc::new(RwLock::new(MinerClaimsCache::default()));
+    let miner_activity_cache = Arc::new(RwLock::new(MinerActivityCache::default()));
+    let pool_listing_cache = Arc::new(RwLock::new(PoolListingCache::default()));
+    let reward_yield_cache = Arc::new(RwLock::new(RewardYieldCache::default()));
+
+    // load wallet
+    let wallet_path = Path::new(&wallet_path_str);
+
+    if !wallet_path.exists() {
+        tracing::error!("Failed to load wallet at: {}", wallet_path_str);
+        return Err("Failed to find wallet path.".into());
+    }
+
+    let wallet = read_keypair_file(wallet_path)
+        .expect("Failed to load keypair from file: {wallet_path_str}");
+    info!("loaded wallet {}", wallet.pubkey().to_string());
+
+
+    info!("loading sol balance...");
+    let balance = if let Ok(balance) = rpc_client.get_balance(&wallet.pubkey()).await {
+        balance
+    } else {
+        return Err("Failed to load balance".into());
+    };
+
+    info!("Balance: {:.2}", balance as f64 / LAMPORTS_PER_SOL as f64);
+
+    if balance < MIN_AUTHORITY_BALANCE_LAMPORTS {
+        return Err("Sol balance is too low!".into());
+    }
+
+    let guild = if let Some(guild) = args.guild.as_ref().filter(|_| is_primary) {
+        let guild = Pubkey::from_str(guild).map_err(|_| "Invalid guild pubkey")?;
+        let guild_status = get_guild_status(&rpc_client, wallet.pubkey(), guild).await?;
+        info!(
+            "Guild {} active: {}, member stake: {}, guild stake: {} of {} total, multiplier: {}",
+            guild_status.guild,
+            guild_status.is_active,
+            guild_status.member_stake,
+            guild_status.guild_stake,
+            guild_status.total_stake,
+            guild_status.total_multiplier
+        );
+        Some(guild_status)
+    } else {
+        None
+    };
+
+    let tool = match args.tool.as_ref().filter(|_| is_primary) {
+        Some(tool) => Some(Pubkey::from_str(tool).map_err(|_| "Invalid tool pubkey")?),
+        None => None,
+    };
+    let tool_status = if let Some(tool) = tool {
+        let tool_status = get_tool_status(&rpc_client, tool).await?;
+        if !tool_status.miner.eq(&wallet.pubkey()) {
+            return Err(format!("Tool {} is not equipped by the pool authority", tool).into());
+        }
+        info!(
+            "Tool {} durability: {}, multiplier: {}",
+            tool, tool_status.durability, tool_status.multiplier
+        );
+        Some(tool_status)
+    } else {
+        None
+    };
+    let tool_status = Arc::new(RwLock::new(tool_status));
+
+    let proof = if let Ok(loaded_proof) = get_proof(&rpc_client, wallet.pubkey()).await {
+        loaded_proof
+    } else {
+        error!("Failed to load proof.");
         info!("Creating proof account...");
 
         let ix = get_register_ix(wallet.pubkey());
 
-        if let Ok((hash, _slot)) = rpc_client
-            .get_latest_blockhash_with_commitment(rpc_client.commitment())
+        if let Ok(tx) = SolanaTransactionBuilder::new()
+            .instruction(ix)
+            .build_and_sign(&wallet, &rpc_client)
             .await
         {
-            let mut tx = Transaction::new_with_payer(&[ix], Some(&wallet.pubkey()));
-
-            tx.sign(&[&wallet], hash);
-
             let result = rpc_client
                 .send_and_confirm_transaction_with_spinner_and_commitment(
                     &tx,
@@ -318,6 +1241,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 pool_id: db_pool.id,
                 challenge: proof.challenge.to_vec(),
                 rewards_earned: None,
+                started_at: chrono::Utc::now().naive_utc(),
             };
             let result = app_database.add_new_challenge(new_challenge).await;
 
@@ -327,70 +1251,272 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    // CLI args only seed pool_config, values stored in the db take precedence.
+    let mut runtime_config = RuntimeConfig {
+        commission_pct: pool.commission_pct.unwrap_or(args.commission_pct),
+        min_claim_amount: args.min_claim_amount,
+        claim_cooldown_secs: args.claim_cooldown,
+        min_difficulty: pool.min_difficulty.unwrap_or(args.min_difficulty),
+    };
+    for key in runtime_config::KEYS {
+        let value = runtime_config.get(key).unwrap();
+        if let Err(e) = runtime_config.clone().set(key, &value) {
+            return Err(format!("Invalid startup config: {}", e).into());
+        }
+    }
+    if app_database
+        .add_pool_config_defaults(db_pool.id, runtime_config.to_entries())
+        .await
+        .is_err()
+    {
+        panic!("Failed to add default pool config to database");
+    }
+    match app_database.get_pool_config(db_pool.id).await {
+        Ok(entries) => {
+            for entry in entries {
+                if let Err(e) = runtime_config.set(&entry.config_key, &entry.config_value) {
+                    error!("Ignoring stored pool config {}: {}", entry.config_key, e);
+                }
+            }
+        }
+        Err(_) => {
+            panic!("Failed to load pool config from database");
+        }
+    }
+    info!("Runtime config: {:?}", runtime_config);
+    let runtime_config = Arc::new(RwLock::new(runtime_config));
+
     let config = Arc::new(Config {
         password,
         whitelist,
         pool_id: db_pool.id,
+        max_miners: args.max_miners,
+        max_devices_per_miner: args.max_devices_per_miner,
+        guild,
+        tool,
+        tool_durability_warning: args.tool_durability_warning,
+        operator_webhook_url: args.operator_webhook_url.clone(),
+        public_urls,
+        network: args
+            .network
+            .unwrap_or_else(|| Network::from_rpc_url(&rpc_client.url())),
+        referral_bonus_pct: args.referral_bonus_pct,
+        min_reputation_score: args.min_reputation_score,
+        auth_window: AuthWindow {
+            max_age_secs: args.auth_max_age,
+            max_clock_skew_secs: args.auth_max_clock_skew,
+        },
+        trusted_proxies: args.trusted_proxies.clone(),
     });
 
-    let epoch_hashes = Arc::new(RwLock::new(EpochHashes {
-        best_hash: BestHash {
-            solution: None,
-            difficulty: 0,
-        },
-        submissions: HashMap::new(),
-    }));
+    let state_snapshot_path = match &pool.name {
+        Some(name) => PathBuf::from(format!("{}.{}", args.state_snapshot_path, name)),
+        None => PathBuf::from(&args.state_snapshot_path),
+    };
+    let (epoch_generation, _) = watch::channel(EpochGeneration::new(proof.challenge));
+    let generation = epoch_generation.borrow().generation;
+    let (epoch_hashes, nonce_start_value) = match state_snapshot::restore(&state_snapshot_path, &proof) {
+        Some(snapshot) => (snapshot.epoch_hashes(generation), snapshot.current_nonce),
+        None => (
+            EpochHashes {
+                generation,
+                best_hash: BestHash {
+                    solution: None,
+                    difficulty: 0,
+                },
+                submissions: HashMap::new(),
+            },
+            nonce_start,
+        ),
+    };
+    let epoch_hashes = Arc::new(RwLock::new(epoch_hashes));
+    let best_difficulty_timeline = BestDifficultyTimeline::default();
 
-    let wallet_extension = Arc::new(wallet);
+    let wallet_extension = PoolWallet::new(wallet_path.to_path_buf(), wallet);
+    let (proof_challenge_sender, proof_challenge_receiver) = watch::channel(proof.challenge);
+    let epoch_challenges = Arc::new(RwLock::new(EpochChallenges::new(proof.challenge)));
     let proof_ext = Arc::new(Mutex::new(proof));
-    let nonce_ext = Arc::new(Mutex::new(0u64));
+    let nonce_ext = Arc::new(Mutex::new(nonce_start_value));
+
+    let epoch_starter = EpochStarter {
+        app_database: app_database.clone(),
+        pool_id: config.pool_id,
+        nonce: nonce_ext.clone(),
+        nonce_start,
+        epoch_hashes: epoch_hashes.clone(),
+        best_difficulty_timeline: best_difficulty_timeline.clone(),
+        epoch_generation: epoch_generation.clone(),
+        state_snapshot_path: state_snapshot_path.clone(),
+        nonces_assigned: Arc::new(AtomicU64::new(0)),
+        lock: Arc::new(Mutex::new(())),
+    };
+
+    let app_epoch_generation = epoch_generation.subscribe();
+    let app_epoch_hashes = epoch_hashes.clone();
+    let app_nonce = nonce_ext.clone();
+    let app_state_snapshot_path = state_snapshot_path.clone();
+    tokio::spawn(async move {
+        state_snapshot::state_snapshot_system(
+            app_state_snapshot_path,
+            app_epoch_generation,
+            app_epoch_hashes,
+            app_nonce,
+        )
+        .await;
+    });
+    // Held while sending pool wallet transactions so mine submissions and
+    // reprocessing never race on blockhash or fee state.
+    let tx_send_lock = Arc::new(Mutex::new(()));
+    let wallet_reload = WalletReload {
+        wallet: wallet_extension.clone(),
+        proof: proof_ext.clone(),
+        tool_status: tool_status.clone(),
+        send_lock: tx_send_lock.clone(),
+        proof_challenge: proof_challenge_sender.clone(),
+        epoch_starter: epoch_starter.clone(),
+    };
+    let reprocess_status = if args.auto_reprocess && is_primary {
+        Some(Arc::new(RwLock::new(ReprocessStatus::default())))
+    } else {
+        None
+    };
 
     let client_nonce_ranges = Arc::new(RwLock::new(HashMap::new()));
 
     let shared_state = Arc::new(RwLock::new(AppState {
         sockets: HashMap::new(),
     }));
-    let ready_clients = Arc::new(Mutex::new(HashSet::new()));
+    let dashboard_bus = DashboardEventBus::new();
+
+    let (audit_log, audit_log_receiver) = AuditLog::new(config.pool_id, config.trusted_proxies.clone());
+    let app_app_database = app_database.clone();
+    let audit_log_stats = audit_log.stats();
+    tokio::spawn(async move {
+        audit_log::audit_log_system(audit_log_receiver, app_app_database, audit_log_stats).await;
+    });
+    let app_app_database = app_database.clone();
+    let pool_id = config.pool_id;
+    let retention_days = args.audit_log_retention_days;
+    tokio::spawn(async move {
+        audit_log::audit_log_pruning_system(app_app_database, pool_id, retention_days).await;
+    });
 
-        let pongs = Arc::new(RwLock::new(LastPong { pongs: HashMap::new() }));
+    #[cfg(feature = "event-bus")]
+    let event_bus_stats = match EventBusConfig::from_env() {
+        Some(event_bus_config) => {
+            let stats = Arc::new(EventBusStats::default());
+            let app_stats = stats.clone();
+            let app_dashboard_bus = dashboard_bus.clone();
+            let pool_id = config.pool_id;
+            tokio::spawn(async move {
+                event_bus::event_publisher_system(
+                    event_bus_config,
+                    pool_id,
+                    app_dashboard_bus,
+                    app_stats,
+                )
+                .await;
+            });
+            Some(stats)
+        }
+        None => None,
+    };
 
-    // Track client pong timings
-    let app_pongs = pongs.clone();
+    let app_proof = proof_ext.clone();
+    let app_epoch_hashes = epoch_hashes.clone();
     let app_state = shared_state.clone();
+    let app_dashboard_bus = dashboard_bus.clone();
+    tokio::spawn(async move {
+        dashboard::epoch_progress_system(app_proof, app_epoch_hashes, app_state, app_dashboard_bus)
+            .await;
+    });
+    let ready_clients: Arc<Mutex<ReadyClients>> = Arc::new(Mutex::new(HashMap::new()));
+    let keepalive = Arc::new(RwLock::new(Keepalive::default()));
+    let keepalive_config = KeepaliveConfig {
+        ping_interval: Duration::from_secs(args.ping_interval),
+        pong_timeout: Duration::from_secs(args.pong_timeout),
+    };
+
+    // COAL in the pool authority's token account, reported with mining results
+    let coal_token_balance = Arc::new(Mutex::new(0u64));
+    let app_rpc_ws_url = rpc_ws_url.clone();
+    let app_rpc_client = rpc_client.clone();
+    let app_wallet = wallet_extension.clone();
+    let app_coal_token_balance = coal_token_balance.clone();
+    tokio::spawn(async move {
+        balance_tracking_system(app_rpc_ws_url, app_rpc_client, app_wallet, app_coal_token_balance)
+            .await;
+    });
+
+    let proof_updates = Arc::new(RwLock::new(ProofUpdates::default()));
+    let missed_epochs = Arc::new(RwLock::new(MissedEpochs::default()));
+
+    let replica_lag = Arc::new(RwLock::new(ReplicaLag::default()));
+    let app_app_database = app_database.clone();
+    let app_app_rr_database = app_rr_database.clone();
+    let pool_id = config.pool_id;
+    let app_replica_lag = replica_lag.clone();
     tokio::spawn(async move {
-        pong_tracking_system(app_pongs, app_state).await;
+        replica_lag::replica_lag_system(
+            app_app_database,
+            app_app_rr_database,
+            pool_id,
+            app_replica_lag,
+        )
+        .await;
     });
-    
     let app_wallet = wallet_extension.clone();
     let app_proof = proof_ext.clone();
+    let app_proof_challenge = proof_challenge_sender.clone();
+    let app_proof_updates = proof_updates.clone();
     // Establish webocket connection for tracking pool proof changes.
     tokio::spawn(async move {
-        proof_tracking_system(rpc_ws_url, app_wallet, app_proof).await;
+        proof_tracking_system(
+            rpc_ws_url,
+            app_wallet,
+            app_proof,
+            app_proof_challenge,
+            app_proof_updates,
+        )
+        .await;
+    });
+    let app_rpc_client = rpc_client.clone();
+    let app_proof_updates = proof_updates.clone();
+    tokio::spawn(async move {
+        proof_updates::proof_lag_system(app_rpc_client, app_proof_updates).await;
     });
 
     let (client_message_sender, client_message_receiver) =
-        tokio::sync::mpsc::unbounded_channel::<ClientMessage>();
+        tokio::sync::mpsc::channel::<ClientMessage>(args.client_message_channel_size);
 
     // Handle client messages
     let app_ready_clients = ready_clients.clone();
-    let app_proof = proof_ext.clone();
+    let app_epoch_generation = epoch_generation.subscribe();
     let app_epoch_hashes = epoch_hashes.clone();
     let app_app_database = app_database.clone();
     let app_client_nonce_ranges = client_nonce_ranges.clone();
     let app_config = config.clone();
     let app_state = shared_state.clone();
-    let app_pongs = pongs.clone();
+    let app_keepalive = keepalive.clone();
+    let app_runtime_config = runtime_config.clone();
+    let app_epoch_challenges = epoch_challenges.clone();
+    let app_dashboard_bus = dashboard_bus.clone();
+    let app_best_difficulty_timeline = best_difficulty_timeline.clone();
     tokio::spawn(async move {
         client_message_handler_system(
             client_message_receiver,
             app_app_database,
             app_ready_clients,
-            app_proof,
+            app_epoch_generation,
             app_epoch_hashes,
+            app_best_difficulty_timeline,
+            app_epoch_challenges,
             app_client_nonce_ranges,
             app_config,
             app_state,
-            app_pongs,
+            app_keepalive,
+            app_runtime_config,
+            app_dashboard_bus,
         )
         .await;
     });
@@ -398,47 +1524,121 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Handle ready clients
     let app_shared_state = shared_state.clone();
     let app_proof = proof_ext.clone();
+    let app_proof_updates = proof_updates.clone();
     let app_epoch_hashes = epoch_hashes.clone();
     let app_nonce = nonce_ext.clone();
     let app_client_nonce_ranges = client_nonce_ranges.clone();
+    let app_ready_clients = ready_clients.clone();
+    let app_epoch_challenges = epoch_challenges.clone();
+    let mut epoch_generation_receiver = epoch_generation.subscribe();
+    let app_epoch_starter = epoch_starter.clone();
+    let work_admission_cutoff = args.work_admission_cutoff;
     tokio::spawn(async move {
+        let ready_clients = app_ready_clients;
+        let mut cutoff_cache: Option<CutoffCache> = None;
+        // when the proof moved to a challenge no epoch was started for yet
+        let mut epoch_pending_since: Option<Instant> = None;
+        // connections told to wait for the next epoch, they stay ready
+        let mut waiting_clients: HashSet<u64> = HashSet::new();
         loop {
             let mut clients = Vec::new();
             {
                 let ready_clients_lock = ready_clients.lock().await;
-                for ready_client in ready_clients_lock.iter() {
-                    clients.push(ready_client.clone());
+                for (connection_id, addr) in ready_clients_lock.iter() {
+                    clients.push((*connection_id, *addr));
                 }
                 drop(ready_clients_lock);
             };
 
-            let lock = app_proof.lock().await;
-            let proof = lock.clone();
-            drop(lock);
+            let current_challenge = *proof_challenge_receiver.borrow();
+            let cached_cutoff = cutoff_cache.as_ref().and_then(|cache| {
+                cache
+                    .cutoff(&current_challenge)
+                    .map(|cutoff| (cutoff, cache.last_hash_at))
+            });
+            let (challenge, cutoff, last_hash_at) = match cached_cutoff {
+                Some((cutoff, last_hash_at)) => (current_challenge, cutoff, last_hash_at),
+                None => {
+                    let lock = app_proof.lock().await;
+                    let proof = lock.clone();
+                    drop(lock);
+
+                    let cutoff = get_cutoff(proof, 5);
+                    cutoff_cache = Some(CutoffCache {
+                        proof_challenge: proof.challenge,
+                        computed_at: Instant::now(),
+                        cutoff_value: cutoff,
+                        last_hash_at: proof.last_hash_at,
+                    });
+                    (proof.challenge, cutoff, proof.last_hash_at)
+                }
+            };
 
-            let cutoff = get_cutoff(proof, 5);
             let mut should_mine = true;
-            let cutoff = if cutoff <= 0 {
-                let solution = app_epoch_hashes.read().await.best_hash.solution;
-                if solution.is_some() {
-                    should_mine = false;
+            if challenge != epoch_generation_receiver.borrow_and_update().challenge {
+                // the new challenge's db row may not exist yet
+                should_mine = false;
+                let pending_since = *epoch_pending_since.get_or_insert_with(Instant::now);
+                if pending_since.elapsed() > EPOCH_START_TIMEOUT {
+                    warn!("No epoch was started for the new challenge, starting it");
+                    let epoch_starter = app_epoch_starter.clone();
+                    tokio::spawn(async move {
+                        epoch_starter.start(challenge).await;
+                    });
+                    epoch_pending_since = None;
                 }
-                0
             } else {
-                cutoff
-            };
+                epoch_pending_since = None;
+            }
+            let has_solution = app_epoch_hashes.read().await.best_hash.solution.is_some();
+            let cutoff = cutoff.max(0);
+            if should_mine && !admits_new_work(cutoff, work_admission_cutoff, has_solution) {
+                should_mine = false;
+                let notice = WaitForNextEpochNotice {
+                    epoch_id: app_epoch_challenges.read().await.current().epoch_id,
+                    seconds_to_cutoff: cutoff,
+                };
+                let text = serde_json::to_string(&notice).unwrap_or_default();
+                let sockets = app_shared_state.read().await.sockets.clone();
+                for (connection_id, client) in &clients {
+                    if waiting_clients.contains(connection_id) {
+                        continue;
+                    }
+                    match sockets.get(client) {
+                        Some(sender) if sender.connection_id == *connection_id => {
+                            waiting_clients.insert(*connection_id);
+                            let (client, sender, text) = (*client, sender.clone(), text.clone());
+                            let ready_clients = ready_clients.clone();
+                            let app_shared_state = app_shared_state.clone();
+                            tokio::spawn(async move {
+                                send_client_message(
+                                    &app_shared_state,
+                                    &ready_clients,
+                                    client,
+                                    &sender,
+                                    Message::Text(text),
+                                )
+                                .await;
+                            });
+                        }
+                        _ => {
+                            ready_clients.lock().await.remove(connection_id);
+                        }
+                    }
+                }
+            }
 
             if should_mine {
-                let challenge = proof.challenge;
-
-                for client in clients {
+                // the waiting clients are among the ready ones handed work below
+                waiting_clients.clear();
+                let epoch = app_epoch_challenges.write().await.assign(challenge);
+                for (connection_id, client) in clients {
                     let nonce_range = {
                         let mut nonce = app_nonce.lock().await;
                         let start = *nonce;
-                        // max hashes possible in 60s for a single client
-                        *nonce += 4_000_000;
-                        let end = *nonce;
-                        start..end
+                        // other instances of a federation hand out the ranges in between
+                        *nonce += nonce_step;
+                        start..start + NONCE_RANGE_SIZE
                     };
                     // message type is 8 bytes = 1 u8
                     // challenge is 256 bytes = 32 u8
@@ -450,57 +1650,144 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     bin_data[33..41].copy_from_slice(&cutoff.to_le_bytes());
                     bin_data[41..49].copy_from_slice(&nonce_range.start.to_le_bytes());
                     bin_data[49..57].copy_from_slice(&nonce_range.end.to_le_bytes());
+                    // epoch_id is 64 bytes = 8 u8, only sent to clients that echo it back
+                    let mut work = bin_data.to_vec();
 
                     let app_client_nonce_ranges = app_client_nonce_ranges.clone();
                     let shared_state = app_shared_state.read().await;
                     let sockets = shared_state.sockets.clone();
                     drop(shared_state);
-                    if let Some(sender) = sockets.get(&client) {
-                        let sender = sender.clone();
-                        let ready_clients = ready_clients.clone();
-                        tokio::spawn(async move {
-                            let _ = sender
-                                .socket
-                                .lock()
-                                .await
-                                .send(Message::Binary(bin_data.to_vec()))
-                                .await;
-                            let _ = ready_clients.lock().await.remove(&client);
-                            let _ = app_client_nonce_ranges
-                                .write()
-                                .await
-                                .insert(sender.pubkey, nonce_range);
-                        });
+                    let sender = match sockets.get(&client) {
+                        Some(sender) if sender.connection_id == connection_id => sender.clone(),
+                        _ => {
+                            // the connection that sent Ready is gone, even if its
+                            // addr has been reused since
+                            ready_clients.lock().await.remove(&connection_id);
+                            continue;
+                        }
+                    };
+                    if sender.protocol_version >= EPOCH_PROTOCOL_VERSION {
+                        work.extend_from_slice(&epoch.epoch_id.to_le_bytes());
+                    }
+                    if sender.protocol_version >= PROOF_METADATA_PROTOCOL_VERSION {
+                        let observed_slot = app_proof_updates
+                            .read()
+                            .await
+                            .status()
+                            .last_update_slot
+                            .unwrap_or(0);
+                        work.extend_from_slice(&last_hash_at.to_le_bytes());
+                        work.extend_from_slice(&observed_slot.to_le_bytes());
                     }
+                    let ready_clients = ready_clients.clone();
+                    let app_shared_state = app_shared_state.clone();
+                    let nonces_assigned = app_epoch_starter.nonces_assigned.clone();
+                    tokio::spawn(async move {
+                        send_client_message(
+                            &app_shared_state,
+                            &ready_clients,
+                            client,
+                            &sender,
+                            Message::Binary(work),
+                        )
+                        .await;
+                        let _ = ready_clients.lock().await.remove(&connection_id);
+                        nonces_assigned.fetch_add(nonce_range.end - nonce_range.start, Ordering::Relaxed);
+                        app_client_nonce_ranges
+                            .write()
+                            .await
+                            .entry((sender.pubkey, sender.device_id))
+                            .and_modify(|ranges| ranges.assign(epoch.epoch_id, nonce_range.clone()))
+                            .or_insert_with(|| AssignedNonceRanges::new(epoch.epoch_id, nonce_range));
+                    });
                 }
             }
 
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                // hand out work for a new epoch right away
+                Ok(()) = epoch_generation_receiver.changed() => {}
+            }
         }
     });
 
+    let reward_cache = RewardCache::default();
     let (mine_success_sender, mut mine_success_receiver) =
         tokio::sync::mpsc::unbounded_channel::<MessageInternalMineSuccess>();
 
     let (all_clients_sender, mut all_clients_receiver) =
         tokio::sync::mpsc::unbounded_channel::<MessageInternalAllClients>();
 
-    let rpc_client = Arc::new(rpc_client);
+    let coal_config_cache: Arc<RwLock<Option<CoalConfigSnapshot>>> = Arc::new(RwLock::new(None));
+
+    // Keep the coal config and busses cached for the submission loop.
+    let app_rpc_client = rpc_client.clone();
+    let app_wallet = wallet_extension.clone();
+    let app_coal_config_cache = coal_config_cache.clone();
+    tokio::spawn(async move {
+        coal_config_refresh_system(app_rpc_client, app_wallet, app_coal_config_cache).await;
+    });
+
+    if config.tool.is_some() {
+        let app_rpc_client = rpc_client.clone();
+        let app_config = config.clone();
+        let app_tool_status = tool_status.clone();
+        let app_webhook_sender = webhook_sender.clone();
+        tokio::spawn(async move {
+            tool_monitor_system(app_rpc_client, app_config, app_tool_status, app_webhook_sender)
+                .await;
+        });
+    }
+
+    if let Some(status) = &reprocess_status {
+        let reprocess_system = ReprocessSystem {
+            rpc_client: rpc_client.clone(),
+            wallet: wallet_extension.clone(),
+            app_database: app_database.clone(),
+            pool_id: config.pool_id,
+            proof: proof_ext.clone(),
+            priority_fee: priority_fee.clone(),
+            send_lock: tx_send_lock.clone(),
+            status: status.clone(),
+            webhook_sender: webhook_sender.clone(),
+            operator_webhook_url: config.operator_webhook_url.clone(),
+            interval: Duration::from_secs(args.reprocess_interval),
+        };
+        tokio::spawn(async move {
+            reprocess_system.run().await;
+        });
+    }
+
     let app_proof = proof_ext.clone();
     let app_epoch_hashes = epoch_hashes.clone();
     let app_wallet = wallet_extension.clone();
     let app_nonce = nonce_ext.clone();
     let app_prio_fee = priority_fee.clone();
+    let app_fee_budget = fee_budget.clone();
+    let app_coal_token_balance = coal_token_balance.clone();
+    let app_webhook_sender = webhook_sender.clone();
     let app_rpc_client = rpc_client.clone();
     let app_config = config.clone();
     let app_app_database = app_database.clone();
     let app_all_clients_sender = all_clients_sender.clone();
+    let app_bus_stats = bus_stats.clone();
+    let app_coal_config_cache = coal_config_cache.clone();
+    let app_tool_status = tool_status.clone();
+    let app_tx_send_lock = tx_send_lock.clone();
+    let app_state_snapshot_path = state_snapshot_path.clone();
+    let app_epoch_starter = epoch_starter.clone();
+    let app_missed_epochs = missed_epochs.clone();
+    let app_epoch_challenges = epoch_challenges.clone();
+    let app_shared_state = shared_state.clone();
+    let app_ready_clients = ready_clients.clone();
+    let app_best_difficulty_timeline = best_difficulty_timeline.clone();
+    let missed_epoch_grace = args.missed_epoch_grace;
     tokio::spawn(async move {
         let rpc_client = app_rpc_client;
         let app_database = app_app_database;
         loop {
             let lock = app_proof.lock().await;
-            let mut old_proof = lock.clone();
+            let old_proof = lock.clone();
             drop(lock);
 
             let cutoff = get_cutoff(old_proof, 0);
@@ -510,15 +1797,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let solution = reader.best_hash.solution.clone();
                 drop(reader);
                 if solution.is_some() {
-                    let signer = app_wallet.clone();
-
                     let mut bus = rand::thread_rng().gen_range(0..BUS_COUNT);
 
                     let mut success = false;
+                    let cutoff_reached_at = Instant::now();
+                    // milliseconds after cutoff, stored with the epoch outcome
+                    let mut timings = EpochTimings::default();
+                    // reused by the attempts while fresh, saves a request per retry
+                    let mut cached_blockhash = None;
                     let reader = app_epoch_hashes.read().await;
                     let best_solution = reader.best_hash.solution.clone();
                     let submissions = reader.submissions.clone();
                     drop(reader);
+                    let histogram = DifficultyHistogram::from_difficulties(
+                        submissions.values().map(|(_, difficulty, _)| *difficulty),
+                    );
+                    let send_guard = app_tx_send_lock.lock().await;
+                    // a wallet swapped while waiting for the lock started a new epoch
+                    if app_proof.lock().await.challenge != old_proof.challenge {
+                        drop(send_guard);
+                        continue;
+                    }
+                    let signer = app_wallet.keypair();
                     for i in 0..10 {
                         if let Some(best_solution) = best_solution {
                             let difficulty = best_solution.to_hash().difficulty();
@@ -528,27 +1828,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 i, difficulty
                             );
                             let mut loaded_config = None;
-                            info!("Getting latest config and busses data.");
-                            if let (Ok(_), Ok(config), Ok(busses)) =
-                                get_proof_and_config_with_busses(&rpc_client, signer.pubkey()).await
+                            if let Some(snapshot) = get_cached_coal_config(
+                                &rpc_client,
+                                signer.pubkey(),
+                                &app_coal_config_cache,
+                            )
+                            .await
                             {
-                                let mut best_bus = 0;
-                                for (i, bus) in busses.iter().enumerate() {
-                                    if let Ok(bus) = bus {
-                                        if bus.rewards > busses[best_bus].unwrap().rewards {
-                                            best_bus = i;
-                                        }
-                                    }
-                                }
-                                bus = best_bus;
-                                loaded_config = Some(config);
+                                let mut bus_stats = app_bus_stats.write().await;
+                                // retries pick from the newest busses, like the first attempt
+                                bus_stats.record(&snapshot.busses, snapshot.fetched_at);
+                                bus = bus_stats.select_bus(bus_selection);
+                                drop(bus_stats);
+                                info!("Selected bus {} using {:?} strategy.", bus, bus_selection);
+                                loaded_config = Some(snapshot.config);
                             }
                             let now = SystemTime::now()
                                 .duration_since(UNIX_EPOCH)
                                 .expect("Time went backwards")
                                 .as_secs();
-                            let mut ixs = vec![];
                             let prio_fee = { app_prio_fee.lock().await.clone() };
+                            let prio_fee = app_fee_budget.lock().await.cap_fee(prio_fee);
 
                             info!("using priority fee of {}", prio_fee);
                             let _ = app_all_clients_sender.send(MessageInternalAllClients {
@@ -557,8 +1857,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                             let mut cu_limit = 485_000;
                             let should_add_reset_ix = if let Some(config) = loaded_config {
-                                let time_until_reset = (config.last_reset_at + 300) - now as i64;
-                                if time_until_reset <= 5 {
+                                let time_until_reset =
+                                    (config.last_reset_at + COAL_RESET_INTERVAL_SECS) - now as i64;
+                                if time_until_reset <= RESET_IX_WINDOW_SECS {
                                     cu_limit = 500_000;
                                     true
                                 } else {
@@ -568,38 +1869,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 false
                             };
 
-                            let cu_limit_ix =
-                                ComputeBudgetInstruction::set_compute_unit_limit(cu_limit);
-                            ixs.push(cu_limit_ix);
-
-                            let prio_fee_ix =
-                                ComputeBudgetInstruction::set_compute_unit_price(prio_fee);
-                            ixs.push(prio_fee_ix);
-
                             let noop_ix = get_auth_ix(signer.pubkey());
-                            let noop_ix_clone = noop_ix.clone();
-                            ixs.push(noop_ix);
-                            ixs.push(noop_ix_clone);
+                            let mut tx_builder = SolanaTransactionBuilder::new()
+                                .compute_limit(cu_limit)
+                                .priority_fee(prio_fee)
+                                .instruction(noop_ix.clone())
+                                .instruction(noop_ix);
 
                             if should_add_reset_ix {
                                 let reset_ix = get_reset_ix(signer.pubkey());
-                                ixs.push(reset_ix);
+                                tx_builder = tx_builder.instruction(reset_ix);
                             }
 
-
-                            let ix_mine = get_mine_ix(signer.pubkey(), best_solution, bus);
-                            ixs.push(ix_mine);
-
-                            if let Ok((hash, _slot)) = rpc_client
-                                .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                                .await
+                            let tool = match *app_tool_status.read().await {
+                                Some(tool) if tool.is_usable(&signer.pubkey()) => Some(tool.tool),
+                                _ => None,
+                            };
+                            let mine_accounts = MineIxAccounts {
+                                tool,
+                                guild: app_config.guild.map(|g| (g.member, g.guild)),
+                            };
+                            let ix_mine =
+                                get_mine_ix(signer.pubkey(), best_solution, bus, mine_accounts);
+                            tx_builder = tx_builder.instruction(ix_mine);
+
+                            if let Ok(blockhash) = get_or_refresh_blockhash(
+                                &mut cached_blockhash,
+                                &rpc_client,
+                                BLOCKHASH_MAX_AGE,
+                            )
+                            .await
                             {
-                                let mut tx =
-                                    Transaction::new_with_payer(&ixs, Some(&signer.pubkey()));
-
-                                tx.sign(&[&signer], hash);
+                                let tx = tx_builder.sign(&signer, blockhash);
                                 info!("Sending signed tx...");
                                 info!("attempt: {}", i + 1);
+                                if timings.first_send_ms.is_none() {
+                                    let elapsed = elapsed_ms(cutoff_reached_at);
+                                    info!("Time from cutoff to first send: {}ms", elapsed);
+                                    timings.first_send_ms = Some(elapsed);
+                                }
                                 let sig = rpc_client
                                     .send_and_confirm_transaction_with_spinner(&tx)
                                     .await;
@@ -608,95 +1916,81 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     Ok(sig) => {
                                         // success
                                         success = true;
+                                        timings.confirmed_ms = Some(elapsed_ms(cutoff_reached_at));
                                         info!("Success!!");
                                         info!("Sig: {}", sig);
-                                        let itxn = InsertTxn {
-                                            txn_type: "mine".to_string(),
-                                            signature: sig.to_string(),
-                                            priority_fee: prio_fee as u32,
-                                        };
-                                        let app_db = app_database.clone();
-                                        tokio::spawn(async move {
-                                            while let Err(_) = app_db.add_new_txn(itxn.clone()).await {
-                                                error!("Failed to add tx to db! Retrying...");
-                                                tokio::time::sleep(Duration::from_millis(2000)).await;
+                                        let mut fee_budget = app_fee_budget.lock().await;
+                                        if fee_budget.record_landed(cu_limit, prio_fee) {
+                                            let status = fee_budget.status();
+                                            error!(
+                                                "Daily fee budget spent ({} lamports), sending with the minimum priority fee",
+                                                status.spent_today_lamports
+                                            );
+                                            if let Some(url) = &app_config.operator_webhook_url {
+                                                let _ = app_webhook_sender.send(WebhookJob {
+                                                    miner_id: None,
+                                                    url: url.clone(),
+                                                    event: WebhookEvent::FeeBudgetExhausted {
+                                                        spent_lamports: status.spent_today_lamports,
+                                                        budget_lamports: status
+                                                            .daily_budget_lamports
+                                                            .unwrap_or_default(),
+                                                    },
+                                                });
                                             }
-                                        });
+                                        }
+                                        drop(fee_budget);
+                                        let app_db = app_database.clone();
+                                        let fee_rpc_client = rpc_client.clone();
+                                        let pool_id = app_config.pool_id;
+                                        // awaited for the challenge's distribution, once
+                                        let mut txn_id_task = Some(tokio::spawn(async move {
+                                            let itxn = InsertTxn {
+                                                txn_type: "mine".to_string(),
+                                                signature: sig.to_string(),
+                                                priority_fee: prio_fee as u32,
+                                                pool_id: Some(pool_id),
+                                                fee_paid_lamports: get_fee_paid(&fee_rpc_client, &sig).await,
+                                            };
+                                            add_confirmed_txn(&app_db, itxn).await
+                                        }));
 
                                         // Handle new hash immediately with websocket
                                         let app_app_proof = app_proof.clone();
-                                        let app_db = app_database.clone();
-                                        let app_nonce = app_nonce.clone();
-                                        let app_config = app_config.clone();
                                         let app_prio_fee = app_prio_fee.clone();
-                                        let app_epoch_hashes = app_epoch_hashes.clone();
+                                        let proof_challenge = proof_challenge_sender.clone();
+                                        let app_rpc_client = rpc_client.clone();
+                                        let authority = signer.pubkey();
+                                        let epoch_starter = app_epoch_starter.clone();
                                         tokio::spawn(async move {
                                             let app_proof = app_app_proof;
-                                            let app_database = app_db;
-                                            loop {
-                                                info!("Waiting for proof hash update");
-                                                let latest_proof = { app_proof.lock().await.clone() };
-
-                                                if old_proof.challenge.eq(&latest_proof.challenge) {
-                                                    info!("Proof challenge not updated yet..");
-                                                    old_proof = latest_proof;
-                                                    tokio::time::sleep(Duration::from_millis(1000)).await;
-                                                    continue;
-                                                } else {
-                                                    info!("Adding new challenge to db");
-                                                    let new_challenge = InsertChallenge {
-                                                        pool_id: app_config.pool_id,
-                                                        challenge: latest_proof.challenge.to_vec(),
-                                                        rewards_earned: None,
-                                                    };
-
-                                                    while let Err(_) = app_database
-                                                        .add_new_challenge(new_challenge.clone())
-                                                        .await
-                                                    {
-                                                        error!("Failed to add new challenge to db, retrying...");
-                                                        tokio::time::sleep(Duration::from_millis(1000))
-                                                            .await;
-                                                    }
-                                                    info!("New challenge successfully added to db");
-
-
-                                                    // Reset mining data
-                                                    {
-                                                        let mut prio_fee = app_prio_fee.lock().await;
-                                                        let mut decrease_amount = 0;
-                                                        if *prio_fee > 20_000 {
-                                                            decrease_amount = 1_000;
-                                                        }
-                                                        if *prio_fee >= 50_000 {
-                                                            decrease_amount = 5_000;
-                                                        }
-                                                        if *prio_fee >= 100_000 {
-                                                            decrease_amount = 10_000;
-                                                        }
-
-                                                        *prio_fee =
-                                                            prio_fee.saturating_sub(decrease_amount);
-                                                    }
-                                                    // reset nonce
-                                                    {
-                                                        let mut nonce = app_nonce.lock().await;
-                                                        *nonce = 0;
-                                                    }
-                                                    // reset epoch hashes
-                                                    {
-                                                        info!("reset epoch hashes");
-                                                        let mut mut_epoch_hashes =
-                                                            app_epoch_hashes.write().await;
-                                                        mut_epoch_hashes.best_hash.solution = None;
-                                                        mut_epoch_hashes.best_hash.difficulty = 0;
-                                                        mut_epoch_hashes.submissions = HashMap::new();
-                                                    }
-
-                                                    break;
+                                            info!("Waiting for proof hash update");
+                                            let latest_proof = wait_for_new_challenge(
+                                                || get_proof(&app_rpc_client, authority),
+                                                old_proof.challenge,
+                                                &app_proof,
+                                                &proof_challenge,
+                                            )
+                                            .await;
+                                            epoch_starter.start(latest_proof.challenge).await;
+
+                                            // Reset mining data
+                                            {
+                                                let mut prio_fee = app_prio_fee.lock().await;
+                                                let mut decrease_amount = 0;
+                                                if *prio_fee > 20_000 {
+                                                    decrease_amount = 1_000;
+                                                }
+                                                if *prio_fee >= 50_000 {
+                                                    decrease_amount = 5_000;
+                                                }
+                                                if *prio_fee >= 100_000 {
+                                                    decrease_amount = 10_000;
                                                 }
-                                            }
 
+                                                *prio_fee =
+                                                    prio_fee.saturating_sub(decrease_amount);
+                                            }
                                         });
 
                                         // get reward amount from MineEvent data and update database
@@ -713,13 +2007,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                                     solana_transaction_status::option_serializer::OptionSerializer::Some(data) => {
                                                         let bytes = BASE64_STANDARD.decode(data.data.0).unwrap();
 
-                                                        if let Ok(mine_event) = bytemuck::try_from_bytes::<MineEvent>(&bytes) {
-                                                            info!("MineEvent: {:?}", mine_event);
-                                                            let rewards = mine_event.reward;
+                                                        if let Some(mine_event) = parse_mine_event(&bytes) {
+                                                            timings.mine_event_ms = Some(elapsed_ms(cutoff_reached_at));
+                                                            info!(
+                                                                "MineEvent: difficulty {}, reward {}, tool reward {}, stake reward {}, timing {}",
+                                                                mine_event.difficulty,
+                                                                mine_event.reward,
+                                                                mine_event.tool_reward,
+                                                                mine_event.stake_reward,
+                                                                mine_event.timing
+                                                            );
+                                                            // includes the guild stake boost when mining in a guild
+                                                            let rewards = mine_event.total_reward();
                                                             // handle sending mine success message
                                                             let mut total_hashpower: u64 = 0;
                                                             for submission in submissions.iter() {
-                                                                total_hashpower += submission.1.2
+                                                                total_hashpower = match total_hashpower.checked_add(submission.1.2) {
+                                                                    Some(total) => total,
+                                                                    None => {
+                                                                        warn!("Total hashpower overflowed, capping at u64::MAX");
+                                                                        u64::MAX
+                                                                    }
+                                                                };
                                                             }
                                                             let challenge;
                                                             loop {
@@ -740,9 +2049,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                                             }
 
                                                             tokio::time::sleep(Duration::from_millis(1000)).await;
-                                                            let latest_proof = { app_proof.lock().await.clone() };
-                                                            let balance = (latest_proof.balance as f64)
-                                                                / 10f64.powf(COAL_TOKEN_DECIMALS as f64);
+                                                            let balance =
+                                                                amount_to_coal(*app_coal_token_balance.lock().await);
+                                                            let txn_id = match txn_id_task.take() {
+                                                                Some(task) => task.await.ok().flatten(),
+                                                                None => None,
+                                                            };
                                                             let _ = mine_success_sender.send(
                                                                 MessageInternalMineSuccess {
                                                                     difficulty,
@@ -751,16 +2063,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                                                     challenge_id: challenge.id,
                                                                     total_hashpower,
                                                                     submissions,
+                                                                    histogram: histogram.clone(),
+                                                                    txn_id,
                                                                 },
                                                             );
                                                             tokio::time::sleep(Duration::from_millis(200)).await;
-                                                            while let Err(_) = app_database
+                                                            while let Err(e) = app_database
                                                                 .update_pool_rewards(
                                                                     app_wallet.pubkey().to_string(),
                                                                     rewards,
                                                                 )
                                                                 .await
                                                             {
+                                                                if !e.is_retriable() {
+                                                                    error!("Non-retriable db error: {:?}", e);
+                                                                    break;
+                                                                }
                                                                 error!(
                                                                     "Failed to update pool rewards! Retrying..."
                                                                 );
@@ -821,15 +2139,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                             }
                                         }
 
+                                        spawn_record_epoch_outcome(
+                                            app_database.clone(),
+                                            app_config.pool_id,
+                                            old_proof.challenge,
+                                            EpochSubmission {
+                                                outcome: EpochOutcome::Success,
+                                                attempts_used: i + 1,
+                                                final_priority_fee: prio_fee,
+                                                timings,
+                                            },
+                                            &histogram,
+                                        );
                                         break;
                                     },
                                     Err(e) => {
                                         error!("Failed to send and confirm txn");
                                         error!("Error: {:?}", e);
+                                        if e.get_transaction_error() == Some(TransactionError::BlockhashNotFound) {
+                                            cached_blockhash = None;
+                                        }
+                                        if let Some(tool) = tool {
+                                            // recheck the tool so the next attempt can mine without it
+                                            let status = get_tool_status(&rpc_client, tool).await.ok();
+                                            let usable = status
+                                                .map(|t| t.is_usable(&signer.pubkey()))
+                                                .unwrap_or(false);
+                                            if !usable {
+                                                error!("Tool {} is no longer usable, mining without it.", tool);
+                                            }
+                                            *app_tool_status.write().await = status;
+                                        }
                                         info!("increasing prio fees");
                                         {
                                             let mut prio_fee = app_prio_fee.lock().await;
-                                            if *prio_fee < 1_000_000 {
+                                            if *prio_fee < MAX_PRIORITY_FEE {
                                                 *prio_fee += 15_000;
                                             }
                                         }
@@ -845,12 +2189,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             tokio::time::sleep(Duration::from_millis(1_000)).await;
                         }
                     }
+                    drop(send_guard);
                     if !success {
                         info!("Failed to send after 10 attempts. Discarding and refreshing data.");
+                        let final_prio_fee = { *app_prio_fee.lock().await };
+                        spawn_record_epoch_outcome(
+                            app_database.clone(),
+                            app_config.pool_id,
+                            old_proof.challenge,
+                            EpochSubmission {
+                                outcome: EpochOutcome::AllAttemptsFailed,
+                                attempts_used: 10,
+                                final_priority_fee: final_prio_fee,
+                                timings,
+                            },
+                            &histogram,
+                        );
                         // reset nonce
                         {
                             let mut nonce = app_nonce.lock().await;
-                            *nonce = 0;
+                            *nonce = nonce_start;
                         }
                         // reset epoch hashes
                         {
@@ -859,11 +2217,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             mut_epoch_hashes.best_hash.solution = None;
                             mut_epoch_hashes.best_hash.difficulty = 0;
                             mut_epoch_hashes.submissions = HashMap::new();
+                            app_best_difficulty_timeline.reset().await;
                         }
+                        state_snapshot::remove(&app_state_snapshot_path);
                     }
                     tokio::time::sleep(Duration::from_millis(500)).await;
                 } else {
-                    error!("No best solution yet.");
+                    let seconds_past_cutoff = cutoff.unsigned_abs();
+                    let now = chrono::Utc::now().timestamp();
+                    if seconds_past_cutoff < missed_epoch_grace {
+                        error!("No best solution yet.");
+                    } else if app_missed_epochs.write().await.record(old_proof.challenge, now) {
+                        warn!(
+                            "No solution {}s past cutoff, marking the epoch missed.",
+                            seconds_past_cutoff
+                        );
+                        let final_prio_fee = { *app_prio_fee.lock().await };
+                        spawn_record_epoch_outcome(
+                            app_database.clone(),
+                            app_config.pool_id,
+                            old_proof.challenge,
+                            EpochSubmission {
+                                outcome: EpochOutcome::Missed,
+                                attempts_used: 0,
+                                final_priority_fee: final_prio_fee,
+                                timings: EpochTimings::default(),
+                            },
+                            &DifficultyHistogram::default(),
+                        );
+                        let notice = EpochMissedNotice {
+                            epoch_id: app_epoch_challenges.read().await.current().epoch_id,
+                            seconds_past_cutoff,
+                        };
+                        if let Ok(text) = serde_json::to_string(&notice) {
+                            let _ = app_all_clients_sender.send(MessageInternalAllClients { text });
+                        }
+                        // hand every connected miner a fresh nonce range for the same challenge
+                        let shared_state = app_shared_state.read().await;
+                        let mut ready_clients = app_ready_clients.lock().await;
+                        for (addr, connection) in shared_state.sockets.iter() {
+                            ready_clients.insert(connection.connection_id, *addr);
+                        }
+                    }
                     tokio::time::sleep(Duration::from_millis(1000)).await;
                 }
             } else {
@@ -875,50 +2270,69 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let app_shared_state = shared_state.clone();
     let app_app_database = app_database.clone();
     let app_config = config.clone();
+    let app_webhook_sender = webhook_sender.clone();
+    let app_runtime_config = runtime_config.clone();
+    let app_dashboard_bus = dashboard_bus.clone();
+    let app_ready_clients = ready_clients.clone();
+    let app_reward_cache = reward_cache.clone();
     tokio::spawn(async move {
         let app_database = app_app_database;
         loop {
             while let Some(msg) = mine_success_receiver.recv().await {
                 {
-                    let mut i_earnings = Vec::new();
-                    let mut i_rewards = Vec::new();
+                    let distributable_rewards = app_runtime_config
+                        .read()
+                        .await
+                        .distributable_rewards(msg.rewards);
+                    // a miner's devices are credited together as one earning
+                    let mut miner_earnings: HashMap<i32, (u64, u64)> = HashMap::new();
                     let shared_state = app_shared_state.read().await;
                     let len = shared_state.sockets.len();
-                    for (_socket_addr, socket_sender) in shared_state.sockets.iter() {
+                    app_dashboard_bus.send(DashboardEvent::MineSuccess {
+                        challenge_id: msg.challenge_id,
+                        difficulty: msg.difficulty,
+                        rewards: msg.rewards,
+                        distributable_rewards,
+                        total_balance: msg.total_balance,
+                        total_hashpower: msg.total_hashpower,
+                        submitting_miners: msg
+                            .submissions
+                            .keys()
+                            .map(|(pubkey, _)| pubkey)
+                            .collect::<HashSet<_>>()
+                            .len(),
+                        connected_miners: len,
+                    });
+                    for (socket_addr, socket_sender) in shared_state.sockets.iter() {
                         let pubkey = socket_sender.pubkey;
 
                         if let Some((miner_id, supplied_diff, pubkey_hashpower)) =
-                            msg.submissions.get(&pubkey)
+                            msg.submissions.get(&(pubkey, socket_sender.device_id.clone()))
                         {
-                            let hashpower_percent = (*pubkey_hashpower as u128)
-                                .saturating_mul(1_000_000)
-                                .saturating_div(msg.total_hashpower as u128);
-
-                            // TODO: handle overflow/underflow and float imprecision issues
-                            let decimals = 10f64.powf(COAL_TOKEN_DECIMALS as f64);
-                            let earned_rewards = hashpower_percent
-                                .saturating_mul(msg.rewards as u128)
-                                .saturating_div(1_000_000)
-                                as u64;
-
-                            let new_earning = InsertEarning {
-                                miner_id: *miner_id,
-                                pool_id: app_config.pool_id,
-                                challenge_id: msg.challenge_id,
-                                amount: earned_rewards,
+                            let earned_rewards = match calculate_earned_rewards(
+                                *pubkey_hashpower,
+                                msg.total_hashpower,
+                                distributable_rewards,
+                            ) {
+                                Ok(earned_rewards) => earned_rewards,
+                                Err(RewardError::RewardOverflow) => {
+                                    error!("Rewards of miner {} overflowed, not crediting them", miner_id);
+                                    continue;
+                                }
+                                Err(RewardError::ExceedsRewards) => {
+                                    error!("Rewards of miner {} exceed the epoch rewards, not crediting them", miner_id);
+                                    continue;
+                                }
                             };
 
-                            let new_reward = UpdateReward {
-                                miner_id: *miner_id,
-                                balance: earned_rewards,
-                            };
-
-                            i_earnings.push(new_earning);
-                            i_rewards.push(new_reward);
+                            socket_sender.session.record_epoch(earned_rewards);
+                            let miner_earning = miner_earnings.entry(*miner_id).or_insert((0, 0));
+                            miner_earning.0 = miner_earning.0.saturating_add(earned_rewards);
+                            miner_earning.1 = miner_earning.1.saturating_add(*pubkey_hashpower);
                             //let _ = app_database.add_new_earning(new_earning).await.unwrap();
 
-                            let earned_rewards_dec = (earned_rewards as f64).div(decimals);
-                            let pool_rewards_dec = (msg.rewards as f64).div(decimals);
+                            let earned_rewards_dec = amount_to_coal(earned_rewards);
+                            let pool_rewards_dec = amount_to_coal(msg.rewards);
 
                             let percentage = if pool_rewards_dec != 0.0 {
                                 (earned_rewards_dec / pool_rewards_dec) * 100.0
@@ -927,47 +2341,136 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             };
                             
                             let message = format!(
-                                "Pool Submitted Difficulty: {}\nPool Earned:  {:.11} COAL\nPool Balance: {:.11}\n----------------------\nActive Miners: {}\n----------------------\nMiner Submitted Difficulty: {}\nMiner Earned: {:.11} COAL\n{:.2}% of total pool reward",
+                                "Pool Submitted Difficulty: {}\nPool Earned:  {:.11} COAL\nPool Balance: {:.11}\n----------------------\nActive Miners: {}\n----------------------\nMiner Submitted Difficulty: {}\nMiner Earned: {:.11} COAL\n{:.2}% of total pool reward\n----------------------\nDifficulty Histogram: {}",
                                 msg.difficulty,
                                 pool_rewards_dec,
                                 msg.total_balance,
                                 len,
                                 supplied_diff,
                                 earned_rewards_dec,
-                                percentage
+                                percentage,
+                                msg.histogram
                             );
                             
+                            let socket_addr = *socket_addr;
                             let socket_sender = socket_sender.clone();
+                            let app_shared_state = app_shared_state.clone();
+                            let ready_clients = app_ready_clients.clone();
                             tokio::spawn(async move {
-                                if let Ok(_) = socket_sender
-                                    .socket
-                                    .lock()
-                                    .await
-                                    .send(Message::Text(message))
-                                    .await
-                                {
-                                } else {
-                                    error!("Failed to send client text");
-                                }
+                                send_client_message(
+                                    &app_shared_state,
+                                    &ready_clients,
+                                    socket_addr,
+                                    &socket_sender,
+                                    Message::Text(message),
+                                )
+                                .await;
                             });
                         }
                     }
+                    let mut i_earnings = Vec::new();
+                    let mut i_rewards = Vec::new();
+                    for (miner_id, (earned_rewards, hashpower)) in miner_earnings {
+                        i_earnings.push(InsertEarning {
+                            miner_id,
+                            pool_id: app_config.pool_id,
+                            challenge_id: msg.challenge_id,
+                            amount: earned_rewards,
+                            hashpower,
+                        });
+                        i_rewards.push(UpdateReward {
+                            miner_id,
+                            balance: earned_rewards,
+                        });
+                    }
                     if i_earnings.len() > 0 {
-                        if let Ok(_) = app_database
+                        if let Ok(result) = app_database
                             .add_new_earnings_batch(i_earnings.clone())
                             .await
                         {
-                            info!("Successfully added earnings batch");
+                            info!(
+                                "Successfully added earnings batch, {} inserted, {} skipped",
+                                result.inserted, result.skipped
+                            );
+                            // a skipped earning was credited when it was first recorded
+                            i_rewards.retain(|r| !result.skipped_miner_ids.contains(&r.miner_id));
                         } else {
                             error!("Failed to insert earnings batch");
                         }
                     }
                     if i_rewards.len() > 0 {
-                        if let Ok(_) = app_database.update_rewards(i_rewards).await {
+                        let earned: HashMap<i32, u64> = i_rewards
+                            .iter()
+                            .map(|r| (r.miner_id, r.balance))
+                            .collect();
+                        if app_database
+                            .update_rewards(i_rewards.clone(), app_config.pool_id)
+                            .await
+                            .is_ok()
+                        {
                             info!("Successfully updated rewards");
+                            match app_database.apply_pending_rewards(app_config.pool_id).await {
+                                Ok(true) => {
+                                    info!("Applied pending rewards");
+                                    // pending rewards may belong to any miner
+                                    app_reward_cache.clear().await;
+                                }
+                                Ok(false) => {}
+                                Err(e) => error!("Failed to apply pending rewards: {:?}", e),
+                            }
+                            let app_database = app_database.clone();
+                            let webhook_sender = app_webhook_sender.clone();
+                            let pool_id = app_config.pool_id;
+                            tokio::spawn(async move {
+                                notify_balance_thresholds(app_database, webhook_sender, pool_id, earned)
+                                    .await;
+                            });
                         } else {
-                            error!("Failed to bulk update rewards");
+                            error!("Failed to bulk update rewards, parking them in pending rewards");
+                            if let Err(e) = app_database
+                                .add_pending_rewards(i_rewards.clone(), app_config.pool_id)
+                                .await
+                            {
+                                error!("Failed to park rewards, unapplied batch: {:?} ({:?})", i_rewards, e);
+                            }
                         }
+                        app_reward_cache
+                            .invalidate(&i_rewards.iter().map(|r| r.miner_id).collect())
+                            .await;
+                    }
+                    // referral bonuses are paid out of the commission
+                    let mut commission = msg.rewards.saturating_sub(distributable_rewards);
+                    if app_config.referral_bonus_pct > 0.0 {
+                        let bonuses: Vec<ReferralBonus> = i_rewards
+                            .iter()
+                            .map(|r| ReferralBonus {
+                                referred_miner_id: r.miner_id,
+                                amount: calculate_referral_bonus(r.balance, app_config.referral_bonus_pct),
+                            })
+                            .filter(|bonus| bonus.amount > 0)
+                            .collect();
+                        if !bonuses.is_empty() {
+                            match app_database
+                                .credit_referral_bonuses(app_config.pool_id, bonuses, commission)
+                                .await
+                            {
+                                Ok(credited) => commission -= credited,
+                                Err(e) => error!("Failed to credit referral bonuses: {:?}", e),
+                            }
+                            // the referrers' ids aren't known here
+                            app_reward_cache.clear().await;
+                        }
+                    }
+                    if let Err(e) = app_database
+                        .update_challenge_distribution(
+                            msg.challenge_id,
+                            msg.txn_id,
+                            commission,
+                            msg.total_hashpower,
+                        )
+                        .await
+                    {
+                        error!("Failed to record challenge distribution: {:?}", e);
                     }
                 }
             }
@@ -975,21 +2478,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
 
     let app_shared_state = shared_state.clone();
+    let app_ready_clients = ready_clients.clone();
     tokio::spawn(async move {
         loop {
             while let Some(msg) = all_clients_receiver.recv().await {
                 {
                     let shared_state = app_shared_state.read().await;
-                    for (_socket_addr, socket_sender) in shared_state.sockets.iter() {
+                    for (socket_addr, socket_sender) in shared_state.sockets.iter() {
                         let text = msg.text.clone();
+                        let socket_addr = *socket_addr;
                         let socket = socket_sender.clone();
+                        let app_shared_state = app_shared_state.clone();
+                        let ready_clients = app_ready_clients.clone();
                         tokio::spawn(async move {
-                            if let Ok(_) =
-                                socket.socket.lock().await.send(Message::Text(text)).await
-                            {
-                            } else {
-                                error!("Failed to send client text");
-                            }
+                            send_client_message(
+                                &app_shared_state,
+                                &ready_clients,
+                                socket_addr,
+                                &socket,
+                                Message::Text(text),
+                            )
+                            .await;
                         });
                     }
                 }
@@ -997,25 +2506,80 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET])
-        .allow_origin(tower_http::cors::Any);
-
     let client_channel = client_message_sender.clone();
+    let signed_requests = SignedRequests {
+        app_database: app_database.clone(),
+        auth_window: config.auth_window,
+        replays: signed_request_replays,
+    };
     let app_shared_state = shared_state.clone();
     let app = Router::new()
         .route("/", get(ws_handler))
+        // the same websocket under /pool/<pool_id>/ws and /pools/<name>/ws
+        .route("/ws", get(ws_handler))
         .route("/latest-blockhash", get(get_latest_blockhash))
         .route("/pool/authority/pubkey", get(get_pool_authority_pubkey))
-        .route("/signup", post(post_signup))
-        .route("/claim", post(post_claim))
+        .route("/pool/info", get(get_pool_info))
+        .route(
+            "/signup",
+            post(post_signup).layer(axum::middleware::from_fn(audit_log::audit_signup)),
+        )
+        .route(
+            "/claim",
+            post(post_claim).layer(axum::middleware::from_fn(audit_log::audit_claim)),
+        )
+        .route("/miner/settings", post(post_miner_settings))
+        .route("/miner/delegate", post(post_miner_delegate))
+        .route("/miner/delegate/revoke", post(post_miner_delegate_revoke))
+        .route("/admin/config", get(get_admin_config).put(put_admin_config))
+        .route("/admin/challenge/:id/redistribute", post(post_admin_challenge_redistribute))
+        .route("/admin/audit-log", get(get_admin_audit_log))
+        .route("/admin/nonce-ranges", get(get_admin_nonce_ranges))
+        .route("/admin/nonce-range/:pubkey", delete(delete_admin_nonce_range))
+        .route("/admin/reload-wallet", post(post_admin_reload_wallet))
+        .route("/admin/miners/import", post(post_admin_miners_import))
         .route("/active-miners", get(get_connected_miners))
+        .route("/pool/active-sessions", get(get_pool_active_sessions))
+        .route("/pool/mining-efficiency", get(get_pool_mining_efficiency))
+        .route("/pool/listing", get(get_pool_listing))
+        .route("/pool/best-solution-timeline", get(get_best_solution_timeline))
+        .route("/pool/estimated-apy", get(get_pool_estimated_apy))
+        .route("/pool/reward-formula", get(get_pool_reward_formula))
         .route("/timestamp", get(get_timestamp))
+        .route("/health", get(get_health))
         .route("/miner/balance", get(get_miner_balance))
+        .route("/miner/devices", get(get_miner_devices))
+        .route("/miner/watchdog", get(get_miner_watchdog))
+        .route("/miner/reputation", get(get_miner_reputation))
+        .route("/miner/info", get(get_miner_info))
+        .route("/pool/busses", get(get_pool_busses))
+        .route("/pool/submission-heatmap", get(get_pool_submission_heatmap))
+        .route("/pool/epoch/history", get(get_pool_epoch_history))
+        .route("/pool/stats", get(get_pool_stats))
+        .route("/pool/epoch-reliability", get(get_pool_epoch_reliability))
+        .route("/ws/dashboard", get(dashboard::dashboard_ws_handler))
         // App RR Database routes
         .route("/last-challenge-submissions", get(get_last_challenge_submissions))
+        .route("/challenge/current/proof", get(get_current_challenge_proof))
+        .route("/challenge/:id", get(get_challenge))
+        .route("/challenge/:id/distribution", get(get_challenge_distribution))
+        .route("/challenge/:id/histogram", get(get_challenge_histogram))
         .route("/miner/rewards", get(get_miner_rewards))
         .route("/miner/submissions", get(get_miner_submissions))
+        .route("/miner/claims", get(get_miner_claims))
+        .route("/pool/claims", get(get_pool_claims))
+        .route("/pool/challenges", get(get_pool_challenges))
+        .route("/pool/challenges/:id", get(get_pool_challenge))
+        .route("/pool/reward-distribution-history", get(get_pool_reward_distribution_history))
+        .route("/pool/totals", get(get_pool_totals))
+        .route("/pool/fees-paid", get(get_pool_fees_paid))
+        .route("/pool/slots-until-reset", get(get_pool_slots_until_reset))
+        .route("/pool/miner-activity", get(get_pool_miner_activity))
+        .route("/miner/total-hashpower-contributed", get(get_miner_total_hashpower))
+        .route("/miner/referrals", get(get_miner_referrals))
+        .route("/miner/next-reward-estimate", get(get_miner_next_reward_estimate))
+        .route("/miner/payout-schedule", get(get_miner_payout_schedule))
+        .route("/pool/total-hashpower-contributed", get(get_pool_total_hashpower))
         .with_state(app_shared_state)
         .layer(Extension(app_database))
         .layer(Extension(app_rr_database))
@@ -1024,699 +2588,4600 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .layer(Extension(client_channel))
         .layer(Extension(rpc_client))
         .layer(Extension(client_nonce_ranges))
-        // Logging
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        )
-        .layer(cors);
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-
-    tracing::info!("listening on {}", listener.local_addr().unwrap());
+        .layer(Extension(epoch_challenges))
+        .layer(Extension(epoch_hashes))
+        .layer(Extension(signed_requests))
+        .layer(Extension(ready_clients.clone()))
+        .layer(Extension(bus_stats))
+        .layer(Extension(webhook_sender))
+        .layer(Extension(runtime_config))
+        .layer(Extension(miner_claims_cache))
+        .layer(Extension(miner_activity_cache))
+        .layer(Extension(pool_listing_cache))
+        .layer(Extension(reward_yield_cache))
+        .layer(Extension(best_difficulty_timeline))
+        .layer(Extension(tool_status))
+        .layer(Extension(reprocess_status))
+        .layer(Extension(dashboard_bus))
+        .layer(Extension(coal_config_cache))
+        .layer(Extension(proof_ext))
+        .layer(Extension(fee_budget))
+        .layer(Extension(audit_log))
+        .layer(Extension(proof_updates))
+        .layer(Extension(missed_epochs))
+        .layer(Extension(reward_cache))
+        .layer(Extension(replica_lag))
+        .layer(Extension(keepalive.clone()))
+        .layer(Extension(keepalive_config))
+        .layer(Extension(wallet_reload));
+    #[cfg(feature = "event-bus")]
+    let app = app.layer(Extension(event_bus_stats));
 
     let app_shared_state = shared_state.clone();
     tokio::spawn(async move {
-        ping_check_system(&app_shared_state).await;
+        keepalive_system(keepalive, keepalive_config, app_shared_state, ready_clients).await;
     });
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    Ok((db_pool.id, app))
+}
 
-    Ok(())
+#[utoipa::path(
+    get,
+    path = "/pool/info",
+    tag = "pool",
+    responses(
+        (status = 200, body = PoolInfo)
+    )
+)]
+async fn get_pool_info(
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(wallet): Extension<PoolWallet>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    Extension(reprocess_status): Extension<Option<Arc<RwLock<ReprocessStatus>>>>,
+) -> Json<PoolInfo> {
+    let runtime_config = runtime_config.read().await.clone();
+    Json(pool_info::get_pool_info(
+        &app_config,
+        &runtime_config,
+        wallet.pubkey().to_string(),
+        reprocess_status.is_some(),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/pool/authority/pubkey",
+    tag = "pool",
+    responses(
+        (status = 200, description = "Pubkey of the pool authority", body = String, content_type = "text/plain")
+    )
+)]
 async fn get_pool_authority_pubkey(
-    Extension(wallet): Extension<Arc<Keypair>>,
-) -> impl IntoResponse {
-    Response::builder()
+    Extension(wallet): Extension<PoolWallet>,
+) -> Result<Response<String>, ApiError> {
+    Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "text/text")
         .body(wallet.pubkey().to_string())
-        .unwrap()
+        .unwrap())
 }
 
+#[utoipa::path(
+    get,
+    path = "/latest-blockhash",
+    responses(
+        (status = 200, description = "Base64 encoded bincode of the latest blockhash", body = String, content_type = "text/plain"),
+        (status = 500, description = "Failed to get the blockhash from the rpc", body = ApiError)
+    )
+)]
 async fn get_latest_blockhash(
     Extension(rpc_client): Extension<Arc<RpcClient>>,
-) -> impl IntoResponse {
-    let latest_blockhash = rpc_client.get_latest_blockhash().await.unwrap();
+) -> Result<Response<String>, ApiError> {
+    let latest_blockhash = rpc_client
+        .get_latest_blockhash()
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::RpcError, "Failed to get latest blockhash"))?;
 
     let serialized_blockhash = bincode::serialize(&latest_blockhash).unwrap();
 
     let encoded_blockhash = BASE64_STANDARD.encode(serialized_blockhash);
-    Response::builder()
+    Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "text/text")
         .body(encoded_blockhash)
-        .unwrap()
+        .unwrap())
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct SignupParams {
-    pubkey: String,
+    /// Pubkey of the pool miner who referred this one, only recorded for new miners
+    #[serde(rename = "ref")]
+    referrer: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/signup",
+    tag = "miner",
+    params(PubkeyParam, SignupParams),
+    request_body(content = String, description = "Base64 encoded signup fee transfer transaction, ignored for whitelisted pubkeys", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Miner signed up", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid pubkey, referrer or transaction", body = ApiError),
+        (status = 500, description = "Failed to send the transaction or save the miner", body = ApiError)
+    )
+)]
 async fn post_signup(
-    query_params: Query<SignupParams>,
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    Query(signup_params): Query<SignupParams>,
     Extension(app_database): Extension<Arc<AppDatabase>>,
     Extension(rpc_client): Extension<Arc<RpcClient>>,
-    Extension(wallet): Extension<Arc<Keypair>>,
+    Extension(wallet): Extension<PoolWallet>,
     Extension(app_config): Extension<Arc<Config>>,
+    Extension(dashboard_bus): Extension<DashboardEventBus>,
     body: String,
-) -> impl IntoResponse {
-    if let Ok(user_pubkey) = Pubkey::from_str(&query_params.pubkey) {
-        let db_miner = app_database
-            .get_miner_by_pubkey_str(user_pubkey.to_string())
-            .await;
+) -> Result<Response<String>, ApiError> {
+    let db_miner = app_database
+        .get_miner_by_pubkey_str(user_pubkey.to_string())
+        .await;
 
-        match db_miner {
-            Ok(miner) => {
-                if miner.enabled {
-                    info!("Miner account already enabled!");
-                    return Response::builder()
-                        .status(StatusCode::OK)
-                        .header("Content-Type", "text/text")
-                        .body("SUCCESS".to_string())
-                        .unwrap();
+    match db_miner {
+        Ok(miner) => {
+            if miner.enabled {
+                info!("Miner account already enabled!");
+                // the miner may have signed up through another pool
+                let new_reward = InsertReward {
+                    miner_id: miner.id,
+                    pool_id: app_config.pool_id,
+                };
+                if app_database.add_new_reward(new_reward).await.is_err() {
+                    error!("Failed to add miner rewards tracker to database");
+                    return Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to add miner rewards tracker to database"));
                 }
-            }
-            Err(AppDatabaseError::FailedToGetConnectionFromPool) => {
-                error!("Failed to get database pool connection");
-                return Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body("Failed to get db pool connection".to_string())
-                    .unwrap();
-            }
-            Err(_) => {
-                info!("No miner account exists. Signing up new user.");
+                return Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header("Content-Type", "text/text")
+                    .body("SUCCESS".to_string())
+                    .unwrap());
             }
         }
-
-        if let Some(whitelist) = &app_config.whitelist {
-            if whitelist.contains(&user_pubkey) {
-                let result = app_database
-                    .add_new_miner(user_pubkey.to_string(), true)
-                    .await;
-                let miner = app_database
-                    .get_miner_by_pubkey_str(user_pubkey.to_string())
-                    .await
-                    .unwrap();
-
-                let wallet_pubkey = wallet.pubkey();
-                let pool = app_database
-                    .get_pool_by_authority_pubkey(wallet_pubkey.to_string())
-                    .await
-                    .unwrap();
-
-                if result.is_ok() {
-                    let new_reward = InsertReward {
-                        miner_id: miner.id,
-                        pool_id: pool.id,
-                    };
-                    let result = app_database.add_new_reward(new_reward).await;
-
-                    if result.is_ok() {
-                        return Response::builder()
-                            .status(StatusCode::OK)
-                            .header("Content-Type", "text/text")
-                            .body("SUCCESS".to_string())
-                            .unwrap();
-                    } else {
-                        error!("Failed to add miner rewards tracker to database");
-                        return Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .body("Failed to add miner rewards tracker to database".to_string())
-                            .unwrap();
-                    }
-                } else {
-                    error!("Failed to add miner to database");
-                    return Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body("Failed to add miner to database".to_string())
-                        .unwrap();
-                }
-            }
+        Err(AppDatabaseError::FailedToGetConnectionFromPool) => {
+            error!("Failed to get database pool connection");
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get db pool connection"));
+        }
+        Err(_) => {
+            info!("No miner account exists. Signing up new user.");
         }
+    }
 
-        let serialized_tx = BASE64_STANDARD.decode(body.clone()).unwrap();
-        let tx: Transaction = if let Ok(tx) = bincode::deserialize(&serialized_tx) {
-            tx
-        } else {
-            error!("Failed to deserialize tx");
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Invalid Tx".to_string())
-                .unwrap();
-        };
+    let referrer_miner_id = match signup_params.referrer {
+        Some(referrer) => Some(get_referrer_miner_id(&app_database, &user_pubkey, &referrer).await?),
+        None => None,
+    };
 
-        if !tx.is_signed() {
-            error!("Tx missing signer");
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Invalid Tx".to_string())
-                .unwrap();
+    if let Some(whitelist) = &app_config.whitelist {
+        if whitelist.contains(&user_pubkey) {
+            let result = app_database
+                .signup_miner(user_pubkey.to_string(), app_config.pool_id, referrer_miner_id)
+                .await;
+            return signup_response(result, &dashboard_bus);
         }
+    }
 
-        let ixs = tx.message.instructions.clone();
+    let serialized_tx = BASE64_STANDARD
+        .decode(body.clone())
+        .map_err(|_| ApiError::new(ApiErrorCode::InvalidTransaction, "Invalid Tx"))?;
+    let tx: Transaction = if let Ok(tx) = bincode::deserialize(&serialized_tx) {
+        tx
+    } else {
+        error!("Failed to deserialize tx");
+        return Err(ApiError::new(ApiErrorCode::InvalidTransaction, "Invalid Tx"));
+    };
 
-        if ixs.len() > 1 {
-            error!("Too many instructions");
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Invalid Tx".to_string())
-                .unwrap();
-        }
+    if !tx.is_signed() {
+        error!("Tx missing signer");
+        return Err(ApiError::new(ApiErrorCode::InvalidTransaction, "Invalid Tx"));
+    }
 
-        let base_ix = system_instruction::transfer(&user_pubkey, &wallet.pubkey(), 1_000_000);
-        let mut accts = Vec::new();
-        for account_index in ixs[0].accounts.clone() {
-            accts.push(tx.key(0, account_index.into()));
-        }
+    let ixs = tx.message.instructions.clone();
 
-        if accts.len() != 2 {
-            error!("too many accts");
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Invalid Tx".to_string())
-                .unwrap();
-        }
+    if ixs.len() > 1 {
+        error!("Too many instructions");
+        return Err(ApiError::new(ApiErrorCode::InvalidTransaction, "Invalid Tx"));
+    }
 
-        if ixs[0].data.ne(&base_ix.data) {
-            error!("data missmatch");
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Invalid Tx".to_string())
-                .unwrap();
-        } else {
-            info!("Valid signup tx, submitting.");
+    let base_ix =
+        system_instruction::transfer(&user_pubkey, &wallet.pubkey(), pool_info::SIGNUP_COST_LAMPORTS);
+    let mut accts = Vec::new();
+    for account_index in ixs[0].accounts.clone() {
+        accts.push(tx.key(0, account_index.into()));
+    }
 
-            let result = rpc_client.send_and_confirm_transaction(&tx).await;
+    if accts.len() != 2 {
+        error!("too many accts");
+        return Err(ApiError::new(ApiErrorCode::InvalidTransaction, "Invalid Tx"));
+    }
 
-            match result {
-                Ok(_sig) => {
-                    let res = app_database
-                        .add_new_miner(user_pubkey.to_string(), true)
-                        .await;
-                    let miner = app_database
-                        .get_miner_by_pubkey_str(user_pubkey.to_string())
-                        .await
-                        .unwrap();
+    if ixs[0].data.ne(&base_ix.data) {
+        error!("data missmatch");
+        Err(ApiError::new(ApiErrorCode::InvalidTransaction, "Invalid Tx"))
+    } else {
+        info!("Valid signup tx, submitting.");
 
-                    let wallet_pubkey = wallet.pubkey();
-                    let pool = app_database
-                        .get_pool_by_authority_pubkey(wallet_pubkey.to_string())
-                        .await
-                        .unwrap();
+        let result = rpc_client.send_and_confirm_transaction(&tx).await;
 
-                    if res.is_ok() {
-                        let new_reward = InsertReward {
-                            miner_id: miner.id,
-                            pool_id: pool.id,
-                        };
-                        let result = app_database.add_new_reward(new_reward).await;
-
-                        if result.is_ok() {
-                            return Response::builder()
-                                .status(StatusCode::OK)
-                                .header("Content-Type", "text/text")
-                                .body("SUCCESS".to_string())
-                                .unwrap();
-                        } else {
-                            error!("Failed to add miner rewards tracker to database");
-                            return Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .body("Failed to add miner rewards tracker to database".to_string())
-                                .unwrap();
-                        }
-                    } else {
-                        error!("Failed to add miner to database");
-                        return Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .body("Failed to add user to database".to_string())
-                            .unwrap();
-                    }
-                },
-                Err(e) => {
-                    error!("{} signup transaction failed...", user_pubkey.to_string());
-                    error!("Signup Tx Error: {:?}", e);
-                    return Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body("Failed to send tx".to_string())
-                        .unwrap();
-                }
+        match result {
+            Ok(_sig) => {
+                let result = app_database
+                    .signup_miner(user_pubkey.to_string(), app_config.pool_id, referrer_miner_id)
+                    .await;
+                signup_response(result, &dashboard_bus)
+            },
+            Err(e) => {
+                error!("{} signup transaction failed...", display_pubkey(&user_pubkey));
+                error!("Signup Tx Error: {:?}", e);
+                Err(ApiError::new(ApiErrorCode::TransactionFailed, "Failed to send tx"))
             }
         }
-    } else {
-        error!("Signup with invalid pubkey");
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Invalid Pubkey".to_string())
-            .unwrap();
     }
 }
 
-#[derive(Deserialize)]
-struct PubkeyParam {
-    pubkey: String,
+/// Miner id of the referrer of a signup, checked before the signup fee is sent.
+async fn get_referrer_miner_id(
+    app_database: &AppDatabase,
+    user_pubkey: &Pubkey,
+    referrer: &str,
+) -> Result<i32, ApiError> {
+    let referrer = Pubkey::from_str(referrer)
+        .map_err(|_| ApiError::new(ApiErrorCode::InvalidPubkey, "Invalid referrer public key"))?;
+    if referrer == *user_pubkey {
+        return Err(ApiError::new(ApiErrorCode::InvalidRequest, "Miners can't refer themselves"));
+    }
+    match app_database.get_miner_by_pubkey_str(referrer.to_string()).await {
+        Ok(miner) if miner.enabled => Ok(miner.id),
+        Ok(_) => Err(ApiError::new(ApiErrorCode::InvalidRequest, "Referrer is disabled")),
+        Err(AppDatabaseError::FailedToGetConnectionFromPool) => {
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get db pool connection"))
+        }
+        Err(_) => Err(ApiError::new(ApiErrorCode::InvalidRequest, "Referrer is not a pool miner")),
+    }
 }
 
-async fn get_miner_rewards(
-    query_params: Query<PubkeyParam>,
-    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
-) -> impl IntoResponse {
-    if let Ok(user_pubkey) = Pubkey::from_str(&query_params.pubkey) {
-        let res = app_rr_database
-            .get_miner_rewards(user_pubkey.to_string())
-            .await;
-
-        match res {
-            Ok(rewards) => {
-                let decimal_bal =
-                    rewards.balance as f64 / 10f64.powf(coal_api::consts::TOKEN_DECIMALS as f64);
-                let response = format!("{}", decimal_bal);
-                return Response::builder()
-                    .status(StatusCode::OK)
-                    .body(response)
-                    .unwrap();
-            }
-            Err(_) => {
-                return Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body("Failed to get balance".to_string())
-                    .unwrap();
-            }
+fn signup_response(
+    result: Result<Miner, AppDatabaseError>,
+    dashboard_bus: &DashboardEventBus,
+) -> Result<Response<String>, ApiError> {
+    match result {
+        Ok(miner) => {
+            dashboard_bus.send(DashboardEvent::MinerSignedUp {
+                pubkey: miner.pubkey,
+            });
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/text")
+                .body("SUCCESS".to_string())
+                .unwrap())
+        }
+        Err(_) => {
+            error!("Failed to add miner to database");
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to add miner to database"))
         }
-    } else {
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Invalid public key".to_string())
-            .unwrap();
     }
 }
 
-async fn get_last_challenge_submissions(
-    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
-) -> Result<Json<Vec<SubmissionWithPubkey>>, String> {
-    let res = app_rr_database
-        .get_last_challenge_submissions()
-        .await;
+/// A COAL amount in raw units along with its exact decimal string.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct TokenAmount {
+    amount: u64,
+    ui_amount: String,
+}
 
-    match res {
-        Ok(submissions) => {
-            Ok(Json(submissions))
+impl TokenAmount {
+    fn new(amount: u64) -> Self {
+        TokenAmount {
+            amount,
+            ui_amount: amount_to_ui_string(amount),
         }
-        Err(_) => {
-            Err("Failed to get submissions for miner".to_string())
+    }
+
+    /// Json for clients that accept it, the decimal string otherwise.
+    fn into_negotiated_response(self, headers: &HeaderMap) -> axum::response::Response {
+        if accepts_json(headers) {
+            Json(self).into_response()
+        } else {
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(self.ui_amount)
+                .unwrap()
+                .into_response()
         }
     }
 }
 
-#[derive(Deserialize)]
-struct GetSubmissionsParams {
-    pubkey: String,
+/// Everything a wallet view needs about a miner's rewards.
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerRewardsResponse {
+    // the unclaimed balance, as amount and ui_amount like before the breakdown
+    #[serde(flatten)]
+    balance: TokenAmount,
+    // unclaimed balance plus everything claimed so far
+    total_credited: TokenAmount,
+    total_claimed: TokenAmount,
+    // what a claim would accept now, 0 below the minimum or during the cooldown
+    claimable: TokenAmount,
+    min_claim_amount: TokenAmount,
+    // unix seconds the claim cooldown ends, None when it isn't active
+    cooldown_ends_at: Option<i64>,
+    // estimated share of the epoch in progress, None until the bus rewards are loaded
+    pending_epoch_estimate: Option<TokenAmount>,
 }
 
-async fn get_miner_submissions(
-    query_params: Query<GetSubmissionsParams>,
+#[utoipa::path(
+    get,
+    path = "/miner/rewards",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "Unclaimed rewards in COAL. With Accept: application/json a breakdown of the credited, claimed, claimable and pending rewards", content(
+            ("text/plain" = String),
+            ("application/json" = MinerRewardsResponse)
+        )),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 500, description = "Failed to get rewards", body = ApiError)
+    )
+)]
+async fn get_miner_rewards(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    headers: HeaderMap,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
     Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
-) -> Result<Json<Vec<Submission>>, String> {
-    if let Ok(user_pubkey) = Pubkey::from_str(&query_params.pubkey) {
-        let res = app_rr_database
-            .get_miner_submissions(user_pubkey.to_string())
-            .await;
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(reward_cache): Extension<RewardCache>,
+    Extension(replica_lag): Extension<Arc<RwLock<ReplicaLag>>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    Extension(epoch_hashes): Extension<Arc<RwLock<EpochHashes>>>,
+    Extension(coal_config_cache): Extension<Arc<RwLock<Option<CoalConfigSnapshot>>>>,
+) -> Result<axum::response::Response, ApiError> {
+    let balance_error = |_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get balance");
+    let rewards = if let Some(rewards) = reward_cache.get(&user_pubkey.to_string()).await {
+        rewards
+    } else if replica_lag.read().await.may_be_stale(&user_pubkey.to_string()) {
+        // a claim the replica hasn't caught up with yet would show the old balance
+        get_cached_miner_rewards(&app_database, &reward_cache, &user_pubkey, app_config.pool_id)
+            .await
+            .map_err(balance_error)?
+    } else {
+        // replica reads aren't cached, they may lag a balance change
+        app_rr_database
+            .get_miner_rewards(user_pubkey.to_string(), app_config.pool_id)
+            .await
+            .map_err(balance_error)?
+    };
+    if !accepts_json(&headers) {
+        return Ok(TokenAmount::new(rewards.balance).into_negotiated_response(&headers));
+    }
 
-        match res {
-            Ok(submissions) => {
-                Ok(Json(submissions))
-            }
-            Err(_) => {
-                Err("Failed to get submissions for miner".to_string())
+    let total_claimed = app_rr_database
+        .get_miner_total_claimed(user_pubkey.to_string(), app_config.pool_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get claimed rewards"))?;
+    let (min_claim_amount, claim_cooldown_secs) = {
+        let runtime_config = runtime_config.read().await;
+        (runtime_config.min_claim_amount, runtime_config.claim_cooldown_secs as i64)
+    };
+    // same rule as post_claim, a miner without claims has no cooldown
+    let cooldown_ends_at = match app_database.get_last_claim(rewards.miner_id, app_config.pool_id).await {
+        Ok(last_claim) => {
+            let last_claim_ts = last_claim.created_at.and_utc().timestamp();
+            let now = chrono::Utc::now().timestamp();
+            if now - last_claim_ts <= claim_cooldown_secs {
+                Some(last_claim_ts + claim_cooldown_secs + 1)
+            } else {
+                None
             }
         }
+        Err(_) => None,
+    };
+    let claimable = if rewards.balance < min_claim_amount || cooldown_ends_at.is_some() {
+        0
     } else {
-        Err("Invalid public key".to_string())
-    }
+        rewards.balance
+    };
+    let pending_epoch_estimate =
+        estimate_epoch_reward(&user_pubkey, &epoch_hashes, &coal_config_cache, &runtime_config)
+            .await
+            .map(|estimate| TokenAmount::new(estimate.your_reward()));
+
+    Ok(Json(MinerRewardsResponse {
+        balance: TokenAmount::new(rewards.balance),
+        total_credited: TokenAmount::new(rewards.balance.saturating_add(total_claimed)),
+        total_claimed: TokenAmount::new(total_claimed),
+        claimable: TokenAmount::new(claimable),
+        min_claim_amount: TokenAmount::new(min_claim_amount),
+        cooldown_ends_at,
+        pending_epoch_estimate,
+    })
+    .into_response())
 }
 
-async fn get_miner_balance(
-    query_params: Query<PubkeyParam>,
-    Extension(rpc_client): Extension<Arc<RpcClient>>,
-) -> impl IntoResponse {
-    if let Ok(user_pubkey) = Pubkey::from_str(&query_params.pubkey) {
-        let miner_token_account = get_associated_token_address(&user_pubkey, &get_coal_mint());
-        if let Ok(response) = rpc_client
-            .get_token_account_balance(&miner_token_account)
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LastChallengeSubmissionsParams {
+    /// Defaults to 100, at most 1000
+    limit: Option<u32>,
+    offset: Option<u32>,
+    min_difficulty: Option<i16>,
+    /// Only return the number of submissions per difficulty
+    summary: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct LastChallengeSummaryResponse {
+    total: u64,
+    difficulties: Vec<DifficultyCount>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/last-challenge-submissions",
+    tag = "pool",
+    params(LastChallengeSubmissionsParams),
+    responses(
+        (status = 200, description = "A page of the submissions, the X-Total-Count header holds the number of matching submissions. With summary=true a LastChallengeSummaryResponse instead", body = Vec<SubmissionWithPubkey>,
+            headers(("X-Total-Count" = u64, description = "Submissions matching min_difficulty"))),
+        (status = 500, description = "Failed to get submissions", body = ApiError)
+    )
+)]
+async fn get_last_challenge_submissions(
+    query_params: Query<LastChallengeSubmissionsParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<axum::response::Response, ApiError> {
+    let db_error = |_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get submissions for the last challenge");
+    let min_difficulty = query_params.min_difficulty.unwrap_or(0);
+
+    if query_params.summary.unwrap_or(false) {
+        let difficulties: Vec<DifficultyCount> = app_rr_database
+            .get_last_challenge_difficulty_counts(app_config.pool_id)
             .await
-        {
-            return Response::builder()
-                .status(StatusCode::OK)
-                .body(response.ui_amount_string)
-                .unwrap();
-        } else {
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body("Failed to get token account balance".to_string())
-                .unwrap();
-        }
-    } else {
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Invalid public key".to_string())
-            .unwrap();
+            .map_err(db_error)?
+            .into_iter()
+            .filter(|d| d.difficulty >= min_difficulty)
+            .collect();
+        let total = difficulties.iter().map(|d| d.count as u64).sum();
+        return Ok(Json(LastChallengeSummaryResponse {
+            total,
+            difficulties,
+        })
+        .into_response());
     }
+
+    let limit = query_params.limit.unwrap_or(100).min(1000);
+    let offset = query_params.offset.unwrap_or(0);
+    let total = app_rr_database
+        .get_last_challenge_submission_count(app_config.pool_id, min_difficulty)
+        .await
+        .map_err(db_error)?;
+    let submissions = app_rr_database
+        .get_last_challenge_submissions(app_config.pool_id, min_difficulty, limit, offset)
+        .await
+        .map_err(db_error)?;
+    let submissions = display_submission_pubkeys(submissions);
+
+    Ok(([("X-Total-Count", total.to_string())], Json(submissions)).into_response())
 }
 
-async fn get_connected_miners(State(app_state): State<Arc<RwLock<AppState>>>) -> impl IntoResponse {
-    let len = app_state.read().await.sockets.len();
-    return Response::builder()
-        .status(StatusCode::OK)
-        .body(len.to_string())
-        .unwrap();
+/// Submissions with their pubkeys in the --log-pubkeys format, for
+/// responses anyone can query.
+fn display_submission_pubkeys(submissions: Vec<SubmissionWithPubkey>) -> Vec<SubmissionWithPubkey> {
+    submissions
+        .into_iter()
+        .map(|mut submission| {
+            submission.pubkey = display_pubkey(&submission.pubkey);
+            submission
+        })
+        .collect()
 }
 
-async fn get_timestamp() -> impl IntoResponse {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs();
-    return Response::builder()
-        .status(StatusCode::OK)
-        .body(now.to_string())
-        .unwrap();
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EpochReliabilityParams {
+    days: Option<u32>,
 }
 
-#[derive(Deserialize)]
-struct ClaimParams {
-    pubkey: String,
-    amount: u64,
+#[derive(Debug, Serialize, ToSchema)]
+struct EpochReliabilityStats {
+    success_count: u64,
+    failure_count: u64,
+    success_rate_pct: f64,
+    avg_attempts_to_success: f64,
+    avg_final_priority_fee: f64,
 }
 
-async fn post_claim(
-    query_params: Query<ClaimParams>,
-    Extension(app_database): Extension<Arc<AppDatabase>>,
-    Extension(rpc_client): Extension<Arc<RpcClient>>,
-    Extension(wallet): Extension<Arc<Keypair>>,
-) -> impl IntoResponse {
-    if let Ok(user_pubkey) = Pubkey::from_str(&query_params.pubkey) {
-        let amount = query_params.amount;
-        if let Ok(miner_rewards) = app_database
-            .get_miner_rewards(user_pubkey.to_string())
-            .await
-        {
-            if amount > miner_rewards.balance {
-                return Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body("claim amount exceeds miner rewards balance".to_string())
-                    .unwrap();
-            }
+impl EpochReliabilityStats {
+    fn new(
+        success_count: u64,
+        failure_count: u64,
+        success_attempts: u64,
+        total_final_priority_fee: u64,
+    ) -> Self {
+        let total = success_count + failure_count;
+        let (success_rate_pct, avg_final_priority_fee) = if total > 0 {
+            (
+                success_count as f64 / total as f64 * 100.0,
+                total_final_priority_fee as f64 / total as f64,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let avg_attempts_to_success = if success_count > 0 {
+            success_attempts as f64 / success_count as f64
+        } else {
+            0.0
+        };
 
-            if let Ok(last_claim) = app_database.get_last_claim(miner_rewards.miner_id).await {
-                let last_claim_ts = last_claim.created_at.and_utc().timestamp();
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_secs() as i64;
-                let time_difference = now - last_claim_ts;
-                if time_difference  <= 1800 {
-                    return Response::builder()
-                        .status(StatusCode::TOO_MANY_REQUESTS)
-                        .body(time_difference.to_string())
-                        .unwrap();
-                }
-            }
+        EpochReliabilityStats {
+            success_count,
+            failure_count,
+            success_rate_pct,
+            avg_attempts_to_success,
+            avg_final_priority_fee,
+        }
+    }
+}
 
-            let coal_mint = get_coal_mint();
-            let miner_token_account = get_associated_token_address(&user_pubkey, &coal_mint);
+#[derive(Debug, Serialize, ToSchema)]
+struct EpochReliabilityDay {
+    day: chrono::NaiveDate,
+    #[serde(flatten)]
+    stats: EpochReliabilityStats,
+}
 
-            let prio_fee: u32 = 20_000;
+#[derive(Debug, Serialize, ToSchema)]
+struct EpochReliabilityResponse {
+    days: u32,
+    #[serde(flatten)]
+    stats: EpochReliabilityStats,
+    daily: Vec<EpochReliabilityDay>,
+}
 
-            let mut ixs = Vec::new();
-            let prio_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(prio_fee as u64);
-            ixs.push(prio_fee_ix);
-            if let Ok(response) = rpc_client
-                .get_token_account_balance(&miner_token_account)
-                .await
-            {
-                if let Some(_amount) = response.ui_amount {
-                    info!("miner has valid token account.");
-                } else {
-                    info!("will create token account for miner");
-                    ixs.push(
-                        spl_associated_token_account::instruction::create_associated_token_account(
-                            &wallet.pubkey(),
-                            &user_pubkey,
-                            &coal_api::consts::MINT_ADDRESS,
-                            &spl_token::id(),
-                        ),
-                    )
-                }
-            } else {
-                info!("Adding create ata ix for miner claim");
-                ixs.push(
-                    spl_associated_token_account::instruction::create_associated_token_account(
-                        &wallet.pubkey(),
-                        &user_pubkey,
-                        &coal_api::consts::MINT_ADDRESS,
-                        &spl_token::id(),
+#[utoipa::path(
+    get,
+    path = "/pool/epoch-reliability",
+    tag = "pool",
+    params(EpochReliabilityParams),
+    responses(
+        (status = 200, body = EpochReliabilityResponse),
+        (status = 500, description = "Failed to get epoch reliability", body = ApiError)
+    )
+)]
+async fn get_pool_epoch_reliability(
+    query_params: Query<EpochReliabilityParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<EpochReliabilityResponse>, ApiError> {
+    let days = query_params.days.unwrap_or(7).clamp(1, 90);
+    let res = app_rr_database
+        .get_epoch_outcomes_by_day(app_config.pool_id, days)
+        .await;
+
+    match res {
+        Ok(rows) => {
+            let mut success_count = 0;
+            let mut failure_count = 0;
+            let mut success_attempts = 0;
+            let mut total_final_priority_fee = 0;
+            let mut daily = Vec::with_capacity(rows.len());
+            for row in rows {
+                success_count += row.success_count;
+                failure_count += row.failure_count;
+                success_attempts += row.success_attempts;
+                total_final_priority_fee += row.total_final_priority_fee;
+                daily.push(EpochReliabilityDay {
+                    day: row.day,
+                    stats: EpochReliabilityStats::new(
+                        row.success_count,
+                        row.failure_count,
+                        row.success_attempts,
+                        row.total_final_priority_fee,
                     ),
-                )
+                });
             }
 
-            let ix = coal_api::instruction::claim(wallet.pubkey(), miner_token_account, amount);
-            ixs.push(ix);
-
-            if let Ok((hash, _slot)) = rpc_client
-                .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                .await
-            {
-                let mut tx = Transaction::new_with_payer(&ixs, Some(&wallet.pubkey()));
+            Ok(Json(EpochReliabilityResponse {
+                days,
+                stats: EpochReliabilityStats::new(
+                    success_count,
+                    failure_count,
+                    success_attempts,
+                    total_final_priority_fee,
+                ),
+                daily,
+            }))
+        }
+        Err(_) => {
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get epoch reliability"))
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MinerActivityParams {
+    days: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/miner-activity",
+    tag = "pool",
+    params(MinerActivityParams),
+    responses(
+        (status = 200, body = Vec<ActivityBucket>),
+        (status = 500, description = "Failed to get miner activity", body = ApiError)
+    )
+)]
+async fn get_pool_miner_activity(
+    query_params: Query<MinerActivityParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(activity_cache): Extension<Arc<RwLock<MinerActivityCache>>>,
+) -> Result<Json<Vec<ActivityBucket>>, ApiError> {
+    let days = query_params.days.unwrap_or(7).clamp(1, 90);
+
+    if let Some((cached_at, buckets)) = activity_cache.read().await.entries.get(&days) {
+        if cached_at.elapsed() < ACTIVITY_CACHE_TTL {
+            return Ok(Json(buckets.clone()));
+        }
+    }
+
+    let res = app_rr_database
+        .get_miner_activity_heatmap(app_config.pool_id, days)
+        .await;
+
+    match res {
+        Ok(buckets) => {
+            activity_cache
+                .write()
+                .await
+                .entries
+                .insert(days, (Instant::now(), buckets.clone()));
+            Ok(Json(buckets))
+        }
+        Err(_) => {
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get miner activity"))
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EpochHistoryParams {
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/epoch/history",
+    tag = "pool",
+    params(EpochHistoryParams),
+    responses(
+        (status = 200, body = Vec<EpochHistoryEntry>),
+        (status = 500, description = "Failed to get epoch history", body = ApiError)
+    )
+)]
+async fn get_pool_epoch_history(
+    query_params: Query<EpochHistoryParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<Vec<EpochHistoryEntry>>, ApiError> {
+    let limit = query_params.limit.unwrap_or(20).min(100);
+    let res = app_rr_database
+        .get_epoch_history(app_config.pool_id, limit)
+        .await;
+
+    match res {
+        Ok(history) => {
+            Ok(Json(history))
+        }
+        Err(_) => {
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get epoch history"))
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ChallengeSubmissionsParams {
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ChallengeResponse {
+    id: i32,
+    // base64 encoded challenge bytes
+    challenge: String,
+    rewards_earned: Option<u64>,
+    started_at: chrono::NaiveDateTime,
+    ended_at: Option<chrono::NaiveDateTime>,
+    limit: u32,
+    offset: u32,
+    submissions: Vec<SubmissionWithPubkey>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CurrentProofResponse {
+    // address of the pool's proof account
+    address: String,
+    // base64 encoded account data, as received by the server
+    data: String,
+    // slot of the notification the data came with
+    slot: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/challenge/current/proof",
+    tag = "pool",
+    responses(
+        (status = 200, description = "The pool proof account as last observed by the server, to check the challenge of work assignments against", body = CurrentProofResponse),
+        (status = 404, description = "No proof notification received since the start or the last wallet swap", body = ApiError)
+    )
+)]
+async fn get_current_challenge_proof(
+    Extension(wallet): Extension<PoolWallet>,
+    Extension(proof_updates): Extension<Arc<RwLock<ProofUpdates>>>,
+) -> Result<Json<CurrentProofResponse>, ApiError> {
+    let proof_updates = proof_updates.read().await;
+    let (data, slot) = proof_updates.account_data().ok_or_else(|| {
+        ApiError::new(ApiErrorCode::NotFound, "The proof hasn't been observed yet, retry shortly")
+    })?;
+
+    Ok(Json(CurrentProofResponse {
+        address: proof_pubkey(wallet.pubkey()).to_string(),
+        data: BASE64_STANDARD.encode(data),
+        slot,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/challenge/{id}",
+    tag = "pool",
+    params(
+        ("id" = i32, Path, description = "Challenge id"),
+        ChallengeSubmissionsParams
+    ),
+    responses(
+        (status = 200, body = ChallengeResponse),
+        (status = 404, description = "No challenge with this id in the pool", body = ApiError),
+        (status = 500, description = "Failed to get challenge", body = ApiError)
+    )
+)]
+async fn get_challenge(
+    axum::extract::Path(id): axum::extract::Path<String>,
+    query_params: Query<ChallengeSubmissionsParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<ChallengeResponse>, ApiError> {
+    let not_found = || ApiError::new(ApiErrorCode::NotFound, "Challenge not found");
+    let challenge_id = id.parse::<i32>().map_err(|_| not_found())?;
+    let limit = query_params.limit.unwrap_or(100).min(1000);
+    let offset = query_params.offset.unwrap_or(0);
+
+    let challenge = app_rr_database
+        .get_challenge_by_id(app_config.pool_id, challenge_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge"))?
+        .ok_or_else(not_found)?;
+    let submissions = app_rr_database
+        .get_challenge_submissions(challenge.id, limit, offset)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge submissions"))?;
+    let submissions = display_submission_pubkeys(submissions);
+
+    Ok(Json(ChallengeResponse {
+        id: challenge.id,
+        challenge: BASE64_STANDARD.encode(&challenge.challenge),
+        rewards_earned: challenge.rewards_earned,
+        started_at: challenge.started_at,
+        ended_at: challenge.ended_at,
+        limit,
+        offset,
+        submissions,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ChallengesParams {
+    /// Defaults to 20, at most 100
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ChallengeListEntry {
+    #[serde(flatten)]
+    detail: ChallengeDetail,
+    rewards_earned_ui: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/challenges",
+    tag = "pool",
+    params(ChallengesParams),
+    responses(
+        (status = 200, description = "Most recent challenges first", body = Vec<ChallengeListEntry>),
+        (status = 500, description = "Failed to get challenges", body = ApiError)
+    )
+)]
+async fn get_pool_challenges(
+    query_params: Query<ChallengesParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<Vec<ChallengeListEntry>>, ApiError> {
+    let limit = query_params.limit.unwrap_or(20).min(100);
+    let offset = query_params.offset.unwrap_or(0);
+
+    let challenges = app_rr_database
+        .get_challenges_paginated(app_config.pool_id, limit, offset)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenges"))?;
+
+    Ok(Json(
+        challenges
+            .into_iter()
+            .map(|detail| ChallengeListEntry {
+                rewards_earned_ui: detail.rewards_earned.map(amount_to_ui_string),
+                detail,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PoolChallengeResponse {
+    challenge_id: i32,
+    // hex encoded challenge bytes
+    challenge_hex: String,
+    started_at: chrono::NaiveDateTime,
+    ended_at: Option<chrono::NaiveDateTime>,
+    rewards_earned: Option<u64>,
+    rewards_earned_ui: Option<String>,
+    // pubkeys of the miners that submitted for the challenge
+    miners: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/challenges/{id}",
+    tag = "pool",
+    params(("id" = i32, Path, description = "Challenge id")),
+    responses(
+        (status = 200, body = PoolChallengeResponse),
+        (status = 404, description = "No challenge with this id in the pool", body = ApiError),
+        (status = 500, description = "Failed to get challenge", body = ApiError)
+    )
+)]
+async fn get_pool_challenge(
+    axum::extract::Path(id): axum::extract::Path<String>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<PoolChallengeResponse>, ApiError> {
+    let not_found = || ApiError::new(ApiErrorCode::NotFound, "Challenge not found");
+    let challenge_id = id.parse::<i32>().map_err(|_| not_found())?;
+
+    let challenge = app_rr_database
+        .get_challenge_by_id(app_config.pool_id, challenge_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge"))?
+        .ok_or_else(not_found)?;
+    let miners = app_rr_database
+        .get_challenge_miner_pubkeys(challenge.id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge miners"))?;
+
+    Ok(Json(pool_challenge_response(challenge, miners)))
+}
+
+/// The challenge with its miners in the --log-pubkeys format.
+fn pool_challenge_response(challenge: models::Challenge, miners: Vec<String>) -> PoolChallengeResponse {
+    PoolChallengeResponse {
+        challenge_id: challenge.id,
+        challenge_hex: challenge.challenge.iter().map(|b| format!("{:02x}", b)).collect(),
+        started_at: challenge.started_at,
+        ended_at: challenge.ended_at,
+        rewards_earned: challenge.rewards_earned,
+        rewards_earned_ui: challenge.rewards_earned.map(amount_to_ui_string),
+        miners: miners.iter().map(display_pubkey).collect(),
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerDistribution {
+    pubkey: String,
+    amount: u64,
+    hashpower: Option<u64>,
+    // hashpower / total_hashpower
+    share: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ChallengeDistributionResponse {
+    id: i32,
+    // reward of the on-chain mine transaction
+    rewards_earned: Option<u64>,
+    txn_signature: Option<String>,
+    commission: Option<u64>,
+    total_hashpower: Option<u64>,
+    distributed: u64,
+    // rewards_earned - commission - distributed, from rounding and miners
+    // that disconnected before the rewards were split
+    undistributed: Option<i64>,
+    miners: Vec<MinerDistribution>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/challenge/{id}/distribution",
+    tag = "pool",
+    params(("id" = i32, Path, description = "Challenge id")),
+    responses(
+        (status = 200, body = ChallengeDistributionResponse),
+        (status = 404, description = "No challenge with this id in the pool", body = ApiError),
+        (status = 500, description = "Failed to get the distribution", body = ApiError)
+    )
+)]
+async fn get_challenge_distribution(
+    axum::extract::Path(id): axum::extract::Path<String>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<ChallengeDistributionResponse>, ApiError> {
+    let not_found = || ApiError::new(ApiErrorCode::NotFound, "Challenge not found");
+    let challenge_id = id.parse::<i32>().map_err(|_| not_found())?;
+
+    let challenge = app_rr_database
+        .get_challenge_distribution(app_config.pool_id, challenge_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge"))?
+        .ok_or_else(not_found)?;
+    let earnings = app_rr_database
+        .get_challenge_earnings(challenge.id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge earnings"))?;
+
+    let distributed: u64 = earnings.iter().map(|e| e.amount).sum();
+    let undistributed = match (challenge.rewards_earned, challenge.commission) {
+        (Some(rewards), Some(commission)) => {
+            Some(rewards as i64 - commission as i64 - distributed as i64)
+        }
+        _ => None,
+    };
+    let miners = earnings
+        .into_iter()
+        .map(|e| MinerDistribution {
+            share: match (e.hashpower, challenge.total_hashpower) {
+                (Some(hashpower), Some(total)) if total > 0 => {
+                    Some(hashpower as f64 / total as f64)
+                }
+                _ => None,
+            },
+            pubkey: display_pubkey(&e.pubkey),
+            amount: e.amount,
+            hashpower: e.hashpower,
+        })
+        .collect();
+
+    Ok(Json(ChallengeDistributionResponse {
+        id: challenge.id,
+        rewards_earned: challenge.rewards_earned,
+        txn_signature: challenge.txn_signature,
+        commission: challenge.commission,
+        total_hashpower: challenge.total_hashpower,
+        distributed,
+        undistributed,
+        miners,
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ChallengeHistogramResponse {
+    challenge_id: i32,
+    submissions: u32,
+    histogram: DifficultyHistogram,
+}
+
+#[utoipa::path(
+    get,
+    path = "/challenge/{id}/histogram",
+    tag = "pool",
+    params(("id" = i32, Path, description = "Challenge id")),
+    responses(
+        (status = 200, body = ChallengeHistogramResponse),
+        (status = 404, description = "No histogram recorded for this challenge in the pool", body = ApiError),
+        (status = 500, description = "Failed to get the histogram", body = ApiError)
+    )
+)]
+async fn get_challenge_histogram(
+    axum::extract::Path(id): axum::extract::Path<String>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<ChallengeHistogramResponse>, ApiError> {
+    let not_found = || ApiError::new(ApiErrorCode::NotFound, "Challenge histogram not found");
+    let challenge_id = id.parse::<i32>().map_err(|_| not_found())?;
+
+    let epoch = app_rr_database
+        .get_epoch_histogram(app_config.pool_id, challenge_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge histogram"))?
+        .ok_or_else(not_found)?;
+    let histogram: DifficultyHistogram = serde_json::from_str(&epoch.difficulty_histogram)
+        .map_err(|e| {
+            error!("Invalid difficulty histogram of challenge {}: {:?}", challenge_id, e);
+            ApiError::new(ApiErrorCode::InternalError, "Failed to read challenge histogram")
+        })?;
+
+    Ok(Json(ChallengeHistogramResponse {
+        challenge_id: epoch.challenge_id,
+        submissions: histogram.submissions(),
+        histogram,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RedistributeParams {
+    /// Only return the report, defaults to false
+    dry_run: Option<bool>,
+    /// Defaults to missing
+    credit: Option<RedistributeCredit>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/challenge/{id}/redistribute",
+    tag = "admin",
+    security(("admin_password" = [])),
+    params(
+        ("id" = i32, Path, description = "Challenge id"),
+        RedistributeParams
+    ),
+    responses(
+        (status = 200, description = "What was, or with dry_run would be, inserted and credited", body = RedistributionReport),
+        (status = 400, description = "The challenge has no recorded reward, or its earnings exceed it", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 404, description = "No challenge with this id in the pool", body = ApiError),
+        (status = 409, description = "The challenge's earnings changed meanwhile, or credit=all for a challenge already redistributed, nothing was applied", body = ApiError),
+        (status = 500, description = "Failed to read or apply the distribution", body = ApiError)
+    )
+)]
+async fn post_admin_challenge_redistribute(
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    query_params: Query<RedistributeParams>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    Extension(reward_cache): Extension<RewardCache>,
+) -> Result<Json<RedistributionReport>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+    let not_found = || ApiError::new(ApiErrorCode::NotFound, "Challenge not found");
+    let challenge_id = id.parse::<i32>().map_err(|_| not_found())?;
+    let dry_run = query_params.dry_run.unwrap_or(false);
+    let credit = query_params.credit.unwrap_or_default();
+
+    let challenge = app_database
+        .get_challenge_distribution(app_config.pool_id, challenge_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge"))?
+        .ok_or_else(not_found)?;
+    let rewards_earned = challenge.rewards_earned.ok_or_else(|| {
+        ApiError::new(ApiErrorCode::InvalidRequest, "The challenge has no recorded reward yet")
+    })?;
+    // credit=all re-credits recorded earnings, it must never run twice
+    if let (RedistributeCredit::All, Some(redistributed_at)) = (credit, challenge.redistributed_at) {
+        return Err(ApiError::new(
+            ApiErrorCode::EarningsConflict,
+            format!("The challenge was already redistributed at {}", redistributed_at),
+        ));
+    }
+    let shares = app_database
+        .get_challenge_miner_shares(app_config.pool_id, challenge_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get challenge earnings"))?;
+
+    let fallback_commission =
+        rewards_earned - runtime_config.read().await.distributable_rewards(rewards_earned);
+    let plan = redistribution::plan_redistribution(
+        app_config.pool_id,
+        &challenge,
+        rewards_earned,
+        fallback_commission,
+        shares,
+        credit,
+    )
+    .map_err(|e| match e {
+        RewardError::ExceedsRewards => ApiError::new(
+            ApiErrorCode::InvalidRequest,
+            "The challenge's earnings would exceed its distributable rewards",
+        ),
+        RewardError::RewardOverflow => {
+            ApiError::new(ApiErrorCode::InternalError, "Redistributed rewards overflowed")
+        }
+    })?;
+
+    let mut report = plan.report;
+    report.dry_run = dry_run;
+    if dry_run || (plan.earnings.is_empty() && plan.credits.is_empty()) {
+        return Ok(Json(report));
+    }
+
+    match app_database
+        .apply_challenge_redistribution(
+            app_config.pool_id,
+            challenge_id,
+            plan.earnings,
+            plan.credits,
+            credit == RedistributeCredit::All,
+        )
+        .await
+    {
+        Ok(()) => {}
+        Err(AppDatabaseError::DuplicateEntry) => {
+            return Err(ApiError::new(
+                ApiErrorCode::EarningsConflict,
+                "Earnings of the challenge changed or it was redistributed meanwhile, nothing was applied",
+            ));
+        }
+        Err(_) => {
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to apply the redistribution"));
+        }
+    }
+    reward_cache.clear().await;
+    info!(
+        "Redistributed challenge {}: inserted {} in earnings, credited {} to balances",
+        challenge_id, report.earnings_inserted, report.balances_credited
+    );
+    report.applied = true;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RewardDistributionParams {
+    challenge_id: i32,
+    /// Defaults to 50, at most 500
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct RewardDistributionEntry {
+    #[serde(flatten)]
+    entry: models::EpochDistributionEntry,
+    earned_coal: f64,
+    // share of everything distributed in the epoch
+    percentage_of_pool: f64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/reward-distribution-history",
+    tag = "pool",
+    params(RewardDistributionParams),
+    responses(
+        (status = 200, description = "Payouts of the epoch, largest first", body = Vec<RewardDistributionEntry>,
+            headers(
+                ("X-Total-Distributed" = u64, description = "Grains distributed to all miners in the epoch"),
+                ("X-Participant-Count" = u64, description = "Miners paid in the epoch")
+            )),
+        (status = 500, description = "Failed to get the distribution", body = ApiError)
+    )
+)]
+async fn get_pool_reward_distribution_history(
+    query_params: Query<RewardDistributionParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<axum::response::Response, ApiError> {
+    let limit = query_params.limit.unwrap_or(50).min(500);
+    let db_error = |_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get the distribution");
+
+    let totals = app_rr_database
+        .get_epoch_distribution_totals(app_config.pool_id, query_params.challenge_id)
+        .await
+        .map_err(db_error)?;
+    let entries = app_rr_database
+        .get_epoch_reward_distribution(app_config.pool_id, query_params.challenge_id, limit)
+        .await
+        .map_err(db_error)?;
+
+    let entries: Vec<RewardDistributionEntry> = entries
+        .into_iter()
+        .map(|mut entry| RewardDistributionEntry {
+            earned_coal: amount_to_coal(entry.earned),
+            percentage_of_pool: if totals.total_distributed > 0 {
+                entry.earned as f64 / totals.total_distributed as f64 * 100.0
+            } else {
+                0.0
+            },
+            entry: {
+                entry.pubkey = display_pubkey(&entry.pubkey);
+                entry
+            },
+        })
+        .collect();
+
+    Ok((
+        [
+            ("X-Total-Distributed", totals.total_distributed.to_string()),
+            ("X-Participant-Count", totals.participant_count.to_string()),
+        ],
+        Json(entries),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/submissions",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, body = Vec<Submission>),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 500, description = "Failed to get submissions", body = ApiError)
+    )
+)]
+async fn get_miner_submissions(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<Vec<Submission>>, ApiError> {
+    let res = app_rr_database
+        .get_miner_submissions(user_pubkey.to_string(), app_config.pool_id)
+        .await;
+
+    match res {
+        Ok(submissions) => {
+            Ok(Json(submissions))
+        }
+        Err(_) => {
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get submissions for miner"))
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MinerClaimsParams {
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ClaimResponse {
+    #[serde(flatten)]
+    record: ClaimRecord,
+    amount_coal_ui: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MinerClaimsResponse {
+    total_claimed_coal: u64,
+    total_claimed_coal_ui: String,
+    claims: Vec<ClaimResponse>,
+    // the miner claimed recently and the read replica may not have it yet
+    may_be_stale: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/claims",
+    tag = "miner",
+    params(PubkeyParam, MinerClaimsParams),
+    responses(
+        (status = 200, body = MinerClaimsResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 500, description = "Failed to get claims", body = ApiError)
+    )
+)]
+async fn get_miner_claims(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    query_params: Query<MinerClaimsParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(claims_cache): Extension<Arc<RwLock<MinerClaimsCache>>>,
+    Extension(replica_lag): Extension<Arc<RwLock<ReplicaLag>>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<MinerClaimsResponse>, ApiError> {
+    let user_pubkey = user_pubkey.to_string();
+    let limit = query_params.limit.unwrap_or(20).min(100);
+    let offset = query_params.offset.unwrap_or(0);
+    let cache_key = (user_pubkey.clone(), limit, offset);
+    let may_be_stale = replica_lag.read().await.may_be_stale(&user_pubkey);
+
+    if !may_be_stale {
+        if let Some((cached_at, response)) = claims_cache.read().await.entries.get(&cache_key) {
+            if cached_at.elapsed() < CLAIMS_CACHE_TTL {
+                return Ok(Json(response.clone()));
+            }
+        }
+    }
+
+    let claims = app_rr_database
+        .get_miner_claims(user_pubkey.clone(), app_config.pool_id, limit, offset)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get claims for miner"))?;
+    let total_claimed_coal = app_rr_database
+        .get_miner_total_claimed(user_pubkey, app_config.pool_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get claims for miner"))?;
+
+    let response = MinerClaimsResponse {
+        total_claimed_coal,
+        total_claimed_coal_ui: amount_to_ui_string(total_claimed_coal),
+        claims: claims
+            .into_iter()
+            .map(|record| ClaimResponse {
+                amount_coal_ui: amount_to_ui_string(record.amount_coal),
+                record,
+            })
+            .collect(),
+        may_be_stale,
+    };
+    if may_be_stale {
+        return Ok(Json(response));
+    }
+
+    let mut cache = claims_cache.write().await;
+    cache
+        .entries
+        .retain(|_, (cached_at, _)| cached_at.elapsed() < CLAIMS_CACHE_TTL);
+    cache
+        .entries
+        .insert(cache_key, (Instant::now(), response.clone()));
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PoolClaimsParams {
+    since: Option<i64>,
+    until: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PoolClaimsResponse {
+    since: i64,
+    until: i64,
+    #[serde(flatten)]
+    stats: PoolClaimStats,
+    total_amount_ui: String,
+    largest_amount_ui: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/claims",
+    tag = "pool",
+    params(PoolClaimsParams),
+    responses(
+        (status = 200, body = PoolClaimsResponse),
+        (status = 400, description = "since is not before until", body = ApiError),
+        (status = 500, description = "Failed to get claims", body = ApiError)
+    )
+)]
+async fn get_pool_claims(
+    query_params: Query<PoolClaimsParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<PoolClaimsResponse>, ApiError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64;
+    // Defaults to the last 24 hours.
+    let until = query_params.until.unwrap_or(now);
+    let since = query_params.since.unwrap_or(until - 86_400);
+    if since >= until {
+        return Err(ApiError::new(ApiErrorCode::InvalidRequest, "since must be before until"));
+    }
+
+    let res = app_rr_database
+        .get_pool_claim_stats(app_config.pool_id, since, until)
+        .await;
+
+    match res {
+        Ok(stats) => {
+            Ok(Json(PoolClaimsResponse {
+                since,
+                until,
+                total_amount_ui: amount_to_ui_string(stats.total_amount),
+                largest_amount_ui: amount_to_ui_string(stats.largest_amount),
+                stats,
+            }))
+        }
+        Err(_) => {
+            Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get pool claims"))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PoolTotalsResponse {
+    // lifetime rewards earned by the pool
+    total_rewards: u64,
+    total_rewards_ui: String,
+    // lifetime rewards claimed by miners
+    claimed_rewards: u64,
+    claimed_rewards_ui: String,
+    // sum of the miners' current reward balances
+    outstanding_rewards: u64,
+    outstanding_rewards_ui: String,
+    // total_rewards - claimed_rewards, should roughly match the unclaimed on-chain balance
+    unclaimed_rewards: i64,
+    unclaimed_rewards_ui: String,
+    // None when the rpc request failed
+    proof_balance: Option<u64>,
+    proof_balance_ui: Option<String>,
+    pool_token_balance: Option<u64>,
+    pool_token_balance_ui: Option<String>,
+    // true when the pool can't pay out every miner, None if a balance is unknown
+    liabilities_exceed_balance: Option<bool>,
+}
+
+/// Amount held by a token account, 0 when it wasn't created yet and None
+/// when it isn't a token account.
+fn token_account_amount(account: Option<&solana_sdk::account::Account>) -> Option<u64> {
+    match account {
+        Some(account) => spl_token::state::Account::unpack(&account.data)
+            .ok()
+            .map(|token_account| token_account.amount),
+        None => Some(0),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/totals",
+    tag = "pool",
+    responses(
+        (status = 200, body = PoolTotalsResponse),
+        (status = 500, description = "Failed to get pool totals", body = ApiError)
+    )
+)]
+async fn get_pool_totals(
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(rpc_client): Extension<Arc<RpcClient>>,
+    Extension(wallet): Extension<PoolWallet>,
+) -> Result<Json<PoolTotalsResponse>, ApiError> {
+    let totals = app_rr_database
+        .get_pool_totals(app_config.pool_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get pool totals"))?;
+
+    let proof_balance = match get_proof(&rpc_client, wallet.pubkey()).await {
+        Ok(proof) => Some(proof.balance),
+        Err(_) => {
+            error!("Failed to get pool proof balance");
+            None
+        }
+    };
+    let pool_token_account = get_associated_token_address(&wallet.pubkey(), &get_coal_mint());
+    let pool_token_balance = match rpc_client
+        .get_account_with_commitment(&pool_token_account, rpc_client.commitment())
+        .await
+    {
+        Ok(response) => token_account_amount(response.value.as_ref()),
+        Err(_) => {
+            error!("Failed to get pool token account balance");
+            None
+        }
+    };
+
+    let liabilities_exceed_balance = match (proof_balance, pool_token_balance) {
+        (Some(proof_balance), Some(token_balance)) => {
+            let available = proof_balance.saturating_add(token_balance);
+            let insolvent = totals.outstanding_rewards > available;
+            if insolvent {
+                error!(
+                    "Pool liabilities of {} exceed the available balance of {}",
+                    totals.outstanding_rewards, available
+                );
+            }
+            Some(insolvent)
+        }
+        _ => None,
+    };
+
+    let unclaimed_rewards = totals.total_rewards as i64 - totals.claimed_rewards as i64;
+    let unclaimed_rewards_ui = if unclaimed_rewards < 0 {
+        format!("-{}", amount_to_ui_string(unclaimed_rewards.unsigned_abs()))
+    } else {
+        amount_to_ui_string(unclaimed_rewards as u64)
+    };
+
+    Ok(Json(PoolTotalsResponse {
+        total_rewards: totals.total_rewards,
+        total_rewards_ui: amount_to_ui_string(totals.total_rewards),
+        claimed_rewards: totals.claimed_rewards,
+        claimed_rewards_ui: amount_to_ui_string(totals.claimed_rewards),
+        outstanding_rewards: totals.outstanding_rewards,
+        outstanding_rewards_ui: amount_to_ui_string(totals.outstanding_rewards),
+        unclaimed_rewards,
+        unclaimed_rewards_ui,
+        proof_balance,
+        proof_balance_ui: proof_balance.map(amount_to_ui_string),
+        pool_token_balance,
+        pool_token_balance_ui: pool_token_balance.map(amount_to_ui_string),
+        liabilities_exceed_balance,
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PoolFeesPaidResponse {
+    // only txns sent since fees are recorded count towards the totals
+    total_fees_sol: f64,
+    mine_fees_sol: f64,
+    claim_fees_sol: f64,
+    avg_mine_fee_sol: f64,
+    lifetime_txn_count: u32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/fees-paid",
+    tag = "pool",
+    responses(
+        (status = 200, body = PoolFeesPaidResponse),
+        (status = 500, description = "Failed to get fees paid", body = ApiError)
+    )
+)]
+async fn get_pool_fees_paid(
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<PoolFeesPaidResponse>, ApiError> {
+    let db_error = |_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get fees paid");
+    let total = app_database
+        .get_total_fees_paid(app_config.pool_id, None)
+        .await
+        .map_err(db_error)?;
+    let mine = app_database
+        .get_total_fees_paid(app_config.pool_id, Some("mine".to_string()))
+        .await
+        .map_err(db_error)?;
+    let claim = app_database
+        .get_total_fees_paid(app_config.pool_id, Some("claim".to_string()))
+        .await
+        .map_err(db_error)?;
+
+    let avg_mine_fee_sol = if mine.txns_with_fee > 0 {
+        lamports_to_sol(mine.fees_paid) / mine.txns_with_fee as f64
+    } else {
+        0.0
+    };
+
+    Ok(Json(PoolFeesPaidResponse {
+        total_fees_sol: lamports_to_sol(total.fees_paid),
+        mine_fees_sol: lamports_to_sol(mine.fees_paid),
+        claim_fees_sol: lamports_to_sol(claim.fees_paid),
+        avg_mine_fee_sol,
+        lifetime_txn_count: total.txn_count.min(u32::MAX as u64) as u32,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TotalHashpowerParams {
+    since: Option<i64>,
+}
+
+fn total_hashpower_response(res: Result<u64, AppDatabaseError>) -> Result<Response<String>, ApiError> {
+    match res {
+        Ok(total) => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("X-Hashpower-Formula", HASHPOWER_FORMULA)
+            .body(total.to_string())
+            .unwrap()),
+        Err(_) => Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get total hashpower")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/total-hashpower-contributed",
+    tag = "miner",
+    params(PubkeyParam, TotalHashpowerParams),
+    responses(
+        (status = 200, description = "Total hashpower, the formula is in the X-Hashpower-Formula header", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 500, description = "Failed to get total hashpower", body = ApiError)
+    )
+)]
+async fn get_miner_total_hashpower(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    query_params: Query<TotalHashpowerParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Response<String>, ApiError> {
+    let res = app_rr_database
+        .get_miner_total_hashpower(user_pubkey.to_string(), app_config.pool_id, query_params.since)
+        .await;
+    total_hashpower_response(res)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerReferralsResponse {
+    // pubkeys of the miners that signed up with this one as referrer, oldest first
+    referred_miners: Vec<String>,
+    // referral bonuses credited across pools
+    total_bonus_earned_coal: f64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/referrals",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, body = MinerReferralsResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 500, description = "Failed to get the referrals", body = ApiError)
+    )
+)]
+async fn get_miner_referrals(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+) -> Result<Json<MinerReferralsResponse>, ApiError> {
+    let referrals = app_rr_database
+        .get_miner_referrals(user_pubkey.to_string())
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get miner referrals"))?;
+
+    let total_bonus_earned = referrals
+        .iter()
+        .fold(0u64, |total, r| total.saturating_add(r.bonus_earned));
+    Ok(Json(MinerReferralsResponse {
+        referred_miners: referrals.into_iter().map(|r| r.pubkey).collect(),
+        total_bonus_earned_coal: amount_to_coal(total_bonus_earned),
+    }))
+}
+
+// Fewer submitting miners than this make the reward estimate unreliable.
+const ESTIMATE_MIN_MINERS: usize = 3;
+// Seconds before the cutoff from which the estimate is unlikely to move much.
+const ESTIMATE_MEDIUM_CONFIDENCE_SECS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum EstimateConfidence {
+    // few miners submitted, or the epoch is far from its cutoff
+    Low,
+    // the cutoff is near
+    Medium,
+    // past the cutoff, the best solution is being submitted
+    High,
+}
+
+/// The epoch in progress as seen by one miner, what the reward estimates are based on.
+struct EpochRewardEstimate {
+    // summed over the miner's devices
+    your_hashpower: u64,
+    total_hashpower: u64,
+    submitting_miners: usize,
+    has_solution: bool,
+    // pool rewards of the best solution so far, after commission
+    distributable_rewards: u64,
+}
+
+impl EpochRewardEstimate {
+    /// The miner's share of the distributable rewards, as the distribution would credit it.
+    fn your_reward(&self) -> u64 {
+        calculate_earned_rewards(self.your_hashpower, self.total_hashpower, self.distributable_rewards)
+            .unwrap_or(0)
+    }
+}
+
+/// None until the bus rewards are loaded.
+async fn estimate_epoch_reward(
+    user_pubkey: &Pubkey,
+    epoch_hashes: &RwLock<EpochHashes>,
+    coal_config_cache: &RwLock<Option<CoalConfigSnapshot>>,
+    runtime_config: &RwLock<RuntimeConfig>,
+) -> Option<EpochRewardEstimate> {
+    let (coal_config, best_bus_rewards) = match coal_config_cache.read().await.as_ref() {
+        Some(snapshot) => (
+            snapshot.config,
+            snapshot
+                .busses
+                .iter()
+                .filter_map(|bus| bus.as_ref().ok())
+                .map(|bus| bus.rewards)
+                .max()
+                .unwrap_or(0),
+        ),
+        None => return None,
+    };
+
+    let reader = epoch_hashes.read().await;
+    let mut your_hashpower = 0u64;
+    let mut total_hashpower = 0u64;
+    let mut submitting_miners = HashSet::new();
+    for ((pubkey, _), (_, _, hashpower)) in reader.submissions.iter() {
+        if pubkey == user_pubkey {
+            your_hashpower = your_hashpower.saturating_add(*hashpower);
+        }
+        total_hashpower = total_hashpower.saturating_add(*hashpower);
+        submitting_miners.insert(*pubkey);
+    }
+    let best_difficulty = reader.best_hash.difficulty as u64;
+    let has_solution = reader.best_hash.solution.is_some();
+    drop(reader);
+
+    // the mine reward doubles per difficulty above the minimum, capped by the bus
+    let pool_rewards = if best_difficulty >= coal_config.min_difficulty {
+        let multiplier = 1u64
+            .checked_shl((best_difficulty - coal_config.min_difficulty) as u32)
+            .unwrap_or(u64::MAX);
+        coal_config
+            .base_reward_rate
+            .saturating_mul(multiplier)
+            .min(best_bus_rewards)
+    } else {
+        0
+    };
+
+    Some(EpochRewardEstimate {
+        your_hashpower,
+        total_hashpower,
+        submitting_miners: submitting_miners.len(),
+        has_solution,
+        distributable_rewards: runtime_config.read().await.distributable_rewards(pool_rewards),
+    })
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct NextRewardEstimateResponse {
+    // summed over the miner's devices
+    your_hashpower: u64,
+    total_hashpower: u64,
+    your_pct: f64,
+    estimated_reward_coal: f64,
+    confidence: EstimateConfidence,
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/next-reward-estimate",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "Estimated reward for the epoch in progress, after commission", body = NextRewardEstimateResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 500, description = "Bus rewards are not loaded yet", body = ApiError)
+    )
+)]
+async fn get_miner_next_reward_estimate(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    Extension(epoch_hashes): Extension<Arc<RwLock<EpochHashes>>>,
+    Extension(proof): Extension<Arc<Mutex<Proof>>>,
+    Extension(coal_config_cache): Extension<Arc<RwLock<Option<CoalConfigSnapshot>>>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+) -> Result<Json<NextRewardEstimateResponse>, ApiError> {
+    let estimate =
+        estimate_epoch_reward(&user_pubkey, &epoch_hashes, &coal_config_cache, &runtime_config).await;
+    let estimate = match estimate {
+        Some(estimate) => estimate,
+        None => {
+            return Err(ApiError::new(ApiErrorCode::RpcError, "Bus rewards are not loaded yet"));
+        }
+    };
+
+    let your_share = if estimate.total_hashpower > 0 {
+        estimate.your_hashpower as f64 / estimate.total_hashpower as f64
+    } else {
+        0.0
+    };
+    let cutoff = get_cutoff(*proof.lock().await, 0);
+    let confidence = if estimate.submitting_miners < ESTIMATE_MIN_MINERS {
+        EstimateConfidence::Low
+    } else if cutoff <= 0 && estimate.has_solution {
+        EstimateConfidence::High
+    } else if cutoff <= ESTIMATE_MEDIUM_CONFIDENCE_SECS {
+        EstimateConfidence::Medium
+    } else {
+        EstimateConfidence::Low
+    };
+
+    Ok(Json(NextRewardEstimateResponse {
+        your_hashpower: estimate.your_hashpower,
+        total_hashpower: estimate.total_hashpower,
+        your_pct: your_share * 100.0,
+        estimated_reward_coal: amount_to_coal(estimate.distributable_rewards) * your_share,
+        confidence,
+    }))
+}
+
+// Epochs the earnings rate and epoch duration of the payout schedule are averaged over.
+const PAYOUT_SCHEDULE_EPOCHS: u32 = 10;
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PayoutScheduleResponse {
+    current_balance_coal: f64,
+    // None unless auto_claim_threshold is set in /miner/settings
+    threshold_coal: Option<f64>,
+    remaining_coal: Option<f64>,
+    // None when the miner earned nothing over the last 10 epochs
+    estimated_epochs: Option<u32>,
+    estimated_hours: Option<f64>,
+    // false, the pool doesn't claim automatically yet
+    auto_claim_enabled: bool,
+}
+
+// Nothing claims a miner's balance once it reaches auto_claim_threshold, the
+// threshold is only a target.
+const AUTO_CLAIM_ENABLED: bool = false;
+
+/// Epochs and hours until `remaining` is earned at avg_per_epoch COAL an
+/// epoch, None when nothing was earned lately.
+fn payout_estimate(remaining: u64, avg_per_epoch: f64, avg_epoch_duration_secs: f64) -> (Option<u32>, Option<f64>) {
+    if remaining == 0 {
+        return (Some(0), Some(0.0));
+    }
+    if avg_per_epoch <= 0.0 {
+        return (None, None);
+    }
+    let epochs = (amount_to_coal(remaining) / avg_per_epoch).ceil();
+    let epochs = epochs.min(u32::MAX as f64) as u32;
+    (Some(epochs), Some(epochs as f64 * avg_epoch_duration_secs / 3600.0))
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/payout-schedule",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "Progress towards the miner's auto-claim threshold, estimated from the last 10 epochs", body = PayoutScheduleResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 404, description = "Miner not found", body = ApiError),
+        (status = 500, description = "Failed to get the payout schedule", body = ApiError)
+    )
+)]
+async fn get_miner_payout_schedule(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<PayoutScheduleResponse>, ApiError> {
+    let progress = app_rr_database
+        .get_miner_payout_progress(user_pubkey.to_string(), app_config.pool_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get balance"))?
+        .ok_or_else(|| ApiError::new(ApiErrorCode::NotFound, "Miner not found"))?;
+    let threshold = match progress.auto_claim_threshold {
+        Some(threshold) => threshold,
+        None => {
+            return Ok(Json(PayoutScheduleResponse {
+                current_balance_coal: amount_to_coal(progress.balance),
+                threshold_coal: None,
+                remaining_coal: None,
+                estimated_epochs: None,
+                estimated_hours: None,
+                auto_claim_enabled: AUTO_CLAIM_ENABLED,
+            }));
+        }
+    };
+    let remaining = threshold.saturating_sub(progress.balance);
+
+    let (estimated_epochs, estimated_hours) = if remaining == 0 {
+        (Some(0), Some(0.0))
+    } else {
+        let avg_per_epoch = app_rr_database
+            .get_miner_avg_earnings_per_epoch(
+                user_pubkey.to_string(),
+                app_config.pool_id,
+                PAYOUT_SCHEDULE_EPOCHS,
+            )
+            .await
+            .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get earnings"))?;
+        let history = app_rr_database
+            .get_epoch_history(app_config.pool_id, PAYOUT_SCHEDULE_EPOCHS)
+            .await
+            .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get epoch history"))?;
+        let durations: Vec<i64> = history.iter().filter_map(|epoch| epoch.duration_secs).collect();
+        let avg_epoch_duration_secs = if durations.is_empty() {
+            get_coal_epoch_duration() as f64
+        } else {
+            durations.iter().sum::<i64>() as f64 / durations.len() as f64
+        };
+
+        payout_estimate(remaining, avg_per_epoch, avg_epoch_duration_secs)
+    };
+
+    Ok(Json(PayoutScheduleResponse {
+        current_balance_coal: amount_to_coal(progress.balance),
+        threshold_coal: Some(amount_to_coal(threshold)),
+        remaining_coal: Some(amount_to_coal(remaining)),
+        estimated_epochs,
+        estimated_hours,
+        auto_claim_enabled: AUTO_CLAIM_ENABLED,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/total-hashpower-contributed",
+    tag = "pool",
+    params(TotalHashpowerParams),
+    responses(
+        (status = 200, description = "Total hashpower, the formula is in the X-Hashpower-Formula header", body = String, content_type = "text/plain"),
+        (status = 500, description = "Failed to get total hashpower", body = ApiError)
+    )
+)]
+async fn get_pool_total_hashpower(
+    query_params: Query<TotalHashpowerParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> impl IntoResponse {
+    let res = app_rr_database
+        .get_pool_total_hashpower(app_config.pool_id, query_params.since)
+        .await;
+    total_hashpower_response(res)
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/balance",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "Wallet token balance, json when requested with Accept: application/json", content(
+            ("text/plain" = String),
+            ("application/json" = TokenAmount)
+        )),
+        (status = 400, description = "Invalid pubkey or missing token account", body = ApiError)
+    )
+)]
+async fn get_miner_balance(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    headers: HeaderMap,
+    Extension(rpc_client): Extension<Arc<RpcClient>>,
+) -> Result<axum::response::Response, ApiError> {
+    let miner_token_account = get_associated_token_address(&user_pubkey, &get_coal_mint());
+    if let Ok(response) = rpc_client
+        .get_token_account_balance(&miner_token_account)
+        .await
+    {
+        let amount = response.amount.parse::<u64>().map_err(|_| {
+            ApiError::new(ApiErrorCode::RpcError, "Invalid token account balance")
+        })?;
+        Ok(TokenAmount::new(amount).into_negotiated_response(&headers))
+    } else {
+        Err(ApiError::new(ApiErrorCode::TokenAccountNotFound, "Failed to get token account balance"))
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerDeviceResponse {
+    // None for an untagged connection
+    device_id: Option<String>,
+    signer: String,
+    // best difficulty and hashpower submitted by the device this epoch
+    epoch_difficulty: Option<u32>,
+    epoch_hashpower: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerReputationResponse {
+    reputation_score: i32,
+    total_valid_submissions: u64,
+    total_invalid_submissions: u64,
+    consecutive_invalid: u8,
+    // None before the miner's first solution
+    last_updated: Option<chrono::NaiveDateTime>,
+    min_reputation_score: i32,
+    can_connect: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/reputation",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "The miner's reputation from its valid and invalid solutions", body = MinerReputationResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 404, description = "Miner not found", body = ApiError),
+        (status = 500, description = "Failed to get the reputation", body = ApiError)
+    )
+)]
+async fn get_miner_reputation(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<MinerReputationResponse>, ApiError> {
+    let miner = app_database
+        .get_miner_by_pubkey_str(user_pubkey.to_string())
+        .await
+        .map_err(|e| match e {
+            AppDatabaseError::FailedToGetConnectionFromPool => {
+                ApiError::new(ApiErrorCode::DatabaseError, "Failed to get db pool connection")
+            }
+            _ => ApiError::new(ApiErrorCode::NotFound, "Miner not found"),
+        })?;
+    let reputation = app_database
+        .get_miner_reputation(miner.id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get reputation"))?;
+
+    let reputation_score = reputation.as_ref().map_or(0, |r| r.reputation_score);
+    Ok(Json(MinerReputationResponse {
+        reputation_score,
+        total_valid_submissions: reputation.as_ref().map_or(0, |r| r.total_valid_submissions),
+        total_invalid_submissions: reputation.as_ref().map_or(0, |r| r.total_invalid_submissions),
+        consecutive_invalid: reputation.as_ref().map_or(0, |r| r.consecutive_invalid),
+        last_updated: reputation.as_ref().map(|r| r.last_updated),
+        min_reputation_score: app_config.min_reputation_score,
+        can_connect: reputation_score >= app_config.min_reputation_score,
+    }))
+}
 
-                tx.sign(&[&wallet], hash);
+#[utoipa::path(
+    get,
+    path = "/miner/devices",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "Connected devices of the miner", body = Vec<MinerDeviceResponse>),
+        (status = 400, description = "Invalid pubkey", body = ApiError)
+    )
+)]
+async fn get_miner_devices(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(epoch_hashes): Extension<Arc<RwLock<EpochHashes>>>,
+) -> Result<Json<Vec<MinerDeviceResponse>>, ApiError> {
+    let connections: Vec<(DeviceId, Pubkey)> = app_state
+        .read()
+        .await
+        .sockets
+        .values()
+        .filter(|c| c.pubkey == user_pubkey)
+        .map(|c| (c.device_id.clone(), c.signer))
+        .collect();
+
+    let epoch_hashes = epoch_hashes.read().await;
+    let devices = connections
+        .into_iter()
+        .map(|(device_id, signer)| {
+            let submission = epoch_hashes.submissions.get(&(user_pubkey, device_id.clone()));
+            MinerDeviceResponse {
+                device_id,
+                signer: signer.to_string(),
+                epoch_difficulty: submission.map(|s| s.1),
+                epoch_hashpower: submission.map(|s| s.2),
+            }
+        })
+        .collect();
 
-                let result = rpc_client
-                    .send_and_confirm_transaction_with_spinner_and_commitment(
-                        &tx,
-                        rpc_client.commitment(),
-                    )
-                    .await;
-                match result {
-                    Ok(sig) => {
-                        info!("Miner successfully claimed.\nSig: {}", sig.to_string());
+    Ok(Json(devices))
+}
 
-                        // TODO: use transacions, or at least put them into one query
-                        let miner = app_database
-                            .get_miner_by_pubkey_str(user_pubkey.to_string())
-                            .await
-                            .unwrap();
-                        let db_pool = app_database
-                            .get_pool_by_authority_pubkey(wallet.pubkey().to_string())
-                            .await
-                            .unwrap();
-                        while let Err(_) = app_database
-                            .decrease_miner_reward(miner.id, amount)
-                            .await 
-                        {
-                            error!("Failed to decrease miner rewards! Retrying...");
-                            tokio::time::sleep(Duration::from_millis(2000)).await;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum WatchdogStatus {
+    // submitted this epoch
+    Healthy,
+    // receiving work but hasn't submitted this epoch yet
+    Idle,
+    // connected but without work for the current epoch, or not answering pings
+    Stalled,
+    Disconnected,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerWatchdogResponse {
+    // checked over all of the miner's devices
+    connected: bool,
+    // a nonce range was assigned for the current epoch
+    has_range: bool,
+    submitted_this_epoch: bool,
+    // most recent pong of any connection, None if none answered a ping yet
+    last_pong_secs_ago: Option<u64>,
+    status: WatchdogStatus,
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/watchdog",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, description = "Whether the miner is connected, receiving work and submitting", body = MinerWatchdogResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError)
+    )
+)]
+async fn get_miner_watchdog(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(client_nonce_ranges): Extension<Arc<RwLock<ClientNonceRanges>>>,
+    Extension(epoch_challenges): Extension<Arc<RwLock<EpochChallenges>>>,
+    Extension(epoch_hashes): Extension<Arc<RwLock<EpochHashes>>>,
+    Extension(keepalive): Extension<Arc<RwLock<Keepalive>>>,
+    Extension(keepalive_config): Extension<KeepaliveConfig>,
+) -> Result<Json<MinerWatchdogResponse>, ApiError> {
+    let connections: Vec<(SocketAddr, u64, DeviceId)> = app_state
+        .read()
+        .await
+        .sockets
+        .iter()
+        .filter(|(_, c)| c.pubkey == user_pubkey)
+        .map(|(addr, c)| (*addr, c.connection_id, c.device_id.clone()))
+        .collect();
+    let connected = !connections.is_empty();
+
+    let epoch_id = epoch_challenges.read().await.current().epoch_id;
+    let has_range = {
+        let ranges = client_nonce_ranges.read().await;
+        connections.iter().any(|(_, _, device_id)| {
+            ranges
+                .get(&(user_pubkey, device_id.clone()))
+                .is_some_and(|assigned| assigned.current().0 == epoch_id)
+        })
+    };
+    let submitted_this_epoch = epoch_hashes
+        .read()
+        .await
+        .submissions
+        .keys()
+        .any(|(pubkey, _)| *pubkey == user_pubkey);
+    let last_pong = {
+        let keepalive = keepalive.read().await;
+        connections
+            .iter()
+            .filter_map(|(addr, connection_id, _)| keepalive.last_valid_pong(*addr, *connection_id))
+            .max()
+    };
+    let last_pong_age = last_pong.map(|at| at.elapsed());
+
+    let status = if !connected {
+        WatchdogStatus::Disconnected
+    } else if !has_range || last_pong_age.is_none_or(|age| age > keepalive_config.pong_timeout) {
+        WatchdogStatus::Stalled
+    } else if submitted_this_epoch {
+        WatchdogStatus::Healthy
+    } else {
+        WatchdogStatus::Idle
+    };
+
+    Ok(Json(MinerWatchdogResponse {
+        connected,
+        has_range,
+        submitted_this_epoch,
+        last_pong_secs_ago: last_pong_age.map(|age| age.as_secs()),
+        status,
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MinerInfoResponse {
+    pubkey: String,
+    miner_id: i32,
+    enabled: bool,
+    // open connections of the miner
+    connected_devices: usize,
+    // None until a connection of the miner has ended
+    last_session: Option<models::MinerSession>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/miner/info",
+    tag = "miner",
+    params(PubkeyParam),
+    responses(
+        (status = 200, body = MinerInfoResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 404, description = "Miner not found", body = ApiError),
+        (status = 500, description = "Failed to get the miner or its last session", body = ApiError)
+    )
+)]
+async fn get_miner_info(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<MinerInfoResponse>, ApiError> {
+    let miner = app_database
+        .get_miner_by_pubkey_str(user_pubkey.to_string())
+        .await
+        .map_err(|e| match e {
+            AppDatabaseError::FailedToGetConnectionFromPool => {
+                ApiError::new(ApiErrorCode::DatabaseError, "Failed to get db pool connection")
+            }
+            _ => ApiError::new(ApiErrorCode::NotFound, "Miner not found"),
+        })?;
+    let last_session = app_rr_database
+        .get_last_miner_session(user_pubkey.to_string(), app_config.pool_id)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get the last session"))?;
+    let connected_devices = app_state
+        .read()
+        .await
+        .sockets
+        .values()
+        .filter(|c| c.pubkey == user_pubkey)
+        .count();
+
+    Ok(Json(MinerInfoResponse {
+        pubkey: miner.pubkey,
+        miner_id: miner.id,
+        enabled: miner.enabled,
+        connected_devices,
+        last_session,
+    }))
+}
+
+// Window used by /active-miners when none is given.
+const ACTIVE_MINERS_DEFAULT_WINDOW_SECS: u64 = 600;
+// Longest window accepted by /active-miners.
+const ACTIVE_MINERS_MAX_WINDOW_SECS: u64 = 7 * 86_400;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum ActiveMinersCriteria {
+    // live websocket connections
+    #[default]
+    Connected,
+    // distinct miners with a submission in the window
+    Submitted,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ActiveMinersParams {
+    /// Window for the submitted criteria, such as 30s, 10m, 1h or 1d. Defaults to 10m.
+    window: Option<String>,
+    criteria: Option<ActiveMinersCriteria>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ActiveMinersResponse {
+    criteria: ActiveMinersCriteria,
+    window_secs: u64,
+    // the count selected by criteria
+    active_miners: u64,
+    connections: usize,
+    // distinct wallets among the connections, a miner with several devices counts once
+    connected_miners: usize,
+    submitted_miners: u64,
+}
+
+/// Parses a window such as 90, 30s, 10m, 1h or 1d into seconds.
+fn parse_window_secs(window: &str) -> Option<u64> {
+    let (value, unit) = match window.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => window.split_at(i),
+        None => (window, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return None,
+    };
+    value.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Pool summary in the shape pool listing sites poll. Every field is always
+/// present, null when it doesn't apply.
+#[derive(Debug, Serialize, ToSchema)]
+struct PoolListingResponse {
+    // estimated hashes per second over the last 24 hours, from the difficulty
+    // of each miner's best submission per epoch
+    hashrate: f64,
+    // distinct connected wallets
+    miners: usize,
+    // connected devices
+    workers: usize,
+    // commission in percent, null without commission
+    fee_pct: Option<u8>,
+    // smallest claim in COAL, null without minimum
+    min_payout: Option<f64>,
+    // epochs whose solution landed in the last 24 hours
+    epochs_won_24h: u64,
+    // average COAL earned per epoch in the last 24 hours, null when none was won
+    avg_reward_24h: Option<f64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/listing",
+    tag = "pool",
+    responses(
+        (status = 200, description = "Pool summary for pool listing sites, the database figures are refreshed every minute", body = PoolListingResponse),
+        (status = 500, description = "Failed to get the pool's epochs", body = ApiError)
+    )
+)]
+async fn get_pool_listing(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    Extension(listing_cache): Extension<Arc<RwLock<PoolListingCache>>>,
+) -> Result<Json<PoolListingResponse>, ApiError> {
+    let cached = match &listing_cache.read().await.stats {
+        Some((cached_at, stats)) if cached_at.elapsed() < LISTING_CACHE_TTL => Some(stats.clone()),
+        _ => None,
+    };
+    let stats = match cached {
+        Some(stats) => stats,
+        None => {
+            let stats = app_rr_database
+                .get_pool_listing_stats(app_config.pool_id, LISTING_WINDOW_HOURS)
+                .await
+                .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get the pool's epochs"))?;
+            listing_cache.write().await.stats = Some((Instant::now(), stats.clone()));
+            stats
+        }
+    };
+
+    let (miners, workers) = {
+        let app_state = app_state.read().await;
+        let wallets: HashSet<Pubkey> = app_state.sockets.values().map(|c| c.pubkey).collect();
+        (wallets.len(), app_state.sockets.len())
+    };
+    let (commission_pct, min_claim_amount) = {
+        let runtime_config = runtime_config.read().await;
+        (runtime_config.commission_pct, runtime_config.min_claim_amount)
+    };
+
+    Ok(Json(PoolListingResponse {
+        hashrate: stats.expected_hashes / (LISTING_WINDOW_HOURS as f64 * 3600.0),
+        miners,
+        workers,
+        fee_pct: (commission_pct > 0).then_some(commission_pct),
+        min_payout: (min_claim_amount > 0).then(|| amount_to_coal(min_claim_amount)),
+        epochs_won_24h: stats.epochs_won,
+        avg_reward_24h: stats.avg_reward.map(amount_to_coal),
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct BestSolutionTimelineResponse {
+    current_best_difficulty: u32,
+    // null until an improvement is seen, the timeline starts empty after a restart
+    seconds_since_last_improvement: Option<u64>,
+    // in the order they were found, each better than the previous
+    timeline: Vec<BestDifficultyImprovement>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/best-solution-timeline",
+    tag = "pool",
+    responses(
+        (status = 200, description = "How the best difficulty of the current epoch improved", body = BestSolutionTimelineResponse),
+    ),
+)]
+async fn get_best_solution_timeline(
+    Extension(epoch_hashes): Extension<Arc<RwLock<EpochHashes>>>,
+    Extension(best_difficulty_timeline): Extension<BestDifficultyTimeline>,
+) -> Json<BestSolutionTimelineResponse> {
+    // the timeline is only changed under the epoch hashes write lock
+    let epoch_hashes = epoch_hashes.read().await;
+    let (timeline, seconds_since_last_improvement) = best_difficulty_timeline.snapshot().await;
+    Json(BestSolutionTimelineResponse {
+        current_best_difficulty: epoch_hashes.best_hash.difficulty,
+        seconds_since_last_improvement,
+        timeline,
+    })
+}
+
+// Longest window /pool/mining-efficiency looks back over.
+const MINING_EFFICIENCY_MAX_HOURS: u32 = 7 * 24;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MiningEfficiencyParams {
+    /// Defaults to 24, at most 168
+    hours: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct MiningEfficiencyResponse {
+    hours: u32,
+    // fraction of the assigned nonces in a range that produced a submission
+    // of at least the minimum difficulty, close to 1.0 when almost every
+    // range handed out leads to a submission
+    useful_work_ratio: f64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/mining-efficiency",
+    tag = "pool",
+    params(MiningEfficiencyParams),
+    responses(
+        (status = 200, description = "Share of the assigned nonce ranges that produced a submission, over the challenges ended in the window", body = MiningEfficiencyResponse),
+        (status = 400, description = "Invalid hours", body = ApiError),
+        (status = 500, description = "Failed to get the mining efficiency", body = ApiError)
+    )
+)]
+async fn get_pool_mining_efficiency(
+    query_params: Query<MiningEfficiencyParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<MiningEfficiencyResponse>, ApiError> {
+    let hours = query_params.hours.unwrap_or(24);
+    if hours == 0 || hours > MINING_EFFICIENCY_MAX_HOURS {
+        return Err(ApiError::new(
+            ApiErrorCode::InvalidRequest,
+            format!("hours must be between 1 and {}", MINING_EFFICIENCY_MAX_HOURS),
+        ));
+    }
+    let since_ts = chrono::Utc::now().timestamp() - hours as i64 * 3600;
+    let useful_work_ratio = app_rr_database
+        .get_mining_efficiency(app_config.pool_id, since_ts)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get the mining efficiency"))?;
+
+    Ok(Json(MiningEfficiencyResponse {
+        hours,
+        useful_work_ratio,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RewardFormulaParams {
+    /// Difficulty of the worked example, defaults to 16
+    difficulty: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/reward-formula",
+    tag = "pool",
+    params(RewardFormulaParams),
+    responses(
+        (status = 200, description = "How submissions are credited and rewards split, with the pool's current parameters and a worked example", body = RewardFormula),
+        (status = 400, description = "Invalid difficulty", body = ApiError)
+    )
+)]
+async fn get_pool_reward_formula(
+    query_params: Query<RewardFormulaParams>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+) -> Result<Json<RewardFormula>, ApiError> {
+    let difficulty = query_params.difficulty.unwrap_or(EXAMPLE_DIFFICULTY);
+    if difficulty > MAX_SUBMISSION_DIFFICULTY {
+        return Err(ApiError::new(
+            ApiErrorCode::InvalidRequest,
+            format!("difficulty must be at most {}", MAX_SUBMISSION_DIFFICULTY),
+        ));
+    }
+    let runtime_config = runtime_config.read().await;
+    Ok(Json(RewardFormula::new(
+        &runtime_config,
+        app_config.referral_bonus_pct,
+        difficulty,
+    )))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EstimatedApyParams {
+    /// Hashpower per epoch, as credited for the miner's submissions
+    hashpower: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct EstimatedApyResponse {
+    // COAL a year for the given hashpower in every epoch
+    annualized_coal: f64,
+    // null, no COAL price source is configured
+    annualized_usd: Option<f64>,
+    // epochs with a recorded reward in the last 30 days
+    based_on_epochs: u32,
+    avg_daily_coal_per_unit_hashpower: f64,
+    note: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/estimated-apy",
+    tag = "pool",
+    params(EstimatedApyParams),
+    responses(
+        (status = 200, description = "Yearly COAL estimate for a hashpower from the pool's last 30 days of rewards, recomputed every hour", body = EstimatedApyResponse),
+        (status = 400, description = "Missing or invalid hashpower", body = ApiError),
+        (status = 500, description = "Failed to get the pool's rewards", body = ApiError)
+    )
+)]
+async fn get_pool_estimated_apy(
+    query_params: Query<EstimatedApyParams>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(reward_yield_cache): Extension<Arc<RwLock<RewardYieldCache>>>,
+) -> Result<Json<EstimatedApyResponse>, ApiError> {
+    let cached = match &reward_yield_cache.read().await.reward_yield {
+        Some((cached_at, reward_yield)) if cached_at.elapsed() < APY_CACHE_TTL => {
+            Some(reward_yield.clone())
+        }
+        _ => None,
+    };
+    let reward_yield = match cached {
+        Some(reward_yield) => reward_yield,
+        None => {
+            let reward_yield = app_rr_database
+                .get_reward_yield(app_config.pool_id, APY_WINDOW_DAYS)
+                .await
+                .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get the pool's rewards"))?;
+            reward_yield_cache.write().await.reward_yield = Some((Instant::now(), reward_yield.clone()));
+            reward_yield
+        }
+    };
+
+    // a pool younger than the window is averaged over its age, at least a day
+    let days = (reward_yield.span_secs as f64 / 86_400.0).clamp(1.0, APY_WINDOW_DAYS as f64);
+    let avg_daily_coal_per_unit_hashpower = reward_yield.rewards_per_hashpower
+        / 10f64.powi(COAL_TOKEN_DECIMALS as i32)
+        / days;
+
+    Ok(Json(EstimatedApyResponse {
+        annualized_coal: avg_daily_coal_per_unit_hashpower * query_params.hashpower as f64 * 365.0,
+        annualized_usd: None,
+        based_on_epochs: reward_yield.epochs.min(u32::MAX as u64) as u32,
+        avg_daily_coal_per_unit_hashpower,
+        note: format!(
+            "Estimated from the pool's rewards of the last {} days, past rewards don't guarantee future ones. Assumes the hashpower is submitted in every epoch.",
+            APY_WINDOW_DAYS
+        ),
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ActiveSessionsResponse {
+    total_connections: u32,
+    unique_ips: u32,
+    // connections open now plus sessions that ended in the last hour
+    connections_last_hour: u32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/active-sessions",
+    tag = "pool",
+    responses(
+        (status = 200, description = "Connection counts for public display, without any ip or pubkey", body = ActiveSessionsResponse),
+        (status = 500, description = "Failed to count the sessions of the last hour", body = ApiError)
+    )
+)]
+async fn get_pool_active_sessions(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<Json<ActiveSessionsResponse>, ApiError> {
+    let (total_connections, unique_ips) = {
+        let app_state = app_state.read().await;
+        let ips: HashSet<IpAddr> = app_state.sockets.values().map(|c| c.real_ip).collect();
+        (app_state.sockets.len() as u32, ips.len() as u32)
+    };
+    let sessions_ended = app_rr_database
+        .get_sessions_ended_count(app_config.pool_id, 1)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to count sessions"))?;
+
+    Ok(Json(ActiveSessionsResponse {
+        total_connections,
+        unique_ips,
+        connections_last_hour: total_connections.saturating_add(sessions_ended as u32),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/active-miners",
+    tag = "pool",
+    params(ActiveMinersParams),
+    responses(
+        (status = 200, description = "Number of connected miners as plain text. Json with every count when a query parameter is given or json is accepted", content(
+            ("text/plain" = String),
+            ("application/json" = ActiveMinersResponse)
+        )),
+        (status = 400, description = "Invalid window", body = ApiError),
+        (status = 500, description = "Failed to count submitting miners", body = ApiError)
+    )
+)]
+async fn get_connected_miners(
+    query_params: Query<ActiveMinersParams>,
+    headers: HeaderMap,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(app_config): Extension<Arc<Config>>,
+) -> Result<axum::response::Response, ApiError> {
+    let (connections, connected_miners) = {
+        let app_state = app_state.read().await;
+        let pubkeys: HashSet<Pubkey> = app_state.sockets.values().map(|c| c.pubkey).collect();
+        (app_state.sockets.len(), pubkeys.len())
+    };
+
+    let wants_json = accepts_json(&headers)
+        || query_params.window.is_some()
+        || query_params.criteria.is_some();
+    if !wants_json {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(connections.to_string())
+            .unwrap()
+            .into_response());
+    }
+
+    let window_secs = match &query_params.window {
+        Some(window) => parse_window_secs(window)
+            .filter(|secs| *secs > 0 && *secs <= ACTIVE_MINERS_MAX_WINDOW_SECS)
+            .ok_or_else(|| {
+                ApiError::new(ApiErrorCode::InvalidRequest, "window must be between 1s and 7d")
+            })?,
+        None => ACTIVE_MINERS_DEFAULT_WINDOW_SECS,
+    };
+    let submitted_miners = app_rr_database
+        .get_submitting_miner_count(app_config.pool_id, window_secs)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to count submitting miners"))?;
+
+    let criteria = query_params.criteria.unwrap_or_default();
+    let active_miners = match criteria {
+        ActiveMinersCriteria::Connected => connected_miners as u64,
+        ActiveMinersCriteria::Submitted => submitted_miners,
+    };
+
+    Ok(Json(ActiveMinersResponse {
+        criteria,
+        window_secs,
+        active_miners,
+        connections,
+        connected_miners,
+        submitted_miners,
+    })
+    .into_response())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct GuildStatsResponse {
+    guild: String,
+    member: String,
+    is_active: bool,
+    member_stake: u64,
+    guild_stake: u64,
+    total_stake: u64,
+    total_multiplier: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ToolStatsResponse {
+    tool: String,
+    is_usable: bool,
+    durability: Option<u64>,
+    multiplier: Option<u64>,
+    durability_warning: u64,
+}
+
+// Window used for the landing latency percentiles on /pool/stats.
+const LANDING_STATS_HOURS: u32 = 24;
+
+#[derive(Debug, Serialize, ToSchema)]
+struct LandingLatencyStats {
+    window_hours: u32,
+    landed_count: usize,
+    failed_count: usize,
+    // None when the percentile falls on a failed epoch
+    confirm_p50_ms: Option<u32>,
+    confirm_p95_ms: Option<u32>,
+    first_send_p50_ms: Option<u32>,
+    first_send_p95_ms: Option<u32>,
+    avg_final_priority_fee: f64,
+    avg_landed_priority_fee: f64,
+}
+
+/// Nearest rank percentile. Missing values sort last, so failed epochs count
+/// as slower than any landed one instead of being dropped.
+fn percentile_ms(values: &[Option<u32>], pct: usize) -> Option<u32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by_key(|v| v.unwrap_or(u32::MAX));
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+impl LandingLatencyStats {
+    fn new(samples: &[LandingSample]) -> Self {
+        let landed: Vec<&LandingSample> = samples
+            .iter()
+            .filter(|s| s.outcome == EpochOutcome::Success.as_str())
+            .collect();
+        // landed epochs recorded before timings were stored have no latency
+        let confirmed: Vec<Option<u32>> = samples
+            .iter()
+            .filter(|s| s.outcome != EpochOutcome::Success.as_str() || s.confirmed_ms.is_some())
+            .map(|s| s.confirmed_ms)
+            .collect();
+        let first_send: Vec<Option<u32>> = samples
+            .iter()
+            .filter_map(|s| s.first_send_ms.map(Some))
+            .collect();
+
+        let avg_fee = |fees: &mut dyn Iterator<Item = u64>, count: usize| {
+            if count > 0 {
+                fees.map(|f| f as f64).sum::<f64>() / count as f64
+            } else {
+                0.0
+            }
+        };
+
+        LandingLatencyStats {
+            window_hours: LANDING_STATS_HOURS,
+            landed_count: landed.len(),
+            failed_count: samples.len() - landed.len(),
+            confirm_p50_ms: percentile_ms(&confirmed, 50),
+            confirm_p95_ms: percentile_ms(&confirmed, 95),
+            first_send_p50_ms: percentile_ms(&first_send, 50),
+            first_send_p95_ms: percentile_ms(&first_send, 95),
+            avg_final_priority_fee: avg_fee(
+                &mut samples.iter().map(|s| s.final_priority_fee),
+                samples.len(),
+            ),
+            avg_landed_priority_fee: avg_fee(
+                &mut landed.iter().map(|s| s.final_priority_fee),
+                landed.len(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PoolStatsResponse {
+    #[serde(flatten)]
+    snapshot: PoolStatsSnapshot,
+    max_miners: Option<usize>,
+    is_full: bool,
+    guild: Option<GuildStatsResponse>,
+    tool: Option<ToolStatsResponse>,
+    reprocess: Option<ReprocessStatus>,
+    landing: Option<LandingLatencyStats>,
+    fee_budget: FeeBudgetStatus,
+    audit_log: AuditLogStatus,
+    proof_updates: ProofUpdateStatus,
+    missed_epochs: MissedEpochStatus,
+    // only built with the event-bus feature, None when EVENT_BUS_URL isn't set
+    #[cfg(feature = "event-bus")]
+    #[schema(inline)]
+    event_bus: Option<EventBusStatus>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/stats",
+    tag = "pool",
+    responses(
+        (status = 200, body = PoolStatsResponse)
+    )
+)]
+async fn get_pool_stats(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(wallet): Extension<PoolWallet>,
+    Extension(tool_status): Extension<Arc<RwLock<Option<ToolStatus>>>>,
+    Extension(reprocess_status): Extension<Option<Arc<RwLock<ReprocessStatus>>>>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+    Extension(epoch_hashes): Extension<Arc<RwLock<EpochHashes>>>,
+    Extension(proof): Extension<Arc<Mutex<Proof>>>,
+    Extension(fee_budget): Extension<Arc<Mutex<FeeBudget>>>,
+    Extension(audit_log): Extension<AuditLog>,
+    Extension(proof_updates): Extension<Arc<RwLock<ProofUpdates>>>,
+    Extension(missed_epochs): Extension<Arc<RwLock<MissedEpochs>>>,
+    #[cfg(feature = "event-bus")] Extension(event_bus_stats): Extension<
+        Option<Arc<EventBusStats>>,
+    >,
+) -> impl IntoResponse {
+    let proof = *proof.lock().await;
+    let snapshot = get_pool_stats_snapshot(
+        &*app_state.read().await,
+        &*epoch_hashes.read().await,
+        &proof,
+    );
+    let is_full = is_pool_full(&app_config, snapshot.active_miners);
+
+    let guild = app_config.guild.map(|g| GuildStatsResponse {
+        guild: g.guild.to_string(),
+        member: g.member.to_string(),
+        is_active: g.is_active,
+        member_stake: g.member_stake,
+        guild_stake: g.guild_stake,
+        total_stake: g.total_stake,
+        total_multiplier: g.total_multiplier,
+    });
+
+    let status = *tool_status.read().await;
+    let tool = app_config.tool.map(|tool| ToolStatsResponse {
+        tool: tool.to_string(),
+        is_usable: status
+            .map(|t| t.is_usable(&wallet.pubkey()))
+            .unwrap_or(false),
+        durability: status.map(|t| t.durability),
+        multiplier: status.map(|t| t.multiplier),
+        durability_warning: app_config.tool_durability_warning,
+    });
+
+    let reprocess = match reprocess_status {
+        Some(status) => Some(status.read().await.clone()),
+        None => None,
+    };
+
+    let landing = match app_rr_database
+        .get_landing_samples(app_config.pool_id, LANDING_STATS_HOURS)
+        .await
+    {
+        Ok(samples) => Some(LandingLatencyStats::new(&samples)),
+        Err(_) => {
+            error!("Failed to get landing latency samples");
+            None
+        }
+    };
+
+    Json(PoolStatsResponse {
+        snapshot,
+        max_miners: app_config.max_miners,
+        is_full,
+        guild,
+        tool,
+        reprocess,
+        landing,
+        fee_budget: fee_budget.lock().await.status(),
+        audit_log: audit_log.stats().status(),
+        proof_updates: proof_updates.read().await.status(),
+        missed_epochs: missed_epochs.read().await.status(),
+        #[cfg(feature = "event-bus")]
+        event_bus: event_bus_stats.map(|stats| stats.status()),
+    })
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ResetTimingResponse {
+    last_reset_at: u64,
+    next_reset_at: u64,
+    // negative when the reset is overdue
+    seconds_remaining: i64,
+    // whether a mine transaction sent now would include the reset instruction
+    reset_ix_will_be_added: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/slots-until-reset",
+    tag = "pool",
+    responses(
+        (status = 200, description = "Coal reset timing from the cached on-chain config", body = ResetTimingResponse),
+        (status = 500, description = "The coal config hasn't been loaded yet", body = ApiError)
+    )
+)]
+async fn get_pool_slots_until_reset(
+    Extension(coal_config_cache): Extension<Arc<RwLock<Option<CoalConfigSnapshot>>>>,
+) -> Result<Json<ResetTimingResponse>, ApiError> {
+    let last_reset_at = match coal_config_cache.read().await.as_ref() {
+        Some(snapshot) => snapshot.config.last_reset_at,
+        None => {
+            return Err(ApiError::new(ApiErrorCode::RpcError, "Coal config not loaded yet"));
+        }
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64;
+    let next_reset_at = last_reset_at + COAL_RESET_INTERVAL_SECS;
+    let seconds_remaining = next_reset_at - now;
+
+    Ok(Json(ResetTimingResponse {
+        last_reset_at: last_reset_at.max(0) as u64,
+        next_reset_at: next_reset_at.max(0) as u64,
+        seconds_remaining,
+        reset_ix_will_be_added: seconds_remaining <= RESET_IX_WINDOW_SECS,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/submission-heatmap",
+    tag = "pool",
+    responses(
+        (status = 200, description = "Nonce ranges assigned to the connected devices in the current epoch, and the gaps between them", body = SubmissionHeatmapResponse)
+    )
+)]
+async fn get_pool_submission_heatmap(
+    Extension(client_nonce_ranges): Extension<Arc<RwLock<ClientNonceRanges>>>,
+    Extension(epoch_challenges): Extension<Arc<RwLock<EpochChallenges>>>,
+) -> Json<SubmissionHeatmapResponse> {
+    let epoch_id = epoch_challenges.read().await.current().epoch_id;
+    let mut ranges: Vec<NonceRangeEntry> = client_nonce_ranges
+        .read()
+        .await
+        .iter()
+        .filter_map(|((pubkey, device_id), assigned)| {
+            // devices that got no work this epoch are still mining an older range
+            let (range_epoch_id, range) = assigned.current();
+            (range_epoch_id == epoch_id).then(|| NonceRangeEntry {
+                pubkey: display_pubkey(pubkey),
+                device_id: device_id.clone(),
+                range_start: range.start,
+                range_end: range.end,
+                range_size: range.end.saturating_sub(range.start),
+            })
+        })
+        .collect();
+    ranges.sort_by_key(|entry| entry.range_start);
+
+    let coverage_gaps = nonce_coverage::coverage_gaps(
+        &ranges
+            .iter()
+            .map(|entry| entry.range_start..entry.range_end)
+            .collect::<Vec<_>>(),
+    );
+    Json(SubmissionHeatmapResponse {
+        epoch_id,
+        ranges,
+        coverage_gaps,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/pool/busses",
+    tag = "pool",
+    responses(
+        (status = 200, body = BusStatsResponse)
+    )
+)]
+async fn get_pool_busses(
+    Extension(bus_stats): Extension<Arc<RwLock<BusStats>>>,
+) -> impl IntoResponse {
+    let response = bus_stats.read().await.to_response();
+    Json(response)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthResponse {
+    status: &'static str,
+    proof: ProofUpdateStatus,
+    replica: ReplicaLagStatus,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "pool",
+    responses(
+        (status = 200, description = "Server is up, with the freshness of the pool proof and the read replica's lag", body = HealthResponse)
+    )
+)]
+async fn get_health(
+    Extension(proof_updates): Extension<Arc<RwLock<ProofUpdates>>>,
+    Extension(replica_lag): Extension<Arc<RwLock<ReplicaLag>>>,
+) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        proof: proof_updates.read().await.status(),
+        replica: replica_lag.read().await.status(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/timestamp",
+    responses(
+        (status = 200, description = "Server unix timestamp in seconds", body = String, content_type = "text/plain")
+    )
+)]
+async fn get_timestamp() -> Result<Response<String>, ApiError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(now.to_string())
+        .unwrap())
+}
+
+/// The miner's rewards from the cache, read from the primary database on a miss.
+async fn get_cached_miner_rewards(
+    app_database: &AppDatabase,
+    reward_cache: &RewardCache,
+    pubkey: &Pubkey,
+    pool_id: i32,
+) -> Result<Reward, AppDatabaseError> {
+    let pubkey = pubkey.to_string();
+    if let Some(reward) = reward_cache.get(&pubkey).await {
+        return Ok(reward);
+    }
+    let reward = app_database.get_miner_rewards(pubkey.clone(), pool_id).await?;
+    reward_cache.insert(pubkey, reward).await;
+    Ok(reward)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ClaimParams {
+    amount: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/claim",
+    tag = "miner",
+    params(PubkeyParam, ClaimParams),
+    responses(
+        (status = 200, description = "Claim confirmed", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid pubkey or claim amount", body = ApiError),
+        (status = 429, description = "Claim cooldown active, the message is the seconds since the last claim", body = ApiError),
+        (status = 500, description = "Claim transaction failed", body = ApiError)
+    )
+)]
+async fn post_claim(
+    ValidatedPubkey(user_pubkey): ValidatedPubkey,
+    query_params: Query<ClaimParams>,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(rpc_client): Extension<Arc<RpcClient>>,
+    Extension(wallet): Extension<PoolWallet>,
+    Extension(webhook_sender): Extension<UnboundedSender<WebhookJob>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(dashboard_bus): Extension<DashboardEventBus>,
+    Extension(reward_cache): Extension<RewardCache>,
+    Extension(replica_lag): Extension<Arc<RwLock<ReplicaLag>>>,
+) -> Result<Response<String>, ApiError> {
+    let amount = query_params.amount;
+    let (min_claim_amount, claim_cooldown_secs) = {
+        let runtime_config = runtime_config.read().await;
+        (runtime_config.min_claim_amount, runtime_config.claim_cooldown_secs as i64)
+    };
+    if amount < min_claim_amount {
+        return Err(ApiError::new(
+            ApiErrorCode::ClaimBelowMinimum,
+            format!("claim amount must be at least {}", min_claim_amount),
+        )
+        .with_details(serde_json::json!({ "min_claim_amount": min_claim_amount })));
+    }
+
+    if let Ok(miner_rewards) =
+        get_cached_miner_rewards(&app_database, &reward_cache, &user_pubkey, app_config.pool_id).await
+    {
+        if amount > miner_rewards.balance {
+            return Err(ApiError::new(ApiErrorCode::InsufficientBalance, "claim amount exceeds miner rewards balance"));
+        }
+
+        if let Ok(last_claim) = app_database.get_last_claim(miner_rewards.miner_id, app_config.pool_id).await {
+            let last_claim_ts = last_claim.created_at.and_utc().timestamp();
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs() as i64;
+            let time_difference = now - last_claim_ts;
+            if time_difference <= claim_cooldown_secs {
+                // plain text clients get the seconds since the last claim, as before
+                return Err(ApiError::new(ApiErrorCode::ClaimCooldown, "Claim cooldown is active")
+                    .with_details(serde_json::json!({
+                        "seconds_since_last_claim": time_difference,
+                        "seconds_remaining": claim_cooldown_secs - time_difference + 1,
+                    }))
+                    .with_text(time_difference.to_string()));
+            }
+        }
+
+        // signed and paid from the same key even if the wallet is swapped meanwhile
+        let wallet = wallet.keypair();
+        let coal_mint = get_coal_mint();
+        let miner_token_account = get_associated_token_address(&user_pubkey, &coal_mint);
+
+        let prio_fee: u32 = 20_000;
+
+        let mut tx_builder = SolanaTransactionBuilder::new().priority_fee(prio_fee as u64);
+        if let Ok(response) = rpc_client
+            .get_token_account_balance(&miner_token_account)
+            .await
+        {
+            if let Some(_amount) = response.ui_amount {
+                info!("miner has valid token account.");
+            } else {
+                info!("will create token account for miner");
+                tx_builder = tx_builder.instruction(
+                    spl_associated_token_account::instruction::create_associated_token_account(
+                        &wallet.pubkey(),
+                        &user_pubkey,
+                        &coal_api::consts::MINT_ADDRESS,
+                        &spl_token::id(),
+                    ),
+                )
+            }
+        } else {
+            info!("Adding create ata ix for miner claim");
+            tx_builder = tx_builder.instruction(
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &wallet.pubkey(),
+                    &user_pubkey,
+                    &coal_api::consts::MINT_ADDRESS,
+                    &spl_token::id(),
+                ),
+            )
+        }
+
+        let ix = coal_api::instruction::claim(wallet.pubkey(), miner_token_account, amount);
+        tx_builder = tx_builder.instruction(ix);
+
+        if let Ok(tx) = tx_builder.build_and_sign(&wallet, &rpc_client).await {
+            let result = rpc_client
+                .send_and_confirm_transaction_with_spinner_and_commitment(
+                    &tx,
+                    rpc_client.commitment(),
+                )
+                .await;
+            match result {
+                Ok(sig) => {
+                    info!("Miner successfully claimed.\nSig: {}", sig.to_string());
+
+                    // looked up before sending, nothing can fail between the
+                    // confirmation and recording the claim but the db writes
+                    let miner_id = miner_rewards.miner_id;
+
+                    let itxn = InsertTxn {
+                        txn_type: "claim".to_string(),
+                        signature: sig.to_string(),
+                        priority_fee: prio_fee,
+                        pool_id: Some(app_config.pool_id),
+                        fee_paid_lamports: get_fee_paid(&rpc_client, &sig).await,
+                    };
+                    let recorded = match add_confirmed_txn(&app_database, itxn).await {
+                        Some(txn_id) => {
+                            record_claim(&app_database, miner_id, app_config.pool_id, txn_id, amount, &sig).await
                         }
-                        while let Err(_) = app_database
-                            .update_pool_claimed(wallet.pubkey().to_string(), amount)
+                        None => false,
+                    };
+                    if recorded {
+                        reward_cache.invalidate(&HashSet::from([miner_id])).await;
+                        replica_lag.write().await.record_write(user_pubkey.to_string());
+                    } else {
+                        error!(
+                            "Claim {} of {} for miner {} landed but is not recorded, its balance must be corrected",
+                            sig, amount, miner_id
+                        );
+                    }
+
+                    let claim_signature = sig.to_string();
+                    dashboard_bus.send(DashboardEvent::ClaimProcessed {
+                        pubkey: user_pubkey.to_string(),
+                        amount,
+                        signature: claim_signature.clone(),
+                    });
+                    tokio::spawn(async move {
+                        if let Ok(Some(setting)) = app_database
+                            .get_miner_notify_setting(miner_id, app_config.pool_id)
                             .await
                         {
-                            error!("Failed to increase pool claimed amount! Retrying...");
-                            tokio::time::sleep(Duration::from_millis(2000)).await;
+                            let _ = webhook_sender.send(WebhookJob {
+                                miner_id: Some(setting.miner_id),
+                                url: setting.notify_url,
+                                event: WebhookEvent::ClaimConfirmed {
+                                    pubkey: setting.pubkey,
+                                    amount,
+                                    signature: claim_signature,
+                                },
+                            });
                         }
+                    });
 
-                        let itxn = InsertTxn {
-                            txn_type: "claim".to_string(),
-                            signature: sig.to_string(),
-                            priority_fee: prio_fee,
-                        };
-                        while let Err(_) = app_database.add_new_txn(itxn.clone()).await {
-                            error!("Failed to increase pool claimed amount! Retrying...");
-                            tokio::time::sleep(Duration::from_millis(2000)).await;
-                        }
+                    Ok(Response::builder()
+                        .status(StatusCode::OK)
+                        .body("SUCCESS".to_string())
+                        .unwrap())
+                }
+                Err(e) => {
+                    error!("ERROR: {:?}", e);
+                    Err(ApiError::new(ApiErrorCode::TransactionFailed, "Claim transaction failed").with_text("FAILED"))
+                }
+            }
+        } else {
+            Err(ApiError::new(ApiErrorCode::TransactionFailed, "Claim transaction failed").with_text("FAILED"))
+        }
+    } else {
+        Err(ApiError::new(ApiErrorCode::DatabaseError, "failed to get miner account from database"))
+    }
+}
 
-                        let txn_id;
-                        loop {
-                            if let Ok(ntxn) = app_database.get_txn_by_sig(sig.to_string()).await {
-                                txn_id = ntxn.id;
-                                break;
-                            } else {
-                                error!("Failed to get tx by sig! Retrying...");
-                                tokio::time::sleep(Duration::from_millis(2000)).await;
-                            }
-                        }
+/// Id of the stored txn row of a confirmed transaction, None when it can't be
+/// stored within TXN_RECORD_MAX_ATTEMPTS.
+async fn add_confirmed_txn(app_database: &AppDatabase, itxn: InsertTxn) -> Option<i32> {
+    for attempt in 1..=TXN_RECORD_MAX_ATTEMPTS {
+        match app_database.add_new_txn(itxn.clone()).await {
+            Ok(txn) => return Some(txn.id),
+            Err(e) if !e.is_retriable() => {
+                error!("Non-retriable db error adding {}: {:?}", InsertTxn::describe(), e);
+                return None;
+            }
+            Err(_) if attempt == TXN_RECORD_MAX_ATTEMPTS => {
+                error!("Failed to add {} to db, giving up", InsertTxn::describe());
+            }
+            Err(_) => {
+                error!("Failed to add {} to db! Retrying...", InsertTxn::describe());
+                tokio::time::sleep(Duration::from_millis(2000)).await;
+            }
+        }
+    }
+    None
+}
+
+/// Debits a confirmed claim from the miner's balance and records it, false
+/// when that can't be done within CLAIM_RECORD_MAX_ATTEMPTS.
+async fn record_claim(
+    app_database: &AppDatabase,
+    miner_id: i32,
+    pool_id: i32,
+    txn_id: i32,
+    amount: u64,
+    sig: &Signature,
+) -> bool {
+    for attempt in 1..=CLAIM_RECORD_MAX_ATTEMPTS {
+        // the balance, pool counter and claim row are only written together
+        match app_database.claim_atomic(miner_id, pool_id, txn_id, amount).await {
+            Ok(result) => {
+                info!("Recorded claim {} for miner {}", result.claim_id, miner_id);
+                return true;
+            }
+            Err(AppDatabaseError::FailedToUpdateRow) => {
+                error!("Claim {} has no rewards or pool row to update, not retrying", sig);
+                return false;
+            }
+            Err(e) if !e.is_retriable() => {
+                error!("Non-retriable db error recording claim {}: {:?}", sig, e);
+                return false;
+            }
+            Err(_) if attempt == CLAIM_RECORD_MAX_ATTEMPTS => {
+                error!("Failed to record {} in db, giving up", InsertClaim::describe());
+            }
+            Err(_) => {
+                error!("Failed to record {} in db! Retrying...", InsertClaim::describe());
+                tokio::time::sleep(Duration::from_millis(2000)).await;
+            }
+        }
+    }
+    false
+}
+
+/// Queues a webhook for every miner whose balance crossed their notify
+/// threshold with this round of rewards.
+async fn notify_balance_thresholds(
+    app_database: Arc<AppDatabase>,
+    webhook_sender: UnboundedSender<WebhookJob>,
+    pool_id: i32,
+    earned: HashMap<i32, u64>,
+) {
+    // only miners that earned this round can have crossed their threshold
+    let miner_ids = earned.keys().copied().collect();
+    let settings = match app_database.get_notify_settings(pool_id, miner_ids).await {
+        Ok(settings) => settings,
+        Err(_) => {
+            error!("Failed to get miner notify settings");
+            return;
+        }
+    };
+
+    for setting in settings {
+        if let Some(earned_rewards) = earned.get(&setting.miner_id) {
+            if crossed_notify_threshold(&setting, *earned_rewards) {
+                let _ = webhook_sender.send(WebhookJob {
+                    miner_id: Some(setting.miner_id),
+                    url: setting.notify_url,
+                    event: WebhookEvent::BalanceThreshold {
+                        pubkey: setting.pubkey,
+                        balance: setting.balance,
+                        threshold: setting.min_notify_amount,
+                    },
+                });
+            }
+        }
+    }
+}
 
+/// Whether the balance reached the miner's notify threshold with the earned rewards.
+fn crossed_notify_threshold(setting: &models::NotifySetting, earned_rewards: u64) -> bool {
+    let previous_balance = setting.balance.saturating_sub(earned_rewards);
+    previous_balance < setting.min_notify_amount && setting.balance >= setting.min_notify_amount
+}
 
-                        let iclaim = InsertClaim {
-                            miner_id: miner.id,
-                            pool_id: db_pool.id,
-                            txn_id,
-                            amount,
-                        };
-                        while let Err(_) = app_database.add_new_claim(iclaim).await {
-                            error!("Failed add new claim to db! Retrying...");
-                            tokio::time::sleep(Duration::from_millis(2000)).await;
-                        }
+/// Verifies a signature made by `pubkey` over the domain, the timestamp
+/// (little endian) and the payload bytes. The domain names the action, so a
+/// request signed for one endpoint can't be sent to another. Signed payloads
+/// are valid for the auth window.
+fn verify_signed_payload(
+    pubkey: &Pubkey,
+    signature: &str,
+    domain: &[u8],
+    timestamp: u64,
+    payload: &[u8],
+    auth_window: &AuthWindow,
+) -> Result<(), ApiError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+    auth_window
+        .check(timestamp, now)
+        .map_err(|e| e.to_api_error(auth_window, timestamp, now))?;
+
+    let verified = match Signature::from_str(signature) {
+        Ok(signature) => {
+            let mut msg = domain.to_vec();
+            msg.extend_from_slice(&timestamp.to_le_bytes());
+            msg.extend_from_slice(payload);
+            signature.verify(&pubkey.to_bytes(), &msg)
+        }
+        Err(_) => false,
+    };
+    if !verified {
+        return Err(ApiError::new(ApiErrorCode::InvalidSignature, "Sig verification failed"));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SignedRequestParams {
+    timestamp: u64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MinerSettingsBody {
+    notify_url: Option<String>,
+    min_notify_amount: u64,
+    // balance at which rewards are claimed automatically, at least min_claim_amount
+    auto_claim_threshold: Option<u64>,
+}
+
+// Signed ahead of the timestamp and body of settings requests.
+const MINER_SETTINGS_DOMAIN: &[u8] = b"settings:";
+
+/// The wallet signs `settings:`, the timestamp (u64 le) and the body.
+#[utoipa::path(
+    post,
+    path = "/miner/settings",
+    tag = "miner",
+    params(SignedRequestParams),
+    request_body = MinerSettingsBody,
+    security(("signed_pubkey" = [])),
+    responses(
+        (status = 200, description = "Settings saved", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid pubkey or settings", body = ApiError),
+        (status = 401, description = "Invalid signature or unknown miner", body = ApiError),
+        (status = 500, description = "Failed to save the settings", body = ApiError)
+    )
+)]
+async fn post_miner_settings(
+    TypedHeader(auth_header): TypedHeader<axum_extra::headers::Authorization<Basic>>,
+    query_params: Query<SignedRequestParams>,
+    Extension(signed_requests): Extension<SignedRequests>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    body: String,
+) -> Result<Response<String>, ApiError> {
+    let user_pubkey = signed_requests
+        .verify_signer(&auth_header, MINER_SETTINGS_DOMAIN, query_params.timestamp, &body)
+        .await?;
+    let app_database = &signed_requests.app_database;
+
+    let settings: MinerSettingsBody = match serde_json::from_str(&body) {
+        Ok(settings) => settings,
+        Err(_) => {
+            return Err(ApiError::new(ApiErrorCode::InvalidRequest, "Invalid settings payload"));
+        }
+    };
+
+    if let Some(url) = &settings.notify_url {
+        if url.len() > 255 {
+            return Err(ApiError::new(ApiErrorCode::InvalidRequest, "notify_url must be an http(s) url of at most 255 characters"));
+        }
+        if let Err(e) = resolve_public_url(url).await {
+            return Err(ApiError::new(ApiErrorCode::InvalidRequest, e));
+        }
+    }
+
+    if let Some(threshold) = settings.auto_claim_threshold {
+        let min_claim_amount = runtime_config.read().await.min_claim_amount;
+        if threshold < min_claim_amount {
+            return Err(ApiError::new(
+                ApiErrorCode::InvalidRequest,
+                format!("auto_claim_threshold must be at least {}", min_claim_amount),
+            ));
+        }
+    }
+
+    let miner = match app_database
+        .get_miner_by_pubkey_str(user_pubkey.to_string())
+        .await
+    {
+        Ok(miner) => miner,
+        Err(_) => {
+            return Err(ApiError::new(ApiErrorCode::NotSignedUp, "pubkey is not signed up"));
+        }
+    };
+
+    match app_database
+        .upsert_miner_settings(
+            miner.id,
+            settings.notify_url,
+            settings.min_notify_amount,
+            settings.auto_claim_threshold,
+        )
+        .await
+    {
+        Ok(_) => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body("SUCCESS".to_string())
+            .unwrap()),
+        Err(_) => Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to save miner settings")),
+    }
+}
+
+/// Admin requests authenticate with `Authorization: Bearer <PASSWORD>`,
+/// compared in constant time. An empty password never matches.
+fn is_admin(headers: &HeaderMap, app_config: &Config) -> bool {
+    if app_config.password.is_empty() {
+        return false;
+    }
+    match headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        Some(value) => value
+            .strip_prefix("Bearer ")
+            .map(|token| token.as_bytes().ct_eq(app_config.password.as_bytes()).into())
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    tag = "admin",
+    security(("admin_password" = [])),
+    responses(
+        (status = 200, body = RuntimeConfig),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    )
+)]
+async fn get_admin_config(
+    headers: HeaderMap,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+) -> Result<Json<RuntimeConfig>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+
+    Ok(Json(runtime_config.read().await.clone()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/config",
+    tag = "admin",
+    request_body(content = HashMap<String, serde_json::Value>, description = "Config keys to update and their new values"),
+    security(("admin_password" = [])),
+    responses(
+        (status = 200, description = "Updated config", body = RuntimeConfig),
+        (status = 400, description = "Unknown key or invalid value", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 500, description = "Failed to save the config", body = ApiError)
+    )
+)]
+async fn put_admin_config(
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(runtime_config): Extension<Arc<RwLock<RuntimeConfig>>>,
+    Json(updates): Json<HashMap<String, serde_json::Value>>,
+) -> Result<Json<RuntimeConfig>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+
+    let changed_by = headers
+        .get("X-Admin-User")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(100).collect::<String>())
+        .unwrap_or_else(|| format!("admin@{}", extract_real_ip(&headers, addr, &app_config.trusted_proxies)));
+
+    // Hold the write lock until the db is updated so concurrent edits can't interleave.
+    let mut runtime_config = runtime_config.write().await;
+    let mut new_config = runtime_config.clone();
+    let mut changes = Vec::new();
+    for (key, value) in updates.iter() {
+        let new_value = match value {
+            serde_json::Value::String(v) => v.clone(),
+            v => v.to_string(),
+        };
+        let old_value = new_config.get(key);
+        new_config
+            .set(key, &new_value)
+            .map_err(|e| ApiError::new(ApiErrorCode::InvalidRequest, e))?;
+        let new_value = new_config.get(key).unwrap();
+        if old_value.as_ref() != Some(&new_value) {
+            changes.push(ConfigChange {
+                config_key: key.clone(),
+                old_value,
+                new_value,
+            });
+        }
+    }
+
+    if !changes.is_empty() {
+        if app_database
+            .update_pool_config(app_config.pool_id, changes.clone(), changed_by.clone())
+            .await
+            .is_err()
+        {
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to update pool config"));
+        }
+        for change in changes {
+            info!(
+                "Pool config {} changed from {:?} to {} by {}",
+                change.config_key, change.old_value, change.new_value, changed_by
+            );
+        }
+        *runtime_config = new_config;
+    }
+
+    Ok(Json(runtime_config.clone()))
+}
+
+// Most audit entries returned by one request.
+const MAX_AUDIT_LOG_LIMIT: u32 = 1000;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AuditLogParams {
+    pubkey: Option<String>,
+    ip: Option<String>,
+    // unix seconds, defaults to the last 24 hours
+    since: Option<i64>,
+    until: Option<i64>,
+    // defaults to 100, at most 1000
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit-log",
+    tag = "admin",
+    params(AuditLogParams),
+    security(("admin_password" = [])),
+    responses(
+        (status = 200, description = "Signup and claim attempts, newest first", body = Vec<AuditEntry>),
+        (status = 400, description = "since is not before until", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 500, description = "Failed to get the audit log", body = ApiError)
+    )
+)]
+async fn get_admin_audit_log(
+    headers: HeaderMap,
+    query_params: Query<AuditLogParams>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(app_rr_database): Extension<Arc<AppRRDatabase>>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let until = query_params.until.unwrap_or(now);
+    let since = query_params.since.unwrap_or(until - 86_400);
+    if since >= until {
+        return Err(ApiError::new(ApiErrorCode::InvalidRequest, "since must be before until"));
+    }
+    let to_datetime = |secs: i64| {
+        chrono::DateTime::from_timestamp(secs, 0)
+            .map(|dt| dt.naive_utc())
+            .ok_or_else(|| ApiError::new(ApiErrorCode::InvalidRequest, "Invalid timestamp"))
+    };
+    let limit = query_params.limit.unwrap_or(100).clamp(1, MAX_AUDIT_LOG_LIMIT);
+
+    let entries = app_rr_database
+        .get_audit_log(
+            app_config.pool_id,
+            query_params.pubkey.clone(),
+            query_params.ip.clone(),
+            to_datetime(since)?,
+            to_datetime(until)?,
+            limit,
+        )
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to get audit log"))?;
+    Ok(Json(entries))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct AssignedNonceRangeEntry {
+    pubkey: String,
+    device_id: Option<String>,
+    epoch_id: u64,
+    range_start: u64,
+    // exclusive
+    range_end: u64,
+    assigned_secs_ago: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/nonce-ranges",
+    tag = "admin",
+    security(("admin_password" = [])),
+    responses(
+        (status = 200, description = "Latest nonce range assigned to every miner device, most recently assigned first", body = Vec<AssignedNonceRangeEntry>),
+        (status = 401, description = "Unauthorized", body = ApiError)
+    )
+)]
+async fn get_admin_nonce_ranges(
+    headers: HeaderMap,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(client_nonce_ranges): Extension<Arc<RwLock<ClientNonceRanges>>>,
+) -> Result<Json<Vec<AssignedNonceRangeEntry>>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+
+    let mut entries: Vec<AssignedNonceRangeEntry> = client_nonce_ranges
+        .read()
+        .await
+        .iter()
+        .map(|((pubkey, device_id), assigned)| {
+            let (epoch_id, range) = assigned.current();
+            AssignedNonceRangeEntry {
+                pubkey: pubkey.to_string(),
+                device_id: device_id.clone(),
+                epoch_id,
+                range_start: range.start,
+                range_end: range.end,
+                assigned_secs_ago: assigned.assigned_at().elapsed().as_secs(),
+            }
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.assigned_secs_ago);
+    Ok(Json(entries))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DeletedNonceRangesResponse {
+    // devices of the miner whose ranges were removed
+    removed: usize,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/nonce-range/{pubkey}",
+    tag = "admin",
+    params(("pubkey" = String, Path, description = "Miner pubkey")),
+    security(("admin_password" = [])),
+    responses(
+        (status = 200, description = "The nonce ranges of every device of the miner were removed, submissions for them are rejected until new work is assigned", body = DeletedNonceRangesResponse),
+        (status = 400, description = "Invalid pubkey", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 404, description = "The miner has no assigned nonce range", body = ApiError)
+    )
+)]
+async fn delete_admin_nonce_range(
+    headers: HeaderMap,
+    axum::extract::Path(pubkey): axum::extract::Path<String>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(client_nonce_ranges): Extension<Arc<RwLock<ClientNonceRanges>>>,
+) -> Result<Json<DeletedNonceRangesResponse>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+    let pubkey = Pubkey::from_str(&pubkey)
+        .map_err(|_| ApiError::new(ApiErrorCode::InvalidPubkey, "Invalid pubkey"))?;
+
+    let mut client_nonce_ranges = client_nonce_ranges.write().await;
+    let before = client_nonce_ranges.len();
+    client_nonce_ranges.retain(|(range_pubkey, _), _| *range_pubkey != pubkey);
+    let removed = before - client_nonce_ranges.len();
+    drop(client_nonce_ranges);
+    if removed == 0 {
+        return Err(ApiError::new(ApiErrorCode::NotFound, "No nonce range assigned to this miner"));
+    }
+
+    info!("Admin removed the nonce ranges of {} devices of {}", removed, display_pubkey(&pubkey));
+    Ok(Json(DeletedNonceRangesResponse { removed }))
+}
+
+/// Signs up miners in bulk without a signup payment, for moving an existing
+/// pool's miners over. Takes a json array of pubkeys, or a csv with the
+/// pubkey in the first column. Invalid and repeated pubkeys are reported per
+/// row without failing the import, and importing the same file again only
+/// reports the miners as existing.
+#[utoipa::path(
+    post,
+    path = "/admin/miners/import",
+    tag = "admin",
+    security(("admin_password" = [])),
+    request_body(content = String, description = "Json array of pubkeys with content type application/json, otherwise a csv", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Outcome of every row", body = MinerImportReport),
+        (status = 400, description = "The body is not a json array of strings, or has too many rows", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 500, description = "Failed to insert the miners, none were imported", body = ApiError)
+    )
+)]
+async fn post_admin_miners_import(
+    headers: HeaderMap,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    body: String,
+) -> Result<Json<MinerImportReport>, ApiError> {
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let rows = miner_import::parse_rows(&body, is_json)
+        .map_err(|e| ApiError::new(ApiErrorCode::InvalidRequest, e))?;
+    let (valid, settled) = miner_import::validate_rows(rows);
+
+    let results = app_database
+        .import_miners(
+            valid.iter().map(|(_, pubkey)| pubkey.to_string()).collect(),
+            app_config.pool_id,
+        )
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to import the miners"))?;
+
+    let mut rows: Vec<MinerImportRow> = valid
+        .into_iter()
+        .zip(results)
+        .map(|((line, pubkey), (miner_created, rewards_created))| MinerImportRow {
+            line,
+            pubkey: pubkey.to_string(),
+            outcome: match (miner_created, rewards_created) {
+                (true, _) => MinerImportOutcome::Created,
+                (false, true) => MinerImportOutcome::Joined,
+                (false, false) => MinerImportOutcome::Existing,
+            },
+        })
+        .chain(settled)
+        .collect();
+    rows.sort_by_key(|row| row.line);
+    let mut report = MinerImportReport::default();
+    for row in rows {
+        report.push(row);
+    }
+
+    info!(
+        "Admin imported miners: {} created, {} joined, {} existing, {} duplicate, {} invalid",
+        report.created, report.joined, report.existing, report.duplicate, report.invalid
+    );
+    Ok(Json(report))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ReloadWalletResponse {
+    previous_pubkey: String,
+    pubkey: String,
+    // false when the file still holds the running key, nothing was swapped
+    swapped: bool,
+    // a proof account was registered for the new key
+    proof_registered: bool,
+}
+
+/// Re-reads the pool's wallet file and swaps the new key in: checks its SOL
+/// balance and tool, registers a proof if it has none, points the pool's db
+/// row at it, then starts a new epoch on its proof's challenge. Refused while
+/// the epoch in progress has a solution, it would be dropped unpaid, and while
+/// the previous proof holds a balance, miners' claims are paid from it.
+#[utoipa::path(
+    post,
+    path = "/admin/reload-wallet",
+    tag = "admin",
+    security(("admin_password" = [])),
+    responses(
+        (status = 200, description = "The key of the wallet file, and whether it was swapped in", body = ReloadWalletResponse),
+        (status = 400, description = "The new key can't run the pool: low SOL balance, tool not equipped, or a guild is configured", body = ApiError),
+        (status = 401, description = "Unauthorized", body = ApiError),
+        (status = 409, description = "A transaction is being sent, the epoch in progress has a solution, the previous proof holds a balance, or the new key is the authority of another pool", body = ApiError),
+        (status = 500, description = "Failed to read the wallet file, register the proof or update the db", body = ApiError)
+    )
+)]
+async fn post_admin_reload_wallet(
+    headers: HeaderMap,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(rpc_client): Extension<Arc<RpcClient>>,
+    Extension(wallet_reload): Extension<WalletReload>,
+) -> Result<Json<ReloadWalletResponse>, ApiError> {
+    let WalletReload {
+        wallet,
+        proof,
+        tool_status,
+        ..
+    } = &wallet_reload;
+    if !is_admin(&headers, &app_config) {
+        return Err(ApiError::new(ApiErrorCode::Unauthorized, "Unauthorized"));
+    }
+    // held until the swap is done, no mine or reprocess transaction is sent meanwhile
+    let _send_guard = wallet_reload.send_lock.try_lock().map_err(|_| {
+        ApiError::new(ApiErrorCode::WalletConflict, "A transaction is being sent, retry once it landed")
+    })?;
+
+    let new_wallet = wallet.read().map_err(|e| {
+        error!("Failed to read wallet {}: {}", wallet.path().display(), e);
+        ApiError::new(ApiErrorCode::InternalError, "Failed to read the wallet file")
+    })?;
+    let previous_pubkey = wallet.pubkey();
+    let pubkey = new_wallet.pubkey();
+    if pubkey == previous_pubkey {
+        return Ok(Json(ReloadWalletResponse {
+            previous_pubkey: previous_pubkey.to_string(),
+            pubkey: pubkey.to_string(),
+            swapped: false,
+            proof_registered: false,
+        }));
+    }
+    ensure_wallet_swappable(&wallet_reload.epoch_starter.epoch_hashes, proof).await?;
+
+    if app_config.guild.is_some() {
+        return Err(ApiError::new(
+            ApiErrorCode::InvalidRequest,
+            "The guild membership is bound to the running key, restart the pool to swap it",
+        ));
+    }
+    let new_tool_status = match app_config.tool {
+        Some(tool) => {
+            let status = get_tool_status(&rpc_client, tool)
+                .await
+                .map_err(|_| ApiError::new(ApiErrorCode::RpcError, "Failed to get the tool"))?;
+            if status.miner != pubkey {
+                return Err(ApiError::new(
+                    ApiErrorCode::InvalidRequest,
+                    format!("Tool {} is not equipped by the new key", tool),
+                ));
+            }
+            Some(status)
+        }
+        None => None,
+    };
+    let balance = rpc_client
+        .get_balance(&pubkey)
+        .await
+        .map_err(|_| ApiError::new(ApiErrorCode::RpcError, "Failed to get the new key's balance"))?;
+    if balance < MIN_AUTHORITY_BALANCE_LAMPORTS {
+        return Err(ApiError::new(ApiErrorCode::InvalidRequest, "The new key's SOL balance is too low")
+            .with_details(serde_json::json!({
+                "balance": balance,
+                "min_balance": MIN_AUTHORITY_BALANCE_LAMPORTS,
+            })));
+    }
+
+    // checked before registering a proof, which costs a transaction
+    let pool_row_matches = match app_database.find_pool_by_authority_pubkey(pubkey.to_string()).await {
+        Ok(Some(pool)) if pool.id == app_config.pool_id => true,
+        Ok(Some(_)) => {
+            return Err(ApiError::new(
+                ApiErrorCode::WalletConflict,
+                "The new key is the authority of another pool",
+            ));
+        }
+        Ok(None) => false,
+        Err(_) => {
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to get the pool"));
+        }
+    };
+
+    let mut proof_registered = false;
+    let new_proof = match get_proof(&rpc_client, pubkey).await {
+        Ok(new_proof) => new_proof,
+        Err(_) => {
+            info!("Registering a proof account for {}", pubkey);
+            let tx = SolanaTransactionBuilder::new()
+                .instruction(get_register_ix(pubkey))
+                .build_and_sign(&new_wallet, &rpc_client)
+                .await
+                .map_err(|_| {
+                    ApiError::new(ApiErrorCode::TransactionFailed, "Failed to build the register transaction")
+                })?;
+            rpc_client
+                .send_and_confirm_transaction_with_spinner_and_commitment(&tx, rpc_client.commitment())
+                .await
+                .map_err(|e| {
+                    error!("Failed to register a proof for {}: {:?}", pubkey, e);
+                    ApiError::new(ApiErrorCode::TransactionFailed, "Failed to register a proof account")
+                })?;
+            proof_registered = true;
+            get_proof(&rpc_client, pubkey)
+                .await
+                .map_err(|_| ApiError::new(ApiErrorCode::RpcError, "Failed to get the registered proof"))?
+        }
+    };
+
+    // a solution may have come in while the proof was registered
+    ensure_wallet_swappable(&wallet_reload.epoch_starter.epoch_hashes, proof).await?;
+    if !pool_row_matches {
+        app_database
+            .update_pool_authority(
+                app_config.pool_id,
+                pubkey.to_string(),
+                proof_pubkey(pubkey).to_string(),
+            )
+            .await
+            .map_err(|_| ApiError::new(ApiErrorCode::DatabaseError, "Failed to update the pool's authority"))?;
+    }
+
+    {
+        // no solution is credited to the previous proof's epoch during the swap
+        let _epoch_hashes = wallet_reload.epoch_starter.epoch_hashes.read().await;
+        let mut proof = proof.lock().await;
+        wallet.swap(new_wallet);
+        *proof = new_proof;
+    }
+    if new_tool_status.is_some() {
+        *tool_status.write().await = new_tool_status;
+    }
+    wallet_reload.proof_challenge.send_replace(new_proof.challenge);
+    wallet_reload.epoch_starter.start(new_proof.challenge).await;
+    warn!("Swapped the pool wallet from {} to {}", previous_pubkey, pubkey);
+
+    Ok(Json(ReloadWalletResponse {
+        previous_pubkey: previous_pubkey.to_string(),
+        pubkey: pubkey.to_string(),
+        swapped: true,
+        proof_registered,
+    }))
+}
+
+/// Refuses a wallet swap that would drop the solved epoch in progress, or
+/// leave miners' balances on a proof the pool no longer signs for.
+async fn ensure_wallet_swappable(epoch_hashes: &RwLock<EpochHashes>, proof: &Mutex<Proof>) -> Result<(), ApiError> {
+    if epoch_hashes.read().await.best_hash.solution.is_some() {
+        return Err(ApiError::new(
+            ApiErrorCode::WalletConflict,
+            "The epoch in progress has a solution, retry once it landed",
+        ));
+    }
+    let balance = proof.lock().await.balance;
+    if balance > 0 {
+        return Err(ApiError::new(
+            ApiErrorCode::WalletConflict,
+            "The previous proof holds a balance, claim it with the previous key first",
+        )
+        .with_details(serde_json::json!({ "balance": balance })));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MinerDelegateBody {
+    delegate: String,
+}
+
+// Signed ahead of the timestamp and body of delegate and revoke requests.
+const DELEGATE_DOMAIN: &[u8] = b"delegate:";
+const REVOKE_DOMAIN: &[u8] = b"revoke:";
+
+/// What signed miner requests are checked against.
+#[derive(Clone)]
+struct SignedRequests {
+    app_database: Arc<AppDatabase>,
+    auth_window: AuthWindow,
+    replays: Arc<Mutex<SignedRequestReplays>>,
+}
+
+impl SignedRequests {
+    /// Verifies a request signed by the wallet in its basic auth header,
+    /// returning the wallet's pubkey. Each signed request is accepted only once.
+    async fn verify_signer(
+        &self,
+        auth_header: &axum_extra::headers::Authorization<Basic>,
+        domain: &[u8],
+        timestamp: u64,
+        body: &str,
+    ) -> Result<Pubkey, ApiError> {
+        let pubkey = Pubkey::from_str(auth_header.username())
+            .map_err(|_| ApiError::new(ApiErrorCode::InvalidPubkey, "Invalid pubkey"))?;
+
+        verify_signed_payload(&pubkey, auth_header.password(), domain, timestamp, body.as_bytes(), &self.auth_window)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+        if !self.replays.lock().await.record(pubkey, timestamp, now, &self.auth_window) {
+            return Err(ApiError::new(ApiErrorCode::InvalidSignature, "Signed request was already used"));
+        }
+        Ok(pubkey)
+    }
+
+    /// Verifies a signed delegate request from a cold wallet, returning its miner
+    /// row and the delegate pubkey.
+    async fn verify(
+        &self,
+        auth_header: &axum_extra::headers::Authorization<Basic>,
+        domain: &[u8],
+        timestamp: u64,
+        body: &str,
+    ) -> Result<(Miner, Pubkey), ApiError> {
+        let miner_pubkey = self.verify_signer(auth_header, domain, timestamp, body).await?;
+
+        let delegate_body: MinerDelegateBody = serde_json::from_str(body)
+            .map_err(|_| ApiError::new(ApiErrorCode::InvalidRequest, "Invalid delegate payload"))?;
+        let delegate = Pubkey::from_str(&delegate_body.delegate)
+            .map_err(|_| ApiError::new(ApiErrorCode::InvalidPubkey, "Invalid delegate pubkey"))?;
+
+        let miner = self
+            .app_database
+            .get_miner_by_pubkey_str(miner_pubkey.to_string())
+            .await
+            .map_err(|_| ApiError::new(ApiErrorCode::NotSignedUp, "pubkey is not signed up"))?;
+
+        Ok((miner, delegate))
+    }
+}
+
+/// The wallet signs `delegate:`, the timestamp (u64 le) and the body.
+#[utoipa::path(
+    post,
+    path = "/miner/delegate",
+    tag = "miner",
+    params(SignedRequestParams),
+    request_body = MinerDelegateBody,
+    security(("signed_pubkey" = [])),
+    responses(
+        (status = 200, description = "Delegate saved", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid pubkey or delegate", body = ApiError),
+        (status = 401, description = "Invalid signature or unknown miner", body = ApiError),
+        (status = 409, description = "Delegate is a miner or delegated to another wallet", body = ApiError),
+        (status = 500, description = "Failed to save the delegate", body = ApiError)
+    )
+)]
+async fn post_miner_delegate(
+    TypedHeader(auth_header): TypedHeader<axum_extra::headers::Authorization<Basic>>,
+    query_params: Query<SignedRequestParams>,
+    Extension(signed_requests): Extension<SignedRequests>,
+    body: String,
+) -> Result<String, ApiError> {
+    let (miner, delegate) =
+        match signed_requests.verify(&auth_header, DELEGATE_DOMAIN, query_params.timestamp, &body).await {
+            Ok(res) => res,
+            Err(e) => return Err(e),
+        };
+    let app_database = &signed_requests.app_database;
+
+    if delegate.to_string() == miner.pubkey {
+        return Err(ApiError::new(ApiErrorCode::InvalidRequest, "A wallet cannot delegate to itself"));
+    }
+
+    // Keys with their own miner row can't be claimed by another wallet.
+    if app_database
+        .get_miner_by_pubkey_str(delegate.to_string())
+        .await
+        .is_ok()
+    {
+        return Err(ApiError::new(ApiErrorCode::DelegateConflict, "Delegate pubkey is already signed up as a miner"));
+    }
+
+    if let Ok(delegated_miner) = app_database.get_delegated_miner(delegate.to_string()).await {
+        if delegated_miner.id != miner.id {
+            return Err(ApiError::new(ApiErrorCode::DelegateConflict, "Delegate pubkey is already delegated to another wallet"));
+        }
+    }
+
+    match app_database
+        .add_miner_delegate(
+            miner.id,
+            delegate.to_string(),
+            auth_header.password().to_string(),
+        )
+        .await
+    {
+        Ok(_) => {
+            info!(
+                "Miner {} delegated mining to {}",
+                display_pubkey(&miner.pubkey),
+                display_pubkey(&delegate)
+            );
+            Ok("SUCCESS".to_string())
+        }
+        Err(_) => Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to save delegate")),
+    }
+}
+
+/// The wallet signs `revoke:`, the timestamp (u64 le) and the body.
+#[utoipa::path(
+    post,
+    path = "/miner/delegate/revoke",
+    tag = "miner",
+    params(SignedRequestParams),
+    request_body = MinerDelegateBody,
+    security(("signed_pubkey" = [])),
+    responses(
+        (status = 200, description = "Delegate revoked", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid pubkey or delegate", body = ApiError),
+        (status = 401, description = "Invalid signature or unknown miner", body = ApiError),
+        (status = 404, description = "No active delegate found", body = ApiError),
+        (status = 500, description = "Failed to revoke the delegate", body = ApiError)
+    )
+)]
+async fn post_miner_delegate_revoke(
+    TypedHeader(auth_header): TypedHeader<axum_extra::headers::Authorization<Basic>>,
+    query_params: Query<SignedRequestParams>,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Extension(signed_requests): Extension<SignedRequests>,
+    Extension(ready_clients): Extension<Arc<Mutex<ReadyClients>>>,
+    body: String,
+) -> Result<String, ApiError> {
+    let (miner, delegate) =
+        match signed_requests.verify(&auth_header, REVOKE_DOMAIN, query_params.timestamp, &body).await {
+            Ok(res) => res,
+            Err(e) => return Err(e),
+        };
+    let app_database = &signed_requests.app_database;
 
-                        return Response::builder()
-                            .status(StatusCode::OK)
-                            .body("SUCCESS".to_string())
-                            .unwrap();
-                    }
-                    Err(e) => {
-                        error!("ERROR: {:?}", e);
-                        return Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .body("FAILED".to_string())
-                            .unwrap();
-                    }
+    match app_database
+        .revoke_miner_delegate(miner.id, delegate.to_string())
+        .await
+    {
+        Ok(true) => {
+            info!(
+                "Miner {} revoked delegate {}",
+                display_pubkey(&miner.pubkey),
+                display_pubkey(&delegate)
+            );
+            // close any session still mining with the revoked key
+            let sockets = app_state.read().await.sockets.clone();
+            for (who, app_client_connection) in sockets.iter() {
+                if app_client_connection.signer == delegate {
+                    let who = *who;
+                    let connection_id = app_client_connection.connection_id;
+                    let app_state = app_state.clone();
+                    let ready_clients = ready_clients.clone();
+                    tokio::spawn(async move {
+                        evict_client_connection(
+                            &app_state,
+                            &ready_clients,
+                            who,
+                            Some(connection_id),
+                            close_code::POLICY,
+                            "delegate revoked",
+                        )
+                        .await;
+                    });
                 }
-            } else {
-                return Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body("FAILED".to_string())
-                    .unwrap();
             }
-        } else {
-            return Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body("failed to get miner account from database".to_string())
-                .unwrap();
+            Ok("SUCCESS".to_string())
         }
-    } else {
-        error!("Claim with invalid pubkey");
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Invalid Pubkey".to_string())
-            .unwrap();
+        Ok(false) => Err(ApiError::new(ApiErrorCode::NotFound, "No active delegate found")),
+        Err(_) => Err(ApiError::new(ApiErrorCode::DatabaseError, "Failed to revoke delegate")),
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct WsQueryParams {
     timestamp: u64,
+    // tags the connection so one wallet can mine from several machines,
+    // signed after the timestamp
+    device_id: Option<String>,
+    // 2 adds the epoch id to work and solution messages, defaults to 1
+    protocol_version: Option<u8>,
 }
 
+/// The mining websocket, also served at /ws so each pool's is at
+/// /pool/{pool_id}/ws and /pools/{name}/ws.
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "miner",
+    params(WsQueryParams),
+    security(("signed_pubkey" = [])),
+    responses(
+        (status = 101, description = "Upgraded to the mining websocket"),
+        (status = 400, description = "device_id is too long, or the pubkey is malformed", body = ApiError),
+        (status = 401, description = "Missing or malformed authorization, invalid signature, expired or future timestamp, unknown miner, or reputation below --min-reputation-score", body = ApiError),
+        (status = 429, description = "A client is already connected with that wallet and device, or the wallet has reached --max-devices-per-miner", body = ApiError),
+        (status = 500, description = "Failed to look up the miner", body = ApiError),
+        (status = 503, description = "Pool is full, see the Retry-After header", body = ApiError)
+    )
+)]
 async fn ws_handler(
     ws: WebSocketUpgrade,
-    TypedHeader(auth_header): TypedHeader<axum_extra::headers::Authorization<Basic>>,
+    headers: HeaderMap,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(app_state): State<Arc<RwLock<AppState>>>,
-    //Extension(app_config): Extension<Arc<Config>>,
-    Extension(client_channel): Extension<UnboundedSender<ClientMessage>>,
+    Extension(app_config): Extension<Arc<Config>>,
+    Extension(client_channel): Extension<Sender<ClientMessage>>,
     Extension(app_database): Extension<Arc<AppDatabase>>,
+    Extension(dashboard_bus): Extension<DashboardEventBus>,
+    Extension(ready_clients): Extension<Arc<Mutex<ReadyClients>>>,
     query_params: Query<WsQueryParams>,
-) -> impl IntoResponse {
-    let msg_timestamp = query_params.timestamp;
-
-    let pubkey = auth_header.username();
-    let signed_msg = auth_header.password();
+) -> Result<axum::response::Response, axum::response::Response> {
+    let device_id = query_params
+        .device_id
+        .clone()
+        .filter(|device_id| !device_id.is_empty());
+    if device_id.as_ref().is_some_and(|d| d.len() > MAX_DEVICE_ID_LEN) {
+        return Err(ApiError::new(ApiErrorCode::InvalidRequest, "device_id is too long").into_response());
+    }
+    let protocol_version = query_params.protocol_version.unwrap_or(1);
+    let real_ip = extract_real_ip(&headers, addr, &app_config.trusted_proxies);
+    let user_agent = extract_user_agent(&headers);
 
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
         .as_secs();
+    // the signature is checked before any database lookup
+    let user_pubkey = match ws_auth::parse_credentials(&headers).and_then(|(pubkey, signature)| {
+        ws_auth::verify_timestamp(
+            &pubkey,
+            &signature,
+            query_params.timestamp,
+            device_id.as_deref(),
+            now,
+            &app_config.auth_window,
+        )
+            .map(|_| pubkey)
+    }) {
+        Ok(user_pubkey) => user_pubkey,
+        Err(e) => {
+            warn!(
+                reason = e.reason(),
+                ip = %real_ip,
+                timestamp = query_params.timestamp,
+                "Rejected websocket authorization"
+            );
+            return Err(e
+                .to_api_error(&app_config.auth_window, query_params.timestamp, now)
+                .into_response());
+        }
+    };
+    let pubkey = user_pubkey.to_string();
+
+    // A delegated hot key mines on behalf of its cold wallet's miner row.
+    let db_miner = match app_database
+        .get_miner_by_pubkey_str(pubkey.to_string())
+        .await
+    {
+        Err(AppDatabaseError::QueryFailed) => {
+            app_database.get_delegated_miner(pubkey.to_string()).await
+        }
+        db_miner => db_miner,
+    };
+
+    let miner;
+    match db_miner {
+        Ok(db_miner) => {
+            miner = db_miner;
+        }
+        Err(AppDatabaseError::QueryFailed) => {
+            return Err(ApiError::new(ApiErrorCode::NotSignedUp, "pubkey is not authorized to mine. please sign up.").into_response());
+        }
+        Err(AppDatabaseError::InteractionFailed) => {
+            return Err(ApiError::new(ApiErrorCode::NotSignedUp, "pubkey is not authorized to mine. please sign up.").into_response());
+        }
+        Err(AppDatabaseError::FailedToGetConnectionFromPool) => {
+            error!("Failed to get database pool connection.");
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Internal Server Error").into_response());
+        }
+        Err(_) => {
+            error!("DB Error: Catch all.");
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Internal Server Error").into_response());
+        }
+    }
+
+    let miner_pubkey = match Pubkey::from_str(&miner.pubkey) {
+        Ok(miner_pubkey) => miner_pubkey,
+        Err(_) => {
+            error!("Invalid pubkey stored for miner {}", miner.id);
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Internal Server Error").into_response());
+        }
+    };
 
-    // Signed authentication message is only valid for 30 seconds
-    if (now - query_params.timestamp) >= 30 {
-        return Err((StatusCode::UNAUTHORIZED, "Timestamp too old."));
+    // checked again when the connection is registered, another upgrade for
+    // the wallet may be accepted meanwhile
+    match device_conflict(&app_state.read().await.device_ids(&miner_pubkey), &device_id, app_config.max_devices_per_miner) {
+        Some(DeviceConflict::AlreadyConnected) => {
+            return Err(ApiError::new(ApiErrorCode::AlreadyConnected, "A client is already connected with that wallet").into_response());
+        }
+        Some(DeviceConflict::TooManyDevices) => {
+            return Err(ApiError::new(ApiErrorCode::AlreadyConnected, "Too many devices connected with that wallet")
+                .with_details(serde_json::json!({ "max_devices_per_miner": app_config.max_devices_per_miner }))
+                .into_response());
+        }
+        None => {}
     }
 
-    // verify client
-    if let Ok(user_pubkey) = Pubkey::from_str(pubkey) {
-        {
-            let mut already_connected = false;
-            for (_, app_client_connection) in app_state.read().await.sockets.iter() {
-                if user_pubkey == app_client_connection.pubkey {
-                    already_connected = true;
+    if !app_config.bypasses_capacity(&miner_pubkey) && is_pool_full(&app_config, app_state.read().await.sockets.len()) {
+        return Err(pool_full_response());
+    }
+
+    if !miner.enabled {
+        return Err(ApiError::new(ApiErrorCode::MinerDisabled, "pubkey is not authorized to mine").into_response());
+    }
+
+    match app_database.get_miner_reputation(miner.id).await {
+        Ok(Some(reputation)) if reputation.reputation_score < app_config.min_reputation_score => {
+            return Err(ApiError::new(ApiErrorCode::LowReputation, "reputation score is too low to mine")
+                .with_details(serde_json::json!({
+                    "reputation_score": reputation.reputation_score,
+                    "min_reputation_score": app_config.min_reputation_score,
+                }))
+                .into_response());
+        }
+        Ok(_) => {}
+        Err(_) => {
+            error!("Failed to get reputation of miner {}", miner.id);
+            return Err(ApiError::new(ApiErrorCode::DatabaseError, "Internal Server Error").into_response());
+        }
+    }
+
+    // a miner signed up on another pool earns here from its first submission
+    let new_reward = InsertReward {
+        miner_id: miner.id,
+        pool_id: app_config.pool_id,
+    };
+    if app_database.add_new_reward(new_reward).await.is_err() {
+        error!("Failed to add the rewards of miner {} in pool {}", miner.id, app_config.pool_id);
+        return Err(ApiError::new(ApiErrorCode::DatabaseError, "Internal Server Error").into_response());
+    }
+
+    if miner_pubkey != user_pubkey {
+        info!(
+            "Client: {addr} connected with delegate {} for {}.",
+            display_pubkey(&pubkey),
+            display_pubkey(&miner_pubkey)
+        );
+    } else {
+        info!("Client: {addr} connected with pubkey {}.", display_pubkey(&pubkey));
+    }
+    if let Some(device_id) = &device_id {
+        info!("Client: {addr} is device {device_id}.");
+    }
+    info!(
+        "Client: {addr} has ip {real_ip} and user agent {}.",
+        user_agent.as_deref().unwrap_or("none")
+    );
+    Ok(ws.on_upgrade(move |socket| {
+        handle_socket(
+            socket,
+            addr,
+            miner_pubkey,
+            user_pubkey,
+            miner.id,
+            device_id,
+            protocol_version,
+            real_ip,
+            user_agent,
+            app_state,
+            ready_clients,
+            app_config,
+            app_database,
+            client_channel,
+            dashboard_bus,
+        )
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeviceConflict {
+    AlreadyConnected,
+    TooManyDevices,
+}
+
+/// Why a new connection for the miner is refused, given its connections,
+/// None when it's admitted. Extra connections are only allowed when every
+/// one of them is tagged with its own device_id.
+fn device_conflict(
+    connected_devices: &[&DeviceId],
+    device_id: &DeviceId,
+    max_devices_per_miner: usize,
+) -> Option<DeviceConflict> {
+    let distinct_device = device_id.is_some()
+        && connected_devices
+            .iter()
+            .all(|d| d.is_some() && *d != device_id);
+    if !connected_devices.is_empty() && !distinct_device {
+        return Some(DeviceConflict::AlreadyConnected);
+    }
+    if connected_devices.len() >= max_devices_per_miner {
+        return Some(DeviceConflict::TooManyDevices);
+    }
+    None
+}
+
+/// How sending an epoch's solution went.
+struct EpochSubmission {
+    outcome: EpochOutcome,
+    attempts_used: u8,
+    final_priority_fee: u64,
+    timings: EpochTimings,
+}
+
+fn spawn_record_epoch_outcome(
+    app_database: Arc<AppDatabase>,
+    pool_id: i32,
+    challenge: [u8; 32],
+    submission: EpochSubmission,
+    histogram: &DifficultyHistogram,
+) {
+    let difficulty_histogram = serde_json::to_string(histogram).ok();
+    tokio::spawn(async move {
+        let Some(challenge_id) = find_epoch_outcome_challenge_id(&app_database, challenge).await
+        else {
+            error!("Challenge not found in db, dropping the epoch outcome");
+            return;
+        };
+
+        let epoch_outcome = InsertEpochOutcome {
+            pool_id,
+            challenge_id,
+            outcome: submission.outcome,
+            attempts_used: submission.attempts_used,
+            final_priority_fee: submission.final_priority_fee,
+            first_send_ms: submission.timings.first_send_ms,
+            confirmed_ms: submission.timings.confirmed_ms,
+            mine_event_ms: submission.timings.mine_event_ms,
+            difficulty_histogram,
+        };
+        for attempt in 1..=EPOCH_OUTCOME_MAX_ATTEMPTS {
+            match app_database.record_epoch_outcome(epoch_outcome.clone()).await {
+                Ok(()) => break,
+                Err(e) if !e.is_retriable() => {
+                    info!("{} already exists in db, not retrying", InsertEpochOutcome::describe());
                     break;
                 }
+                Err(_) if attempt == EPOCH_OUTCOME_MAX_ATTEMPTS => {
+                    error!("Failed to add {} to db, giving up", InsertEpochOutcome::describe());
+                }
+                Err(_) => {
+                    error!("Failed to add {} to db! Retrying...", InsertEpochOutcome::describe());
+                    tokio::time::sleep(Duration::from_millis(2000)).await;
+                }
             }
-            if already_connected {
-                return Err((
-                    StatusCode::TOO_MANY_REQUESTS,
-                    "A client is already connected with that wallet",
-                ));
-            }
-        };
-
-        let db_miner = app_database
-            .get_miner_by_pubkey_str(pubkey.to_string())
-            .await;
+        }
+    });
+}
 
-        let miner;
-        match db_miner {
-            Ok(db_miner) => {
-                miner = db_miner;
-            }
-            Err(AppDatabaseError::QueryFailed) => {
-                return Err((
-                    StatusCode::UNAUTHORIZED,
-                    "pubkey is not authorized to mine. please sign up.",
-                ));
-            }
-            Err(AppDatabaseError::InteractionFailed) => {
-                return Err((
-                    StatusCode::UNAUTHORIZED,
-                    "pubkey is not authorized to mine. please sign up.",
-                ));
-            }
-            Err(AppDatabaseError::FailedToGetConnectionFromPool) => {
-                error!("Failed to get database pool connection.");
-                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error"));
+/// Id of the challenge row of the epoch, None when it can't be found within
+/// EPOCH_OUTCOME_MAX_ATTEMPTS, so a missing row doesn't keep a task alive.
+async fn find_epoch_outcome_challenge_id(
+    app_database: &AppDatabase,
+    challenge: [u8; 32],
+) -> Option<i32> {
+    for attempt in 1..=EPOCH_OUTCOME_MAX_ATTEMPTS {
+        match app_database.get_challenge_by_challenge(challenge.to_vec()).await {
+            Ok(c) => return Some(c.id),
+            Err(e) if !e.is_retriable() => {
+                error!("Non-retriable db error: {:?}", e);
+                return None;
             }
-            Err(_) => {
-                error!("DB Error: Catch all.");
-                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error"));
+            Err(_) if attempt < EPOCH_OUTCOME_MAX_ATTEMPTS => {
+                error!("Failed to get challenge for epoch outcome! Retrying...");
+                tokio::time::sleep(Duration::from_millis(1000)).await;
             }
+            Err(_) => {}
         }
+    }
+    None
+}
 
-        if !miner.enabled {
-            return Err((StatusCode::UNAUTHORIZED, "pubkey is not authorized to mine"));
+/// Epoch and challenge a solution from a legacy client, which doesn't name its
+/// epoch, was computed for and whether it's valid. A solution that only holds
+/// for the previous challenge was computed before the rotation.
+fn legacy_solution_challenge(
+    solution: &Solution,
+    current_challenge: [u8; 32],
+    previous: Option<EpochChallenge>,
+) -> (Option<u64>, [u8; 32], bool) {
+    if solution.is_valid(&current_challenge) {
+        return (None, current_challenge, true);
+    }
+    match previous {
+        Some(previous)
+            if previous.challenge != current_challenge && solution.is_valid(&previous.challenge) =>
+        {
+            (Some(previous.epoch_id), previous.challenge, true)
         }
+        _ => (None, current_challenge, false),
+    }
+}
 
-        if let Ok(signature) = Signature::from_str(signed_msg) {
-            let ts_msg = msg_timestamp.to_le_bytes();
+fn spawn_record_miner_solution(app_database: Arc<AppDatabase>, miner_id: i32, valid: bool) {
+    tokio::spawn(async move {
+        if app_database.record_miner_solution(miner_id, valid).await.is_err() {
+            error!("Failed to update reputation of miner {}", miner_id);
+        }
+    });
+}
 
-            if signature.verify(&user_pubkey.to_bytes(), &ts_msg) {
-                info!("Client: {addr} connected with pubkey {pubkey}.");
-                return Ok(ws.on_upgrade(move |socket| {
-                    handle_socket(
-                        socket,
-                        addr,
-                        user_pubkey,
-                        miner.id,
-                        app_state,
-                        client_channel,
-                    )
-                }));
-            } else {
-                return Err((StatusCode::UNAUTHORIZED, "Sig verification failed"));
-            }
-        } else {
-            return Err((StatusCode::UNAUTHORIZED, "Invalid signature"));
+async fn record_late_submission(
+    app_database: &AppDatabase,
+    challenge: [u8; 32],
+    miner_id: i32,
+    solution: &Solution,
+    range_size: u64,
+) {
+    let difficulty = stored_difficulty(solution, range_size);
+    let challenge_id = match app_database.get_challenge_by_challenge(challenge.to_vec()).await {
+        Ok(challenge) => challenge.id,
+        Err(_) => {
+            error!("Challenge of late submission not found in db");
+            return;
         }
-    } else {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid pubkey"));
+    };
+    let late_submission = InsertSubmission {
+        miner_id,
+        challenge_id,
+        nonce: u64::from_le_bytes(solution.n),
+        difficulty,
+        range_size,
+    };
+    if app_database.add_late_submission(late_submission).await.is_err() {
+        error!("Failed to add late submission to db");
+    }
+}
+
+fn elapsed_ms(since: Instant) -> u32 {
+    since.elapsed().as_millis().min(u32::MAX as u128) as u32
+}
+
+fn is_pool_full(app_config: &Config, active_miners: usize) -> bool {
+    match app_config.max_miners {
+        Some(max_miners) => active_miners >= max_miners,
+        None => false,
     }
 }
 
+fn pool_full_response() -> axum::response::Response {
+    let mut response = ApiError::new(ApiErrorCode::PoolFull, "Pool is at capacity, please try again later.").into_response();
+    response.headers_mut().insert(
+        RETRY_AFTER,
+        HeaderValue::from(POOL_FULL_RETRY_AFTER_SECS),
+    );
+    response
+}
+
 async fn handle_socket(
     mut socket: WebSocket,
     who: SocketAddr,
     who_pubkey: Pubkey,
+    who_signer: Pubkey,
     who_miner_id: i32,
+    who_device_id: DeviceId,
+    who_protocol_version: u8,
+    who_real_ip: IpAddr,
+    who_user_agent: Option<String>,
     rw_app_state: Arc<RwLock<AppState>>,
-    client_channel: UnboundedSender<ClientMessage>,
+    ready_clients: Arc<Mutex<ReadyClients>>,
+    app_config: Arc<Config>,
+    app_database: Arc<AppDatabase>,
+    client_channel: Sender<ClientMessage>,
+    dashboard_bus: DashboardEventBus,
 ) {
+    // empty, the keepalive system ignores its pong
     if socket
-        .send(axum::extract::ws::Message::Ping(vec![1, 2, 3]))
+        .send(axum::extract::ws::Message::Ping(Vec::new()))
         .await
         .is_ok()
     {
@@ -1729,40 +7194,219 @@ async fn handle_socket(
     }
 
     let (sender, mut receiver) = socket.split();
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+    let session = Arc::new(SessionStats::new());
     let mut app_state = rw_app_state.write().await;
     if app_state.sockets.contains_key(&who) {
         info!("Socket addr: {who} already has an active connection");
         return;
+    } else if !app_config.bypasses_capacity(&who_pubkey) && is_pool_full(&app_config, app_state.sockets.len()) {
+        // Another client may have taken the last slot since the upgrade was accepted.
+        info!("Pool is full, dropping connection from {who}");
+        return;
+    } else if let Some(conflict) =
+        device_conflict(&app_state.device_ids(&who_pubkey), &who_device_id, app_config.max_devices_per_miner)
+    {
+        // Another upgrade for the wallet may have registered since this one was accepted.
+        info!("{:?}, dropping connection from {who}", conflict);
+        return;
     } else {
         let new_app_client_connection = AppClientConnection {
+            connection_id,
             pubkey: who_pubkey,
+            signer: who_signer,
             miner_id: who_miner_id,
+            device_id: who_device_id.clone(),
             socket: Arc::new(Mutex::new(sender)),
+            protocol_version: who_protocol_version,
+            send_failures: Arc::new(AtomicU32::new(0)),
+            session: session.clone(),
+            real_ip: who_real_ip,
+            user_agent: who_user_agent,
         };
         app_state.sockets.insert(who, new_app_client_connection);
     }
+    let count = app_state.sockets.len();
     drop(app_state);
+    dashboard_bus.send(DashboardEvent::MinerJoined {
+        pubkey: who_pubkey.to_string(),
+    });
+    dashboard_bus.send(DashboardEvent::ConnectedMiners { count });
 
+    let app_client_channel = client_channel.clone();
     let _ = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
-            if process_message(msg, who, client_channel.clone()).is_break() {
+            if process_message(msg, who, connection_id, app_client_channel.clone()).is_break() {
                 break;
             }
         }
     })
     .await;
 
-    let mut app_state = rw_app_state.write().await;
-    app_state.sockets.remove(&who);
-    drop(app_state);
+    if client_channel
+        .send(ClientMessage::Disconnect(who, connection_id))
+        .await
+        .is_err()
+    {
+        // the handler is gone, at least free the connection slot
+        remove_client_connection(&rw_app_state, &ready_clients, who, Some(connection_id)).await;
+    }
+    let summary = session.summary();
+    let miner_session = InsertMinerSession {
+        miner_id: who_miner_id,
+        pool_id: app_config.pool_id,
+        device_id: who_device_id,
+        started_at: summary.started_at,
+        ended_at: summary.ended_at,
+        epochs: summary.epochs,
+        submissions: summary.submissions,
+        best_difficulty: summary.best_difficulty,
+        earned: summary.earned,
+    };
+    if app_database.add_miner_session(miner_session).await.is_err() {
+        error!("Failed to add {} to db", InsertMinerSession::describe());
+    }
+    dashboard_bus.send(DashboardEvent::MinerLeft {
+        pubkey: who_pubkey.to_string(),
+    });
+
+    info!("Client: {} disconnected!", display_pubkey(&who_pubkey));
+}
+
+/// Removes a client's connection and its readiness. Every disconnect path
+/// goes through here. With a connection_id, a newer connection that reused
+/// the addr is left alone.
+async fn remove_client_connection(
+    app_state: &RwLock<AppState>,
+    ready_clients: &Mutex<ReadyClients>,
+    who: SocketAddr,
+    connection_id: Option<u64>,
+) {
+    let mut writer = app_state.write().await;
+    let removed = match writer.sockets.get(&who) {
+        Some(connection) if connection_id.is_none_or(|id| id == connection.connection_id) => {
+            writer.sockets.remove(&who)
+        }
+        _ => None,
+    };
+    drop(writer);
+
+    let mut ready_clients = ready_clients.lock().await;
+    if let Some(connection) = removed {
+        ready_clients.remove(&connection.connection_id);
+    }
+    if let Some(connection_id) = connection_id {
+        ready_clients.remove(&connection_id);
+    }
+}
+
+/// Sends the client a close frame saying why it is dropped and when to
+/// reconnect, then removes its connection. The close is best effort.
+async fn evict_client_connection(
+    app_state: &RwLock<AppState>,
+    ready_clients: &Mutex<ReadyClients>,
+    who: SocketAddr,
+    connection_id: Option<u64>,
+    code: u16,
+    reason: &str,
+) {
+    let connection = app_state
+        .read()
+        .await
+        .sockets
+        .get(&who)
+        .filter(|connection| connection_id.is_none_or(|id| id == connection.connection_id))
+        .cloned();
+    if let Some(connection) = connection {
+        let summary = serde_json::to_string(&connection.session.summary()).unwrap_or_default();
+        let send_summary = async {
+            connection.socket.lock().await.send(Message::Text(summary)).await
+        };
+        let _ = tokio::time::timeout(SEND_TIMEOUT, send_summary).await;
+        let close_frame = CloseFrame {
+            code,
+            reason: format!("{}: reconnect in {} seconds", reason, RECONNECT_DELAY_SECS).into(),
+        };
+        let send_close = async {
+            connection
+                .socket
+                .lock()
+                .await
+                .send(Message::Close(Some(close_frame)))
+                .await
+        };
+        if !matches!(tokio::time::timeout(SEND_TIMEOUT, send_close).await, Ok(Ok(()))) {
+            info!("Could not send close frame to {}", who);
+        }
+    }
+    remove_client_connection(app_state, ready_clients, who, connection_id).await;
+}
+
+/// Sends a message to a client connection, returning whether it was sent.
+/// After MAX_CONSECUTIVE_SEND_FAILURES failures in a row the connection is
+/// removed right away. Callers must not hold the app state lock.
+async fn send_client_message(
+    app_state: &RwLock<AppState>,
+    ready_clients: &Mutex<ReadyClients>,
+    who: SocketAddr,
+    connection: &AppClientConnection,
+    msg: Message,
+) -> bool {
+    let send = async { connection.socket.lock().await.send(msg).await };
+    if let Ok(Ok(())) = tokio::time::timeout(SEND_TIMEOUT, send).await {
+        connection.send_failures.store(0, Ordering::Relaxed);
+        return true;
+    }
+
+    let failures = connection.send_failures.fetch_add(1, Ordering::Relaxed) + 1;
+    if failures >= MAX_CONSECUTIVE_SEND_FAILURES {
+        error!("{} consecutive sends to {} failed, disconnecting", failures, who);
+        evict_client_connection(
+            app_state,
+            ready_clients,
+            who,
+            Some(connection.connection_id),
+            close_code::ERROR,
+            "Too many failed sends",
+        )
+        .await;
+    } else {
+        error!("Failed to send message to {}", who);
+    }
+    false
+}
+
+/// Tells the miner on `who` why its submission wasn't credited.
+async fn send_submission_rejected(
+    app_state: &RwLock<AppState>,
+    ready_clients: &Mutex<ReadyClients>,
+    who: SocketAddr,
+    rejected: SubmissionRejected,
+) {
+    let connection = app_state.read().await.sockets.get(&who).cloned();
+    if let (Some(connection), Ok(text)) = (connection, serde_json::to_string(&rejected)) {
+        send_client_message(app_state, ready_clients, who, &connection, Message::Text(text)).await;
+    }
+}
 
-    info!("Client: {} disconnected!", who_pubkey.to_string());
+/// Preflight OPTIONS requests are answered by the cors layer itself.
+fn cors_layer() -> CorsLayer {
+    CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([CONTENT_TYPE, AUTHORIZATION])
+        .expose_headers([
+            api_error::REQUEST_ID_HEADER,
+            axum::http::HeaderName::from_static("x-total-distributed"),
+            axum::http::HeaderName::from_static("x-participant-count"),
+        ])
+        .allow_origin(tower_http::cors::Any)
 }
 
 fn process_message(
     msg: Message,
     who: SocketAddr,
-    client_channel: UnboundedSender<ClientMessage>,
+    connection_id: u64,
+    client_channel: Sender<ClientMessage>,
 ) -> ControlFlow<(), ()> {
     match msg {
         Message::Text(_t) => {
@@ -1770,21 +7414,40 @@ fn process_message(
         }
         Message::Binary(d) => {
             // first 8 bytes are message type
-            let message_type = d[0];
+            let Some(&message_type) = d.first() else {
+                error!(">>> {} sent an empty message", who);
+                return ControlFlow::Break(());
+            };
             match message_type {
                 0 => {
-                    let msg = ClientMessage::Ready(who);
-                    let _ = client_channel.send(msg);
+                    let msg = ClientMessage::Ready(who, connection_id);
+                    return enqueue_client_message(&client_channel, who, msg);
                 }
                 1 => {
                     let msg = ClientMessage::Mining(who);
-                    let _ = client_channel.send(msg);
+                    return enqueue_client_message(&client_channel, who, msg);
                 }
-                2 => {
+                2 | 3 => {
+                    // type 3 is a type 2 solution preceded by the epoch id it was computed for
+                    let epoch_id_len = if message_type == 3 { 8 } else { 0 };
+                    // type, epoch id, digest, nonce and pubkey, then the signature
+                    if d.len() < 1 + epoch_id_len + 16 + 8 + 32 {
+                        error!(">>> {} sent a truncated solution, disconnecting", who);
+                        return ControlFlow::Break(());
+                    }
+                    let mut b_index = 1;
+                    let epoch_id = if message_type == 3 {
+                        let mut epoch_id = [0u8; 8];
+                        epoch_id.copy_from_slice(&d[b_index..b_index + 8]);
+                        b_index += 8;
+                        Some(u64::from_le_bytes(epoch_id))
+                    } else {
+                        None
+                    };
+
                     // parse solution from message data
                     let mut solution_bytes = [0u8; 16];
                     // extract (16 u8's) from data for hash digest
-                    let mut b_index = 1;
                     for i in 0..16 {
                         solution_bytes[i] = d[i + b_index];
                     }
@@ -1816,8 +7479,8 @@ fn process_message(
                             if sig.verify(&pubkey.to_bytes(), &hash_nonce_message) {
                                 let solution = Solution::new(solution_bytes, nonce);
 
-                                let msg = ClientMessage::BestSolution(who, solution, pubkey);
-                                let _ = client_channel.send(msg);
+                                let msg = ClientMessage::BestSolution(who, solution, pubkey, epoch_id);
+                                return enqueue_client_message(&client_channel, who, msg);
                             } else {
                                 error!("Client submission sig verification failed.");
                             }
@@ -1844,9 +7507,9 @@ fn process_message(
             }
             return ControlFlow::Break(());
         }
-        Message::Pong(_v) => {
-            let msg = ClientMessage::Pong(who);
-            let _ = client_channel.send(msg);
+        Message::Pong(payload) => {
+            let msg = ClientMessage::Pong(who, connection_id, payload);
+            return enqueue_client_message(&client_channel, who, msg);
         }
         Message::Ping(_v) => {
             //println!(">>> {who} sent ping with {v:?}");
@@ -1856,7 +7519,184 @@ fn process_message(
     ControlFlow::Continue(())
 }
 
-async fn proof_tracking_system(ws_url: String, wallet: Arc<Keypair>, proof: Arc<Mutex<Proof>>) {
+/// Queues a message for the client message handler. A client whose messages
+/// arrive faster than they are handled fills the channel and is disconnected.
+fn enqueue_client_message(
+    client_channel: &Sender<ClientMessage>,
+    who: SocketAddr,
+    msg: ClientMessage,
+) -> ControlFlow<(), ()> {
+    match client_channel.try_send(msg) {
+        Ok(_) => ControlFlow::Continue(()),
+        Err(TrySendError::Full(_)) => {
+            error!("Client message channel is full, disconnecting {}", who);
+            ControlFlow::Break(())
+        }
+        Err(TrySendError::Closed(_)) => {
+            error!("Client message channel is closed, disconnecting {}", who);
+            ControlFlow::Break(())
+        }
+    }
+}
+
+/// Moves the pool to a new challenge: closes the previous challenge and adds
+/// the new one in the db, resets the nonce counter, then bumps the epoch
+/// generation and clears the epoch hashes of the previous generation under
+/// the same lock, so no submission for the previous challenge is mixed in.
+#[derive(Clone)]
+struct EpochStarter {
+    app_database: Arc<AppDatabase>,
+    pool_id: i32,
+    nonce: Arc<Mutex<u64>>,
+    nonce_start: u64,
+    epoch_hashes: Arc<RwLock<EpochHashes>>,
+    best_difficulty_timeline: BestDifficultyTimeline,
+    epoch_generation: watch::Sender<EpochGeneration>,
+    state_snapshot_path: PathBuf,
+    // nonces handed out for the current challenge, stored when it closes
+    nonces_assigned: Arc<AtomicU64>,
+    // held for a whole transition, a challenge is only started once
+    lock: Arc<Mutex<()>>,
+}
+
+/// What POST /admin/reload-wallet swaps or restarts along with the keypair.
+#[derive(Clone)]
+struct WalletReload {
+    wallet: PoolWallet,
+    proof: Arc<Mutex<Proof>>,
+    tool_status: Arc<RwLock<Option<ToolStatus>>>,
+    // held by the mine submission loop and reprocessing while they send
+    send_lock: Arc<Mutex<()>>,
+    proof_challenge: watch::Sender<[u8; 32]>,
+    epoch_starter: EpochStarter,
+}
+
+impl EpochStarter {
+    async fn start(&self, challenge: [u8; 32]) {
+        let _guard = self.lock.lock().await;
+        let previous = *self.epoch_generation.borrow();
+        if previous.challenge == challenge {
+            return;
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        info!("Closing previous challenge in db");
+        while let Err(e) = self
+            .app_database
+            .close_challenge(previous.challenge.to_vec(), now)
+            .await
+        {
+            if !e.is_retriable() {
+                error!("Non-retriable db error: {:?}", e);
+                break;
+            }
+            error!("Failed to close previous challenge in db, retrying...");
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+        }
+        let nonces_assigned = self.nonces_assigned.swap(0, Ordering::Relaxed);
+        if let Err(e) = self
+            .app_database
+            .add_challenge_nonces_assigned(previous.challenge.to_vec(), nonces_assigned)
+            .await
+        {
+            error!("Failed to store the nonces assigned for the previous challenge: {:?}", e);
+        }
+
+        info!("Adding new challenge to db");
+        let new_challenge = InsertChallenge {
+            pool_id: self.pool_id,
+            challenge: challenge.to_vec(),
+            rewards_earned: None,
+            started_at: now,
+        };
+        // the challenge may already exist if the server restarted mid-epoch
+        let mut backoff = Duration::from_millis(1000);
+        while let Err(e) = self
+            .app_database
+            .add_new_challenge(new_challenge.clone())
+            .await
+        {
+            if !e.is_retriable() {
+                info!("{} already exists in db, not retrying", InsertChallenge::describe());
+                break;
+            }
+            error!("Failed to add {} to db, retrying...", InsertChallenge::describe());
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(Duration::from_secs(16));
+        }
+        info!("New challenge successfully added to db");
+
+        *self.nonce.lock().await = self.nonce_start;
+
+        let next = previous.next(challenge);
+        let mut epoch_hashes = self.epoch_hashes.write().await;
+        self.epoch_generation.send_replace(next);
+        if epoch_hashes.advance(previous.generation, next.generation) {
+            info!("reset epoch hashes");
+            self.best_difficulty_timeline.reset().await;
+        }
+        drop(epoch_hashes);
+        state_snapshot::remove(&self.state_snapshot_path);
+    }
+}
+
+/// Waits for the pool proof's challenge to move past old_challenge. When no
+/// rotation is notified within CHALLENGE_CHANGE_TIMEOUT the proof is fetched
+/// directly with fetch_proof, in case the proof websocket missed the update.
+async fn wait_for_new_challenge<F, Fut>(
+    fetch_proof: F,
+    old_challenge: [u8; 32],
+    proof: &Mutex<Proof>,
+    proof_challenge: &watch::Sender<[u8; 32]>,
+) -> Proof
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Proof, GetProofError>>,
+{
+    let mut receiver = proof_challenge.subscribe();
+    loop {
+        let rotated = tokio::time::timeout(
+            CHALLENGE_CHANGE_TIMEOUT,
+            receiver.wait_for(|challenge| *challenge != old_challenge),
+        )
+        .await
+        .is_ok();
+        if rotated {
+            return *proof.lock().await;
+        }
+
+        warn!(
+            "No challenge rotation notified in {}s, fetching the proof",
+            CHALLENGE_CHANGE_TIMEOUT.as_secs()
+        );
+        match fetch_proof().await {
+            Ok(latest_proof) if latest_proof.challenge != old_challenge => {
+                let mut app_proof = proof.lock().await;
+                // the websocket may have caught up while fetching
+                if app_proof.challenge == old_challenge {
+                    *app_proof = latest_proof;
+                    proof_challenge.send_replace(latest_proof.challenge);
+                }
+                return *app_proof;
+            }
+            Ok(_) => {
+                info!("Proof challenge not updated yet..");
+            }
+            Err(e) => {
+                error!("Failed to fetch the proof: {:?}", e);
+            }
+        }
+    }
+}
+
+async fn proof_tracking_system(
+    ws_url: String,
+    wallet: PoolWallet,
+    proof: Arc<Mutex<Proof>>,
+    proof_challenge: watch::Sender<[u8; 32]>,
+    proof_updates: Arc<RwLock<ProofUpdates>>,
+) {
+    let mut wallet_changes = wallet.subscribe();
     loop {
         info!("Establishing rpc websocket connection...");
         let mut ps_client = PubsubClient::new(&ws_url).await;
@@ -1870,11 +7710,10 @@ async fn proof_tracking_system(ws_url: String, wallet: Arc<Keypair>, proof: Arc<
         }
         info!("RPC WS connection established!");
 
-        let app_wallet = wallet.clone();
         if let Ok(ps_client) = ps_client {
             let ps_client = Arc::new(ps_client);
             let app_proof = proof.clone();
-            let account_pubkey = proof_pubkey(app_wallet.pubkey());
+            let account_pubkey = proof_pubkey(wallet_changes.borrow_and_update().pubkey());
             let pubsub = ps_client
                 .account_subscribe(
                     &account_pubkey,
@@ -1889,7 +7728,18 @@ async fn proof_tracking_system(ws_url: String, wallet: Arc<Keypair>, proof: Arc<
 
             info!("Tracking pool proof updates with websocket");
             if let Ok((mut account_sub_notifications, _account_unsub)) = pubsub {
-                while let Some(response) = account_sub_notifications.next().await {
+                loop {
+                    // a swapped wallet has another proof, resubscribe to it
+                    let response = tokio::select! {
+                        response = account_sub_notifications.next() => match response {
+                            Some(response) => response,
+                            None => break,
+                        },
+                        _ = wallet_changes.changed() => {
+                            proof_updates.write().await.forget_account_data();
+                            break;
+                        }
+                    };
                     let data = response.value.data.decode();
                     if let Some(data_bytes) = data {
                         // if let Ok(bus) = Bus::try_from_bytes(&data_bytes) {
@@ -1899,13 +7749,33 @@ async fn proof_tracking_system(ws_url: String, wallet: Arc<Keypair>, proof: Arc<
                         //     let _ = sender.send(AccountUpdatesData::TreasuryConfigData(*coal_config));
                         // }
                         if let Ok(new_proof) = Proof::try_from_bytes(&data_bytes) {
-                            info!("Got new proof data");
                             // let _ = sender.send(AccountUpdatesData::ProofData(*proof));
                             //
+                            let now = chrono::Utc::now().timestamp();
                             {
                                 let mut app_proof = app_proof.lock().await;
+                                let changed = *app_proof != *new_proof;
+                                if !proof_updates.write().await.record_notification(
+                                    response.context.slot,
+                                    &data_bytes,
+                                    changed,
+                                    now,
+                                ) {
+                                    continue;
+                                }
+                                info!("Got new proof data at slot {}", response.context.slot);
                                 *app_proof = *new_proof;
                             }
+                            // only wakes the tasks waiting on the challenge when it changed,
+                            // not on balance updates
+                            let modified = proof_challenge.send_if_modified(|challenge| {
+                                let modified = *challenge != new_proof.challenge;
+                                *challenge = new_proof.challenge;
+                                modified
+                            });
+                            if modified {
+                                proof_updates.write().await.record_challenge_change();
+                            }
                         }
                     }
                 }
@@ -1914,84 +7784,349 @@ async fn proof_tracking_system(ws_url: String, wallet: Arc<Keypair>, proof: Arc<
     }
 }
 
-async fn pong_tracking_system(
-    app_pongs: Arc<RwLock<LastPong>>,
-    app_state: Arc<RwLock<AppState>>,
+/// Keeps coal_token_balance in sync with the pool authority's COAL token
+/// account, which is not the unclaimed balance of the proof.
+async fn balance_tracking_system(
+    ws_url: String,
+    rpc_client: Arc<RpcClient>,
+    wallet: PoolWallet,
+    coal_token_balance: Arc<Mutex<u64>>,
+) {
+    let mut wallet_changes = wallet.subscribe();
+    loop {
+        let token_account = get_associated_token_address(
+            &wallet_changes.borrow_and_update().pubkey(),
+            &get_coal_mint(),
+        );
+        match rpc_client.get_token_account_balance(&token_account).await {
+            Ok(balance) => {
+                if let Ok(amount) = balance.amount.parse::<u64>() {
+                    *coal_token_balance.lock().await = amount;
+                }
+            }
+            Err(_) => {
+                error!("Failed to get pool token account balance");
+            }
+        }
+
+        let ps_client = match PubsubClient::new(&ws_url).await {
+            Ok(ps_client) => ps_client,
+            Err(_) => {
+                error!("Failed to connect to websocket for balance tracking, retrying...");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let pubsub = ps_client
+            .account_subscribe(
+                &token_account,
+                Some(RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: None,
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    min_context_slot: None,
+                }),
+            )
+            .await;
+
+        info!("Tracking pool token balance with websocket");
+        let mut wallet_changed = false;
+        if let Ok((mut account_sub_notifications, _account_unsub)) = pubsub {
+            loop {
+                let response = tokio::select! {
+                    response = account_sub_notifications.next() => match response {
+                        Some(response) => response,
+                        None => break,
+                    },
+                    _ = wallet_changes.changed() => {
+                        info!("Pool wallet changed, tracking its token balance");
+                        wallet_changed = true;
+                        break;
+                    }
+                };
+                if let Some(data_bytes) = response.value.data.decode() {
+                    if let Ok(account) = spl_token::state::Account::unpack(&data_bytes) {
+                        *coal_token_balance.lock().await = account.amount;
+                    }
+                }
+            }
+        }
+        if wallet_changed {
+            continue;
+        }
+        error!("Pool token balance subscription ended, reconnecting...");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn fetch_coal_config_snapshot(
+    rpc_client: &RpcClient,
+    authority: Pubkey,
+) -> Option<CoalConfigSnapshot> {
+    if let (Ok(_), Ok(config), Ok(busses)) =
+        get_proof_and_config_with_busses(rpc_client, authority).await
+    {
+        Some(CoalConfigSnapshot {
+            config,
+            busses,
+            fetched_at: Instant::now(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Returns the cached coal config and busses, fetching them directly when
+/// the cache is missing or older than CONFIG_CACHE_MAX_AGE.
+async fn get_cached_coal_config(
+    rpc_client: &RpcClient,
+    authority: Pubkey,
+    cache: &RwLock<Option<CoalConfigSnapshot>>,
+) -> Option<CoalConfigSnapshot> {
+    if let Some(snapshot) = cache.read().await.as_ref() {
+        if snapshot.fetched_at.elapsed() <= CONFIG_CACHE_MAX_AGE {
+            return Some(snapshot.clone());
+        }
+    }
+
+    info!("Cached config is stale, getting latest config and busses data.");
+    let snapshot = fetch_coal_config_snapshot(rpc_client, authority).await;
+    if let Some(snapshot) = &snapshot {
+        *cache.write().await = Some(snapshot.clone());
+    }
+    snapshot
+}
+
+/// Refreshes the equipped tool and alerts the operator once when its
+/// durability drops below the configured warning threshold.
+async fn tool_monitor_system(
+    rpc_client: Arc<RpcClient>,
+    app_config: Arc<Config>,
+    tool_status: Arc<RwLock<Option<ToolStatus>>>,
+    webhook_sender: UnboundedSender<WebhookJob>,
 ) {
+    let tool = match app_config.tool {
+        Some(tool) => tool,
+        None => return,
+    };
+    let threshold = app_config.tool_durability_warning;
+    let mut warned = false;
+
     loop {
-        let reader = app_pongs.read().await;
-        let pongs = reader.pongs.clone();
-        drop(reader);
-
-        for pong in pongs.iter() {
-            if pong.1.elapsed().as_secs() > 45 {
-                let mut writer = app_state.write().await;
-                writer.sockets.remove(pong.0);
-                drop(writer);
+        tokio::time::sleep(TOOL_REFRESH_INTERVAL).await;
 
-                let mut writer = app_pongs.write().await;
-                writer.pongs.remove(pong.0);
-                drop(writer)
+        let status = match get_tool_status(&rpc_client, tool).await {
+            Ok(status) => Some(status),
+            Err(e) => {
+                error!("Failed to refresh tool {}: {}", tool, e);
+                None
             }
+        };
+        *tool_status.write().await = status;
+
+        let durability = match status {
+            Some(status) => status.durability,
+            None => continue,
+        };
+        if durability >= threshold {
+            warned = false;
+            continue;
+        }
+        if warned {
+            continue;
+        }
+        warned = true;
+        error!("Tool {} durability {} is below {}", tool, durability, threshold);
+
+        if let Some(url) = &app_config.operator_webhook_url {
+            let _ = webhook_sender.send(WebhookJob {
+                miner_id: None,
+                url: url.clone(),
+                event: WebhookEvent::ToolDurabilityLow {
+                    tool: tool.to_string(),
+                    durability,
+                    threshold,
+                },
+            });
+        }
+    }
+}
+
+async fn coal_config_refresh_system(
+    rpc_client: Arc<RpcClient>,
+    wallet: PoolWallet,
+    cache: Arc<RwLock<Option<CoalConfigSnapshot>>>,
+) {
+    loop {
+        if let Some(snapshot) = fetch_coal_config_snapshot(&rpc_client, wallet.pubkey()).await {
+            *cache.write().await = Some(snapshot);
+        } else {
+            error!("Failed to refresh coal config and busses.");
         }
 
-        tokio::time::sleep(Duration::from_secs(15)).await;
+        tokio::time::sleep(CONFIG_REFRESH_INTERVAL).await;
     }
 }
 
 async fn client_message_handler_system(
-    mut receiver_channel: UnboundedReceiver<ClientMessage>,
+    mut receiver_channel: Receiver<ClientMessage>,
     app_database: Arc<AppDatabase>,
-    ready_clients: Arc<Mutex<HashSet<SocketAddr>>>,
-    proof: Arc<Mutex<Proof>>,
+    ready_clients: Arc<Mutex<ReadyClients>>,
+    epoch_generation: watch::Receiver<EpochGeneration>,
     epoch_hashes: Arc<RwLock<EpochHashes>>,
-    client_nonce_ranges: Arc<RwLock<HashMap<Pubkey, Range<u64>>>>,
+    best_difficulty_timeline: BestDifficultyTimeline,
+    epoch_challenges: Arc<RwLock<EpochChallenges>>,
+    client_nonce_ranges: Arc<RwLock<ClientNonceRanges>>,
     app_config: Arc<Config>,
     app_state: Arc<RwLock<AppState>>,
-    app_pongs: Arc<RwLock<LastPong>>
+    keepalive: Arc<RwLock<Keepalive>>,
+    runtime_config: Arc<RwLock<RuntimeConfig>>,
+    dashboard_bus: DashboardEventBus,
 ) {
     while let Some(client_message) = receiver_channel.recv().await {
         match client_message {
-            ClientMessage::Pong(addr) => {
-                let mut writer = app_pongs.write().await;
-                writer.pongs.insert(addr, Instant::now());
+            ClientMessage::Pong(addr, connection_id, payload) => {
+                let mut writer = keepalive.write().await;
+                let outcome = writer.record_pong(addr, connection_id, &payload, Instant::now());
+                let suspicious_pongs = writer.suspicious_pongs(addr, connection_id);
                 drop(writer);
+                match outcome {
+                    PongOutcome::Valid { rtt } => {
+                        tracing::debug!("Pong from {} in {}ms", addr, rtt.as_millis());
+                        if let Some(connection) = app_state.read().await.sockets.get(&addr) {
+                            if connection.connection_id == connection_id {
+                                connection.send_failures.store(0, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    PongOutcome::Ignored => {}
+                    // not counted as the connection being alive
+                    PongOutcome::Stale | PongOutcome::Unsolicited => {
+                        // the user agent tells which client builds misbehave
+                        let user_agent = app_state
+                            .read()
+                            .await
+                            .sockets
+                            .get(&addr)
+                            .and_then(|connection| connection.user_agent.clone());
+                        warn!(
+                            "Suspicious pong from {} ({:?}), {} so far, user agent {}",
+                            addr,
+                            outcome,
+                            suspicious_pongs,
+                            user_agent.as_deref().unwrap_or("none")
+                        );
+                    }
+                }
             }
-            ClientMessage::Ready(addr) => {
+            ClientMessage::Ready(addr, connection_id) => {
                 let ready_clients = ready_clients.clone();
                 tokio::spawn(async move {
                     info!("Client {} is ready!", addr.to_string());
                     let mut ready_clients = ready_clients.lock().await;
-                    ready_clients.insert(addr);
+                    ready_clients.insert(connection_id, addr);
                 });
             }
             ClientMessage::Mining(addr) => {
                 info!("Client {} has started mining!", addr.to_string());
             }
-            ClientMessage::BestSolution(addr, solution, pubkey) => {
+            ClientMessage::Disconnect(addr, connection_id) => {
+                remove_client_connection(&app_state, &ready_clients, addr, Some(connection_id))
+                    .await;
+
+                let reader = app_state.read().await;
+                let count = reader.sockets.len();
+                // also drops ranges left behind by evicted connections
+                let live_devices: HashSet<(Pubkey, DeviceId)> = reader
+                    .sockets
+                    .values()
+                    .map(|connection| (connection.pubkey, connection.device_id.clone()))
+                    .collect();
+                drop(reader);
+
+                client_nonce_ranges
+                    .write()
+                    .await
+                    .retain(|device, _| live_devices.contains(device));
+                keepalive.write().await.remove(addr, connection_id);
+                dashboard_bus.send(DashboardEvent::ConnectedMiners { count });
+            }
+            ClientMessage::BestSolution(addr, solution, pubkey, epoch_id) => {
                 let app_epoch_hashes = epoch_hashes.clone();
+                let best_difficulty_timeline = best_difficulty_timeline.clone();
+                let epoch_challenges = epoch_challenges.clone();
                 let app_app_database = app_database.clone();
-                let app_proof = proof.clone();
+                let epoch_generation = epoch_generation.clone();
                 let app_client_nonce_ranges = client_nonce_ranges.clone();
                 let app_config = app_config.clone();
                 let app_state = app_state.clone();
+                let ready_clients = ready_clients.clone();
+                let runtime_config = runtime_config.clone();
+                let dashboard_bus = dashboard_bus.clone();
                 tokio::spawn(async move {
                     let epoch_hashes = app_epoch_hashes;
                     let app_database = app_app_database;
-                    let proof = app_proof;
                     let client_nonce_ranges = app_client_nonce_ranges;
 
+                    let reader = app_state.read().await;
+                    let miner_id;
+                    let signer = pubkey;
+                    let pubkey;
+                    let device_id;
+                    let session;
+                    if let Some(app_client_socket) = reader.sockets.get(&addr) {
+                        miner_id = app_client_socket.miner_id;
+                        pubkey = app_client_socket.pubkey;
+                        device_id = app_client_socket.device_id.clone();
+                        session = app_client_socket.session.clone();
+                        if app_client_socket.signer != signer {
+                            error!("Solution signed by {} does not match connection signer for addr: {}", display_pubkey(&signer), addr);
+                            return;
+                        }
+                    } else {
+                        error!("Failed to get client socket for addr: {}", addr);
+                        return;
+                    }
+                    drop(reader);
+
                     let pubkey_str = pubkey.to_string();
-                    let lock = proof.lock().await;
-                    let challenge = lock.challenge;
-                    drop(lock);
+                    // the challenge of the epoch work is handed out for, which
+                    // lags the proof until the new challenge's db row exists
+                    let generation = *epoch_generation.borrow();
+                    let current_challenge = generation.challenge;
+
+                    // solutions are checked against the challenge of the epoch
+                    // they name, legacy clients are assumed to be on the current one
+                    let (epoch_id, challenge, valid) = match epoch_id {
+                        None => {
+                            let previous = epoch_challenges.read().await.previous();
+                            legacy_solution_challenge(&solution, current_challenge, previous)
+                        }
+                        Some(epoch_id) => match epoch_challenges.read().await.challenge(epoch_id) {
+                            Some(challenge) => (Some(epoch_id), challenge, solution.is_valid(&challenge)),
+                            None => {
+                                info!("{} submitted for expired epoch {}, skipping", display_pubkey(&pubkey), epoch_id);
+                                let rejected = SubmissionRejected::new(RejectReason::ExpiredEpoch, None);
+                                send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
+                                return;
+                            }
+                        },
+                    };
+                    let late = challenge != current_challenge;
 
                     let reader = client_nonce_ranges.read().await;
                     let nonce_range: Range<u64> = {
-                        if let Some(nr) = reader.get(&pubkey) {
-                            nr.clone()
+                        if let Some(nr) = reader
+                            .get(&(pubkey, device_id.clone()))
+                            .and_then(|ranges| ranges.get(epoch_id))
+                        {
+                            nr
                         } else {
+                            drop(reader);
                             error!("Client nonce range not set!");
+                            let rejected = SubmissionRejected::new(RejectReason::NonceOutOfRange, None);
+                            send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
                             return;
                         }
                     };
@@ -2001,40 +8136,77 @@ async fn client_message_handler_system(
 
                     if !nonce_range.contains(&nonce) {
                         error!("Client submitted nonce out of assigned range");
+                        let rejected = SubmissionRejected::new(RejectReason::NonceOutOfRange, None);
+                        send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
                         return;
                     }
+                    let range_size = nonce_range.end - nonce_range.start;
 
-                    let reader = app_state.read().await;
-                    let miner_id;
-                    if let Some(app_client_socket) = reader.sockets.get(&addr) {
-                        miner_id = app_client_socket.miner_id;
-                    } else {
-                        error!("Failed to get client socket for addr: {}", addr);
-                        return;
+                    // invalid work for an older challenge may have raced the
+                    // rotation, only invalid work for the current one costs reputation
+                    if valid || !late {
+                        spawn_record_miner_solution(app_database.clone(), miner_id, valid);
                     }
-                    drop(reader);
-
-                    if solution.is_valid(&challenge) {
+                    if valid {
                         let diff = solution.to_hash().difficulty();
-                        info!("{} found diff: {}", pubkey_str, diff);
-                        if diff >= MIN_DIFF {
-                            // calculate rewards
-                            let mut hashpower = MIN_HASHPOWER * 2u64.pow(diff - MIN_DIFF);
-                            if hashpower > 81_920 {
-                                hashpower = 81_920;
+                        info!("{} found diff: {}", display_pubkey(&pubkey), diff);
+                        let min_difficulty = runtime_config.read().await.min_difficulty;
+                        if late {
+                            // valid work for the previous challenge, kept out of the rewards
+                            if diff >= min_difficulty {
+                                record_late_submission(&app_database, challenge, miner_id, &solution, range_size)
+                                    .await;
                             }
-                            {
+                            let rejected = SubmissionRejected::new(RejectReason::Late, Some(diff));
+                            send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
+                            return;
+                        }
+                        if diff >= min_difficulty {
+                            // calculate rewards
+                            let hashpower = hashpower_for_difficulty(diff);
+                            let ack = {
                                 let mut epoch_hashes = epoch_hashes.write().await;
-                                epoch_hashes
-                                    .submissions
-                                    .insert(pubkey, (miner_id, diff, hashpower));
-                                if diff > epoch_hashes.best_hash.difficulty {
-                                    epoch_hashes.best_hash.difficulty = diff;
-                                    epoch_hashes.best_hash.solution = Some(solution);
+                                let key = (pubkey, device_id.clone());
+                                let Some((improved, better)) = epoch_hashes
+                                    .credit(generation.generation, &key, miner_id, solution, diff)
+                                else {
+                                    // the epoch moved on while the solution was checked
+                                    drop(epoch_hashes);
+                                    record_late_submission(&app_database, challenge, miner_id, &solution, range_size)
+                                        .await;
+                                    let rejected = SubmissionRejected::new(RejectReason::Late, Some(diff));
+                                    send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
+                                    return;
+                                };
+                                if better {
+                                    best_difficulty_timeline.record(diff, pubkey).await;
+                                }
+                                let epoch_hashes = epoch_hashes.downgrade();
+                                improved.then(|| SubmissionAck::new(&epoch_hashes, &key, diff))
+                            };
+                            session.record_submission(diff);
+                            dashboard_bus.send(DashboardEvent::SubmissionAccepted {
+                                pubkey: pubkey_str.clone(),
+                                device_id,
+                                difficulty: diff,
+                                hashpower,
+                            });
+                            match ack {
+                                Some(ack) => {
+                                    let connection = app_state.read().await.sockets.get(&addr).cloned();
+                                    if let (Some(connection), Ok(text)) = (connection, serde_json::to_string(&ack)) {
+                                        send_client_message(&app_state, &ready_clients, addr, &connection, Message::Text(text))
+                                            .await;
+                                    }
+                                }
+                                None => {
+                                    // a duplicate or worse resubmission
+                                    let rejected = SubmissionRejected::new(RejectReason::NotImproved, Some(diff));
+                                    send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
                                 }
-                                drop(epoch_hashes);
                             }
                             tokio::time::sleep(Duration::from_millis(100)).await;
+                            let stored_difficulty = stored_difficulty(&solution, range_size);
                             if let Ok(challenge) = app_database
                                 .get_challenge_by_challenge(challenge.to_vec())
                                 .await
@@ -2044,15 +8216,20 @@ async fn client_message_handler_system(
                                     miner_id,
                                     challenge_id: challenge.id,
                                     nonce,
-                                    difficulty: diff as i8,
+                                    difficulty: stored_difficulty,
+                                    range_size,
                                 };
 
                                 tokio::time::sleep(Duration::from_millis(100)).await;
-                                while let Err(_) = app_database
+                                while let Err(e) = app_database
                                     .add_new_submission(new_submission.clone())
                                     .await
                                 {
-                                    error!("Failed to add new submission! Retrying...");
+                                    if !e.is_retriable() {
+                                        info!("{} already exists in db, not retrying", InsertSubmission::describe());
+                                        break;
+                                    }
+                                    error!("Failed to add {} to db! Retrying...", InsertSubmission::describe());
                                     tokio::time::sleep(Duration::from_millis(2000)).await;
                                 }
                             } else {
@@ -2062,26 +8239,27 @@ async fn client_message_handler_system(
                                     pool_id: app_config.pool_id,
                                     challenge: challenge.to_vec(),
                                     rewards_earned: None,
+                                    started_at: chrono::Utc::now().naive_utc(),
                                 };
-                                if let Err(_) = app_database.add_new_challenge(new_challenge).await
-                                {
-                                    error!("Failed to add challenge to db");
+                                match app_database.add_new_challenge(new_challenge).await {
+                                    Ok(_) => {}
+                                    Err(AppDatabaseError::DuplicateEntry) => {
+                                        info!("Challenge was already added to db");
+                                    }
+                                    Err(_) => {
+                                        error!("Failed to add challenge to db");
+                                    }
                                 }
                             }
                         } else {
                             error!("Diff to low, skipping");
+                            let rejected = SubmissionRejected::new(RejectReason::BelowMinDifficulty, Some(diff));
+                            send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
                         }
                     } else {
-                        error!("{} returned an invalid solution!", pubkey);
-
-                        let reader = app_state.read().await;
-                        if let Some(app_client_socket) = reader.sockets.get(&addr) {
-                            let _ = app_client_socket.socket.lock().await.send(Message::Text("Invalid solution. If this keeps happening, please contact support.".to_string())).await;
-                        } else {
-                            error!("Failed to get client socket for addr: {}", addr);
-                            return;
-                        }
-                        drop(reader);
+                        error!("{} returned an invalid solution!", display_pubkey(&pubkey));
+                        let rejected = SubmissionRejected::new(RejectReason::Invalid, None);
+                        send_submission_rejected(&app_state, &ready_clients, addr, rejected).await;
                     }
                 });
             }
@@ -2089,46 +8267,684 @@ async fn client_message_handler_system(
     }
 }
 
-async fn ping_check_system(shared_state: &Arc<RwLock<AppState>>) {
+/// Pings every connection each ping_interval and drops the connections
+/// that left a ping unanswered for longer than pong_timeout.
+async fn keepalive_system(
+    keepalive: Arc<RwLock<Keepalive>>,
+    config: KeepaliveConfig,
+    shared_state: Arc<RwLock<AppState>>,
+    ready_clients: Arc<Mutex<ReadyClients>>,
+) {
+    let mut interval = tokio::time::interval(config.ping_interval);
     loop {
-        // send ping to all sockets
-        let app_state = shared_state.read().await;
-
-        let mut handles = Vec::new();
-        for (who, socket) in app_state.sockets.iter() {
-            let who = who.clone();
-            let socket = socket.clone();
-            handles.push(tokio::spawn(async move {
-                if socket
-                    .socket
-                    .lock()
-                    .await
-                    .send(Message::Ping(vec![1, 2, 3]))
-                    .await
-                    .is_ok()
-                {
-                    return None;
-                } else {
-                    return Some(who.clone());
-                }
-            }));
+        interval.tick().await;
+
+        let timed_out = keepalive
+            .read()
+            .await
+            .timed_out(Instant::now(), config.pong_timeout);
+        for (who, connection_id) in timed_out {
+            info!("{} left a ping unanswered for {:?}, disconnecting", who, config.pong_timeout);
+            evict_client_connection(
+                &shared_state,
+                &ready_clients,
+                who,
+                Some(connection_id),
+                close_code::POLICY,
+                "Pong timeout",
+            )
+            .await;
+            keepalive.write().await.remove(who, connection_id);
         }
-        drop(app_state);
 
-        // remove any sockets where ping failed
-        for handle in handles {
-            match handle.await {
-                Ok(Some(who)) => {
-                    let mut app_state = shared_state.write().await;
-                    app_state.sockets.remove(&who);
-                }
-                Ok(None) => {}
-                Err(_) => {
-                    error!("Got error sending ping to client.");
+        let sockets = shared_state.read().await.sockets.clone();
+        let now = Instant::now();
+        let now_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        let mut writer = keepalive.write().await;
+        let pings: Vec<_> = sockets
+            .into_iter()
+            .map(|(who, connection)| {
+                let payload = writer.next_ping(who, connection.connection_id, now, now_ms);
+                (who, connection, payload)
+            })
+            .collect();
+        drop(writer);
+
+        // not awaited, a slow connection must not delay the pings of the others
+        for (who, connection, payload) in pings {
+            let shared_state = shared_state.clone();
+            let ready_clients = ready_clients.clone();
+            tokio::spawn(async move {
+                let sent = send_client_message(
+                    &shared_state,
+                    &ready_clients,
+                    who,
+                    &connection,
+                    Message::Ping(payload),
+                )
+                .await;
+                if !sent {
+                    evict_client_connection(
+                        &shared_state,
+                        &ready_clients,
+                        who,
+                        Some(connection.connection_id),
+                        close_code::ERROR,
+                        "Ping failed",
+                    )
+                    .await;
                 }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cors_preflight_allows_post_with_auth_headers() {
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route("/signup", post(|| async { "" }))
+            .layer(cors_layer());
+        let request = axum::http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/signup")
+            .header("origin", "https://pool.example")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "authorization,content-type")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = |name: &str| {
+            response.headers().get(name).unwrap().to_str().unwrap().to_ascii_lowercase()
+        };
+        assert!(header("access-control-allow-methods").contains("post"));
+        assert!(header("access-control-allow-headers").contains("authorization"));
+        assert!(header("access-control-allow-headers").contains("content-type"));
+        assert_eq!(header("access-control-allow-origin"), "*");
+    }
+
+    #[test]
+    fn truncated_solutions_disconnect() {
+        let (client_channel, mut messages) = tokio::sync::mpsc::channel(1);
+        let who: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        // type 3 needs 65 bytes before the signature, an epoch id alone is too short
+        for frame in [vec![], vec![3u8; 9], vec![3u8; 64], vec![2u8; 56]] {
+            let flow = process_message(Message::Binary(frame), who, 1, client_channel.clone());
+            assert_eq!(flow, ControlFlow::Break(()));
+        }
+        assert!(messages.try_recv().is_err());
+
+        // a full length frame with a bad signature is dropped, not disconnected
+        let flow = process_message(Message::Binary(vec![3u8; 65]), who, 1, client_channel);
+        assert_eq!(flow, ControlFlow::Continue(()));
+    }
+
+    fn solution(seed: u8) -> Solution {
+        Solution::new([seed; 16], (seed as u64 * 7919).to_le_bytes())
+    }
+
+    #[test]
+    fn stored_difficulty_is_recomputed_from_the_solution() {
+        // difficulties of the hashes of these solutions, the miner sends none
+        for (seed, difficulty) in [(11u8, 2), (22, 4), (23, 1), (24, 4)] {
+            assert_eq!(stored_difficulty(&solution(seed), NONCE_RANGE_SIZE), difficulty);
+        }
+    }
+
+    #[test]
+    fn stored_difficulty_is_capped() {
+        assert_eq!(clamp_stored_difficulty(MIN_DIFF, NONCE_RANGE_SIZE), MIN_DIFF as i16);
+        assert_eq!(
+            clamp_stored_difficulty(MAX_SUBMISSION_DIFFICULTY, NONCE_RANGE_SIZE),
+            MAX_SUBMISSION_DIFFICULTY as i16
+        );
+        assert_eq!(
+            clamp_stored_difficulty(MAX_SUBMISSION_DIFFICULTY + 1, NONCE_RANGE_SIZE),
+            MAX_SUBMISSION_DIFFICULTY as i16
+        );
+        assert_eq!(clamp_stored_difficulty(u32::MAX, NONCE_RANGE_SIZE), MAX_SUBMISSION_DIFFICULTY as i16);
+    }
+
+    #[test]
+    fn plausible_difficulty_grows_with_the_range() {
+        // 4_000_000 nonces take 22 bits
+        assert_eq!(max_plausible_difficulty(NONCE_RANGE_SIZE), 22 + PLAUSIBLE_DIFFICULTY_MARGIN);
+        assert_eq!(max_plausible_difficulty(0), PLAUSIBLE_DIFFICULTY_MARGIN);
+        assert_eq!(max_plausible_difficulty(u64::MAX), 64 + PLAUSIBLE_DIFFICULTY_MARGIN);
+    }
+
+    fn proof_with_challenge(challenge: [u8; 32]) -> Proof {
+        let mut proof = <Proof as bytemuck::Zeroable>::zeroed();
+        proof.challenge = challenge;
+        proof
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delayed_rotation_is_still_detected() {
+        let old = proof_with_challenge([1; 32]);
+        let proof = Arc::new(Mutex::new(old));
+        let (proof_challenge, _) = watch::channel(old.challenge);
+        let fetches = AtomicU32::new(0);
+        let delay = CHALLENGE_CHANGE_TIMEOUT * 2 + Duration::from_secs(5);
+
+        // the websocket only catches up after two fetches still saw the old challenge
+        let rotation = {
+            let proof = proof.clone();
+            let proof_challenge = proof_challenge.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                *proof.lock().await = proof_with_challenge([2; 32]);
+                proof_challenge.send_replace([2; 32]);
+            })
+        };
+        let started = Instant::now();
+        let latest = wait_for_new_challenge(
+            || {
+                fetches.fetch_add(1, Ordering::Relaxed);
+                async move { Ok(old) }
+            },
+            old.challenge,
+            &proof,
+            &proof_challenge,
+        )
+        .await;
+        rotation.await.unwrap();
+
+        assert_eq!(latest.challenge, [2; 32]);
+        assert_eq!(started.elapsed(), delay);
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missed_rotation_is_fetched_and_published_once() {
+        let old = proof_with_challenge([1; 32]);
+        let new = proof_with_challenge([2; 32]);
+        let proof = Mutex::new(old);
+        let (proof_challenge, mut receiver) = watch::channel(old.challenge);
+
+        let started = Instant::now();
+        let latest =
+            wait_for_new_challenge(|| async { Ok(new) }, old.challenge, &proof, &proof_challenge)
+                .await;
+
+        assert_eq!(latest.challenge, new.challenge);
+        assert_eq!(started.elapsed(), CHALLENGE_CHANGE_TIMEOUT);
+        assert_eq!(proof.lock().await.challenge, new.challenge);
+        assert_eq!(*receiver.borrow_and_update(), new.challenge);
+        assert!(!receiver.has_changed().unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn websocket_catching_up_during_the_fetch_wins() {
+        let old = proof_with_challenge([1; 32]);
+        let fetched = proof_with_challenge([2; 32]);
+        let notified = proof_with_challenge([3; 32]);
+        let proof = Mutex::new(old);
+        let (proof_challenge, _) = watch::channel(old.challenge);
+
+        let (proof_ref, proof_challenge_ref) = (&proof, &proof_challenge);
+        let latest = wait_for_new_challenge(
+            move || async move {
+                *proof_ref.lock().await = notified;
+                proof_challenge_ref.send_replace(notified.challenge);
+                Ok(fetched)
+            },
+            old.challenge,
+            &proof,
+            &proof_challenge,
+        )
+        .await;
+
+        assert_eq!(latest.challenge, notified.challenge);
+        assert_eq!(proof.lock().await.challenge, notified.challenge);
+        assert_eq!(*proof_challenge.borrow(), notified.challenge);
+    }
+
+    fn empty_epoch_hashes(generation: u64) -> EpochHashes {
+        EpochHashes {
+            generation,
+            best_hash: BestHash {
+                solution: None,
+                difficulty: 0,
+            },
+            submissions: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn devices_are_credited_with_their_best_submission() {
+        let mut epoch_hashes = empty_epoch_hashes(1);
+        let device = (Pubkey::new_unique(), Some("rig".to_string()));
+        let other_device = (device.0, None);
+
+        assert_eq!(epoch_hashes.credit(1, &device, 7, solution(22), 4), Some((true, true)));
+        assert_eq!(epoch_hashes.credit(1, &device, 7, solution(11), 2), Some((false, false)));
+        assert_eq!(epoch_hashes.credit(1, &other_device, 7, solution(23), 1), Some((true, false)));
+
+        assert_eq!(epoch_hashes.submissions[&device], (7, 4, hashpower_for_difficulty(4)));
+        assert_eq!(epoch_hashes.submissions[&other_device], (7, 1, hashpower_for_difficulty(1)));
+        assert_eq!(epoch_hashes.best_hash.difficulty, 4);
+    }
+
+    #[test]
+    fn submissions_checked_before_a_transition_are_late() {
+        let mut epoch_hashes = empty_epoch_hashes(1);
+        let device = (Pubkey::new_unique(), None);
+        assert!(epoch_hashes.credit(1, &device, 7, solution(22), 4).is_some());
+
+        // the epoch moves on while another solution is checked for generation 1
+        assert!(epoch_hashes.advance(1, 2));
+        assert_eq!(epoch_hashes.credit(1, &device, 7, solution(11), 2), None);
+        assert_eq!(epoch_hashes.generation, 2);
+        assert!(epoch_hashes.submissions.is_empty());
+        assert!(epoch_hashes.best_hash.solution.is_none());
+        assert_eq!(epoch_hashes.best_hash.difficulty, 0);
+
+        assert_eq!(epoch_hashes.credit(2, &device, 7, solution(11), 2), Some((true, true)));
+    }
+
+    #[test]
+    fn a_stale_transition_keeps_the_new_epoch() {
+        let mut epoch_hashes = empty_epoch_hashes(2);
+        let device = (Pubkey::new_unique(), None);
+        epoch_hashes.credit(2, &device, 7, solution(22), 4);
+
+        assert!(!epoch_hashes.advance(1, 2));
+        assert_eq!(epoch_hashes.generation, 2);
+        assert_eq!(epoch_hashes.submissions.len(), 1);
+        assert_eq!(epoch_hashes.best_hash.difficulty, 4);
+    }
+
+    fn test_database() -> Option<Arc<AppDatabase>> {
+        match std::env::var("DATABASE_URL") {
+            Ok(url) => Some(Arc::new(AppDatabase::new(url))),
+            Err(_) => {
+                eprintln!("DATABASE_URL is not set, skipping");
+                None
+            }
+        }
+    }
+
+    async fn test_epoch_starter(app_database: Arc<AppDatabase>, challenge: [u8; 32]) -> EpochStarter {
+        let authority = Pubkey::new_unique().to_string();
+        app_database
+            .add_new_pool(authority.clone(), Pubkey::new_unique().to_string())
+            .await
+            .unwrap();
+        let pool_id = app_database
+            .get_pool_by_authority_pubkey(authority)
+            .await
+            .unwrap()
+            .id;
+        EpochStarter {
+            app_database,
+            pool_id,
+            nonce: Arc::new(Mutex::new(0)),
+            nonce_start: 0,
+            epoch_hashes: Arc::new(RwLock::new(empty_epoch_hashes(1))),
+            best_difficulty_timeline: BestDifficultyTimeline::default(),
+            epoch_generation: watch::channel(EpochGeneration::new(challenge)).0,
+            state_snapshot_path: std::env::temp_dir()
+                .join(format!("state-snapshot-{}.json", rand::random::<u64>())),
+            nonces_assigned: Arc::new(AtomicU64::new(0)),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_challenge_is_started_once() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let old = rand::random::<[u8; 32]>();
+        let new = rand::random::<[u8; 32]>();
+        let epoch_starter = test_epoch_starter(app_database.clone(), old).await;
+
+        // the rotation waiter and the mine loop can both start the new challenge
+        tokio::join!(epoch_starter.start(new), epoch_starter.start(new));
+        assert_eq!(
+            *epoch_starter.epoch_generation.borrow(),
+            EpochGeneration {
+                generation: 2,
+                challenge: new,
             }
+        );
+        let challenge = app_database.get_challenge_by_challenge(new.to_vec()).await.unwrap();
+        assert_eq!(challenge.pool_id, epoch_starter.pool_id);
+
+        // work done in the new epoch isn't reset by a late start
+        *epoch_starter.nonce.lock().await = 5;
+        epoch_starter
+            .epoch_hashes
+            .write()
+            .await
+            .submissions
+            .insert((Pubkey::new_unique(), None), (1, 10, 1));
+        epoch_starter.start(new).await;
+
+        assert_eq!(epoch_starter.epoch_generation.borrow().generation, 2);
+        assert_eq!(*epoch_starter.nonce.lock().await, 5);
+        let epoch_hashes = epoch_starter.epoch_hashes.read().await;
+        assert_eq!(epoch_hashes.generation, 2);
+        assert_eq!(epoch_hashes.submissions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn start_moves_submissions_to_the_new_epoch() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let old = rand::random::<[u8; 32]>();
+        let new = rand::random::<[u8; 32]>();
+        let epoch_starter = test_epoch_starter(app_database.clone(), old).await;
+        app_database
+            .add_new_challenge(InsertChallenge {
+                pool_id: epoch_starter.pool_id,
+                challenge: old.to_vec(),
+                rewards_earned: None,
+                started_at: chrono::Utc::now().naive_utc(),
+            })
+            .await
+            .unwrap();
+        let device = (Pubkey::new_unique(), None);
+        epoch_starter
+            .epoch_hashes
+            .write()
+            .await
+            .credit(1, &device, 7, solution(22), 4);
+        *epoch_starter.nonce.lock().await = 1_000;
+
+        epoch_starter.start(new).await;
+
+        let closed = app_database.get_challenge_by_challenge(old.to_vec()).await.unwrap();
+        assert!(closed.ended_at.is_some());
+        let started = app_database.get_challenge_by_challenge(new.to_vec()).await.unwrap();
+        assert!(started.ended_at.is_none());
+        assert_eq!(*epoch_starter.nonce.lock().await, epoch_starter.nonce_start);
+
+        // a solution checked against the old generation doesn't reach the new epoch
+        let mut epoch_hashes = epoch_starter.epoch_hashes.write().await;
+        assert_eq!(epoch_hashes.generation, 2);
+        assert!(epoch_hashes.submissions.is_empty());
+        assert_eq!(epoch_hashes.credit(1, &device, 7, solution(11), 2), None);
+        assert!(epoch_hashes.credit(2, &device, 7, solution(11), 2).is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_missing_epoch_challenge_is_given_up_on() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let started = Instant::now();
+        let challenge_id =
+            find_epoch_outcome_challenge_id(&app_database, rand::random::<[u8; 32]>()).await;
+
+        assert_eq!(challenge_id, None);
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_secs(EPOCH_OUTCOME_MAX_ATTEMPTS as u64 - 1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_claim_without_a_rewards_row_is_not_retried() {
+        let Some(app_database) = test_database() else {
+            return;
+        };
+        let sig = Signature::new_unique();
+        let itxn = InsertTxn {
+            txn_type: "claim".to_string(),
+            signature: sig.to_string(),
+            priority_fee: 0,
+            pool_id: None,
+            fee_paid_lamports: None,
+        };
+        let txn_id = add_confirmed_txn(&app_database, itxn).await.unwrap();
+
+        let started = Instant::now();
+        assert!(!record_claim(&app_database, -1, -1, txn_id, 1, &sig).await);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    // a solution that holds for the challenge
+    fn valid_solution(challenge: &[u8; 32]) -> Solution {
+        (0u64..)
+            .find_map(|nonce| {
+                let nonce = nonce.to_le_bytes();
+                drillx_2::hash(challenge, &nonce).ok().map(|hash| Solution::new(hash.d, nonce))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn legacy_solutions_for_the_current_challenge_are_on_time() {
+        let previous = EpochChallenge { epoch_id: 1, challenge: [1; 32] };
+        let solution = valid_solution(&[2; 32]);
+        assert_eq!(
+            legacy_solution_challenge(&solution, [2; 32], Some(previous)),
+            (None, [2; 32], true)
+        );
+    }
+
+    #[test]
+    fn legacy_solutions_for_the_previous_challenge_are_late() {
+        let previous = EpochChallenge { epoch_id: 1, challenge: [1; 32] };
+        let solution = valid_solution(&[1; 32]);
+        assert_eq!(
+            legacy_solution_challenge(&solution, [2; 32], Some(previous)),
+            (Some(1), [1; 32], true)
+        );
+        // before the first rotation there's only the current challenge
+        assert_eq!(legacy_solution_challenge(&solution, [2; 32], None), (None, [2; 32], false));
+    }
+
+    #[test]
+    fn legacy_solutions_for_neither_challenge_are_invalid() {
+        let previous = EpochChallenge { epoch_id: 1, challenge: [1; 32] };
+        let solution = valid_solution(&[3; 32]);
+        assert_eq!(
+            legacy_solution_challenge(&solution, [2; 32], Some(previous)),
+            (None, [2; 32], false)
+        );
+    }
+
+    #[tokio::test]
+    async fn a_wallet_swap_waits_for_a_solved_epoch_and_an_empty_proof() {
+        let epoch_hashes = RwLock::new(empty_epoch_hashes(1));
+        let proof = Mutex::new(proof_with_challenge([1; 32]));
+        assert!(ensure_wallet_swappable(&epoch_hashes, &proof).await.is_ok());
+
+        epoch_hashes.write().await.best_hash.solution = Some(solution(11));
+        let err = ensure_wallet_swappable(&epoch_hashes, &proof).await.unwrap_err();
+        assert_eq!(err.code, ApiErrorCode::WalletConflict);
+
+        epoch_hashes.write().await.best_hash.solution = None;
+        proof.lock().await.balance = 1;
+        let err = ensure_wallet_swappable(&epoch_hashes, &proof).await.unwrap_err();
+        assert_eq!(err.code, ApiErrorCode::WalletConflict);
+    }
+
+    #[test]
+    fn a_missing_token_account_holds_nothing() {
+        assert_eq!(token_account_amount(None), Some(0));
+
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        let token_account = spl_token::state::Account {
+            mint: get_coal_mint(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        spl_token::state::Account::pack(token_account, &mut data).unwrap();
+        let account = solana_sdk::account::Account {
+            data,
+            owner: spl_token::id(),
+            ..Default::default()
+        };
+        assert_eq!(token_account_amount(Some(&account)), Some(42));
+
+        let not_a_token_account = solana_sdk::account::Account::default();
+        assert_eq!(token_account_amount(Some(&not_a_token_account)), None);
+    }
+
+    fn signed_requests() -> SignedRequests {
+        SignedRequests {
+            // never connected to by verify_signer
+            app_database: Arc::new(AppDatabase::new("mysql://localhost/unused".to_string())),
+            auth_window: AuthWindow {
+                max_age_secs: 30,
+                max_clock_skew_secs: 5,
+            },
+            replays: Arc::new(Mutex::new(SignedRequestReplays::default())),
+        }
+    }
+
+    fn signed_auth_header(
+        domain: &[u8],
+        timestamp: u64,
+        body: &str,
+    ) -> (Pubkey, axum_extra::headers::Authorization<Basic>) {
+        use solana_sdk::signature::{Keypair, Signer};
+
+        let keypair = Keypair::new();
+        let mut msg = domain.to_vec();
+        msg.extend_from_slice(&timestamp.to_le_bytes());
+        msg.extend_from_slice(body.as_bytes());
+        let signature = keypair.sign_message(&msg);
+        (
+            keypair.pubkey(),
+            axum_extra::headers::Authorization::basic(&keypair.pubkey().to_string(), &signature.to_string()),
+        )
+    }
+
+    #[tokio::test]
+    async fn a_signed_settings_request_is_accepted_once() {
+        let signed_requests = signed_requests();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let body = r#"{"notify_url":null,"min_notify_amount":0}"#;
+        let (pubkey, auth_header) = signed_auth_header(MINER_SETTINGS_DOMAIN, timestamp, body);
+
+        let signer = signed_requests
+            .verify_signer(&auth_header, MINER_SETTINGS_DOMAIN, timestamp, body)
+            .await
+            .unwrap();
+        assert_eq!(signer, pubkey);
+
+        let err = signed_requests
+            .verify_signer(&auth_header, MINER_SETTINGS_DOMAIN, timestamp, body)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ApiErrorCode::InvalidSignature);
+    }
+
+    #[tokio::test]
+    async fn a_request_signed_for_another_action_is_refused() {
+        let signed_requests = signed_requests();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let body = r#"{"notify_url":null,"min_notify_amount":0}"#;
+
+        for domain in [b"".as_slice(), DELEGATE_DOMAIN] {
+            let (_, auth_header) = signed_auth_header(domain, timestamp, body);
+            let err = signed_requests
+                .verify_signer(&auth_header, MINER_SETTINGS_DOMAIN, timestamp, body)
+                .await
+                .unwrap_err();
+            assert_eq!(err.code, ApiErrorCode::InvalidSignature);
         }
+    }
+
+    #[test]
+    fn notify_thresholds_are_crossed_once() {
+        let setting = |balance| models::NotifySetting {
+            miner_id: 1,
+            pubkey: "miner".to_string(),
+            notify_url: "https://example.com".to_string(),
+            min_notify_amount: 100,
+            balance,
+        };
+        assert!(crossed_notify_threshold(&setting(100), 10));
+        assert!(crossed_notify_threshold(&setting(150), 60));
+        // already above it before this round
+        assert!(!crossed_notify_threshold(&setting(150), 50));
+        assert!(!crossed_notify_threshold(&setting(99), 99));
+        // earned more than the balance holds, e.g. after a claim
+        assert!(crossed_notify_threshold(&setting(120), 500));
+    }
+
+    #[test]
+    fn a_wallet_connects_once_unless_every_device_is_tagged() {
+        let laptop = Some("laptop".to_string());
+        let desktop = Some("desktop".to_string());
 
-        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(device_conflict(&[], &None, 2), None);
+        assert_eq!(device_conflict(&[], &laptop, 2), None);
+        assert_eq!(device_conflict(&[&laptop], &desktop, 2), None);
+
+        assert_eq!(device_conflict(&[&None], &None, 2), Some(DeviceConflict::AlreadyConnected));
+        assert_eq!(device_conflict(&[&None], &laptop, 2), Some(DeviceConflict::AlreadyConnected));
+        assert_eq!(device_conflict(&[&laptop], &None, 2), Some(DeviceConflict::AlreadyConnected));
+        assert_eq!(device_conflict(&[&laptop], &laptop, 2), Some(DeviceConflict::AlreadyConnected));
+    }
+
+    #[test]
+    fn tagged_devices_are_capped() {
+        let devices = ["a", "b", "c"].map(|d| Some(d.to_string()));
+        let connected: Vec<&DeviceId> = devices[..2].iter().collect();
+        assert_eq!(device_conflict(&connected, &devices[2], 2), Some(DeviceConflict::TooManyDevices));
+        assert_eq!(device_conflict(&connected, &devices[2], 3), None);
+    }
+
+    #[test]
+    fn the_payout_estimate_rounds_up_to_whole_epochs() {
+        let coal = 10u64.pow(COAL_TOKEN_DECIMALS as u32);
+        assert_eq!(payout_estimate(0, 0.0, 60.0), (Some(0), Some(0.0)));
+        // nothing earned over the window
+        assert_eq!(payout_estimate(coal, 0.0, 60.0), (None, None));
+
+        let (epochs, hours) = payout_estimate(coal * 5 / 2, 1.0, 60.0);
+        assert_eq!(epochs, Some(3));
+        assert!((hours.unwrap() - 0.05).abs() < 1e-9);
+
+        assert_eq!(payout_estimate(u64::MAX, f64::MIN_POSITIVE, 60.0).0, Some(u32::MAX));
+    }
+
+    #[test]
+    fn pool_challenge_miners_follow_the_pubkey_format() {
+        pubkey_display::init(PubkeyFormat::Truncated);
+        let miner = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string();
+        let challenge = models::Challenge {
+            id: 1,
+            pool_id: 1,
+            submission_id: None,
+            challenge: vec![0xab; 32],
+            rewards_earned: None,
+            started_at: chrono::NaiveDateTime::default(),
+            ended_at: None,
+        };
+
+        let response = pool_challenge_response(challenge, vec![miner.clone()]);
+        assert_eq!(response.miners, vec![display_pubkey(&miner)]);
+        assert_eq!(response.miners, vec!["Toke..Q5DA".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn an_unknown_miner_has_no_payout_progress() {
+        if test_database().is_none() {
+            return;
+        }
+        let app_rr_database = AppRRDatabase::new(std::env::var("DATABASE_URL").unwrap());
+        let progress = app_rr_database
+            .get_miner_payout_progress(Pubkey::new_unique().to_string(), 1)
+            .await
+            .unwrap();
+        assert!(progress.is_none());
     }
 }
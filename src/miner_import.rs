@@ -0,0 +1,110 @@
+use std::{collections::HashSet, str::FromStr};
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use utoipa::ToSchema;
+
+// Rows accepted in a single import, larger files are split by the operator.
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MinerImportOutcome {
+    // new miner, signed up with the pool
+    Created,
+    // the miner existed and was signed up with the pool
+    Joined,
+    // already signed up with the pool, nothing changed
+    Existing,
+    // appears earlier in the same import
+    Duplicate,
+    InvalidPubkey,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MinerImportRow {
+    // 1 based line of the csv, or position in the json array
+    pub line: usize,
+    pub pubkey: String,
+    pub outcome: MinerImportOutcome,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct MinerImportReport {
+    pub created: usize,
+    pub joined: usize,
+    pub existing: usize,
+    pub duplicate: usize,
+    pub invalid: usize,
+    pub rows: Vec<MinerImportRow>,
+}
+
+impl MinerImportReport {
+    pub fn push(&mut self, row: MinerImportRow) {
+        match row.outcome {
+            MinerImportOutcome::Created => self.created += 1,
+            MinerImportOutcome::Joined => self.joined += 1,
+            MinerImportOutcome::Existing => self.existing += 1,
+            MinerImportOutcome::Duplicate => self.duplicate += 1,
+            MinerImportOutcome::InvalidPubkey => self.invalid += 1,
+        }
+        self.rows.push(row);
+    }
+}
+
+/// The pubkeys of a json array of strings, or of a csv with the pubkey in the
+/// first column and an optional `pubkey` header, with their line.
+pub fn parse_rows(body: &str, is_json: bool) -> Result<Vec<(usize, String)>, String> {
+    let rows: Vec<(usize, String)> = if is_json {
+        let pubkeys: Vec<String> = serde_json::from_str(body)
+            .map_err(|e| format!("Expected a json array of pubkeys: {}", e))?;
+        pubkeys
+            .into_iter()
+            .enumerate()
+            .map(|(i, pubkey)| (i + 1, pubkey))
+            .collect()
+    } else {
+        body.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let pubkey = line.split(',').next().unwrap_or_default();
+                (i + 1, pubkey.trim().trim_matches('"').to_string())
+            })
+            .filter(|(i, pubkey)| {
+                let is_header = *i == 1 && pubkey.eq_ignore_ascii_case("pubkey");
+                !pubkey.is_empty() && !is_header
+            })
+            .collect()
+    };
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(format!(
+            "At most {} rows can be imported at once",
+            MAX_IMPORT_ROWS
+        ));
+    }
+    Ok(rows)
+}
+
+/// Splits the rows into the pubkeys to import and the rows already settled,
+/// invalid pubkeys and repeats of an earlier row.
+pub fn validate_rows(rows: Vec<(usize, String)>) -> (Vec<(usize, Pubkey)>, Vec<MinerImportRow>) {
+    let mut seen = HashSet::new();
+    let mut valid = Vec::new();
+    let mut settled = Vec::new();
+    for (line, value) in rows {
+        let outcome = match Pubkey::from_str(&value) {
+            Ok(pubkey) if seen.insert(pubkey) => {
+                valid.push((line, pubkey));
+                continue;
+            }
+            Ok(_) => MinerImportOutcome::Duplicate,
+            Err(_) => MinerImportOutcome::InvalidPubkey,
+        };
+        settled.push(MinerImportRow {
+            line,
+            pubkey: value,
+            outcome,
+        });
+    }
+    (valid, settled)
+}
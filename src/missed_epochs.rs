@@ -0,0 +1,50 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Epochs whose cutoff passed without a single submission, counted since the
+/// pool started.
+#[derive(Debug, Default)]
+pub struct MissedEpochs {
+    missed: u64,
+    // unix seconds
+    last_missed_at: Option<i64>,
+    // each challenge is only counted once
+    last_missed_challenge: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct MissedEpochStatus {
+    pub missed: u64,
+    // unix seconds of the last missed epoch
+    pub last_missed_at: Option<i64>,
+}
+
+impl MissedEpochs {
+    /// Whether the challenge's epoch is newly missed.
+    pub fn record(&mut self, challenge: [u8; 32], now: i64) -> bool {
+        if self.last_missed_challenge == Some(challenge) {
+            return false;
+        }
+        self.missed += 1;
+        self.last_missed_at = Some(now);
+        self.last_missed_challenge = Some(challenge);
+        true
+    }
+
+    pub fn status(&self) -> MissedEpochStatus {
+        MissedEpochStatus {
+            missed: self.missed,
+            last_missed_at: self.last_missed_at,
+        }
+    }
+}
+
+/// Sent to every connected miner as a json text message when an epoch is
+/// missed, with `"type": "epoch_missed"`. Fresh nonce ranges for the same
+/// challenge follow right after.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "epoch_missed")]
+pub struct EpochMissedNotice {
+    pub epoch_id: u64,
+    pub seconds_past_cutoff: u64,
+}
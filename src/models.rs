@@ -1,7 +1,8 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::{mysql::MysqlType, prelude::*};
 use serde::{Deserialize, Serialize};
-use diesel::sql_types::{Integer, Text, BigInt, TinyInt, Unsigned, Nullable, Binary, Timestamp};
+use utoipa::ToSchema;
+use diesel::sql_types::{Integer, Text, BigInt, SmallInt, TinyInt, Unsigned, Nullable, Binary, Timestamp, Date, Double};
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = crate::schema::challenges)]
@@ -12,6 +13,8 @@ pub struct Challenge {
     pub submission_id: Option<i32>,
     pub challenge: Vec<u8>,
     pub rewards_earned: Option<u64>,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
@@ -21,9 +24,24 @@ pub struct InsertChallenge {
     pub pool_id: i32,
     pub challenge: Vec<u8>,
     pub rewards_earned: Option<u64>,
+    pub started_at: NaiveDateTime,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
+#[derive(Debug, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct EpochHistoryEntry {
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub rewards_earned: Option<u64>,
+    #[diesel(sql_type = Timestamp)]
+    pub started_at: NaiveDateTime,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub ended_at: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub duration_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = crate::schema::challenges)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct UpdateChallengeRewards {
@@ -57,6 +75,12 @@ pub struct InsertClaim {
     pub amount: u64,
 }
 
+#[derive(Debug, Copy, Clone, QueryableByName)]
+pub struct InsertClaimResult {
+    #[diesel(sql_type = BigInt)]
+    pub claim_id: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = crate::schema::miners)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
@@ -77,7 +101,7 @@ pub struct Pool {
     pub claimed_rewards: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName, ToSchema)]
 #[diesel(table_name = crate::schema::submissions)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct Submission {
@@ -85,11 +109,11 @@ pub struct Submission {
     pub miner_id: i32,
     pub challenge_id: i32,
     pub nonce: u64,
-    pub difficulty: i8,
+    pub difficulty: i16,
     pub created_at: NaiveDateTime,
 }
 
-#[derive(Debug, Deserialize, Serialize, QueryableByName)]
+#[derive(Debug, Deserialize, Serialize, QueryableByName, ToSchema)]
 pub struct SubmissionWithPubkey {
     #[sql_type = "Integer"]
     pub id: i32,
@@ -99,8 +123,8 @@ pub struct SubmissionWithPubkey {
     pub challenge_id: i32,
     #[sql_type = "Unsigned<BigInt>"]
     pub nonce: u64,
-    #[sql_type = "TinyInt"]
-    pub difficulty: i8,
+    #[sql_type = "SmallInt"]
+    pub difficulty: i16,
     #[sql_type = "Timestamp"]
     pub created_at: NaiveDateTime,
     #[sql_type = "Text"]
@@ -114,7 +138,9 @@ pub struct InsertSubmission {
     pub miner_id: i32,
     pub challenge_id: i32,
     pub nonce: u64,
-    pub difficulty: i8,
+    pub difficulty: i16,
+    // size of the nonce range the solution was found in
+    pub range_size: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
@@ -148,9 +174,12 @@ pub struct InsertTxn {
     pub txn_type: String,
     pub signature: String,
     pub priority_fee: u32,
+    pub pool_id: Option<i32>,
+    // base and priority fee, None when the confirmed transaction couldn't be fetched
+    pub fee_paid_lamports: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = crate::schema::rewards)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct InsertReward {
@@ -158,7 +187,7 @@ pub struct InsertReward {
     pub pool_id: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = crate::schema::rewards)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct UpdateReward {
@@ -166,7 +195,38 @@ pub struct UpdateReward {
     pub balance: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
+/// Bonus credited to the referrer of a miner for the miner's earnings.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ReferralBonus {
+    pub referred_miner_id: i32,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ReferredMiner {
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub bonus_earned: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct PayoutProgress {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub balance: u64,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub auto_claim_threshold: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct EpochEarningsTotal {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_earned: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub epochs: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Queryable, Selectable, QueryableByName)]
 #[diesel(table_name = crate::schema::rewards)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct Reward {
@@ -174,7 +234,7 @@ pub struct Reward {
     pub miner_id: i32,
 }
 
-#[derive(Debug, Copy, Clone, Deserialize, Insertable)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Insertable)]
 #[diesel(table_name = crate::schema::earnings)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct InsertEarning {
@@ -182,4 +242,510 @@ pub struct InsertEarning {
     pub pool_id: i32,
     pub challenge_id: i32,
     pub amount: u64,
+    // the miner's share numerator, their hashpower in the challenge
+    pub hashpower: u64,
+}
+
+/// Rows of a batch insert that were written, and the ones skipped because
+/// they already existed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchInsertResult {
+    pub inserted: usize,
+    pub skipped: usize,
+    // miners whose earning was already recorded, they were credited for it then
+    pub skipped_miner_ids: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, QueryableByName, ToSchema)]
+pub struct DifficultyCount {
+    #[diesel(sql_type = SmallInt)]
+    pub difficulty: i16,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EpochOutcome {
+    Success,
+    AllAttemptsFailed,
+    // no solution was found by the cutoff
+    Missed,
+}
+
+impl EpochOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EpochOutcome::Success => "success",
+            EpochOutcome::AllAttemptsFailed => "all_attempts_failed",
+            EpochOutcome::Missed => "missed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InsertEpochOutcome {
+    pub pool_id: i32,
+    pub challenge_id: i32,
+    pub outcome: EpochOutcome,
+    pub attempts_used: u8,
+    pub final_priority_fee: u64,
+    pub first_send_ms: Option<u32>,
+    pub confirmed_ms: Option<u32>,
+    pub mine_event_ms: Option<u32>,
+    // json of the epoch's DifficultyHistogram
+    pub difficulty_histogram: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct EpochHistogram {
+    #[diesel(sql_type = Integer)]
+    pub challenge_id: i32,
+    #[diesel(sql_type = Text)]
+    pub difficulty_histogram: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct InsertMinerSession {
+    pub miner_id: i32,
+    pub pool_id: i32,
+    pub device_id: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+    pub epochs: u32,
+    pub submissions: u32,
+    pub best_difficulty: u32,
+    pub earned: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct InsertAuditEntry {
+    pub pool_id: i32,
+    pub action: String,
+    // as sent by the client, it may not be a valid pubkey
+    pub pubkey: String,
+    pub ip: String,
+    pub amount: Option<u64>,
+    pub outcome: String,
+    pub error_code: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct AuditEntry {
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Text)]
+    pub action: String,
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+    #[diesel(sql_type = Text)]
+    pub ip: String,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub amount: Option<u64>,
+    #[diesel(sql_type = Text)]
+    pub outcome: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub error_code: Option<String>,
+    #[diesel(sql_type = Timestamp)]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct MinerReputation {
+    #[diesel(sql_type = Integer)]
+    pub miner_id: i32,
+    #[diesel(sql_type = Integer)]
+    pub reputation_score: i32,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_valid_submissions: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_invalid_submissions: u64,
+    #[diesel(sql_type = Unsigned<TinyInt>)]
+    pub consecutive_invalid: u8,
+    #[diesel(sql_type = Timestamp)]
+    pub last_updated: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct MinerSession {
+    #[diesel(sql_type = Nullable<Text>)]
+    pub device_id: Option<String>,
+    #[diesel(sql_type = Timestamp)]
+    pub started_at: NaiveDateTime,
+    #[diesel(sql_type = Timestamp)]
+    pub ended_at: NaiveDateTime,
+    #[diesel(sql_type = Unsigned<Integer>)]
+    pub epochs: u32,
+    #[diesel(sql_type = Unsigned<Integer>)]
+    pub submissions: u32,
+    #[diesel(sql_type = Unsigned<Integer>)]
+    pub best_difficulty: u32,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub earned: u64,
+}
+
+/// Submission loop timestamps in milliseconds after the epoch cutoff.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct EpochTimings {
+    pub first_send_ms: Option<u32>,
+    pub confirmed_ms: Option<u32>,
+    pub mine_event_ms: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, QueryableByName)]
+pub struct LandingSample {
+    #[diesel(sql_type = Text)]
+    pub outcome: String,
+    #[diesel(sql_type = Nullable<Unsigned<Integer>>)]
+    pub first_send_ms: Option<u32>,
+    #[diesel(sql_type = Nullable<Unsigned<Integer>>)]
+    pub confirmed_ms: Option<u32>,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub final_priority_fee: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, QueryableByName)]
+pub struct EpochOutcomeDay {
+    #[diesel(sql_type = Date)]
+    pub day: NaiveDate,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub success_count: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub failure_count: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub success_attempts: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_final_priority_fee: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct NotifySetting {
+    #[diesel(sql_type = Integer)]
+    pub miner_id: i32,
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+    #[diesel(sql_type = Text)]
+    pub notify_url: String,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub min_notify_amount: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub balance: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct PoolConfigEntry {
+    #[diesel(sql_type = Text)]
+    pub config_key: String,
+    #[diesel(sql_type = Text)]
+    pub config_value: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigChange {
+    pub config_key: String,
+    pub old_value: Option<String>,
+    pub new_value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct ClaimRecord {
+    #[diesel(sql_type = Integer)]
+    pub claim_id: i32,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub amount_coal: u64,
+    #[diesel(sql_type = Text)]
+    pub txn_signature: String,
+    #[diesel(sql_type = Text)]
+    pub explorer_url: String,
+    #[diesel(sql_type = Timestamp)]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ClaimTotal {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_claimed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct FeesPaid {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub fees_paid: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub txn_count: u64,
+    // txns with a known fee, fees_paid only sums those
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub txns_with_fee: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct MaxId {
+    #[diesel(sql_type = Nullable<Integer>)]
+    pub max_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct SubmissionCount {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub submission_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct ChallengeDetail {
+    #[diesel(sql_type = Integer)]
+    pub challenge_id: i32,
+    // first 8 bytes of the challenge, hex encoded
+    #[diesel(sql_type = Text)]
+    pub challenge_hex_prefix: String,
+    #[diesel(sql_type = Timestamp)]
+    pub started_at: NaiveDateTime,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub ended_at: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub duration_secs: Option<i64>,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub rewards_earned: Option<u64>,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub submission_count: u64,
+    #[diesel(sql_type = Nullable<SmallInt>)]
+    pub best_difficulty: Option<i16>,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub participating_miners: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct MinerPubkey {
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct MinerCount {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub miner_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ReplicationHeartbeat {
+    #[diesel(sql_type = Timestamp)]
+    pub beat_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct MiningEfficiency {
+    // nonces of the ranges that produced a submission
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub useful_nonces: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub assigned_nonces: u64,
+}
+
+/// The pool's epochs of a window, as summarized for pool listing sites.
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct PoolListingStats {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub epochs_won: u64,
+    // None when no epoch earned rewards
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub avg_reward: Option<u64>,
+    // sum of 2^difficulty of each miner's best submission per challenge
+    #[diesel(sql_type = Double)]
+    pub expected_hashes: f64,
+}
+
+/// Rewards the pool's epochs of a window paid out per unit of hashpower.
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct RewardYield {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub epochs: u64,
+    // sum over the epochs of the distributed rewards over their total hashpower, in grains
+    #[diesel(sql_type = Double)]
+    pub rewards_per_hashpower: f64,
+    // from the start of the window's first epoch until now
+    #[diesel(sql_type = BigInt)]
+    pub span_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct SessionCount {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub session_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ChallengeDistribution {
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub rewards_earned: Option<u64>,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub commission: Option<u64>,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub total_hashpower: Option<u64>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub txn_signature: Option<String>,
+    // set once a redistribution was applied to the challenge
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub redistributed_at: Option<NaiveDateTime>,
+}
+
+/// A miner's earnings and best submission in a challenge.
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ChallengeMinerShare {
+    #[diesel(sql_type = Integer)]
+    pub miner_id: i32,
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+    #[diesel(sql_type = Nullable<SmallInt>)]
+    pub best_difficulty: Option<i16>,
+    // sum of the miner's earnings rows, None when they have none
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub recorded: Option<u64>,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub recorded_hashpower: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ChallengeEarning {
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub amount: u64,
+    // None for earnings recorded before hashpower was stored
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub hashpower: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct EpochDistributionEntry {
+    #[diesel(sql_type = Text)]
+    pub pubkey: String,
+    // best difficulty the miner submitted in the epoch
+    #[diesel(sql_type = Nullable<SmallInt>)]
+    pub difficulty_submitted: Option<i16>,
+    #[diesel(sql_type = Nullable<Unsigned<BigInt>>)]
+    pub hashpower: Option<u64>,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub earned: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct EpochDistributionTotals {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_distributed: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub participant_count: u64,
+}
+
+/// Lifetime counters of a pool and the rewards it still owes its miners.
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct PoolTotals {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_rewards: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub claimed_rewards: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub outstanding_rewards: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+pub struct ActivityTotals {
+    #[diesel(sql_type = Integer)]
+    pub day_of_week: i32,
+    #[diesel(sql_type = Integer)]
+    pub hour_of_day: i32,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_active_miners: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_submissions: u64,
+}
+
+/// Average activity in one hour of the week, day_of_week 0 is Monday.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ActivityBucket {
+    pub hour_of_day: u8,
+    pub day_of_week: u8,
+    pub avg_active_miners: f64,
+    pub avg_submissions: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName, ToSchema)]
+pub struct PoolClaimStats {
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub claim_count: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub unique_miners: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub total_amount: u64,
+    #[diesel(sql_type = Unsigned<BigInt>)]
+    pub largest_amount: u64,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub first_claim_at: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub last_claim_at: Option<NaiveDateTime>,
+}
+
+/// Human readable description of a model, used in log messages.
+pub trait ModelDescription {
+    fn describe() -> &'static str;
+}
+
+impl ModelDescription for InsertChallenge {
+    fn describe() -> &'static str {
+        "new challenge"
+    }
+}
+
+impl ModelDescription for UpdateChallengeRewards {
+    fn describe() -> &'static str {
+        "challenge rewards update"
+    }
+}
+
+impl ModelDescription for InsertClaim {
+    fn describe() -> &'static str {
+        "new claim"
+    }
+}
+
+impl ModelDescription for InsertSubmission {
+    fn describe() -> &'static str {
+        "new submission"
+    }
+}
+
+impl ModelDescription for InsertTxn {
+    fn describe() -> &'static str {
+        "new transaction"
+    }
+}
+
+impl ModelDescription for InsertReward {
+    fn describe() -> &'static str {
+        "new miner rewards account"
+    }
+}
+
+impl ModelDescription for UpdateReward {
+    fn describe() -> &'static str {
+        "miner rewards update"
+    }
+}
+
+impl ModelDescription for InsertEarning {
+    fn describe() -> &'static str {
+        "new earning"
+    }
+}
+
+impl ModelDescription for InsertMinerSession {
+    fn describe() -> &'static str {
+        "miner session"
+    }
+}
+
+impl ModelDescription for InsertEpochOutcome {
+    fn describe() -> &'static str {
+        "epoch outcome"
+    }
 }
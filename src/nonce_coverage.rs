@@ -0,0 +1,41 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NonceRangeEntry {
+    pub pubkey: String,
+    pub device_id: Option<String>,
+    pub range_start: u64,
+    // exclusive
+    pub range_end: u64,
+    pub range_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SubmissionHeatmapResponse {
+    pub epoch_id: u64,
+    // ordered by range_start
+    pub ranges: Vec<NonceRangeEntry>,
+    // unassigned [start, end) ranges between 0 and the highest assigned nonce,
+    // including the ranges other federation instances hand out
+    #[schema(value_type = Vec<Vec<u64>>)]
+    pub coverage_gaps: Vec<(u64, u64)>,
+}
+
+/// Unassigned [start, end) ranges between 0 and the end of the highest range.
+pub fn coverage_gaps(ranges: &[Range<u64>]) -> Vec<(u64, u64)> {
+    let mut sorted: Vec<&Range<u64>> = ranges.iter().filter(|range| !range.is_empty()).collect();
+    sorted.sort_by_key(|range| range.start);
+
+    let mut gaps = Vec::new();
+    let mut covered_to = 0u64;
+    for range in sorted {
+        if range.start > covered_to {
+            gaps.push((covered_to, range.start));
+        }
+        covered_to = covered_to.max(range.end);
+    }
+    gaps
+}
@@ -0,0 +1,214 @@
+use axum::{response::Html, Json};
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::api_error::{ApiError, ApiErrorCode};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Coal pool server",
+        description = "HTTP API of the coal mining pool. Pools other than the primary one \
+            serve the same routes under /pools/{name}.\n\n\
+            Error responses are an ApiError json, its error_code is one of the \
+            values of ApiErrorCode. Requests accepting only `text/plain` get \
+            the plain text message instead. Every response carries an \
+            X-Request-Id header, echoed from the request when it sets one."
+    ),
+    paths(
+        crate::ws_handler,
+        crate::dashboard::dashboard_ws_handler,
+        crate::get_latest_blockhash,
+        crate::get_pool_authority_pubkey,
+        crate::get_pool_info,
+        crate::post_signup,
+        crate::post_claim,
+        crate::post_miner_settings,
+        crate::post_miner_delegate,
+        crate::post_miner_delegate_revoke,
+        crate::get_admin_config,
+        crate::put_admin_config,
+        crate::post_admin_challenge_redistribute,
+        crate::get_admin_audit_log,
+        crate::get_admin_nonce_ranges,
+        crate::delete_admin_nonce_range,
+        crate::post_admin_reload_wallet,
+        crate::post_admin_miners_import,
+        crate::get_connected_miners,
+        crate::get_timestamp,
+        crate::get_health,
+        crate::get_miner_balance,
+        crate::get_miner_devices,
+        crate::get_miner_watchdog,
+        crate::get_miner_reputation,
+        crate::get_pool_active_sessions,
+        crate::get_pool_mining_efficiency,
+        crate::get_pool_listing,
+        crate::get_pool_reward_formula,
+        crate::get_best_solution_timeline,
+        crate::get_pool_estimated_apy,
+        crate::get_miner_info,
+        crate::get_pool_busses,
+        crate::get_pool_submission_heatmap,
+        crate::get_pool_epoch_history,
+        crate::get_pool_stats,
+        crate::get_pool_epoch_reliability,
+        crate::get_last_challenge_submissions,
+        crate::get_current_challenge_proof,
+        crate::get_challenge,
+        crate::get_challenge_distribution,
+        crate::get_challenge_histogram,
+        crate::get_pool_challenges,
+        crate::get_pool_challenge,
+        crate::get_pool_reward_distribution_history,
+        crate::get_miner_rewards,
+        crate::get_miner_submissions,
+        crate::get_miner_claims,
+        crate::get_pool_claims,
+        crate::get_pool_totals,
+        crate::get_pool_fees_paid,
+        crate::get_pool_slots_until_reset,
+        crate::get_pool_miner_activity,
+        crate::get_miner_total_hashpower,
+        crate::get_miner_referrals,
+        crate::get_miner_next_reward_estimate,
+        crate::get_miner_payout_schedule,
+        crate::get_pool_total_hashpower,
+    ),
+    components(schemas(
+        ApiError,
+        ApiErrorCode,
+        crate::MinerSettingsBody,
+        crate::MinerDelegateBody,
+        crate::pool_info::PoolInfo,
+        crate::pool_info::Network,
+        crate::redistribution::RedistributionReport,
+        crate::redistribution::RedistributionMiner,
+        crate::redistribution::RedistributeCredit,
+        crate::PoolStatsResponse,
+        crate::pool_stats::PoolStatsSnapshot,
+        crate::fee_budget::FeeBudgetStatus,
+        crate::GuildStatsResponse,
+        crate::ToolStatsResponse,
+        crate::LandingLatencyStats,
+        crate::EpochReliabilityStats,
+        crate::EpochReliabilityDay,
+        crate::EpochReliabilityResponse,
+        crate::TokenAmount,
+        crate::MinerRewardsResponse,
+        crate::ActiveMinersCriteria,
+        crate::ActiveMinersResponse,
+        crate::EstimateConfidence,
+        crate::NextRewardEstimateResponse,
+        crate::PayoutScheduleResponse,
+        crate::MinerDeviceResponse,
+        crate::WatchdogStatus,
+        crate::MinerWatchdogResponse,
+        crate::MinerReputationResponse,
+        crate::ActiveSessionsResponse,
+        crate::MiningEfficiencyResponse,
+        crate::PoolListingResponse,
+        crate::reward_formula::RewardFormula,
+        crate::reward_formula::RewardExample,
+        crate::BestSolutionTimelineResponse,
+        crate::best_difficulty_timeline::BestDifficultyImprovement,
+        crate::CurrentProofResponse,
+        crate::EstimatedApyResponse,
+        crate::MinerInfoResponse,
+        crate::models::MinerSession,
+        crate::models::AuditEntry,
+        crate::AssignedNonceRangeEntry,
+        crate::DeletedNonceRangesResponse,
+        crate::ReloadWalletResponse,
+        crate::miner_import::MinerImportReport,
+        crate::miner_import::MinerImportRow,
+        crate::miner_import::MinerImportOutcome,
+        crate::audit_log::AuditLogStatus,
+        crate::HealthResponse,
+        crate::proof_updates::ProofUpdateStatus,
+        crate::replica_lag::ReplicaLagStatus,
+        crate::missed_epochs::MissedEpochStatus,
+        crate::ClaimResponse,
+        crate::MinerClaimsResponse,
+        crate::PoolClaimsResponse,
+        crate::PoolTotalsResponse,
+        crate::PoolFeesPaidResponse,
+        crate::ChallengeResponse,
+        crate::MinerDistribution,
+        crate::MinerReferralsResponse,
+        crate::ChallengeDistributionResponse,
+        crate::histogram::HistogramBucket,
+        crate::histogram::DifficultyHistogram,
+        crate::ChallengeHistogramResponse,
+        crate::RewardDistributionEntry,
+        crate::models::EpochDistributionEntry,
+        crate::ChallengeListEntry,
+        crate::PoolChallengeResponse,
+        crate::models::ChallengeDetail,
+        crate::ResetTimingResponse,
+        crate::LastChallengeSummaryResponse,
+        crate::models::DifficultyCount,
+        crate::bus_stats::BusStatsResponse,
+        crate::bus_stats::BusObservation,
+        crate::nonce_coverage::NonceRangeEntry,
+        crate::nonce_coverage::SubmissionHeatmapResponse,
+        crate::reprocess::ReprocessStatus,
+        crate::runtime_config::RuntimeConfig,
+        crate::models::Submission,
+        crate::models::SubmissionWithPubkey,
+        crate::models::EpochHistoryEntry,
+        crate::models::ClaimRecord,
+        crate::models::PoolClaimStats,
+        crate::models::ActivityBucket,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "miner", description = "Signup, claims and per miner data"),
+        (name = "pool", description = "Pool wide stats"),
+        (name = "admin", description = "Runtime configuration, requires the pool password"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        // Basic auth where the username is the miner pubkey and the password a
+        // signature over the request timestamp (and body, if any).
+        components.add_security_scheme(
+            "signed_pubkey",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)),
+        );
+        components.add_security_scheme(
+            "admin_password",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+pub async fn get_openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <title>Coal pool server API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>"##;
+
+pub async fn get_swagger_ui() -> Html<&'static str> {
+    Html(SWAGGER_UI_HTML)
+}
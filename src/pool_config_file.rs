@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Additional pools read from --config-file, served next to the primary pool
+/// of WALLET_PATH:
+///
+/// ```toml
+/// [[pools]]
+/// name = "cpu"
+/// wallet_path = "/keys/cpu.json"
+/// min_difficulty = 8
+/// commission_pct = 3
+/// ```
+///
+/// min_difficulty and commission_pct only seed the pool's config the first
+/// time it starts, like the matching arguments, and default to them.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfigFile {
+    #[serde(default)]
+    pub pools: Vec<PoolConfigEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfigEntry {
+    // served under /pools/<name>
+    pub name: String,
+    pub wallet_path: String,
+    pub min_difficulty: Option<u32>,
+    pub commission_pct: Option<u8>,
+}
+
+impl PoolConfigFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,297 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use solana_sdk::native_token::lamports_to_sol;
+use utoipa::ToSchema;
+
+use crate::{
+    coal_utils::amount_to_ui_string, epochs::PROOF_METADATA_PROTOCOL_VERSION,
+    runtime_config::RuntimeConfig, Config,
+};
+
+// Lamports transferred to the pool authority by the signup transaction.
+pub const SIGNUP_COST_LAMPORTS: u64 = 1_000_000;
+
+// Errors are ApiError json with an error_code.
+pub const CAPABILITY_JSON_ERRORS: &str = "json-errors";
+// One wallet may mine from several machines, tagged by device_id.
+pub const CAPABILITY_MULTI_DEVICE: &str = "multi-device";
+// Miners may let a delegate key mine and sign for them.
+pub const CAPABILITY_DELEGATES: &str = "delegates";
+// Work and solutions carry an epoch id with protocol_version 2.
+pub const CAPABILITY_EPOCH_BINDING: &str = "epoch-binding";
+// Work carries the proof's last_hash_at and observed slot with protocol_version 3,
+// and /challenge/current/proof serves the raw proof account.
+pub const CAPABILITY_PROOF_METADATA: &str = "proof-metadata";
+// Live pool events on /ws/dashboard.
+pub const CAPABILITY_DASHBOARD_WS: &str = "dashboard-ws";
+// The pool reprocesses its proof for chromium rewards.
+pub const CAPABILITY_CHROMIUM: &str = "chromium";
+// The pool mines as a guild member.
+pub const CAPABILITY_GUILD: &str = "guild";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Devnet,
+    Testnet,
+}
+
+impl Network {
+    /// Cluster named in the rpc url, mainnet for any other url.
+    pub fn from_rpc_url(rpc_url: &str) -> Self {
+        let rpc_url = rpc_url.to_lowercase();
+        if rpc_url.contains("devnet") {
+            Network::Devnet
+        } else if rpc_url.contains("testnet") {
+            Network::Testnet
+        } else {
+            Network::Mainnet
+        }
+    }
+}
+
+/// Where miners reach a pool, built from --public-url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicUrls {
+    pub websocket_url: String,
+    pub http_url: String,
+}
+
+impl PublicUrls {
+    /// Takes a ws, wss, http or https url of the server, path is the prefix
+    /// the pool is served under.
+    pub fn new(public_url: &str, path: &str) -> Result<Self, String> {
+        let public_url = public_url.trim_end_matches('/');
+        let (scheme, host) = public_url
+            .split_once("://")
+            .ok_or(format!("Invalid public url {}", public_url))?;
+        let (ws_scheme, http_scheme) = match scheme {
+            "ws" | "http" => ("ws", "http"),
+            "wss" | "https" => ("wss", "https"),
+            _ => {
+                return Err(format!(
+                    "Invalid public url {}, expected a ws, wss, http or https url",
+                    public_url
+                ))
+            }
+        };
+        if host.is_empty() {
+            return Err(format!("Invalid public url {}", public_url));
+        }
+        Ok(PublicUrls {
+            websocket_url: format!("{}://{}{}", ws_scheme, host, path),
+            http_url: format!("{}://{}{}", http_scheme, host, path),
+        })
+    }
+}
+
+/// Parameters and features of the pool for client software. Fields are only
+/// ever added, optional ones are left out when unset, and clients should
+/// feature-detect against capabilities.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolInfo {
+    pub pool_authority: String,
+    pub pool_id: i32,
+    pub server_version: String,
+    // newest websocket protocol version, see protocol_versions
+    pub protocol_version: u16,
+    // only known when the server was started with --public-url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub websocket_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_url: Option<String>,
+    pub network: Network,
+    pub signup_cost_lamports: u64,
+    pub signup_cost_sol: f64,
+    pub min_difficulty: u32,
+    pub claim_cooldown_secs: u64,
+    pub min_claim_amount: u64,
+    pub min_claim_amount_ui: String,
+    pub commission_pct: u8,
+    // websocket protocol versions, see the protocol_version ws param
+    pub protocol_versions: Vec<u8>,
+    // whitelisted miners sign up for free and bypass max_miners
+    pub whitelist_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_miners: Option<usize>,
+    pub max_devices_per_miner: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guild: Option<String>,
+    pub capabilities: Vec<String>,
+}
+
+pub fn get_pool_info(
+    config: &Config,
+    runtime_config: &RuntimeConfig,
+    pool_authority: String,
+    auto_reprocess: bool,
+) -> PoolInfo {
+    let mut capabilities = vec![
+        CAPABILITY_JSON_ERRORS,
+        CAPABILITY_MULTI_DEVICE,
+        CAPABILITY_DELEGATES,
+        CAPABILITY_EPOCH_BINDING,
+        CAPABILITY_PROOF_METADATA,
+        CAPABILITY_DASHBOARD_WS,
+    ];
+    if auto_reprocess {
+        capabilities.push(CAPABILITY_CHROMIUM);
+    }
+    if config.guild.is_some() {
+        capabilities.push(CAPABILITY_GUILD);
+    }
+
+    PoolInfo {
+        pool_authority,
+        pool_id: config.pool_id,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROOF_METADATA_PROTOCOL_VERSION as u16,
+        websocket_url: config.public_urls.as_ref().map(|urls| urls.websocket_url.clone()),
+        http_url: config.public_urls.as_ref().map(|urls| urls.http_url.clone()),
+        network: config.network,
+        signup_cost_lamports: SIGNUP_COST_LAMPORTS,
+        signup_cost_sol: lamports_to_sol(SIGNUP_COST_LAMPORTS),
+        min_difficulty: runtime_config.min_difficulty,
+        claim_cooldown_secs: runtime_config.claim_cooldown_secs,
+        min_claim_amount: runtime_config.min_claim_amount,
+        min_claim_amount_ui: amount_to_ui_string(runtime_config.min_claim_amount),
+        commission_pct: runtime_config.commission_pct,
+        protocol_versions: (1..=PROOF_METADATA_PROTOCOL_VERSION).collect(),
+        whitelist_enabled: config.whitelist.is_some(),
+        max_miners: config.max_miners,
+        max_devices_per_miner: config.max_devices_per_miner,
+        guild: config.guild.map(|g| g.guild.to_string()),
+        capabilities: capabilities.into_iter().map(String::from).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::{coal_utils::GuildStatus, ws_auth::AuthWindow};
+
+    fn config() -> Config {
+        Config {
+            password: String::new(),
+            whitelist: None,
+            pool_id: 3,
+            max_miners: None,
+            max_devices_per_miner: 4,
+            guild: None,
+            tool: None,
+            tool_durability_warning: 0,
+            operator_webhook_url: None,
+            public_urls: None,
+            network: Network::Mainnet,
+            referral_bonus_pct: 0.0,
+            min_reputation_score: 0,
+            auth_window: AuthWindow {
+                max_age_secs: 30,
+                max_clock_skew_secs: 5,
+            },
+            trusted_proxies: "".parse().unwrap(),
+        }
+    }
+
+    fn runtime_config() -> RuntimeConfig {
+        RuntimeConfig {
+            commission_pct: 5,
+            min_claim_amount: 500_000_000_000,
+            claim_cooldown_secs: 1800,
+            min_difficulty: 8,
+        }
+    }
+
+    #[test]
+    fn pool_info_schema_is_stable() {
+        let pool_info = get_pool_info(&config(), &runtime_config(), "authority".to_string(), false);
+
+        // optional fields are left out, clients may rely on every other one
+        assert_eq!(
+            serde_json::to_value(pool_info).unwrap(),
+            json!({
+                "pool_authority": "authority",
+                "pool_id": 3,
+                "server_version": env!("CARGO_PKG_VERSION"),
+                "protocol_version": 3,
+                "network": "mainnet",
+                "signup_cost_lamports": 1_000_000,
+                "signup_cost_sol": 0.001,
+                "min_difficulty": 8,
+                "claim_cooldown_secs": 1800,
+                "min_claim_amount": 500_000_000_000u64,
+                "min_claim_amount_ui": "5",
+                "commission_pct": 5,
+                "protocol_versions": [1, 2, 3],
+                "whitelist_enabled": false,
+                "max_devices_per_miner": 4,
+                "capabilities": [
+                    "json-errors",
+                    "multi-device",
+                    "delegates",
+                    "epoch-binding",
+                    "proof-metadata",
+                    "dashboard-ws",
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn optional_fields_and_capabilities_follow_the_config() {
+        let guild = Pubkey::new_unique();
+        let config = Config {
+            whitelist: Some(HashSet::new()),
+            max_miners: Some(100),
+            guild: Some(GuildStatus {
+                member: Pubkey::new_unique(),
+                guild,
+                member_stake: 0,
+                guild_stake: 0,
+                total_stake: 0,
+                total_multiplier: 0,
+                is_active: true,
+            }),
+            public_urls: Some(PublicUrls::new("https://pool.example", "/pool/3").unwrap()),
+            network: Network::Devnet,
+            ..config()
+        };
+        let pool_info = get_pool_info(&config, &runtime_config(), "authority".to_string(), true);
+        let value = serde_json::to_value(pool_info).unwrap();
+
+        assert_eq!(value["websocket_url"], "wss://pool.example/pool/3");
+        assert_eq!(value["http_url"], "https://pool.example/pool/3");
+        assert_eq!(value["network"], "devnet");
+        assert_eq!(value["whitelist_enabled"], true);
+        assert_eq!(value["max_miners"], 100);
+        assert_eq!(value["guild"], guild.to_string());
+        let capabilities = value["capabilities"].as_array().unwrap();
+        assert_eq!(capabilities.len(), 8);
+        assert_eq!(capabilities[6], "chromium");
+        assert_eq!(capabilities[7], "guild");
+    }
+
+    #[test]
+    fn public_urls_take_the_scheme_of_either_protocol() {
+        let urls = PublicUrls::new("http://pool.example:3000/", "").unwrap();
+        assert_eq!(urls.websocket_url, "ws://pool.example:3000");
+        assert_eq!(urls.http_url, "http://pool.example:3000");
+
+        assert!(PublicUrls::new("pool.example", "").is_err());
+        assert!(PublicUrls::new("ftp://pool.example", "").is_err());
+        assert!(PublicUrls::new("wss://", "").is_err());
+    }
+
+    #[test]
+    fn network_is_read_from_the_rpc_url() {
+        assert_eq!(Network::from_rpc_url("https://api.devnet.solana.com"), Network::Devnet);
+        assert_eq!(Network::from_rpc_url("https://api.TESTNET.solana.com"), Network::Testnet);
+        assert_eq!(Network::from_rpc_url("https://rpc.example"), Network::Mainnet);
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use coal_api::state::Proof;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use utoipa::ToSchema;
+
+use crate::{coal_utils::amount_to_ui_string, AppState, EpochHashes};
+
+/// Live figures of the current epoch, served by /pool/stats and broadcast to
+/// dashboards.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolStatsSnapshot {
+    // open miner connections, a wallet mining with several devices counts once per device
+    pub active_miners: usize,
+    // distinct wallets among the connections
+    pub connected_wallets: usize,
+    // submissions received this epoch
+    pub submissions: usize,
+    // distinct wallets that submitted this epoch
+    pub submitting_miners: usize,
+    pub best_difficulty: u32,
+    // sum of the hashpower submitted this epoch
+    pub total_hashpower: u64,
+    pub proof_balance: u64,
+    pub proof_balance_ui: String,
+    pub last_hash_at: i64,
+}
+
+pub fn get_pool_stats_snapshot(
+    state: &AppState,
+    epoch_hashes: &EpochHashes,
+    proof: &Proof,
+) -> PoolStatsSnapshot {
+    let connection_pubkeys: Vec<Pubkey> = state.sockets.values().map(|c| c.pubkey).collect();
+    pool_stats_snapshot(&connection_pubkeys, epoch_hashes, proof)
+}
+
+/// The snapshot for connections of these miner pubkeys, one per connection.
+fn pool_stats_snapshot(
+    connection_pubkeys: &[Pubkey],
+    epoch_hashes: &EpochHashes,
+    proof: &Proof,
+) -> PoolStatsSnapshot {
+    let connected_wallets = connection_pubkeys.iter().collect::<HashSet<_>>().len();
+    let submitting_miners = epoch_hashes
+        .submissions
+        .keys()
+        .map(|(pubkey, _)| pubkey)
+        .collect::<HashSet<_>>()
+        .len();
+    let total_hashpower = epoch_hashes
+        .submissions
+        .values()
+        .fold(0u64, |total, (_, _, hashpower)| total.saturating_add(*hashpower));
+
+    PoolStatsSnapshot {
+        active_miners: connection_pubkeys.len(),
+        connected_wallets,
+        submissions: epoch_hashes.submissions.len(),
+        submitting_miners,
+        best_difficulty: epoch_hashes.best_hash.difficulty,
+        total_hashpower,
+        proof_balance: proof.balance,
+        proof_balance_ui: amount_to_ui_string(proof.balance),
+        last_hash_at: proof.last_hash_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bytemuck::Zeroable;
+
+    use super::*;
+    use crate::BestHash;
+
+    fn proof(balance: u64, last_hash_at: i64) -> Proof {
+        let mut proof = Proof::zeroed();
+        proof.balance = balance;
+        proof.last_hash_at = last_hash_at;
+        proof
+    }
+
+    #[test]
+    fn empty_epoch_without_connections() {
+        let state = AppState {
+            sockets: HashMap::new(),
+        };
+        let epoch_hashes = EpochHashes {
+            generation: 1,
+            best_hash: BestHash {
+                solution: None,
+                difficulty: 0,
+            },
+            submissions: HashMap::new(),
+        };
+        let snapshot = get_pool_stats_snapshot(&state, &epoch_hashes, &proof(0, 0));
+
+        assert_eq!(snapshot.active_miners, 0);
+        assert_eq!(snapshot.connected_wallets, 0);
+        assert_eq!(snapshot.submissions, 0);
+        assert_eq!(snapshot.submitting_miners, 0);
+        assert_eq!(snapshot.best_difficulty, 0);
+        assert_eq!(snapshot.total_hashpower, 0);
+        assert_eq!(snapshot.proof_balance_ui, "0");
+    }
+
+    #[test]
+    fn devices_count_per_connection_and_wallets_once() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let device = |name: &str| Some(name.to_string());
+        let epoch_hashes = EpochHashes {
+            generation: 3,
+            best_hash: BestHash {
+                solution: None,
+                difficulty: 21,
+            },
+            submissions: HashMap::from([
+                ((alice, device("rig-1")), (1, 21, 40_960)),
+                ((alice, device("rig-2")), (1, 17, 2_560)),
+                ((bob, None), (2, u32::MAX, u64::MAX)),
+            ]),
+        };
+        let snapshot = pool_stats_snapshot(
+            &[alice, alice, bob, Pubkey::new_unique()],
+            &epoch_hashes,
+            &proof(150_000_000_000, 1_724_000_000),
+        );
+
+        assert_eq!(snapshot.active_miners, 4);
+        assert_eq!(snapshot.connected_wallets, 3);
+        assert_eq!(snapshot.submissions, 3);
+        assert_eq!(snapshot.submitting_miners, 2);
+        assert_eq!(snapshot.best_difficulty, 21);
+        // saturates instead of overflowing
+        assert_eq!(snapshot.total_hashpower, u64::MAX);
+        assert_eq!(snapshot.proof_balance, 150_000_000_000);
+        assert_eq!(snapshot.proof_balance_ui, "1.5");
+        assert_eq!(snapshot.last_hash_at, 1_724_000_000);
+    }
+}
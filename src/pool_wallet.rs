@@ -0,0 +1,57 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+};
+use tokio::sync::watch;
+
+/// The pool authority keypair, swappable while the pool runs. Tasks keep the
+/// handle and read the current keypair whenever they sign, tasks with a
+/// subscription bound to the key watch it and resubscribe when it changes.
+#[derive(Clone)]
+pub struct PoolWallet {
+    path: Arc<PathBuf>,
+    keypair: Arc<watch::Sender<Arc<Keypair>>>,
+}
+
+impl PoolWallet {
+    pub fn new(path: PathBuf, keypair: Keypair) -> Self {
+        let (sender, _) = watch::channel(Arc::new(keypair));
+        PoolWallet {
+            path: Arc::new(path),
+            keypair: Arc::new(sender),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current keypair, kept no longer than one transaction.
+    pub fn keypair(&self) -> Arc<Keypair> {
+        self.keypair.borrow().clone()
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.borrow().pubkey()
+    }
+
+    /// Notified each time the keypair is swapped.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Keypair>> {
+        self.keypair.subscribe()
+    }
+
+    /// Reads the keypair file again, without swapping it in.
+    pub fn read(&self) -> Result<Keypair, String> {
+        read_keypair_file(self.path.as_path()).map_err(|e| e.to_string())
+    }
+
+    pub fn swap(&self, keypair: Keypair) {
+        self.keypair.send_replace(Arc::new(keypair));
+    }
+}
@@ -0,0 +1,182 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::sync::RwLock;
+use tracing::error;
+use utoipa::ToSchema;
+
+// How often the rpc's latest slot is fetched to work out the lag.
+const LATEST_SLOT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// When the pool proof was last updated by an account notification, and how
+/// far behind the rpc's latest slot notifications arrive.
+#[derive(Debug, Default)]
+pub struct ProofUpdates {
+    // unix seconds
+    last_update_at: Option<i64>,
+    last_update_slot: Option<u64>,
+    // context slot of the last notification, applied or coalesced
+    last_notification_slot: Option<u64>,
+    // latest slot minus the context slot of the last notification, when it arrived
+    lag_slots: Option<u64>,
+    latest_slot: Option<u64>,
+    notifications: u64,
+    // notifications older than the last update, or with unchanged proof data
+    coalesced: u64,
+    challenge_changes: u64,
+    // raw proof account of the last applied notification
+    account_data: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct ProofUpdateStatus {
+    // unix seconds of the last applied notification
+    pub last_update_at: Option<i64>,
+    // context slot of the last applied notification
+    pub last_update_slot: Option<u64>,
+    // context slot of the last notification, applied or coalesced
+    pub last_notification_slot: Option<u64>,
+    // the rpc's latest confirmed slot, fetched every 10 seconds
+    pub latest_slot: Option<u64>,
+    // slots the last notification was behind the latest slot when it arrived
+    pub lag_slots: Option<u64>,
+    pub notifications: u64,
+    pub coalesced: u64,
+    pub challenge_changes: u64,
+}
+
+impl ProofUpdates {
+    /// Whether a notification should overwrite the shared proof. Notifications
+    /// from before the last applied slot, as resent after a reconnect, and
+    /// ones that don't change the proof are coalesced.
+    pub fn record_notification(
+        &mut self,
+        context_slot: u64,
+        data: &[u8],
+        changed: bool,
+        now: i64,
+    ) -> bool {
+        self.notifications += 1;
+        self.last_notification_slot = Some(context_slot);
+        self.lag_slots = self.latest_slot.map(|latest| latest.saturating_sub(context_slot));
+        if self.last_update_slot.is_some_and(|slot| context_slot < slot) || !changed {
+            self.coalesced += 1;
+            return false;
+        }
+        self.last_update_slot = Some(context_slot);
+        self.last_update_at = Some(now);
+        self.account_data = Some(data.to_vec());
+        true
+    }
+
+    /// Raw proof account of the last applied notification, with its slot.
+    pub fn account_data(&self) -> Option<(&[u8], u64)> {
+        self.account_data.as_deref().zip(self.last_update_slot)
+    }
+
+    /// Forgets the account data once it is for the previous wallet's proof.
+    pub fn forget_account_data(&mut self) {
+        self.account_data = None;
+    }
+
+    /// Keeps the highest slot seen, a lagging rpc node doesn't move it back.
+    pub fn record_latest_slot(&mut self, slot: u64) {
+        self.latest_slot = self.latest_slot.max(Some(slot));
+    }
+
+    pub fn record_challenge_change(&mut self) {
+        self.challenge_changes += 1;
+    }
+
+    pub fn status(&self) -> ProofUpdateStatus {
+        ProofUpdateStatus {
+            last_update_at: self.last_update_at,
+            last_update_slot: self.last_update_slot,
+            last_notification_slot: self.last_notification_slot,
+            latest_slot: self.latest_slot,
+            lag_slots: self.lag_slots,
+            notifications: self.notifications,
+            coalesced: self.coalesced,
+            challenge_changes: self.challenge_changes,
+        }
+    }
+}
+
+/// Keeps the rpc's latest slot in proof_updates so the lag of the proof
+/// notifications can be reported.
+pub async fn proof_lag_system(rpc_client: Arc<RpcClient>, proof_updates: Arc<RwLock<ProofUpdates>>) {
+    loop {
+        match rpc_client
+            .get_slot_with_commitment(CommitmentConfig::confirmed())
+            .await
+        {
+            Ok(slot) => {
+                proof_updates.write().await.record_latest_slot(slot);
+            }
+            Err(e) => {
+                error!("Failed to get latest slot for proof lag: {:?}", e);
+            }
+        }
+        tokio::time::sleep(LATEST_SLOT_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_notifications_are_applied_in_slot_order() {
+        let mut updates = ProofUpdates::default();
+        assert!(updates.record_notification(100, &[1], true, 10));
+        assert!(updates.record_notification(100, &[2], true, 11));
+        assert!(updates.record_notification(105, &[3], true, 12));
+        // resent after a reconnect
+        assert!(!updates.record_notification(101, &[4], true, 13));
+        // the same proof again
+        assert!(!updates.record_notification(106, &[3], false, 14));
+
+        let status = updates.status();
+        assert_eq!(status.last_update_slot, Some(105));
+        assert_eq!(status.last_update_at, Some(12));
+        assert_eq!(status.last_notification_slot, Some(106));
+        assert_eq!((status.notifications, status.coalesced), (5, 2));
+        assert_eq!(updates.account_data(), Some((&[3u8][..], 105)));
+
+        updates.forget_account_data();
+        assert_eq!(updates.account_data(), None);
+    }
+
+    #[test]
+    fn lag_is_measured_when_a_notification_arrives() {
+        let mut updates = ProofUpdates::default();
+        updates.record_notification(100, &[1], true, 10);
+        // no latest slot fetched yet
+        assert_eq!(updates.status().lag_slots, None);
+
+        updates.record_latest_slot(103);
+        updates.record_notification(101, &[2], true, 11);
+        assert_eq!(updates.status().lag_slots, Some(2));
+
+        // unchanged notifications still count, the lag doesn't grow between mines
+        updates.record_latest_slot(200);
+        updates.record_notification(199, &[2], false, 12);
+        let status = updates.status();
+        assert_eq!(status.lag_slots, Some(1));
+        assert_eq!(status.last_update_slot, Some(101));
+
+        // a notification ahead of the last fetched slot has no lag
+        updates.record_notification(205, &[3], true, 13);
+        assert_eq!(updates.status().lag_slots, Some(0));
+    }
+
+    #[test]
+    fn the_latest_slot_never_moves_back() {
+        let mut updates = ProofUpdates::default();
+        updates.record_latest_slot(50);
+        updates.record_latest_slot(40);
+        assert_eq!(updates.status().latest_slot, Some(50));
+    }
+}
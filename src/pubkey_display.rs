@@ -0,0 +1,88 @@
+use std::{fmt, sync::OnceLock};
+
+use clap::ValueEnum;
+use solana_sdk::hash::hash;
+
+// Characters kept from each end of a truncated pubkey.
+const TRUNCATED_CHARS: usize = 4;
+// Bytes of the sha256 digest shown for a hashed pubkey, as hex.
+const HASHED_BYTES: usize = 6;
+
+static LOG_PUBKEYS: OnceLock<PubkeyFormat> = OnceLock::new();
+
+/// How miner pubkeys appear in logs and in api responses that aren't the
+/// miner's own data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PubkeyFormat {
+    /// The full base58 pubkey
+    Full,
+    /// The first and last 4 characters, e.g. `7xKX..gAsU`
+    Truncated,
+    /// The first 6 bytes of the sha256 of the base58 pubkey, as hex
+    Hashed,
+}
+
+impl PubkeyFormat {
+    pub fn format(&self, pubkey: &str) -> String {
+        match self {
+            PubkeyFormat::Full => pubkey.to_string(),
+            PubkeyFormat::Truncated => {
+                let chars: Vec<char> = pubkey.chars().collect();
+                if chars.len() <= TRUNCATED_CHARS * 2 {
+                    return pubkey.to_string();
+                }
+                let head: String = chars[..TRUNCATED_CHARS].iter().collect();
+                let tail: String = chars[chars.len() - TRUNCATED_CHARS..].iter().collect();
+                format!("{}..{}", head, tail)
+            }
+            PubkeyFormat::Hashed => hash(pubkey.as_bytes()).to_bytes()[..HASHED_BYTES]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        }
+    }
+}
+
+/// Sets the format used by display_pubkey, only the first call has an effect.
+pub fn init(format: PubkeyFormat) {
+    let _ = LOG_PUBKEYS.set(format);
+}
+
+/// The pubkey in the configured --log-pubkeys format, full if it was never set.
+pub fn display_pubkey(pubkey: &impl fmt::Display) -> String {
+    LOG_PUBKEYS
+        .get()
+        .copied()
+        .unwrap_or(PubkeyFormat::Full)
+        .format(&pubkey.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    #[test]
+    fn full_keeps_the_pubkey() {
+        assert_eq!(PubkeyFormat::Full.format(TOKEN_PROGRAM), TOKEN_PROGRAM);
+    }
+
+    #[test]
+    fn truncated_keeps_four_characters_of_each_end() {
+        assert_eq!(PubkeyFormat::Truncated.format(TOKEN_PROGRAM), "Toke..Q5DA");
+        assert_eq!(PubkeyFormat::Truncated.format("123456789"), "1234..6789");
+        // too short to shorten
+        assert_eq!(PubkeyFormat::Truncated.format("12345678"), "12345678");
+        assert_eq!(PubkeyFormat::Truncated.format(""), "");
+    }
+
+    #[test]
+    fn hashed_is_the_sha256_prefix_in_hex() {
+        assert_eq!(PubkeyFormat::Hashed.format(TOKEN_PROGRAM), "3df99733e34b");
+        assert_eq!(
+            PubkeyFormat::Hashed.format("11111111111111111111111111111111"),
+            "8a83665f3798"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    hashpower_for_difficulty,
+    models::{ChallengeDistribution, ChallengeMinerShare, InsertEarning, UpdateReward},
+    rewards::{calculate_earned_rewards, RewardError},
+};
+
+/// Which rewards balances a redistribution credits, depending on which part
+/// of the original distribution was lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RedistributeCredit {
+    /// Credit the earnings that are inserted, both writes were lost for those miners
+    #[default]
+    Missing,
+    /// Only insert the missing earnings, the rewards update went through
+    None,
+    /// Credit every miner's earning, the earnings were recorded but the rewards update was lost.
+    /// Refused once any redistribution was applied to the challenge
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RedistributionMiner {
+    pub miner_id: i32,
+    pub pubkey: String,
+    // recorded with the earnings, or of the miner's best submission when none were
+    pub hashpower: u64,
+    // share of the distributable rewards recomputed from the hashpower
+    pub expected: u64,
+    // sum of the existing earnings rows, None when the miner has none
+    pub recorded: Option<u64>,
+    pub earning_inserted: u64,
+    pub balance_credited: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RedistributionReport {
+    pub challenge_id: i32,
+    pub dry_run: bool,
+    pub applied: bool,
+    pub credit: RedistributeCredit,
+    pub rewards_earned: u64,
+    pub commission: u64,
+    // false when the distribution didn't record its commission and the
+    // current commission_pct was used
+    pub commission_recorded: bool,
+    pub distributable_rewards: u64,
+    pub total_hashpower: u64,
+    pub earnings_inserted: u64,
+    pub balances_credited: u64,
+    pub miners: Vec<RedistributionMiner>,
+}
+
+pub struct RedistributionPlan {
+    pub report: RedistributionReport,
+    pub earnings: Vec<InsertEarning>,
+    pub credits: Vec<UpdateReward>,
+}
+
+/// Works out the earnings and balance credits missing from a challenge's
+/// distribution. Earnings rows are written all at once, so a miner with any
+/// row for the challenge is taken as recorded and keeps its amount, only
+/// miners without one get an earning inserted. Miners without a row are
+/// credited the hashpower of their best submission only: submissions don't
+/// record the device, so a miner on several devices gets less than the
+/// distribution's per-device best would have given.
+pub fn plan_redistribution(
+    pool_id: i32,
+    challenge: &ChallengeDistribution,
+    rewards_earned: u64,
+    fallback_commission: u64,
+    shares: Vec<ChallengeMinerShare>,
+    credit: RedistributeCredit,
+) -> Result<RedistributionPlan, RewardError> {
+    let challenge_id = challenge.id;
+    let commission_recorded = challenge.commission.is_some();
+    let commission = challenge
+        .commission
+        .unwrap_or(fallback_commission)
+        .min(rewards_earned);
+    let distributable_rewards = rewards_earned - commission;
+
+    let hashpowers: Vec<u64> = shares
+        .iter()
+        .map(|share| match (share.recorded, share.recorded_hashpower) {
+            (Some(_), Some(hashpower)) => hashpower,
+            _ => share
+                .best_difficulty
+                .map(|diff| hashpower_for_difficulty(diff.max(0) as u32))
+                .unwrap_or(0),
+        })
+        .collect();
+    let total_hashpower = match challenge.total_hashpower {
+        Some(total) => total,
+        None => hashpowers.iter().fold(0u64, |total, h| total.saturating_add(*h)),
+    };
+
+    let mut earnings = Vec::new();
+    let mut credits = Vec::new();
+    let mut miners = Vec::new();
+    let mut total_earned: u64 = 0;
+    for (share, hashpower) in shares.into_iter().zip(hashpowers) {
+        let expected = calculate_earned_rewards(hashpower, total_hashpower, distributable_rewards)?;
+        let earning_inserted = match share.recorded {
+            Some(_) => 0,
+            None => expected,
+        };
+        let balance_credited = match credit {
+            RedistributeCredit::Missing => earning_inserted,
+            RedistributeCredit::None => 0,
+            RedistributeCredit::All => share.recorded.unwrap_or(expected),
+        };
+        total_earned = total_earned
+            .checked_add(share.recorded.unwrap_or(expected))
+            .ok_or(RewardError::RewardOverflow)?;
+
+        if earning_inserted > 0 {
+            earnings.push(InsertEarning {
+                miner_id: share.miner_id,
+                pool_id,
+                challenge_id,
+                amount: earning_inserted,
+                hashpower,
+            });
+        }
+        if balance_credited > 0 {
+            credits.push(UpdateReward {
+                miner_id: share.miner_id,
+                balance: balance_credited,
+            });
+        }
+        miners.push(RedistributionMiner {
+            miner_id: share.miner_id,
+            pubkey: share.pubkey,
+            hashpower,
+            expected,
+            recorded: share.recorded,
+            earning_inserted,
+            balance_credited,
+        });
+    }
+    if total_earned > distributable_rewards {
+        return Err(RewardError::ExceedsRewards);
+    }
+
+    Ok(RedistributionPlan {
+        report: RedistributionReport {
+            challenge_id,
+            dry_run: true,
+            applied: false,
+            credit,
+            rewards_earned,
+            commission,
+            commission_recorded,
+            distributable_rewards,
+            total_hashpower,
+            earnings_inserted: earnings.iter().map(|e| e.amount).sum(),
+            balances_credited: credits.iter().map(|c| c.balance).sum(),
+            miners,
+        },
+        earnings,
+        credits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(commission: Option<u64>, total_hashpower: Option<u64>) -> ChallengeDistribution {
+        ChallengeDistribution {
+            id: 7,
+            rewards_earned: Some(1_000),
+            commission,
+            total_hashpower,
+            txn_signature: None,
+            redistributed_at: None,
+        }
+    }
+
+    fn share(miner_id: i32, best_difficulty: Option<i16>, recorded: Option<(u64, u64)>) -> ChallengeMinerShare {
+        ChallengeMinerShare {
+            miner_id,
+            pubkey: format!("miner-{}", miner_id),
+            best_difficulty,
+            recorded: recorded.map(|(amount, _)| amount),
+            recorded_hashpower: recorded.map(|(_, hashpower)| hashpower),
+        }
+    }
+
+    // one miner with its earning recorded, two credited from their best submission
+    fn shares() -> Vec<ChallengeMinerShare> {
+        vec![
+            share(1, Some(12), Some((300, 20))),
+            share(2, Some(10), None),
+            share(3, Some(9), None),
+        ]
+    }
+
+    fn credits(plan: &RedistributionPlan) -> Vec<(i32, u64)> {
+        plan.credits.iter().map(|c| (c.miner_id, c.balance)).collect()
+    }
+
+    #[test]
+    fn missing_earnings_are_inserted_and_recorded_ones_kept() {
+        let plan = plan_redistribution(3, &challenge(Some(100), None), 1_000, 0, shares(), RedistributeCredit::Missing)
+            .unwrap();
+        let report = &plan.report;
+        assert_eq!(report.distributable_rewards, 900);
+        // the recorded hashpower of miner 1, the best submissions of 2 and 3
+        assert_eq!(report.total_hashpower, 20 + hashpower_for_difficulty(10) + hashpower_for_difficulty(9));
+        assert_eq!(report.total_hashpower, 50);
+
+        let miner = &report.miners[0];
+        assert_eq!((miner.hashpower, miner.expected, miner.recorded), (20, 360, Some(300)));
+        assert_eq!(miner.earning_inserted, 0);
+
+        assert_eq!(
+            plan.earnings.iter().map(|e| (e.miner_id, e.amount, e.hashpower, e.challenge_id, e.pool_id)).collect::<Vec<_>>(),
+            vec![(2, 360, 20, 7, 3), (3, 180, 10, 7, 3)]
+        );
+        assert_eq!(credits(&plan), vec![(2, 360), (3, 180)]);
+        assert_eq!((report.earnings_inserted, report.balances_credited), (540, 540));
+    }
+
+    #[test]
+    fn the_credit_mode_picks_the_balances_credited() {
+        let none = plan_redistribution(3, &challenge(Some(100), None), 1_000, 0, shares(), RedistributeCredit::None)
+            .unwrap();
+        assert_eq!(none.earnings.len(), 2);
+        assert!(none.credits.is_empty());
+
+        // the recorded amount is credited as is, not the recomputed one
+        let all = plan_redistribution(3, &challenge(Some(100), None), 1_000, 0, shares(), RedistributeCredit::All)
+            .unwrap();
+        assert_eq!(all.earnings.len(), 2);
+        assert_eq!(credits(&all), vec![(1, 300), (2, 360), (3, 180)]);
+    }
+
+    #[test]
+    fn only_the_best_submission_of_a_miner_without_earnings_counts() {
+        let plan = plan_redistribution(
+            3,
+            &challenge(Some(0), None),
+            1_000,
+            0,
+            vec![share(1, Some(11), None)],
+            RedistributeCredit::Missing,
+        )
+        .unwrap();
+        assert_eq!(plan.report.miners[0].hashpower, hashpower_for_difficulty(11));
+        assert_eq!(credits(&plan), vec![(1, 1_000)]);
+    }
+
+    #[test]
+    fn recorded_earnings_beyond_the_rewards_are_refused() {
+        let conflicting = vec![share(1, Some(12), Some((800, 20))), share(2, Some(10), None)];
+        let res = plan_redistribution(3, &challenge(Some(100), None), 1_000, 0, conflicting, RedistributeCredit::Missing);
+        assert_eq!(res.err(), Some(RewardError::ExceedsRewards));
+
+        // recorded amounts that fit alongside the inserted ones are kept
+        let fitting = vec![share(1, Some(12), Some((400, 20))), share(2, Some(10), None)];
+        let plan = plan_redistribution(3, &challenge(Some(100), None), 1_000, 0, fitting, RedistributeCredit::Missing)
+            .unwrap();
+        assert_eq!(credits(&plan), vec![(2, 450)]);
+    }
+
+    #[test]
+    fn zero_hashpower_credits_nothing() {
+        // below MIN_DIFF a submission has no hashpower
+        let shares = vec![share(1, Some(5), None), share(2, None, None)];
+        let plan = plan_redistribution(3, &challenge(Some(100), None), 1_000, 0, shares, RedistributeCredit::Missing)
+            .unwrap();
+        assert_eq!(plan.report.total_hashpower, 0);
+        assert!(plan.report.miners.iter().all(|m| m.hashpower == 0 && m.expected == 0));
+        assert!(plan.earnings.is_empty());
+        assert!(plan.credits.is_empty());
+
+        // a recorded total of 0 too
+        let plan = plan_redistribution(
+            3,
+            &challenge(Some(100), Some(0)),
+            1_000,
+            0,
+            vec![share(1, Some(12), None)],
+            RedistributeCredit::Missing,
+        )
+        .unwrap();
+        assert_eq!(plan.report.miners[0].expected, 0);
+        assert!(plan.earnings.is_empty());
+    }
+
+    #[test]
+    fn an_unrecorded_commission_falls_back_and_is_capped() {
+        let plan = plan_redistribution(3, &challenge(None, None), 1_000, 50, shares(), RedistributeCredit::Missing)
+            .unwrap();
+        assert!(!plan.report.commission_recorded);
+        assert_eq!((plan.report.commission, plan.report.distributable_rewards), (50, 950));
+
+        let plan = plan_redistribution(3, &challenge(None, None), 1_000, 5_000, shares(), RedistributeCredit::Missing);
+        // nothing is left to distribute, so miner 1's recorded 300 can't fit
+        assert_eq!(plan.err(), Some(RewardError::ExceedsRewards));
+    }
+}
@@ -0,0 +1,138 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use tokio::{sync::RwLock, time::Instant};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{app_database::AppDatabase, app_rr_database::AppRRDatabase};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+// A measurement older than this no longer counts, the lag is unknown.
+const MEASUREMENT_MAX_AGE: Duration = Duration::from_secs(10);
+// How long a write is assumed to be missing on the replica while the lag is unknown.
+const UNKNOWN_LAG_WINDOW: Duration = Duration::from_secs(60);
+
+/// How far the read replica is behind the primary, measured with a heartbeat
+/// row written to the primary and read back from the replica. Also remembers
+/// which miners had their data changed recently, so their replica reads can
+/// be flagged or sent to the primary.
+#[derive(Debug, Default)]
+pub struct ReplicaLag {
+    lag: Option<Duration>,
+    measured_at: Option<Instant>,
+    // heartbeat last written to the primary
+    last_beat: Option<NaiveDateTime>,
+    primary_failures: u64,
+    replica_failures: u64,
+    // miner pubkey -> when its claims or balance last changed on the primary
+    recent_writes: HashMap<String, Instant>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct ReplicaLagStatus {
+    // None while the replica or the primary can't be reached
+    pub lag_ms: Option<u64>,
+    // failed heartbeat writes and reads since startup
+    pub primary_failures: u64,
+    pub replica_failures: u64,
+}
+
+impl ReplicaLag {
+    fn current_lag(&self) -> Option<Duration> {
+        match self.measured_at {
+            Some(measured_at) if measured_at.elapsed() < MEASUREMENT_MAX_AGE => self.lag,
+            _ => None,
+        }
+    }
+
+    // a write older than this is on the replica by now
+    fn stale_window(&self) -> Duration {
+        match self.current_lag() {
+            Some(lag) => lag + HEARTBEAT_INTERVAL,
+            None => UNKNOWN_LAG_WINDOW,
+        }
+    }
+
+    pub fn record_write(&mut self, pubkey: String) {
+        let window = self.stale_window().max(UNKNOWN_LAG_WINDOW);
+        self.recent_writes
+            .retain(|_, written_at| written_at.elapsed() < window);
+        self.recent_writes.insert(pubkey, Instant::now());
+    }
+
+    /// Whether the replica may not have the miner's latest write yet.
+    pub fn may_be_stale(&self, pubkey: &str) -> bool {
+        self.recent_writes
+            .get(pubkey)
+            .is_some_and(|written_at| written_at.elapsed() < self.stale_window())
+    }
+
+    pub fn status(&self) -> ReplicaLagStatus {
+        ReplicaLagStatus {
+            lag_ms: self.current_lag().map(|lag| lag.as_millis() as u64),
+            primary_failures: self.primary_failures,
+            replica_failures: self.replica_failures,
+        }
+    }
+}
+
+/// Writes a heartbeat to the primary every 2 seconds and works out the
+/// replica's lag from the last heartbeat it has. Either database being
+/// unreachable only leaves the lag unknown until it is back.
+pub async fn replica_lag_system(
+    app_database: Arc<AppDatabase>,
+    app_rr_database: Arc<AppRRDatabase>,
+    pool_id: i32,
+    replica_lag: Arc<RwLock<ReplicaLag>>,
+) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        // checked before the next beat, a caught up replica has the last one
+        match app_rr_database.get_replication_heartbeat(pool_id).await {
+            Ok(Some(replica_beat)) => {
+                let mut replica_lag = replica_lag.write().await;
+                let lag = if replica_lag
+                    .last_beat
+                    .is_some_and(|beat| beat <= replica_beat)
+                {
+                    Duration::ZERO
+                } else {
+                    (chrono::Utc::now().naive_utc() - replica_beat)
+                        .to_std()
+                        .unwrap_or_default()
+                };
+                replica_lag.lag = Some(lag);
+                replica_lag.measured_at = Some(Instant::now());
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(
+                    "Failed to read the replication heartbeat from the replica: {:?}",
+                    e
+                );
+                replica_lag.write().await.replica_failures += 1;
+            }
+        }
+
+        // millisecond precision, as stored
+        let beat = chrono::DateTime::from_timestamp_millis(chrono::Utc::now().timestamp_millis())
+            .unwrap_or_default()
+            .naive_utc();
+        match app_database
+            .write_replication_heartbeat(pool_id, beat)
+            .await
+        {
+            Ok(()) => {
+                replica_lag.write().await.last_beat = Some(beat);
+            }
+            Err(e) => {
+                warn!("Failed to write the replication heartbeat: {:?}", e);
+                replica_lag.write().await.primary_failures += 1;
+            }
+        }
+    }
+}
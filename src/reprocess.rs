@@ -0,0 +1,196 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use coal_api::state::Proof;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use tokio::sync::{mpsc::UnboundedSender, Mutex, RwLock};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    app_database::AppDatabase,
+    coal_utils::{
+        get_chromium_mint, get_cutoff, get_fee_paid, get_init_reprocess_ix, get_reprocess_ix,
+        get_reprocessor_slot, REPROCESS_TARGET_SLOT,
+    },
+    models::InsertTxn,
+    pool_wallet::PoolWallet,
+    tx_builder::SolanaTransactionBuilder,
+    webhooks::{WebhookEvent, WebhookJob},
+};
+
+// Reprocessing is only started when the current epoch has at least this many
+// seconds left, so it never delays a mine submission.
+const MIN_SECS_BEFORE_CUTOFF: i64 = 20;
+const CU_LIMIT: u32 = 200_000;
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ReprocessStatus {
+    pub success_count: u64,
+    pub failure_count: u64,
+    pub last_attempt_at: Option<u64>,
+    pub last_success_at: Option<u64>,
+    pub last_signature: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Periodically reprocesses the pool wallet. Transactions are sent while
+/// holding the send lock shared with the mine submission loop, and use the
+/// same priority fee.
+pub struct ReprocessSystem {
+    pub rpc_client: Arc<RpcClient>,
+    pub wallet: PoolWallet,
+    pub app_database: Arc<AppDatabase>,
+    pub pool_id: i32,
+    pub proof: Arc<Mutex<Proof>>,
+    pub priority_fee: Arc<Mutex<u64>>,
+    pub send_lock: Arc<Mutex<()>>,
+    pub status: Arc<RwLock<ReprocessStatus>>,
+    pub webhook_sender: UnboundedSender<WebhookJob>,
+    pub operator_webhook_url: Option<String>,
+    pub interval: Duration,
+}
+
+impl ReprocessSystem {
+    pub async fn run(self) {
+        loop {
+            tokio::time::sleep(self.interval).await;
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs();
+            let result = self.reprocess().await;
+
+            let mut status = self.status.write().await;
+            status.last_attempt_at = Some(now);
+            let event = match result {
+                Ok(sig) => {
+                    info!("Reprocess succeeded. Sig: {}", sig);
+                    status.success_count += 1;
+                    status.last_success_at = Some(now);
+                    status.last_signature = Some(sig.clone());
+                    status.last_error = None;
+                    WebhookEvent::ReprocessSucceeded { signature: sig }
+                }
+                Err(e) => {
+                    error!("Reprocess failed: {}", e);
+                    status.failure_count += 1;
+                    status.last_error = Some(e.clone());
+                    WebhookEvent::ReprocessFailed { error: e }
+                }
+            };
+            drop(status);
+
+            if let Some(url) = &self.operator_webhook_url {
+                let _ = self.webhook_sender.send(WebhookJob {
+                    miner_id: None,
+                    url: url.clone(),
+                    event,
+                });
+            }
+        }
+    }
+
+    async fn reprocess(&self) -> Result<String, String> {
+        // a run started before the wallet was swapped finishes with its key
+        let wallet = self.wallet.keypair();
+        let signer = wallet.pubkey();
+
+        let start_slot = match get_reprocessor_slot(&self.rpc_client, signer).await? {
+            Some(slot) => slot,
+            None => {
+                let ix = get_init_reprocess_ix(signer);
+                self.send(&wallet, "reprocess_init", vec![ix]).await?;
+                get_reprocessor_slot(&self.rpc_client, signer)
+                    .await?
+                    .ok_or("Reprocessor missing after init")?
+            }
+        };
+
+        let target_slot = start_slot + REPROCESS_TARGET_SLOT;
+        loop {
+            let slot = self
+                .rpc_client
+                .get_slot()
+                .await
+                .map_err(|_| "Failed to get slot".to_string())?;
+            if slot >= target_slot {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(400)).await;
+        }
+
+        let create_ata_ix = create_associated_token_account_idempotent(
+            &signer,
+            &signer,
+            &get_chromium_mint(),
+            &spl_token::id(),
+        );
+        let ix = get_reprocess_ix(signer);
+        self.send(&wallet, "reprocess", vec![create_ata_ix, ix]).await
+    }
+
+    async fn wait_for_epoch_headroom(&self) {
+        loop {
+            let proof = *self.proof.lock().await;
+            if get_cutoff(proof, 0) >= MIN_SECS_BEFORE_CUTOFF {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    async fn send(
+        &self,
+        wallet: &Keypair,
+        txn_type: &str,
+        ixs: Vec<solana_sdk::instruction::Instruction>,
+    ) -> Result<String, String> {
+        // mine submissions near the cutoff go first
+        self.wait_for_epoch_headroom().await;
+        let send_guard = self.send_lock.lock().await;
+        let prio_fee = *self.priority_fee.lock().await;
+
+        let mut tx_builder = SolanaTransactionBuilder::new()
+            .compute_limit(CU_LIMIT)
+            .priority_fee(prio_fee);
+        for ix in ixs {
+            tx_builder = tx_builder.instruction(ix);
+        }
+        let tx = tx_builder
+            .build_and_sign(wallet, &self.rpc_client)
+            .await
+            .map_err(|e| format!("Failed to build {} transaction: {:?}", txn_type, e))?;
+
+        let sig = self
+            .rpc_client
+            .send_transaction(&tx)
+            .await
+            .map_err(|e| format!("Failed to send {} transaction: {:?}", txn_type, e))?;
+        // held only while sending, mine submissions don't wait for the confirmation
+        drop(send_guard);
+        self.rpc_client
+            .confirm_transaction_with_spinner(&sig, &tx.message.recent_blockhash, self.rpc_client.commitment())
+            .await
+            .map_err(|e| format!("Failed to confirm {} transaction: {:?}", txn_type, e))?;
+
+        let itxn = InsertTxn {
+            txn_type: txn_type.to_string(),
+            signature: sig.to_string(),
+            priority_fee: prio_fee as u32,
+            pool_id: Some(self.pool_id),
+            fee_paid_lamports: get_fee_paid(&self.rpc_client, &sig).await,
+        };
+        if let Err(e) = self.app_database.add_new_txn(itxn).await {
+            error!("Failed to add {} txn to db: {:?}", txn_type, e);
+        }
+
+        Ok(sig.to_string())
+    }
+}
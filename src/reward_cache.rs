@@ -0,0 +1,43 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::models::Reward;
+
+// How long a balance read from the database is reused.
+const REWARD_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Miner reward balances read from the primary database, keyed by miner
+/// pubkey. Entries are dropped whenever the miner's balance is written.
+#[derive(Clone, Default)]
+pub struct RewardCache(Arc<RwLock<HashMap<String, (Reward, Instant)>>>);
+
+impl RewardCache {
+    pub async fn get(&self, pubkey: &str) -> Option<Reward> {
+        match self.0.read().await.get(pubkey) {
+            Some((reward, cached_at)) if cached_at.elapsed() < REWARD_CACHE_TTL => Some(*reward),
+            _ => None,
+        }
+    }
+
+    pub async fn insert(&self, pubkey: String, reward: Reward) {
+        let mut cache = self.0.write().await;
+        cache.retain(|_, (_, cached_at)| cached_at.elapsed() < REWARD_CACHE_TTL);
+        cache.insert(pubkey, (reward, Instant::now()));
+    }
+
+    pub async fn invalidate(&self, miner_ids: &HashSet<i32>) {
+        self.0
+            .write()
+            .await
+            .retain(|_, (reward, _)| !miner_ids.contains(&reward.miner_id));
+    }
+
+    pub async fn clear(&self) {
+        self.0.write().await.clear();
+    }
+}
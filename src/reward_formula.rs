@@ -0,0 +1,110 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    coal_utils::COAL_TOKEN_DECIMALS,
+    hashpower_for_difficulty,
+    rewards::{calculate_earned_rewards, calculate_referral_bonus},
+    runtime_config::RuntimeConfig,
+    MAX_HASHPOWER, MAX_SUBMISSION_DIFFICULTY, MIN_DIFF, MIN_HASHPOWER,
+};
+
+// Difficulty of the worked example when the request doesn't pick one.
+pub const EXAMPLE_DIFFICULTY: u32 = 16;
+// The example epoch has this many times the example submission's hashpower.
+const EXAMPLE_HASHPOWER_MULTIPLE: u64 = 10;
+
+/// The parameters the reward distribution uses, read from the same constants,
+/// functions and config, so it can't drift from what miners are paid.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RewardFormula {
+    // each epoch's rewards after commission are split in proportion to the
+    // hashpower of its submissions, a device is credited with its best
+    // submission of the epoch and a miner's devices are summed
+    pub reward_mode: String,
+    pub formula: String,
+    // lowest difficulty the pool accepts
+    pub min_difficulty: u32,
+    // difficulty credited with min_hashpower
+    pub base_difficulty: u32,
+    pub min_hashpower: u64,
+    // hashpower is multiplied by this for each difficulty above base_difficulty
+    pub growth_base: u64,
+    pub max_hashpower: u64,
+    // lowest difficulty credited with max_hashpower
+    pub max_hashpower_difficulty: Option<u32>,
+    pub commission_pct: u8,
+    // paid to the referrer out of the commission, on top of what the referred miner earns
+    pub referral_bonus_pct: f64,
+    pub example: RewardExample,
+}
+
+/// A submission of the given difficulty in an epoch of 1 COAL, where the
+/// other submissions add up to nine times its hashpower. Amounts in grains.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RewardExample {
+    pub difficulty: u32,
+    // below min_difficulty the submission is rejected and earns nothing
+    pub accepted: bool,
+    pub hashpower: u64,
+    pub epoch_total_hashpower: u64,
+    pub epoch_rewards: u64,
+    // epoch_rewards after commission
+    pub distributable_rewards: u64,
+    pub earned: u64,
+    pub referrer_bonus: u64,
+}
+
+impl RewardFormula {
+    pub fn new(
+        runtime_config: &RuntimeConfig,
+        referral_bonus_pct: f64,
+        example_difficulty: u32,
+    ) -> Self {
+        let growth_base = hashpower_for_difficulty(MIN_DIFF + 1)
+            .checked_div(hashpower_for_difficulty(MIN_DIFF))
+            .unwrap_or(0);
+        let max_hashpower_difficulty = (MIN_DIFF..=MAX_SUBMISSION_DIFFICULTY)
+            .find(|difficulty| hashpower_for_difficulty(*difficulty) >= MAX_HASHPOWER);
+
+        let accepted = example_difficulty >= runtime_config.min_difficulty;
+        let hashpower = if accepted {
+            hashpower_for_difficulty(example_difficulty)
+        } else {
+            0
+        };
+        let epoch_total_hashpower = hashpower.saturating_mul(EXAMPLE_HASHPOWER_MULTIPLE);
+        let epoch_rewards = 10u64.pow(COAL_TOKEN_DECIMALS as u32);
+        let distributable_rewards = runtime_config.distributable_rewards(epoch_rewards);
+        let earned =
+            calculate_earned_rewards(hashpower, epoch_total_hashpower, distributable_rewards)
+                .unwrap_or(0);
+
+        RewardFormula {
+            reward_mode: "proportional".to_string(),
+            formula: format!(
+                "hashpower = {} * {}^(difficulty - {}), at most {}; earned = distributable_rewards * hashpower / epoch_total_hashpower; distributable_rewards = rewards * (100 - {}) / 100",
+                MIN_HASHPOWER, growth_base, MIN_DIFF, MAX_HASHPOWER, runtime_config.commission_pct
+            ),
+            min_difficulty: runtime_config.min_difficulty,
+            base_difficulty: MIN_DIFF,
+            min_hashpower: MIN_HASHPOWER,
+            growth_base,
+            max_hashpower: MAX_HASHPOWER,
+            max_hashpower_difficulty,
+            commission_pct: runtime_config.commission_pct,
+            referral_bonus_pct,
+            example: RewardExample {
+                difficulty: example_difficulty,
+                accepted,
+                hashpower,
+                epoch_total_hashpower,
+                epoch_rewards,
+                distributable_rewards,
+                earned,
+                referrer_bonus: calculate_referral_bonus(earned, referral_bonus_pct)
+                    .min(epoch_rewards - distributable_rewards),
+            },
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::cmp::Ordering;
+
+use drillx_2::Solution;
+
+// Hashpower shares are computed in millionths of the total.
+const SHARE_PRECISION: u128 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewardError {
+    // the earned amount doesn't fit in a u64
+    RewardOverflow,
+    // the earned amount is more than the rewards being distributed
+    ExceedsRewards,
+}
+
+/// Share of the distributable rewards earned by hashpower out of
+/// total_hashpower. Never more than distributable_rewards.
+pub fn calculate_earned_rewards(
+    hashpower: u64,
+    total_hashpower: u64,
+    distributable_rewards: u64,
+) -> Result<u64, RewardError> {
+    if total_hashpower == 0 {
+        return Ok(0);
+    }
+    let hashpower_share = (hashpower as u128)
+        .saturating_mul(SHARE_PRECISION)
+        .saturating_div(total_hashpower as u128);
+    let earned_rewards = hashpower_share
+        .saturating_mul(distributable_rewards as u128)
+        .saturating_div(SHARE_PRECISION);
+
+    let earned_rewards = u64::try_from(earned_rewards).map_err(|_| RewardError::RewardOverflow)?;
+    if earned_rewards > distributable_rewards {
+        return Err(RewardError::ExceedsRewards);
+    }
+    Ok(earned_rewards)
+}
+
+/// Bonus paid to the referrer of a miner that earned `earned`, bonus_pct
+/// percent of it. The percentage is rounded to basis points.
+pub fn calculate_referral_bonus(earned: u64, bonus_pct: f64) -> u64 {
+    let basis_points = (bonus_pct * 100.0).round().clamp(0.0, 10_000.0) as u128;
+    // never more than earned, so it fits in a u64
+    (earned as u128 * basis_points / 10_000) as u64
+}
+
+/// Whether new should replace current as the epoch's best solution. The
+/// higher difficulty wins, and on equal difficulty the lexicographically
+/// smaller hash wins, so the outcome doesn't depend on arrival order.
+pub fn is_better_solution(new: &Solution, current: &Solution) -> bool {
+    let new_hash = new.to_hash();
+    let current_hash = current.to_hash();
+    match new_hash.difficulty().cmp(&current_hash.difficulty()) {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => new_hash.h < current_hash.h,
+    }
+}
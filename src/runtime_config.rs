@@ -0,0 +1,104 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::MIN_DIFF;
+
+pub const COMMISSION_PCT: &str = "commission_pct";
+pub const MIN_CLAIM_AMOUNT: &str = "min_claim_amount";
+pub const CLAIM_COOLDOWN_SECS: &str = "claim_cooldown_secs";
+pub const MIN_DIFFICULTY: &str = "min_difficulty";
+
+pub const KEYS: [&str; 4] = [
+    COMMISSION_PCT,
+    MIN_CLAIM_AMOUNT,
+    CLAIM_COOLDOWN_SECS,
+    MIN_DIFFICULTY,
+];
+
+// Upper bound on the claim cooldown, one week.
+const MAX_CLAIM_COOLDOWN_SECS: u64 = 604_800;
+// Difficulties above this are practically unreachable and would lock miners out.
+const MAX_MIN_DIFFICULTY: u32 = 32;
+
+/// Pool settings that can be changed at runtime through the admin API.
+/// Stored as key/value rows in the pool_config table.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RuntimeConfig {
+    /// Percentage of each mine reward kept by the pool
+    pub commission_pct: u8,
+    /// Smallest amount a miner may claim, in grains
+    pub min_claim_amount: u64,
+    /// Seconds a miner must wait between claims
+    pub claim_cooldown_secs: u64,
+    /// Lowest difficulty accepted from miners
+    pub min_difficulty: u32,
+}
+
+impl RuntimeConfig {
+    /// Validates and applies a single key, leaving the config untouched on error.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            COMMISSION_PCT => {
+                let v: u8 = value
+                    .parse()
+                    .map_err(|_| format!("{} must be an integer", key))?;
+                if v > 100 {
+                    return Err(format!("{} must be between 0 and 100", key));
+                }
+                self.commission_pct = v;
+            }
+            MIN_CLAIM_AMOUNT => {
+                self.min_claim_amount = value
+                    .parse()
+                    .map_err(|_| format!("{} must be an unsigned integer", key))?;
+            }
+            CLAIM_COOLDOWN_SECS => {
+                let v: u64 = value
+                    .parse()
+                    .map_err(|_| format!("{} must be an unsigned integer", key))?;
+                if v > MAX_CLAIM_COOLDOWN_SECS {
+                    return Err(format!(
+                        "{} must be at most {}",
+                        key, MAX_CLAIM_COOLDOWN_SECS
+                    ));
+                }
+                self.claim_cooldown_secs = v;
+            }
+            MIN_DIFFICULTY => {
+                let v: u32 = value
+                    .parse()
+                    .map_err(|_| format!("{} must be an unsigned integer", key))?;
+                if !(MIN_DIFF..=MAX_MIN_DIFFICULTY).contains(&v) {
+                    return Err(format!(
+                        "{} must be between {} and {}",
+                        key, MIN_DIFF, MAX_MIN_DIFFICULTY
+                    ));
+                }
+                self.min_difficulty = v;
+            }
+            _ => return Err(format!("unknown config key {}", key)),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            COMMISSION_PCT => Some(self.commission_pct.to_string()),
+            MIN_CLAIM_AMOUNT => Some(self.min_claim_amount.to_string()),
+            CLAIM_COOLDOWN_SECS => Some(self.claim_cooldown_secs.to_string()),
+            MIN_DIFFICULTY => Some(self.min_difficulty.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn to_entries(&self) -> Vec<(String, String)> {
+        KEYS.iter()
+            .filter_map(|key| self.get(key).map(|v| (key.to_string(), v)))
+            .collect()
+    }
+
+    /// Amount of a mine reward that is distributed to miners after commission.
+    pub fn distributable_rewards(&self, rewards: u64) -> u64 {
+        ((rewards as u128) * (100 - self.commission_pct.min(100) as u128) / 100) as u64
+    }
+}
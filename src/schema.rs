@@ -1,5 +1,24 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        pool_id -> Integer,
+        #[max_length = 16]
+        action -> Varchar,
+        #[max_length = 64]
+        pubkey -> Varchar,
+        #[max_length = 45]
+        ip -> Varchar,
+        amount -> Nullable<Unsigned<Bigint>>,
+        #[max_length = 16]
+        outcome -> Varchar,
+        #[max_length = 32]
+        error_code -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     challenges (id) {
         id -> Integer,
@@ -10,6 +29,13 @@ diesel::table! {
         rewards_earned -> Nullable<Unsigned<Bigint>>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        started_at -> Timestamp,
+        ended_at -> Nullable<Timestamp>,
+        txn_id -> Nullable<Integer>,
+        commission -> Nullable<Unsigned<Bigint>>,
+        total_hashpower -> Nullable<Unsigned<Bigint>>,
+        nonces_assigned -> Nullable<Unsigned<Bigint>>,
+        redistributed_at -> Nullable<Timestamp>,
     }
 }
 
@@ -25,6 +51,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    config_history (id) {
+        id -> Integer,
+        pool_id -> Integer,
+        #[max_length = 64]
+        config_key -> Varchar,
+        #[max_length = 255]
+        old_value -> Nullable<Varchar>,
+        #[max_length = 255]
+        new_value -> Varchar,
+        #[max_length = 100]
+        changed_by -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     earnings (id) {
         id -> Integer,
@@ -34,6 +76,103 @@ diesel::table! {
         amount -> Unsigned<Bigint>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        hashpower -> Nullable<Unsigned<Bigint>>,
+    }
+}
+
+diesel::table! {
+    epoch_outcomes (id) {
+        id -> Integer,
+        pool_id -> Integer,
+        challenge_id -> Integer,
+        #[max_length = 30]
+        outcome -> Varchar,
+        attempts_used -> Unsigned<Tinyint>,
+        final_priority_fee -> Unsigned<Bigint>,
+        created_at -> Timestamp,
+        first_send_ms -> Nullable<Unsigned<Integer>>,
+        confirmed_ms -> Nullable<Unsigned<Integer>>,
+        mine_event_ms -> Nullable<Unsigned<Integer>>,
+        difficulty_histogram -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    late_submissions (id) {
+        id -> Integer,
+        miner_id -> Integer,
+        challenge_id -> Integer,
+        nonce -> Unsigned<Bigint>,
+        difficulty -> Smallint,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    miner_delegates (id) {
+        id -> Integer,
+        miner_id -> Integer,
+        #[max_length = 44]
+        delegate_pubkey -> Varchar,
+        #[max_length = 200]
+        authorization_signature -> Varchar,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    miner_referrals (id) {
+        id -> Integer,
+        referrer_miner_id -> Integer,
+        referred_miner_id -> Integer,
+        bonus_earned -> Unsigned<Bigint>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    miner_reputations (miner_id) {
+        miner_id -> Integer,
+        reputation_score -> Integer,
+        total_valid_submissions -> Unsigned<Bigint>,
+        total_invalid_submissions -> Unsigned<Bigint>,
+        consecutive_invalid -> Unsigned<Tinyint>,
+        last_updated -> Timestamp,
+    }
+}
+
+diesel::table! {
+    miner_sessions (id) {
+        id -> Integer,
+        miner_id -> Integer,
+        pool_id -> Integer,
+        #[max_length = 64]
+        device_id -> Nullable<Varchar>,
+        started_at -> Timestamp,
+        ended_at -> Timestamp,
+        epochs -> Unsigned<Integer>,
+        submissions -> Unsigned<Integer>,
+        best_difficulty -> Unsigned<Integer>,
+        earned -> Unsigned<Bigint>,
+    }
+}
+
+diesel::table! {
+    miner_settings (id) {
+        id -> Integer,
+        miner_id -> Integer,
+        #[max_length = 255]
+        notify_url -> Nullable<Varchar>,
+        min_notify_amount -> Unsigned<Bigint>,
+        notifications_enabled -> Bool,
+        consecutive_failures -> Unsigned<Integer>,
+        #[max_length = 255]
+        disabled_reason -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        auto_claim_threshold -> Nullable<Unsigned<Bigint>>,
     }
 }
 
@@ -48,6 +187,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    pool_config (pool_id, config_key) {
+        pool_id -> Integer,
+        #[max_length = 64]
+        config_key -> Varchar,
+        #[max_length = 255]
+        config_value -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    pending_rewards (id) {
+        id -> Integer,
+        miner_id -> Integer,
+        pool_id -> Integer,
+        balance -> Unsigned<Bigint>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     pools (id) {
         id -> Integer,
@@ -62,6 +223,13 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    replication_heartbeats (pool_id) {
+        pool_id -> Integer,
+        beat_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     rewards (id) {
         id -> Integer,
@@ -78,12 +246,13 @@ diesel::table! {
         id -> Integer,
         miner_id -> Integer,
         challenge_id -> Integer,
-        difficulty -> Tinyint,
+        difficulty -> Smallint,
         nonce -> Unsigned<Bigint>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
         #[max_length = 16]
         digest -> Nullable<Binary>,
+        range_size -> Unsigned<Bigint>,
     }
 }
 
@@ -97,15 +266,29 @@ diesel::table! {
         priority_fee -> Unsigned<Integer>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        pool_id -> Nullable<Integer>,
+        fee_paid_lamports -> Nullable<Unsigned<Bigint>>,
     }
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
     challenges,
     claims,
+    config_history,
     earnings,
+    epoch_outcomes,
+    late_submissions,
+    miner_delegates,
+    miner_referrals,
+    miner_reputations,
+    miner_sessions,
+    miner_settings,
     miners,
+    pending_rewards,
+    pool_config,
     pools,
+    replication_heartbeats,
     rewards,
     submissions,
     txns,
@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::coal_utils::amount_to_ui_string;
+
+/// What a miner connection accomplished, updated by the submission handler
+/// and the reward distribution while the connection is open.
+#[derive(Debug)]
+pub struct SessionStats {
+    started_at: NaiveDateTime,
+    // epochs the connection was credited in
+    epochs: AtomicU32,
+    // accepted submissions of the current epoch, late ones aren't counted
+    submissions: AtomicU32,
+    best_difficulty: AtomicU32,
+    earned: AtomicU64,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        SessionStats {
+            started_at: chrono::Utc::now().naive_utc(),
+            epochs: AtomicU32::new(0),
+            submissions: AtomicU32::new(0),
+            best_difficulty: AtomicU32::new(0),
+            earned: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_submission(&self, difficulty: u32) {
+        self.submissions.fetch_add(1, Ordering::Relaxed);
+        self.best_difficulty.fetch_max(difficulty, Ordering::Relaxed);
+    }
+
+    pub fn record_epoch(&self, earned: u64) {
+        self.epochs.fetch_add(1, Ordering::Relaxed);
+        // saturating, an overflow here would only skew the summary
+        let _ = self
+            .earned
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                Some(total.saturating_add(earned))
+            });
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        let ended_at = chrono::Utc::now().naive_utc();
+        let earned = self.earned.load(Ordering::Relaxed);
+        SessionSummary {
+            started_at: self.started_at,
+            ended_at,
+            duration_secs: (ended_at - self.started_at).num_seconds().max(0) as u64,
+            epochs: self.epochs.load(Ordering::Relaxed),
+            submissions: self.submissions.load(Ordering::Relaxed),
+            best_difficulty: self.best_difficulty.load(Ordering::Relaxed),
+            earned,
+            earned_ui: amount_to_ui_string(earned),
+        }
+    }
+}
+
+/// Sent to the miner as a json text message before the server closes the
+/// connection, with `"type": "session_summary"`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "session_summary")]
+pub struct SessionSummary {
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+    pub duration_secs: u64,
+    pub epochs: u32,
+    pub submissions: u32,
+    pub best_difficulty: u32,
+    // estimated, in grains, the credited earnings may differ slightly
+    pub earned: u64,
+    pub earned_ui: String,
+}
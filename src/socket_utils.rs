@@ -0,0 +1,202 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use axum::http::{header::USER_AGENT, HeaderMap};
+use ipnet::IpNet;
+
+// Longest user agent kept, longer ones are cut.
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Reverse proxies whose X-Forwarded-For and X-Real-IP headers are believed,
+/// a comma separated list of ips and networks from --trusted-proxies.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("{} is not an ip or network", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(TrustedProxies)
+    }
+}
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// The client's ip. Behind a reverse proxy the peer is the proxy, so the
+/// forwarding headers are used instead, but only when the peer is a trusted
+/// proxy, anyone else could set them to anything. Each proxy appends the
+/// address it got the request from to X-Forwarded-For, so the entries are
+/// walked from the right and the first one not added by a trusted proxy is
+/// the client. X-Real-IP is used when there is no X-Forwarded-For.
+pub fn extract_real_ip(
+    headers: &HeaderMap,
+    addr: SocketAddr,
+    trusted_proxies: &TrustedProxies,
+) -> IpAddr {
+    let peer = addr.ip().to_canonical();
+    if !trusted_proxies.contains(peer) {
+        return peer;
+    }
+    let forwarded_for: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    if forwarded_for.is_empty() {
+        return headers
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+            .map(|ip| ip.to_canonical())
+            .unwrap_or(peer);
+    }
+    let mut client = peer;
+    for entry in forwarded_for.iter().rev() {
+        match entry.parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip.to_canonical();
+                if !trusted_proxies.contains(client) {
+                    break;
+                }
+            }
+            // the hop that added it is the last one that can be believed
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+/// The User-Agent header, cut to MAX_USER_AGENT_LEN.
+pub fn extract_user_agent(headers: &HeaderMap) -> Option<String> {
+    let user_agent = headers.get(USER_AGENT)?.to_str().ok()?;
+    Some(user_agent.chars().take(MAX_USER_AGENT_LEN).collect())
+}
+
+/// Whether ip is reachable on the public internet. Loopback, private, link
+/// local, unique local and other special purpose addresses are not.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // shared address space, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+                // 0.0.0.0/8
+                || a == 0
+                // IETF protocol assignments, 192.0.0.0/24
+                || (a == 192 && b == 0 && c == 0)
+                // benchmarking, 198.18.0.0/15
+                || (a == 198 && (b & 0xfe) == 18)
+                // reserved, 240.0.0.0/4
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let segments = ip.segments();
+                let first = segments[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local addresses, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // link local addresses, fe80::/10
+                    || (first & 0xffc0) == 0xfe80
+                    // NAT64, 64:ff9b::/96, can reach any IPv4 address
+                    || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                    // documentation, 2001:db8::/32
+                    || (first == 0x2001 && segments[1] == 0xdb8)
+                    // 6to4, 2002::/16, can reach any IPv4 address
+                    || first == 0x2002)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn public_addresses_are_public() {
+        assert!(is_public("1.1.1.1"));
+        assert!(is_public("100.128.0.1"));
+        assert!(is_public("192.0.1.1"));
+        assert!(is_public("198.20.0.1"));
+        assert!(is_public("2606:4700:4700::1111"));
+        assert!(is_public("::ffff:1.1.1.1"));
+    }
+
+    #[test]
+    fn special_purpose_ipv4_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "0.1.2.3",
+            "100.64.0.1",
+            "100.127.255.255",
+            "192.0.0.8",
+            "192.0.2.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "203.0.113.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(!is_public(ip), "{} is public", ip);
+        }
+    }
+
+    #[test]
+    fn special_purpose_ipv6_addresses_are_not_public() {
+        for ip in [
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "ff02::1",
+            "64:ff9b::a00:1",
+            "2001:db8::1",
+            "2002:a00:1::1",
+            "::ffff:10.0.0.1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip), "{} is public", ip);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use coal_api::state::Proof;
+use drillx_2::Solution;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::{watch, Mutex, RwLock};
+use tracing::{error, info, warn};
+
+use crate::{epochs::EpochGeneration, BestHash, DeviceId, EpochHashes};
+
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The epoch in progress, written to disk so a restart mid-epoch keeps the
+/// submissions received so far.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub current_nonce: u64,
+    pub current_challenge: [u8; 32],
+    // solution digest, nonce and difficulty
+    pub best_solution: Option<([u8; 16], [u8; 8], u32)>,
+    // (pubkey, device id) -> (miner_id, difficulty, hashpower)
+    pub submissions: HashMap<(String, DeviceId), (i32, u32, u64)>,
+}
+
+impl StateSnapshot {
+    pub fn capture(
+        current_nonce: u64,
+        current_challenge: [u8; 32],
+        epoch_hashes: &EpochHashes,
+    ) -> Self {
+        StateSnapshot {
+            current_nonce,
+            current_challenge,
+            best_solution: epoch_hashes
+                .best_hash
+                .solution
+                .map(|solution| (solution.d, solution.n, epoch_hashes.best_hash.difficulty)),
+            submissions: epoch_hashes
+                .submissions
+                .iter()
+                .map(|((pubkey, device_id), submission)| {
+                    ((pubkey.to_string(), device_id.clone()), *submission)
+                })
+                .collect(),
+        }
+    }
+
+    /// The epoch hashes to resume with, submissions with an invalid pubkey are dropped.
+    pub fn epoch_hashes(&self, generation: u64) -> EpochHashes {
+        let (solution, difficulty) = match self.best_solution {
+            Some((digest, nonce, difficulty)) => (Some(Solution::new(digest, nonce)), difficulty),
+            None => (None, 0),
+        };
+        EpochHashes {
+            generation,
+            best_hash: BestHash {
+                solution,
+                difficulty,
+            },
+            submissions: self
+                .submissions
+                .iter()
+                .filter_map(|((pubkey, device_id), submission)| {
+                    let pubkey = Pubkey::from_str(pubkey).ok()?;
+                    Some(((pubkey, device_id.clone()), *submission))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.best_solution.is_none() && self.submissions.is_empty()
+    }
+
+    /// Writes to a temporary file first so a crash never leaves a partial snapshot.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = bincode::serialize(self).map_err(io::Error::other)?;
+        let tmp_path = tmp_path(path);
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    PathBuf::from(tmp_path)
+}
+
+/// The snapshot at path if it is for the proof's current challenge.
+pub fn restore(path: &Path, proof: &Proof) -> Option<StateSnapshot> {
+    if !path.exists() {
+        return None;
+    }
+    match StateSnapshot::load(path) {
+        Ok(snapshot) if snapshot.current_challenge == proof.challenge => {
+            info!(
+                "Restoring {} submissions from state snapshot {}",
+                snapshot.submissions.len(),
+                path.display()
+            );
+            Some(snapshot)
+        }
+        Ok(_) => {
+            info!(
+                "State snapshot {} is for an older challenge, ignoring it",
+                path.display()
+            );
+            remove(path);
+            None
+        }
+        Err(e) => {
+            warn!("Failed to read state snapshot {}: {:?}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Deletes the snapshot once its epoch has been submitted.
+pub fn remove(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            error!(
+                "Failed to delete state snapshot {}: {:?}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Saves the epoch in progress every SNAPSHOT_INTERVAL, nothing is written
+/// while the epoch has no submissions.
+pub async fn state_snapshot_system(
+    path: PathBuf,
+    epoch_generation: watch::Receiver<EpochGeneration>,
+    epoch_hashes: Arc<RwLock<EpochHashes>>,
+    nonce: Arc<Mutex<u64>>,
+) {
+    loop {
+        tokio::time::sleep(SNAPSHOT_INTERVAL).await;
+
+        let current_nonce = *nonce.lock().await;
+        // the generation only changes while the epoch hashes are write locked
+        let reader = epoch_hashes.read().await;
+        let current_challenge = epoch_generation.borrow().challenge;
+        let snapshot = StateSnapshot::capture(current_nonce, current_challenge, &reader);
+        drop(reader);
+        if snapshot.is_empty() {
+            continue;
+        }
+        let app_path = path.clone();
+        match tokio::task::spawn_blocking(move || snapshot.save(&app_path)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                error!("Failed to write state snapshot {}: {:?}", path.display(), e);
+            }
+            Err(e) => {
+                error!("State snapshot task failed: {:?}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{DeviceId, EpochHashes};
+
+/// Sent to the miner as a json text message once a submission improves the
+/// device's epoch best, with `"type": "submission_ack"`. Every other
+/// submission gets a SubmissionRejected instead.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "submission_ack")]
+pub struct SubmissionAck {
+    // difficulty the server computed for the solution
+    pub difficulty: u32,
+    // always true, kept for clients that read it
+    pub improved: bool,
+    // 1 is the best, devices with the same difficulty share a rank
+    pub rank: usize,
+    pub submitters: usize,
+    pub pool_best_difficulty: u32,
+}
+
+impl SubmissionAck {
+    /// Ranks the device's epoch best among every submission of the epoch.
+    pub fn new(epoch_hashes: &EpochHashes, key: &(Pubkey, DeviceId), difficulty: u32) -> Self {
+        let best = epoch_hashes
+            .submissions
+            .get(key)
+            .map_or(difficulty, |(_, best, _)| *best);
+        let rank = 1 + epoch_hashes
+            .submissions
+            .values()
+            .filter(|(_, other, _)| *other > best)
+            .count();
+        SubmissionAck {
+            difficulty,
+            improved: true,
+            rank,
+            submitters: epoch_hashes.submissions.len(),
+            pool_best_difficulty: epoch_hashes.best_hash.difficulty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    // the solution doesn't hash to the challenge
+    Invalid,
+    // the epoch it names is no longer kept
+    ExpiredEpoch,
+    // the nonce isn't in a range assigned to the device for the epoch
+    NonceOutOfRange,
+    // valid, but for a challenge the pool already moved on from
+    Late,
+    BelowMinDifficulty,
+    // no better than the device's earlier submissions this epoch
+    NotImproved,
+}
+
+/// Sent to the miner as a json text message when a submission isn't
+/// credited, with `"type": "submission_rejected"`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "submission_rejected")]
+pub struct SubmissionRejected {
+    pub reason: RejectReason,
+    // difficulty the server computed, None when the solution wasn't valid
+    pub difficulty: Option<u32>,
+}
+
+impl SubmissionRejected {
+    pub fn new(reason: RejectReason, difficulty: Option<u32>) -> Self {
+        SubmissionRejected { reason, difficulty }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::BestHash;
+
+    fn epoch_hashes(bests: &[(u8, u32)], pool_best: u32) -> EpochHashes {
+        EpochHashes {
+            generation: 1,
+            best_hash: BestHash {
+                solution: None,
+                difficulty: pool_best,
+            },
+            submissions: bests
+                .iter()
+                .map(|(device, best)| (key(*device), (1, *best, 0)))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    fn key(device: u8) -> (Pubkey, DeviceId) {
+        (Pubkey::new_from_array([device; 32]), Some(device.to_string()))
+    }
+
+    #[test]
+    fn devices_are_ranked_by_their_epoch_best() {
+        let epoch_hashes = epoch_hashes(&[(1, 20), (2, 15), (3, 15), (4, 9)], 20);
+
+        let ack = SubmissionAck::new(&epoch_hashes, &key(1), 20);
+        assert_eq!((ack.rank, ack.submitters, ack.pool_best_difficulty), (1, 4, 20));
+
+        // ties share a rank, the next one counts everyone above
+        assert_eq!(SubmissionAck::new(&epoch_hashes, &key(2), 15).rank, 2);
+        assert_eq!(SubmissionAck::new(&epoch_hashes, &key(3), 15).rank, 2);
+        assert_eq!(SubmissionAck::new(&epoch_hashes, &key(4), 9).rank, 4);
+    }
+
+    #[test]
+    fn the_rank_is_of_the_credited_best_not_the_submission() {
+        let epoch_hashes = epoch_hashes(&[(1, 20), (2, 15)], 20);
+        let ack = SubmissionAck::new(&epoch_hashes, &key(2), 12);
+        assert_eq!((ack.difficulty, ack.rank), (12, 2));
+
+        // a device that isn't credited yet ranks with the submitted difficulty
+        let ack = SubmissionAck::new(&epoch_hashes, &key(3), 16);
+        assert_eq!((ack.rank, ack.submitters), (2, 2));
+    }
+
+    #[test]
+    fn acks_and_rejections_are_typed() {
+        let epoch_hashes = epoch_hashes(&[(1, 20)], 20);
+        assert_eq!(
+            serde_json::to_value(SubmissionAck::new(&epoch_hashes, &key(1), 20)).unwrap(),
+            json!({
+                "type": "submission_ack",
+                "difficulty": 20,
+                "improved": true,
+                "rank": 1,
+                "submitters": 1,
+                "pool_best_difficulty": 20,
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(SubmissionRejected::new(RejectReason::NotImproved, Some(12))).unwrap(),
+            json!({"type": "submission_rejected", "reason": "not_improved", "difficulty": 12})
+        );
+        assert_eq!(
+            serde_json::to_value(SubmissionRejected::new(RejectReason::BelowMinDifficulty, Some(3))).unwrap()["reason"],
+            "below_min_difficulty"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction,
+    signature::Keypair, signer::Signer, transaction::Transaction,
+};
+use tokio::time::Instant;
+use tracing::error;
+
+// Blockhashes expire after 150 blocks, 60 to 90 seconds, a cached one is
+// refetched once older than this.
+pub const BLOCKHASH_MAX_AGE: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum BuildError {
+    FailedToGetBlockhash,
+}
+
+/// Collects the instructions of a transaction paid and signed by a single
+/// keypair. Compute budget instructions always come first.
+#[derive(Debug, Default, Clone)]
+pub struct SolanaTransactionBuilder {
+    compute_limit: Option<u32>,
+    priority_fee: Option<u64>,
+    ixs: Vec<Instruction>,
+}
+
+impl SolanaTransactionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compute_limit(mut self, limit: u32) -> Self {
+        self.compute_limit = Some(limit);
+        self
+    }
+
+    /// Priority fee in microlamports per compute unit.
+    pub fn priority_fee(mut self, fee: u64) -> Self {
+        self.priority_fee = Some(fee);
+        self
+    }
+
+    pub fn instruction(mut self, ix: Instruction) -> Self {
+        self.ixs.push(ix);
+        self
+    }
+
+    pub fn instructions(self) -> Vec<Instruction> {
+        let mut ixs = Vec::with_capacity(self.ixs.len() + 2);
+        if let Some(limit) = self.compute_limit {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(limit));
+        }
+        if let Some(fee) = self.priority_fee {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(fee));
+        }
+        ixs.extend(self.ixs);
+        ixs
+    }
+
+    /// Signs the transaction with a blockhash fetched at the client's commitment.
+    pub async fn build_and_sign(
+        self,
+        signer: &Keypair,
+        rpc: &RpcClient,
+    ) -> Result<Transaction, BuildError> {
+        let hash = fetch_blockhash(rpc).await?;
+        Ok(self.sign(signer, hash))
+    }
+
+    pub fn sign(self, signer: &Keypair, hash: Hash) -> Transaction {
+        let mut tx = Transaction::new_with_payer(&self.instructions(), Some(&signer.pubkey()));
+        tx.sign(&[signer], hash);
+        tx
+    }
+}
+
+async fn fetch_blockhash(rpc: &RpcClient) -> Result<Hash, BuildError> {
+    let (hash, _slot) = rpc
+        .get_latest_blockhash_with_commitment(rpc.commitment())
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            BuildError::FailedToGetBlockhash
+        })?;
+    Ok(hash)
+}
+
+/// The cached blockhash while it is younger than max_age, otherwise a fresh
+/// one which replaces it. Lets retries skip the blockhash request without
+/// signing with one close to expiring.
+pub async fn get_or_refresh_blockhash(
+    cached: &mut Option<(Hash, Instant)>,
+    rpc: &RpcClient,
+    max_age: Duration,
+) -> Result<Hash, BuildError> {
+    if let Some((hash, fetched_at)) = cached {
+        if fetched_at.elapsed() <= max_age {
+            return Ok(*hash);
+        }
+    }
+    let hash = fetch_blockhash(rpc).await?;
+    *cached = Some((hash, Instant::now()));
+    Ok(hash)
+}
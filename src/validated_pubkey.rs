@@ -0,0 +1,37 @@
+use std::str::FromStr;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use utoipa::IntoParams;
+
+use crate::api_error::{ApiError, ApiErrorCode};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PubkeyParam {
+    pub pubkey: String,
+}
+
+/// The `pubkey` query parameter, parsed. Requests without a valid pubkey are
+/// rejected with an InvalidPubkey error before the handler runs.
+pub struct ValidatedPubkey(pub Pubkey);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ValidatedPubkey {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let invalid = || ApiError::new(ApiErrorCode::InvalidPubkey, "Invalid public key");
+        let Query(param) = Query::<PubkeyParam>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| invalid())?;
+        Pubkey::from_str(&param.pubkey)
+            .map(ValidatedPubkey)
+            .map_err(|_| invalid())
+    }
+}
@@ -0,0 +1,280 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use reqwest::{redirect::Policy, Url};
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{error, info};
+
+use crate::{app_database::AppDatabase, socket_utils::is_public_ip};
+
+// Delivery attempts per webhook before it counts as a failure for the miner.
+const DELIVERY_ATTEMPTS: u32 = 3;
+// Consecutive failed deliveries before a miner's notifications are disabled.
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    BalanceThreshold {
+        pubkey: String,
+        balance: u64,
+        threshold: u64,
+    },
+    ClaimConfirmed {
+        pubkey: String,
+        amount: u64,
+        signature: String,
+    },
+    ToolDurabilityLow {
+        tool: String,
+        durability: u64,
+        threshold: u64,
+    },
+    ReprocessSucceeded {
+        signature: String,
+    },
+    ReprocessFailed {
+        error: String,
+    },
+    FeeBudgetExhausted {
+        spent_lamports: u64,
+        budget_lamports: u64,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookJob {
+    /// Miner the webhook belongs to, None for operator alerts
+    pub miner_id: Option<i32>,
+    pub url: String,
+    pub event: WebhookEvent,
+}
+
+/// Resolves a miner's webhook url, failing unless it is http(s) and every
+/// address its host resolves to is public, so miners can't make the pool send
+/// requests into its own network.
+pub async fn resolve_public_url(url: &str) -> Result<(Url, Vec<SocketAddr>), String> {
+    let parsed = Url::parse(url).map_err(|_| "notify_url is not a valid url".to_string())?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err("notify_url must be an http(s) url".to_string());
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| "notify_url has no host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| format!("notify_url host {} could not be resolved", host))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("notify_url host {} could not be resolved", host));
+    }
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err("notify_url must resolve to public addresses only".to_string());
+    }
+    Ok((parsed, addrs))
+}
+
+fn client_builder() -> reqwest::ClientBuilder {
+    // a redirect could point anywhere, past the address checks
+    reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(Policy::none())
+}
+
+/// Delivers queued webhooks. Each job is sent from its own task so a slow
+/// endpoint never holds up the queue or the code that enqueued it.
+pub async fn webhook_delivery_system(
+    mut receiver: UnboundedReceiver<WebhookJob>,
+    app_database: Arc<AppDatabase>,
+) {
+    let client = match client_builder().build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to build webhook http client: {:?}", e);
+            return;
+        }
+    };
+
+    while let Some(job) = receiver.recv().await {
+        let client = client.clone();
+        let app_database = app_database.clone();
+        tokio::spawn(async move {
+            // miner urls are checked again at delivery, the host may resolve
+            // elsewhere since it was saved. The checked addresses are pinned
+            // so the request can't be sent to another resolution.
+            // Operator alerts are configured by the operator and trusted.
+            let client = if job.miner_id.is_some() {
+                match pinned_client(&job.url).await {
+                    Ok(client) => client,
+                    Err(e) => {
+                        error!("Webhook to {} refused: {}", job.url, e);
+                        record_failure(&app_database, job.miner_id).await;
+                        return;
+                    }
+                }
+            } else {
+                client
+            };
+            deliver(client, app_database, job).await;
+        });
+    }
+}
+
+async fn pinned_client(url: &str) -> Result<reqwest::Client, String> {
+    let (url, addrs) = resolve_public_url(url).await?;
+    let mut builder = client_builder();
+    if let Some(domain) = url.domain() {
+        builder = builder.resolve_to_addrs(domain, &addrs);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+async fn deliver(client: reqwest::Client, app_database: Arc<AppDatabase>, job: WebhookJob) {
+    for attempt in 1..=DELIVERY_ATTEMPTS {
+        match client.post(&job.url).json(&job.event).send().await {
+            Ok(response) if response.status().is_success() => {
+                if let Some(miner_id) = job.miner_id {
+                    let _ = app_database.reset_notify_failures(miner_id).await;
+                }
+                return;
+            }
+            Ok(response) => {
+                error!(
+                    "Webhook to {} returned {} (attempt {})",
+                    job.url,
+                    response.status(),
+                    attempt
+                );
+            }
+            Err(e) => {
+                error!(
+                    "Webhook to {} failed (attempt {}): {:?}",
+                    job.url, attempt, e
+                );
+            }
+        }
+        if let Some(delay) = retry_delay(attempt) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    record_failure(&app_database, job.miner_id).await;
+}
+
+/// Wait before retrying a failed delivery attempt, None after the last one.
+fn retry_delay(attempt: u32) -> Option<Duration> {
+    (attempt < DELIVERY_ATTEMPTS).then(|| Duration::from_secs(2u64.pow(attempt)))
+}
+
+async fn record_failure(app_database: &AppDatabase, miner_id: Option<i32>) {
+    // operator alerts are never disabled
+    let miner_id = match miner_id {
+        Some(miner_id) => miner_id,
+        None => return,
+    };
+
+    match app_database
+        .record_notify_failure(miner_id, MAX_CONSECUTIVE_FAILURES)
+        .await
+    {
+        Ok(true) => {
+            info!(
+                "Disabled notifications for miner {} after {} failed deliveries",
+                miner_id, MAX_CONSECUTIVE_FAILURES
+            );
+        }
+        Ok(false) => {}
+        Err(_) => {
+            error!("Failed to record webhook failure for miner {}", miner_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::post, Json, Router};
+    use tokio::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn no_wait_follows_the_last_attempt() {
+        assert_eq!(retry_delay(1), Some(Duration::from_secs(2)));
+        assert_eq!(retry_delay(2), Some(Duration::from_secs(4)));
+        assert_eq!(retry_delay(DELIVERY_ATTEMPTS), None);
+    }
+
+    #[test]
+    fn events_are_tagged_with_their_name() {
+        let event = WebhookEvent::ClaimConfirmed {
+            pubkey: "miner".to_string(),
+            amount: 5,
+            signature: "sig".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(event).unwrap(),
+            serde_json::json!({
+                "event": "claim_confirmed",
+                "pubkey": "miner",
+                "amount": 5,
+                "signature": "sig",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn only_public_http_urls_are_resolved() {
+        for url in [
+            "not a url",
+            "ftp://1.1.1.1/hook",
+            "http://127.0.0.1/hook",
+            "http://10.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "https://[fe80::1]/hook",
+        ] {
+            assert!(resolve_public_url(url).await.is_err(), "{} was resolved", url);
+        }
+
+        let (url, addrs) = resolve_public_url("https://1.1.1.1/hook").await.unwrap();
+        assert_eq!(url.path(), "/hook");
+        assert_eq!(addrs, vec!["1.1.1.1:443".parse().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn operator_alerts_are_delivered_once() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let app_received = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                app_received.lock().await.push(body);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        // never connected to, operator alerts don't touch the db
+        let app_database = Arc::new(AppDatabase::new("mysql://localhost/unused".to_string()));
+        let job = WebhookJob {
+            miner_id: None,
+            url: format!("http://{}/hook", addr),
+            event: WebhookEvent::ReprocessSucceeded {
+                signature: "sig".to_string(),
+            },
+        };
+        deliver(client_builder().build().unwrap(), app_database, job).await;
+
+        assert_eq!(
+            *received.lock().await,
+            vec![serde_json::json!({ "event": "reprocess_succeeded", "signature": "sig" })]
+        );
+    }
+}
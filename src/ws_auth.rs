@@ -0,0 +1,382 @@
+use std::{collections::HashSet, str::FromStr};
+
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::api_error::{ApiError, ApiErrorCode};
+
+// Longest "Basic <base64>" header accepted, a pubkey and signature need about 180.
+const MAX_AUTH_HEADER_LEN: usize = 256;
+// Longest base58 encodings of a pubkey and a signature.
+const MAX_PUBKEY_LEN: usize = 44;
+const MAX_SIGNATURE_LEN: usize = 88;
+
+/// How old or how far ahead of the server clock a signed timestamp may be.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthWindow {
+    // the signed timestamp is valid for this long
+    pub max_age_secs: u64,
+    // how far ahead of the server clock a client's timestamp may be
+    pub max_clock_skew_secs: u64,
+}
+
+impl AuthWindow {
+    pub fn check(&self, timestamp: u64, now: u64) -> Result<(), WsAuthError> {
+        if timestamp > now.saturating_add(self.max_clock_skew_secs) {
+            return Err(WsAuthError::TimestampInFuture);
+        }
+        if now.saturating_sub(timestamp) >= self.max_age_secs {
+            return Err(WsAuthError::TimestampExpired);
+        }
+        Ok(())
+    }
+}
+
+/// (pubkey, timestamp) pairs of the signed requests accepted within the auth
+/// window, so a captured request can't be sent again while it is still valid.
+#[derive(Debug, Default)]
+pub struct SignedRequestReplays {
+    seen: HashSet<(Pubkey, u64)>,
+}
+
+impl SignedRequestReplays {
+    /// Records the pair, false if it was already used. Pairs past the window
+    /// are dropped, their requests are refused as expired anyway.
+    pub fn record(&mut self, pubkey: Pubkey, timestamp: u64, now: u64, window: &AuthWindow) -> bool {
+        self.seen
+            .retain(|(_, seen)| now.saturating_sub(*seen) < window.max_age_secs);
+        self.seen.insert((pubkey, timestamp))
+    }
+}
+
+/// Why a websocket connection's authorization was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsAuthError {
+    MissingHeader,
+    MalformedHeader,
+    PubkeyTooLong,
+    InvalidPubkey,
+    SignatureTooLong,
+    InvalidSignature,
+    SignatureMismatch,
+    TimestampExpired,
+    TimestampInFuture,
+}
+
+impl WsAuthError {
+    /// Short reason for the logs, stable so rejections can be counted by it.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            WsAuthError::MissingHeader => "missing_header",
+            WsAuthError::MalformedHeader => "malformed_header",
+            WsAuthError::PubkeyTooLong => "pubkey_too_long",
+            WsAuthError::InvalidPubkey => "invalid_pubkey",
+            WsAuthError::SignatureTooLong => "signature_too_long",
+            WsAuthError::InvalidSignature => "invalid_signature",
+            WsAuthError::SignatureMismatch => "signature_mismatch",
+            WsAuthError::TimestampExpired => "timestamp_expired",
+            WsAuthError::TimestampInFuture => "timestamp_in_future",
+        }
+    }
+
+    /// The error sent to the client. Timestamp errors include the server time
+    /// so a client can tell its user how far off its clock is.
+    pub fn to_api_error(self, window: &AuthWindow, timestamp: u64, now: u64) -> ApiError {
+        let clock_details = || {
+            serde_json::json!({
+                "server_time": now,
+                "clock_offset_secs": timestamp as i64 - now as i64,
+                "max_age_secs": window.max_age_secs,
+                "max_clock_skew_secs": window.max_clock_skew_secs,
+            })
+        };
+        match self {
+            WsAuthError::MissingHeader => ApiError::new(
+                ApiErrorCode::Unauthorized,
+                "Missing basic authorization with the pubkey and signed timestamp",
+            ),
+            WsAuthError::MalformedHeader => {
+                ApiError::new(ApiErrorCode::Unauthorized, "Malformed basic authorization")
+            }
+            WsAuthError::PubkeyTooLong => {
+                ApiError::new(ApiErrorCode::InvalidPubkey, "Pubkey is too long")
+            }
+            WsAuthError::InvalidPubkey => {
+                ApiError::new(ApiErrorCode::InvalidPubkey, "Pubkey is not valid base58")
+            }
+            WsAuthError::SignatureTooLong => {
+                ApiError::new(ApiErrorCode::InvalidSignature, "Signature is too long")
+            }
+            WsAuthError::InvalidSignature => {
+                ApiError::new(ApiErrorCode::InvalidSignature, "Signature is not valid base58")
+            }
+            WsAuthError::SignatureMismatch => ApiError::new(
+                ApiErrorCode::InvalidSignature,
+                "Signature does not match the pubkey and timestamp",
+            ),
+            WsAuthError::TimestampExpired => ApiError::new(
+                ApiErrorCode::InvalidSignature,
+                format!(
+                    "Timestamp is older than {} seconds, server time is {}",
+                    window.max_age_secs, now
+                ),
+            )
+            .with_details(clock_details()),
+            WsAuthError::TimestampInFuture => ApiError::new(
+                ApiErrorCode::InvalidSignature,
+                format!(
+                    "Timestamp is ahead of the server clock, server time is {}",
+                    now
+                ),
+            )
+            .with_details(clock_details()),
+        }
+    }
+}
+
+/// Pubkey and signature from the Basic authorization header, with every
+/// field length checked before it is decoded.
+pub fn parse_credentials(headers: &HeaderMap) -> Result<(Pubkey, Signature), WsAuthError> {
+    let header = headers
+        .get(AUTHORIZATION)
+        .ok_or(WsAuthError::MissingHeader)?;
+    if header.len() > MAX_AUTH_HEADER_LEN {
+        return Err(WsAuthError::MalformedHeader);
+    }
+    let encoded = header
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Basic "))
+        .ok_or(WsAuthError::MalformedHeader)?;
+    let decoded = BASE64_STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or(WsAuthError::MalformedHeader)?;
+    let (pubkey, signature) = decoded
+        .split_once(':')
+        .ok_or(WsAuthError::MalformedHeader)?;
+
+    if pubkey.len() > MAX_PUBKEY_LEN {
+        return Err(WsAuthError::PubkeyTooLong);
+    }
+    let pubkey = Pubkey::from_str(pubkey).map_err(|_| WsAuthError::InvalidPubkey)?;
+    if signature.len() > MAX_SIGNATURE_LEN {
+        return Err(WsAuthError::SignatureTooLong);
+    }
+    let signature = Signature::from_str(signature).map_err(|_| WsAuthError::InvalidSignature)?;
+    Ok((pubkey, signature))
+}
+
+/// Checks the signed timestamp is recent and signed by the pubkey. The
+/// timestamp (u64 le) is signed followed by the device_id, if any, so a
+/// captured signature can't be replayed as another device.
+pub fn verify_timestamp(
+    pubkey: &Pubkey,
+    signature: &Signature,
+    timestamp: u64,
+    device_id: Option<&str>,
+    now: u64,
+    window: &AuthWindow,
+) -> Result<(), WsAuthError> {
+    window.check(timestamp, now)?;
+    let mut msg = timestamp.to_le_bytes().to_vec();
+    if let Some(device_id) = device_id {
+        msg.extend_from_slice(device_id.as_bytes());
+    }
+    if !signature.verify(&pubkey.to_bytes(), &msg) {
+        return Err(WsAuthError::SignatureMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderValue, StatusCode};
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use super::*;
+
+    const ALL_ERRORS: [WsAuthError; 9] = [
+        WsAuthError::MissingHeader,
+        WsAuthError::MalformedHeader,
+        WsAuthError::PubkeyTooLong,
+        WsAuthError::InvalidPubkey,
+        WsAuthError::SignatureTooLong,
+        WsAuthError::InvalidSignature,
+        WsAuthError::SignatureMismatch,
+        WsAuthError::TimestampExpired,
+        WsAuthError::TimestampInFuture,
+    ];
+
+    const NOW: u64 = 1_724_000_000;
+    const WINDOW: AuthWindow = AuthWindow {
+        max_age_secs: 30,
+        max_clock_skew_secs: 5,
+    };
+
+    #[test]
+    fn timestamp_expires_at_max_age() {
+        assert_eq!(WINDOW.check(NOW - 29, NOW), Ok(()));
+        assert_eq!(WINDOW.check(NOW - 30, NOW), Err(WsAuthError::TimestampExpired));
+        assert_eq!(WINDOW.check(NOW - 31, NOW), Err(WsAuthError::TimestampExpired));
+        assert_eq!(WINDOW.check(0, NOW), Err(WsAuthError::TimestampExpired));
+    }
+
+    #[test]
+    fn timestamp_may_be_ahead_by_the_clock_skew() {
+        assert_eq!(WINDOW.check(NOW, NOW), Ok(()));
+        assert_eq!(WINDOW.check(NOW + 4, NOW), Ok(()));
+        assert_eq!(WINDOW.check(NOW + 5, NOW), Ok(()));
+        assert_eq!(WINDOW.check(NOW + 6, NOW), Err(WsAuthError::TimestampInFuture));
+        assert_eq!(WINDOW.check(u64::MAX, NOW), Err(WsAuthError::TimestampInFuture));
+    }
+
+    #[test]
+    fn no_clock_skew_refuses_any_future_timestamp() {
+        let window = AuthWindow {
+            max_clock_skew_secs: 0,
+            ..WINDOW
+        };
+        assert_eq!(window.check(NOW, NOW), Ok(()));
+        assert_eq!(window.check(NOW + 1, NOW), Err(WsAuthError::TimestampInFuture));
+        assert_eq!(window.check(u64::MAX, u64::MAX), Ok(()));
+    }
+
+    fn basic(credentials: &str) -> HeaderMap {
+        authorization(&format!("Basic {}", BASE64_STANDARD.encode(credentials)))
+    }
+
+    fn authorization(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn parses_a_pubkey_and_signature() {
+        let keypair = Keypair::new();
+        let signature = keypair.sign_message(&NOW.to_le_bytes());
+        let headers = basic(&format!("{}:{}", keypair.pubkey(), signature));
+        assert_eq!(parse_credentials(&headers), Ok((keypair.pubkey(), signature)));
+    }
+
+    #[test]
+    fn parse_credentials_errors() {
+        let pubkey = Keypair::new().pubkey().to_string();
+        let signature = Keypair::new().sign_message(b"").to_string();
+        let cases = [
+            (HeaderMap::new(), WsAuthError::MissingHeader),
+            (authorization(&format!("Bearer {}", pubkey)), WsAuthError::MalformedHeader),
+            (authorization("Basic not base64!"), WsAuthError::MalformedHeader),
+            (basic(&pubkey), WsAuthError::MalformedHeader),
+            (authorization(&format!("Basic {}", "A".repeat(MAX_AUTH_HEADER_LEN))), WsAuthError::MalformedHeader),
+            (basic(&format!("{}:{}", "1".repeat(MAX_PUBKEY_LEN + 1), signature)), WsAuthError::PubkeyTooLong),
+            (basic(&format!("0OIl:{}", signature)), WsAuthError::InvalidPubkey),
+            (basic(&format!("{}:{}1", pubkey, "1".repeat(MAX_SIGNATURE_LEN))), WsAuthError::SignatureTooLong),
+            (basic(&format!("{}:0OIl", pubkey)), WsAuthError::InvalidSignature),
+        ];
+        for (headers, error) in cases {
+            assert_eq!(parse_credentials(&headers), Err(error), "{:?}", headers);
+        }
+    }
+
+    #[test]
+    fn verify_timestamp_errors() {
+        let keypair = Keypair::new();
+        let sign = |timestamp: u64| keypair.sign_message(&timestamp.to_le_bytes());
+        let pubkey = keypair.pubkey();
+
+        assert_eq!(verify_timestamp(&pubkey, &sign(NOW), NOW, None, NOW, &WINDOW), Ok(()));
+        assert_eq!(
+            verify_timestamp(&pubkey, &sign(NOW), NOW - 1, None, NOW, &WINDOW),
+            Err(WsAuthError::SignatureMismatch)
+        );
+        assert_eq!(
+            verify_timestamp(&Keypair::new().pubkey(), &sign(NOW), NOW, None, NOW, &WINDOW),
+            Err(WsAuthError::SignatureMismatch)
+        );
+        assert_eq!(
+            verify_timestamp(&pubkey, &sign(NOW - 30), NOW - 30, None, NOW, &WINDOW),
+            Err(WsAuthError::TimestampExpired)
+        );
+        assert_eq!(
+            verify_timestamp(&pubkey, &sign(NOW + 6), NOW + 6, None, NOW, &WINDOW),
+            Err(WsAuthError::TimestampInFuture)
+        );
+    }
+
+    #[test]
+    fn the_device_id_is_signed() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let mut msg = NOW.to_le_bytes().to_vec();
+        msg.extend_from_slice(b"laptop");
+        let signature = keypair.sign_message(&msg);
+
+        assert_eq!(verify_timestamp(&pubkey, &signature, NOW, Some("laptop"), NOW, &WINDOW), Ok(()));
+        assert_eq!(
+            verify_timestamp(&pubkey, &signature, NOW, Some("desktop"), NOW, &WINDOW),
+            Err(WsAuthError::SignatureMismatch)
+        );
+        // a signature over the timestamp alone doesn't carry over to a device
+        let timestamp_only = keypair.sign_message(&NOW.to_le_bytes());
+        assert_eq!(
+            verify_timestamp(&pubkey, &timestamp_only, NOW, Some("laptop"), NOW, &WINDOW),
+            Err(WsAuthError::SignatureMismatch)
+        );
+    }
+
+    #[test]
+    fn reasons_are_stable() {
+        let reasons: Vec<&str> = ALL_ERRORS.iter().map(|e| e.reason()).collect();
+        assert_eq!(
+            reasons,
+            [
+                "missing_header",
+                "malformed_header",
+                "pubkey_too_long",
+                "invalid_pubkey",
+                "signature_too_long",
+                "invalid_signature",
+                "signature_mismatch",
+                "timestamp_expired",
+                "timestamp_in_future",
+            ]
+        );
+    }
+
+    #[test]
+    fn api_errors_map_to_their_status() {
+        for error in ALL_ERRORS {
+            let api_error = error.to_api_error(&WINDOW, NOW - 40, NOW);
+            let (code, status) = match error {
+                WsAuthError::MissingHeader | WsAuthError::MalformedHeader => {
+                    (ApiErrorCode::Unauthorized, StatusCode::UNAUTHORIZED)
+                }
+                WsAuthError::PubkeyTooLong | WsAuthError::InvalidPubkey => {
+                    (ApiErrorCode::InvalidPubkey, StatusCode::BAD_REQUEST)
+                }
+                WsAuthError::SignatureTooLong
+                | WsAuthError::InvalidSignature
+                | WsAuthError::SignatureMismatch
+                | WsAuthError::TimestampExpired
+                | WsAuthError::TimestampInFuture => {
+                    (ApiErrorCode::InvalidSignature, StatusCode::UNAUTHORIZED)
+                }
+            };
+            assert_eq!(api_error.code, code, "{:?}", error);
+            assert_eq!(api_error.code.status(), status, "{:?}", error);
+
+            // only the timestamp errors tell the client the server's clock
+            let timestamp_error =
+                matches!(error, WsAuthError::TimestampExpired | WsAuthError::TimestampInFuture);
+            assert_eq!(api_error.details.is_some(), timestamp_error, "{:?}", error);
+            if let Some(details) = api_error.details {
+                assert_eq!(details["server_time"], NOW);
+                assert_eq!(details["clock_offset_secs"], -40);
+            }
+        }
+    }
+}